@@ -0,0 +1,75 @@
+use std::hint::black_box;
+
+use bloom::{BlockedBloomFilter, BloomFilter};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+const HASHES: u64 = 4096;
+
+// A bit vector size that isn't a power of two, as sizes derived from an item
+// count almost never are.
+const RANGE: u64 = 95_850_584;
+
+fn modulo(hash: u64, range: u64) -> u64 {
+    hash % range
+}
+
+// The multiply-shift reduction the filter uses to map hashes to bit indices.
+fn fastrange(hash: u64, range: u64) -> u64 {
+    ((u128::from(hash) * u128::from(range)) >> 64) as u64
+}
+
+fn hashes() -> Vec<u64> {
+    // SplitMix64, so the inputs look like hasher output.
+    let mut state = 0u64;
+    (0..HASHES)
+        .map(|_| {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        })
+        .collect()
+}
+
+fn reduction(c: &mut Criterion) {
+    let hashes = hashes();
+    let mut group = c.benchmark_group("reduction");
+    group.throughput(Throughput::Elements(HASHES));
+    group.bench_function("modulo", |b| {
+        b.iter(|| hashes.iter().fold(0, |acc, &h| acc ^ modulo(h, black_box(RANGE))))
+    });
+    group.bench_function("fastrange", |b| {
+        b.iter(|| hashes.iter().fold(0, |acc, &h| acc ^ fastrange(h, black_box(RANGE))))
+    });
+    group.finish();
+}
+
+fn contains(c: &mut Criterion) {
+    let mut filter = BloomFilter::<u64>::new(10_000_000, 0.01);
+    filter.extend(0..1_000_000u64);
+    let queries: Vec<u64> = (500_000..501_000).collect();
+    let mut group = c.benchmark_group("contains");
+    group.throughput(Throughput::Elements(queries.len() as u64));
+    group.bench_function("10M items at 1%", |b| {
+        b.iter(|| queries.iter().filter(|q| filter.contains(*q)).count())
+    });
+    group.bench_function("10M items at 1%, batched", |b| {
+        b.iter(|| filter.contains_many(&queries).into_iter().filter(|&found| found).count())
+    });
+    // Each lookup reads one aligned cache line.
+    let mut blocked = BlockedBloomFilter::<u64>::new(10_000_000, 0.01);
+    for i in 0..1_000_000u64 {
+        blocked.insert(&i);
+    }
+    group.bench_function("10M items at 1%, blocked", |b| {
+        b.iter(|| queries.iter().filter(|q| blocked.contains(*q)).count())
+    });
+    group.bench_function("10M items at 1%, blocked and batched", |b| {
+        b.iter(|| blocked.contains_many(&queries).into_iter().filter(|&found| found).count())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, reduction, contains);
+criterion_main!(benches);
@@ -0,0 +1,89 @@
+use std::hint::black_box;
+
+use bloom::hash::HashScheme;
+use bloom::{ApproximateMembership, BlockedBloomFilter, BloomFilter, CountingBloomFilter, CuckooFilter};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+
+const CAPACITY: usize = 1_000_000;
+
+const FALSE_POSITIVE_PROB: f64 = 0.01;
+
+// The number of items each benchmark iteration inserts or looks up.
+const BATCH: usize = 10_000;
+
+// SplitMix64, so that keys look like arbitrary data.
+fn random_words(count: usize, seed: u64) -> Vec<u64> {
+    let mut state = seed;
+    (0..count)
+        .map(|_| {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        })
+        .collect()
+}
+
+// Inserts a batch into an empty filter, and looks a batch up in a half-full
+// one, half of it present.
+fn bench_variant<F: ApproximateMembership<u64> + Clone>(c: &mut Criterion, name: &str, empty: F) {
+    let keys = random_words(CAPACITY / 2 + BATCH / 2, 1);
+    let absent = random_words(BATCH / 2, 2);
+    let mut full = empty.clone();
+    for key in &keys[..CAPACITY / 2] {
+        full.insert(key).unwrap();
+    }
+    let queries: Vec<u64> = keys[..BATCH / 2].iter().chain(&absent).copied().collect();
+
+    let mut group = c.benchmark_group("variants");
+    group.throughput(Throughput::Elements(BATCH as u64));
+    group.bench_function(BenchmarkId::new("insert", name), |b| {
+        b.iter_batched_ref(
+            || empty.clone(),
+            |filter| {
+                for key in &keys[..BATCH] {
+                    black_box(filter.insert(key).unwrap());
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function(BenchmarkId::new("contains", name), |b| {
+        b.iter(|| queries.iter().filter(|key| full.contains(key)).count())
+    });
+    group.finish();
+}
+
+fn variants(c: &mut Criterion) {
+    bench_variant(c, "standard", BloomFilter::<u64>::new(CAPACITY, FALSE_POSITIVE_PROB));
+    bench_variant(c, "blocked", BlockedBloomFilter::<u64>::new(CAPACITY, FALSE_POSITIVE_PROB));
+    bench_variant(c, "counting", CountingBloomFilter::<u64>::new(CAPACITY, FALSE_POSITIVE_PROB));
+    bench_variant(c, "cuckoo", CuckooFilter::<u64>::new(CAPACITY, FALSE_POSITIVE_PROB));
+}
+
+// Looks up keys of each size with each hash scheme, so that the cost of
+// hashing can be told apart from the cost of reading bits.
+fn hashers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hashers");
+    group.throughput(Throughput::Elements(BATCH as u64));
+    for &key_len in &[8, 64, 1024] {
+        let keys: Vec<Vec<u8>> = random_words(BATCH * key_len / 8, key_len as u64)
+            .chunks(key_len / 8)
+            .map(|words| words.iter().flat_map(|word| word.to_le_bytes()).collect())
+            .collect();
+        for &scheme in HashScheme::ALL.iter() {
+            let mut filter = BloomFilter::<Vec<u8>, HashScheme>::with_hasher(BATCH, FALSE_POSITIVE_PROB, scheme);
+            for key in keys.iter().step_by(2) {
+                filter.insert(key);
+            }
+            group.bench_function(BenchmarkId::new(format!("{:?}", scheme), key_len), |b| {
+                b.iter(|| keys.iter().filter(|key| filter.contains(key.as_slice())).count())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, variants, hashers);
+criterion_main!(benches);
@@ -0,0 +1,412 @@
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::bit_vec::BitVec;
+use crate::probe::Probes;
+use crate::{math, params, ApproximateMembership, BloomError, DefaultBuildHasher, Result};
+
+/// The most slices a filter can have, so that an item's bits in every slice
+/// fit in one `u128`.
+const MAX_SLICES: usize = 128;
+
+/// A sliding-window filter that remembers the last `w` insertions in a
+/// single array of slices that age together (Shtul, Baquero and Almeida,
+/// "Age-Partitioned Bloom Filters").
+///
+/// The filter has `k + l` slices of `m` bits, newest first, and each item
+/// sets one bit in each of the `k` newest. After every `g` insertions the
+/// oldest slice is cleared and becomes the newest, so the slices an item set
+/// move along by one. An item is present if it has bits set in `k`
+/// consecutive slices: it's found for the `l` generations after the one it
+/// was inserted in, and lost when its last slice is recycled.
+///
+/// Compared with a [`SlidingBloomFilter`](crate::SlidingBloomFilter), which
+/// keeps a whole bloom filter per generation, each slice here is shared by
+/// `k` generations, so generations can be far shorter: [`new`] picks dozens
+/// of them, and the window is precise to a percent or two. Rotating that
+/// many generations takes about as much memory, but probes every generation
+/// on each lookup, where this hashes an item once and reads one bit per
+/// slice.
+///
+/// [`new`]: AgePartitionedBloomFilter::new
+///
+/// ```
+/// use bloom::AgePartitionedBloomFilter;
+///
+/// let mut recent = AgePartitionedBloomFilter::<u64>::new(1000, 0.01);
+/// for i in 0..10_000 {
+///     recent.insert(&i);
+/// }
+/// assert!(recent.contains(&9000));
+/// assert!(!recent.contains(&5000));
+/// ```
+#[derive(Debug)]
+pub struct AgePartitionedBloomFilter<T, S = DefaultBuildHasher> {
+    /// The slices, in a ring whose newest is at `newest` and whose older
+    /// slices follow it.
+    slices: Vec<BitVec>,
+    newest: usize,
+    hash_count: usize,
+    generation_len: u64,
+    /// The number of insertions into the current generation, and the number
+    /// of generations completed since the filter was created or cleared.
+    current_len: u64,
+    generations: u64,
+    window: u64,
+    false_positive_prob: Option<f64>,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for AgePartitionedBloomFilter<T, S> {
+    fn clone(&self) -> AgePartitionedBloomFilter<T, S> {
+        AgePartitionedBloomFilter {
+            slices: self.slices.clone(),
+            newest: self.newest,
+            hash_count: self.hash_count,
+            generation_len: self.generation_len,
+            current_len: self.current_len,
+            generations: self.generations,
+            window: self.window,
+            false_positive_prob: self.false_positive_prob,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> AgePartitionedBloomFilter<T> {
+    /// Creates a filter remembering at least the last `window` insertions,
+    /// with `k` and `l` chosen to take the least memory for the given false
+    /// positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is 0, if `false_positive_prob` is not strictly
+    /// between 0 and 1, or if the filter would be too large to allocate.
+    pub fn new(window: u64, false_positive_prob: f64) -> AgePartitionedBloomFilter<T> {
+        AgePartitionedBloomFilter::with_hasher(window, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](AgePartitionedBloomFilter::new), but mixes `seed` into
+    /// every hash.
+    pub fn with_seed(window: u64, false_positive_prob: f64, seed: u64) -> AgePartitionedBloomFilter<T> {
+        let mut filter = AgePartitionedBloomFilter::new(window, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter remembering at least the last `window` insertions in
+    /// `hashes + age_slices` slices: `k` and `l` respectively.
+    ///
+    /// Each of the `l` generations is `window / l` insertions, rounded up,
+    /// and each slice is sized to be half full once it has taken `k`
+    /// generations.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of them is 0, if there are more than 128 slices, if
+    /// `window` is less than `age_slices`, or if the filter would be too
+    /// large to allocate.
+    pub fn from_params(window: u64, hashes: usize, age_slices: usize) -> AgePartitionedBloomFilter<T> {
+        AgePartitionedBloomFilter::from_params_with_hasher(window, hashes, age_slices, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> AgePartitionedBloomFilter<T, S> {
+    /// Like [`new`](AgePartitionedBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(window: u64, false_positive_prob: f64, hash_builder: S) -> AgePartitionedBloomFilter<T, S> {
+        if let Err(e) = params::validate(1, false_positive_prob) {
+            panic!("{}", e);
+        }
+        // Memory is proportional to `k (k + l) / l`, which shrinks as `l`
+        // grows while the false positive probability rises, so for each `k`
+        // take the most age slices that are good enough, and keep the
+        // smallest of those.
+        let mut best = None;
+        for k in 1..=64 {
+            let mut l = 0;
+            while k + l < MAX_SLICES && design_fpr(k, l + 1) <= false_positive_prob {
+                l += 1;
+            }
+            let cost = k * (k + l) * 1000 / cmp::max(l, 1);
+            if l > 0 && best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, k, l));
+            }
+        }
+        let (k, l) = match best {
+            Some((_, k, l)) => (k, l),
+            None => panic!(
+                "{}",
+                BloomError::InvalidParams(format!(
+                    "no more than {} slices reach a false positive probability of {}",
+                    MAX_SLICES, false_positive_prob
+                ))
+            ),
+        };
+        let l = cmp::min(l as u64, cmp::max(window, 1)) as usize;
+        let mut filter = AgePartitionedBloomFilter::from_params_with_hasher(window, k, l, hash_builder);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](AgePartitionedBloomFilter::from_params), but
+    /// hashes items with `hash_builder`.
+    pub fn from_params_with_hasher(
+        window: u64,
+        hashes: usize,
+        age_slices: usize,
+        hash_builder: S,
+    ) -> AgePartitionedBloomFilter<T, S> {
+        if hashes == 0 || age_slices == 0 || hashes + age_slices > MAX_SLICES || window < age_slices as u64 {
+            panic!(
+                "{}",
+                BloomError::InvalidParams(format!(
+                    "need 1 to {} slices and a window of at least the age slices (got {} + {} slices for {})",
+                    MAX_SLICES, hashes, age_slices, window
+                ))
+            );
+        }
+        let generation_len = window.div_ceil(age_slices as u64);
+        let slice_bits = math::ceil(hashes as f64 * generation_len as f64 / core::f64::consts::LN_2) as u64;
+        if let Err(e) = params::validate_layout(slice_bits.saturating_mul((hashes + age_slices) as u64), hashes) {
+            panic!("{}", e);
+        }
+        AgePartitionedBloomFilter {
+            slices: vec![BitVec::new(slice_bits); hashes + age_slices],
+            newest: 0,
+            hash_count: hashes,
+            generation_len,
+            current_len: 0,
+            generations: 0,
+            window: generation_len * age_slices as u64,
+            false_positive_prob: None,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item`.
+    pub fn add<Q: ?Sized + Hash>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+    {
+        self.insert(item);
+    }
+
+    /// Records `item`, returning `true` if it was probably inserted within
+    /// the window before.
+    ///
+    /// An item that was already present is still recorded, so that it stays
+    /// for another full window.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let present = self.contains(item);
+        if self.current_len == self.generation_len {
+            self.shift();
+        }
+        let count = self.slices.len();
+        for (slice, index) in self.probes(item).enumerate() {
+            if (slice + count - self.newest) % count < self.hash_count {
+                self.slices[slice].set(index);
+            }
+        }
+        self.current_len += 1;
+        present
+    }
+
+    /// Returns `true` if `item` was probably inserted within the window, and
+    /// `false` if it definitely was not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        // One bit per slice, then rotated so that bit `i` is the slice `i`
+        // generations old.
+        let count = self.slices.len() as u32;
+        let mut found = self
+            .probes(item)
+            .enumerate()
+            .filter(|&(slice, index)| self.slices[slice].get(index))
+            .fold(0u128, |found, (slice, _)| found | 1 << slice);
+        if self.newest > 0 {
+            found = found >> self.newest | found << (count - self.newest as u32);
+        }
+        found &= u128::MAX >> (128 - count);
+        // After this, bit `i` is set if slices `i..i + k` all are.
+        for _ in 1..self.hash_count {
+            found &= found >> 1;
+        }
+        found != 0
+    }
+
+    /// Removes every item from the filter, keeping its allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        for slice in &mut self.slices {
+            slice.clear();
+        }
+        self.current_len = 0;
+        self.generations = 0;
+    }
+
+    /// Returns `true` if nothing is remembered.
+    pub fn is_empty(&self) -> bool {
+        self.slices.iter().all(BitVec::none)
+    }
+
+    /// The number of insertions the filter remembers, counting repeats: the
+    /// current generation's and those of the `l` before it.
+    pub fn len(&self) -> u64 {
+        let age_slices = (self.slices.len() - self.hash_count) as u64;
+        self.current_len + cmp::min(self.generations, age_slices) * self.generation_len
+    }
+
+    /// Estimates the false positive probability of the filter as it is now,
+    /// from the fill ratio of each slice.
+    pub fn current_fpr(&self) -> f64 {
+        let fills = (0..self.slices.len()).map(|age| {
+            let slice = &self.slices[(self.newest + age) % self.slices.len()];
+            slice.count_ones() as f64 / slice.len() as f64
+        });
+        run_prob(self.hash_count, fills)
+    }
+
+    /// The false positive probability of the filter's shape once it's been
+    /// running for a while.
+    pub fn expected_fpr(&self) -> f64 {
+        design_fpr(self.hash_count, self.slices.len() - self.hash_count)
+    }
+
+    /// The number of insertions the filter is guaranteed to remember:
+    /// `l` generations. Up to one generation more is remembered at times.
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    /// The number of insertions `g` in each generation.
+    pub fn generation_len(&self) -> u64 {
+        self.generation_len
+    }
+
+    /// The number of slices, `k + l`.
+    pub fn slice_count(&self) -> usize {
+        self.slices.len()
+    }
+
+    /// The number of bits `m` in each slice.
+    pub fn slice_bits(&self) -> u64 {
+        self.slices[0].len()
+    }
+
+    /// The number of slices each item sets a bit in, `k`.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The false positive probability the filter was sized for, or `None` if
+    /// it was built from explicit parameters.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The item's bit in each slice, in the order the slices are stored.
+    fn probes<Q: ?Sized + Hash>(&self, item: &Q) -> Probes<S::Hasher> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let size = self.slices[0].len();
+        Probes::new(hasher, size, self.slices.len(), size > params::WIDE_HASH_THRESHOLD)
+    }
+
+    /// Starts a new generation, recycling the oldest slice as the newest.
+    fn shift(&mut self) {
+        self.newest = (self.newest + self.slices.len() - 1) % self.slices.len();
+        self.slices[self.newest].clear();
+        self.current_len = 0;
+        self.generations += 1;
+    }
+}
+
+/// The false positive probability of a filter with `k + l` slices, each
+/// half full once it has taken its `k` generations, just before a shift.
+fn design_fpr(k: usize, l: usize) -> f64 {
+    let fills = (0..k + l).map(|age| 1.0 - math::powf(0.5, (cmp::min(age + 1, k) as f64) / k as f64));
+    run_prob(k, fills)
+}
+
+/// The probability of a run of `k` set bits somewhere along independent
+/// bits that are set with probabilities `fills`.
+fn run_prob<I: Iterator<Item = f64>>(k: usize, fills: I) -> f64 {
+    // `runs[i]` is the probability that no run of `k` has been seen yet and
+    // the current one is `i` long.
+    let mut runs = vec![0.0; k];
+    runs[0] = 1.0;
+    for fill in fills {
+        let alive: f64 = runs.iter().sum();
+        for i in (1..k).rev() {
+            runs[i] = runs[i - 1] * fill;
+        }
+        runs[0] = alive * (1.0 - fill);
+    }
+    1.0 - runs.iter().sum::<f64>()
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for AgePartitionedBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(AgePartitionedBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        AgePartitionedBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        AgePartitionedBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        self.len() as f64
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self)
+            + self
+                .slices
+                .iter()
+                .map(|slice| mem::size_of_val(slice) + slice.heap_bytes())
+                .sum::<usize>()
+    }
+}
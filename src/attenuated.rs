@@ -0,0 +1,306 @@
+use alloc::format;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use crate::{params, ApproximateMembership, BloomError, BloomFilter, BloomFilterBuilder, DefaultBuildHasher, Result};
+
+/// A stack of [`BloomFilter`]s by hop distance (Rhea and Kubiatowicz,
+/// "Probabilistic Location and Routing"), for nodes of a peer-to-peer or
+/// mesh network advertising what content can be reached through them.
+///
+/// Level 0 holds what a node has itself, and level `d` what is `d` hops
+/// away. Nodes periodically send their filters to their neighbours, who
+/// [merge each one in a hop further away][shifted], so that what a
+/// neighbour holds at distance `d` is reachable at `d + 1`. Whatever is
+/// further away than the stack is deep falls off the end. A lookup returns
+/// the nearest level holding an item, and a router forwards queries to the
+/// neighbour whose advertisement puts the item nearest.
+///
+/// Every level has the same size and seed, so levels of different nodes can
+/// be merged.
+///
+/// [shifted]: AttenuatedBloomFilter::try_merge_shifted
+///
+/// ```
+/// use bloom::AttenuatedBloomFilter;
+///
+/// let mut far = AttenuatedBloomFilter::<&str>::new(3, 1000, 0.01);
+/// far.insert(&"song.mp3");
+/// let mut near = AttenuatedBloomFilter::new(3, 1000, 0.01);
+/// near.insert(&"film.mkv");
+/// near.try_merge_shifted(&far).unwrap();
+///
+/// let mut here = AttenuatedBloomFilter::new(3, 1000, 0.01);
+/// here.try_merge_shifted(&near).unwrap();
+/// assert_eq!(here.distance(&"film.mkv"), Some(1));
+/// assert_eq!(here.distance(&"song.mp3"), Some(2));
+/// ```
+#[derive(Debug)]
+pub struct AttenuatedBloomFilter<T, S = DefaultBuildHasher> {
+    /// One filter per hop distance, nearest first.
+    levels: Vec<BloomFilter<T, S>>,
+    item_count: usize,
+    false_positive_prob: f64,
+    seed: u64,
+    hash_builder: S,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for AttenuatedBloomFilter<T, S> {
+    fn clone(&self) -> AttenuatedBloomFilter<T, S> {
+        AttenuatedBloomFilter {
+            levels: self.levels.clone(),
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+impl<T: Hash> AttenuatedBloomFilter<T> {
+    /// Creates a filter of `depth` levels, each sized for `item_count`
+    /// distinct items with a false positive probability of
+    /// `false_positive_prob`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `depth` or `item_count` is 0, if `false_positive_prob` is
+    /// not strictly between 0 and 1, or if the levels are too large to
+    /// allocate.
+    pub fn new(depth: usize, item_count: usize, false_positive_prob: f64) -> AttenuatedBloomFilter<T> {
+        AttenuatedBloomFilter::with_hasher(depth, item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](AttenuatedBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(depth: usize, item_count: usize, false_positive_prob: f64, seed: u64) -> AttenuatedBloomFilter<T> {
+        let mut filter = AttenuatedBloomFilter::new(depth, item_count, false_positive_prob);
+        filter.seed = seed;
+        filter.levels = filter.build(depth);
+        filter
+    }
+}
+
+impl<T: Hash, S: BuildHasher + Clone> AttenuatedBloomFilter<T, S> {
+    /// Like [`new`](AttenuatedBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(
+        depth: usize,
+        item_count: usize,
+        false_positive_prob: f64,
+        hash_builder: S,
+    ) -> AttenuatedBloomFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        assert!(depth > 0, "the depth must be nonzero");
+        let mut filter = AttenuatedBloomFilter {
+            levels: Vec::new(),
+            item_count,
+            false_positive_prob,
+            seed: 0,
+            hash_builder,
+        };
+        filter.levels = filter.build(depth);
+        filter
+    }
+
+    /// Adds `item` to this node's own level, returning `true` if it was
+    /// probably there already.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.levels[0].insert(item)
+    }
+
+    /// Adds `item` as reachable `hops` hops away, returning `true` if it was
+    /// probably at that level already.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hops` is not less than the depth.
+    pub fn insert_at<Q: ?Sized + Hash>(&mut self, item: &Q, hops: usize) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.levels[hops].insert(item)
+    }
+
+    /// The fewest hops away `item` is probably reachable, or `None` if it
+    /// definitely isn't reachable within the depth.
+    pub fn distance<Q: ?Sized + Hash>(&self, item: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+    {
+        self.levels.iter().position(|level| level.contains(item))
+    }
+
+    /// Returns `true` if `item` is probably reachable within the depth.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.distance(item).is_some()
+    }
+
+    /// Returns `true` if `item` is probably reachable exactly `hops` hops
+    /// away, and `false` if it definitely isn't or `hops` is past the depth.
+    pub fn contains_at<Q: ?Sized + Hash>(&self, item: &Q, hops: usize) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.levels.get(hops).is_some_and(|level| level.contains(item))
+    }
+
+    /// Adds every level of `other` to the same level of this filter.
+    ///
+    /// Both filters must have the same depth and compatible levels, as for
+    /// [`BloomFilter::try_union`]; otherwise [`BloomError::Incompatible`] is
+    /// returned and this filter is unchanged.
+    pub fn try_merge(&mut self, other: &AttenuatedBloomFilter<T, S>) -> Result<()> {
+        self.check_compatible(other)?;
+        for (level, other) in self.levels.iter_mut().zip(other.levels.iter()) {
+            level.try_union(other)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a neighbour's advertised filter, one hop further away: level `d`
+    /// of `neighbor` is added to level `d + 1` of this filter, and the
+    /// neighbour's deepest level is dropped.
+    ///
+    /// Requires compatible filters, as for
+    /// [`try_merge`](AttenuatedBloomFilter::try_merge).
+    pub fn try_merge_shifted(&mut self, neighbor: &AttenuatedBloomFilter<T, S>) -> Result<()> {
+        self.check_compatible(neighbor)?;
+        for (level, other) in self.levels.iter_mut().skip(1).zip(neighbor.levels.iter()) {
+            level.try_union(other)?;
+        }
+        Ok(())
+    }
+
+    /// Moves every level one hop further away, dropping the deepest and
+    /// leaving this node's own level empty.
+    pub fn shift(&mut self) {
+        self.levels.rotate_right(1);
+        self.levels[0].clear();
+    }
+
+    /// Removes every item from every level, keeping the configuration.
+    pub fn clear(&mut self) {
+        for level in &mut self.levels {
+            level.clear();
+        }
+    }
+
+    /// Returns `true` if no level holds any items.
+    pub fn is_empty(&self) -> bool {
+        self.levels.iter().all(BloomFilter::is_empty)
+    }
+
+    /// The number of levels, one more than the furthest hop distance they
+    /// cover.
+    pub fn depth(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// The levels, nearest first.
+    pub fn levels(&self) -> &[BloomFilter<T, S>] {
+        &self.levels
+    }
+
+    /// The number of distinct items each level was sized for.
+    pub fn item_count(&self) -> usize {
+        self.item_count
+    }
+
+    /// The false positive probability each level was sized for.
+    pub fn false_positive_prob(&self) -> f64 {
+        self.false_positive_prob
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    fn check_compatible(&self, other: &AttenuatedBloomFilter<T, S>) -> Result<()> {
+        if self.levels.len() != other.levels.len() {
+            return Err(BloomError::Incompatible(format!(
+                "depths differ ({} and {})",
+                self.levels.len(),
+                other.levels.len()
+            )));
+        }
+        // Checking every level up front keeps a failed merge from changing
+        // some of them.
+        self.levels
+            .iter()
+            .zip(&other.levels)
+            .try_for_each(|(level, other)| level.check_compatible(other))
+    }
+
+    /// Creates `depth` empty levels.
+    fn build(&self, depth: usize) -> Vec<BloomFilter<T, S>> {
+        (0..depth)
+            .map(|_| {
+                let level = BloomFilterBuilder::new()
+                    .hasher(self.hash_builder.clone())
+                    .item_count(self.item_count)
+                    .false_positive_prob(self.false_positive_prob)
+                    .seed(self.seed)
+                    .build();
+                match level {
+                    Ok(level) => level,
+                    Err(e) => panic!("{}", e),
+                }
+            })
+            .collect()
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for AttenuatedBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher + Clone,
+{
+    /// Adds `item` to this node's own level.
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(AttenuatedBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        AttenuatedBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        AttenuatedBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        self.levels.iter().map(BloomFilter::estimated_len).sum()
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        1.0 - self.levels.iter().map(|level| 1.0 - level.current_fpr()).product::<f64>()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self)
+            + self
+                .levels
+                .iter()
+                .map(|level| ApproximateMembership::<Q>::memory_bytes(level))
+                .sum::<usize>()
+    }
+}
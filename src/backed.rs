@@ -0,0 +1,386 @@
+use alloc::format;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+#[cfg(feature = "allocator_api")]
+use allocator_api2::alloc::Allocator;
+
+use crate::probe::Probes;
+#[cfg(feature = "allocator_api")]
+use crate::storage::AllocBits;
+use crate::storage::{BitStorage, DenseBits};
+use crate::{math, params, ApproximateMembership, BloomError, DefaultBuildHasher, Result};
+
+/// A bloom filter that keeps its bits in any [`BitStorage`], so the same
+/// filter can be a plain bitmap or, with the `roaring` feature, a
+/// compressed `RoaringBitmap`.
+///
+/// A roaring bitmap takes a few bytes per set bit while the filter is
+/// sparse, and its unions and intersections skip the empty stretches, so
+/// combining many lightly filled filters is much cheaper than with plain
+/// bitmaps. Items map to the same bits as in a
+/// [`BloomFilter`](crate::BloomFilter) of the same parameters, whatever the
+/// storage.
+///
+/// ```
+/// use bloom::{BackedBloomFilter, DenseBits};
+///
+/// let mut filter = BackedBloomFilter::<&str, DenseBits>::new(1000, 0.01);
+/// filter.insert(&"apple");
+/// assert!(filter.contains(&"apple"));
+/// assert!(!filter.contains(&"pear"));
+/// ```
+#[derive(Debug)]
+pub struct BackedBloomFilter<T, B = DenseBits, S = DefaultBuildHasher> {
+    bits: B,
+    bit_vec_size: u64,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    hash_count: usize,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, B: Clone, S: Clone> Clone for BackedBloomFilter<T, B, S> {
+    fn clone(&self) -> BackedBloomFilter<T, B, S> {
+        BackedBloomFilter {
+            bits: self.bits.clone(),
+            bit_vec_size: self.bit_vec_size,
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            hash_count: self.hash_count,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash, B: BitStorage> BackedBloomFilter<T, B> {
+    /// Creates a filter sized to hold `item_count` items with the given
+    /// false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0, if `false_positive_prob` is not strictly
+    /// between 0 and 1, or if the storage can't hold the bits needed.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> BackedBloomFilter<T, B> {
+        BackedBloomFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](BackedBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> BackedBloomFilter<T, B> {
+        let mut filter = BackedBloomFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter with exactly `bits` bits and `hashes` hash functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` or `hashes` is 0, or if the storage can't hold
+    /// `bits` bits.
+    pub fn from_params(bits: u64, hashes: usize) -> BackedBloomFilter<T, B> {
+        BackedBloomFilter::from_params_with_hasher(bits, hashes, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, B: BitStorage, S: BuildHasher> BackedBloomFilter<T, B, S> {
+    /// Like [`new`](BackedBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> BackedBloomFilter<T, B, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let bits = params::optimal_bits(item_count, false_positive_prob);
+        let mut filter =
+            BackedBloomFilter::from_params_with_hasher(bits, params::optimal_hashes(bits, item_count), hash_builder);
+        filter.item_count = Some(item_count);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](BackedBloomFilter::from_params), but hashes
+    /// items with `hash_builder`.
+    pub fn from_params_with_hasher(bits: u64, hashes: usize, hash_builder: S) -> BackedBloomFilter<T, B, S> {
+        if let Err(e) = params::validate_layout(bits, hashes) {
+            panic!("{}", e);
+        }
+        let storage = match B::with_len(bits) {
+            Ok(storage) => storage,
+            Err(e) => panic!("{}", e),
+        };
+        BackedBloomFilter::from_storage(storage, bits, hashes, hash_builder)
+    }
+
+    /// Creates a filter over `storage`, which must hold `bits` cleared bits.
+    fn from_storage(storage: B, bits: u64, hashes: usize, hash_builder: S) -> BackedBloomFilter<T, B, S> {
+        BackedBloomFilter {
+            bits: storage,
+            bit_vec_size: bits,
+            item_count: None,
+            false_positive_prob: None,
+            hash_count: hashes,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let mut present = true;
+        for index in self.probes(item) {
+            if !self.bits.set(index) {
+                present = false;
+            }
+        }
+        present
+    }
+
+    /// Returns `true` if `item` has probably been added, and `false` if it
+    /// definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.probes(item).all(|index| self.bits.get(index))
+    }
+
+    /// Adds every item in `other` to this filter, so that it holds the union
+    /// of both sets.
+    ///
+    /// Both filters must have the same bit vector size, hash count and seed;
+    /// otherwise [`BloomError::Incompatible`] is returned and this filter is
+    /// unchanged.
+    pub fn try_union(&mut self, other: &BackedBloomFilter<T, B, S>) -> Result<()> {
+        self.check_compatible(other)?;
+        self.bits.union_with(&other.bits);
+        Ok(())
+    }
+
+    /// Keeps only the bits set in both this filter and `other`, approximating
+    /// the intersection of their sets, as
+    /// [`BloomFilter::try_intersect`](crate::BloomFilter::try_intersect)
+    /// does.
+    pub fn try_intersect(&mut self, other: &BackedBloomFilter<T, B, S>) -> Result<()> {
+        self.check_compatible(other)?;
+        self.bits.intersect_with(&other.bits);
+        Ok(())
+    }
+
+    /// Removes every item from the filter, keeping its parameters.
+    pub fn clear(&mut self) {
+        self.bits.clear();
+    }
+
+    /// Returns `true` if nothing has been added since the filter was created
+    /// or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.count_ones() == 0
+    }
+
+    /// The number of bits that are set.
+    pub fn count_ones(&self) -> u64 {
+        self.bits.count_ones()
+    }
+
+    /// The fraction of bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.bit_vec_size as f64
+    }
+
+    /// Estimates the probability that a lookup of an absent item returns
+    /// `true`, as the filter is now.
+    pub fn current_fpr(&self) -> f64 {
+        math::powf(self.fill_ratio(), self.hash_count as f64)
+    }
+
+    /// Estimates how many distinct items have been added, from the number of
+    /// bits that are set.
+    pub fn estimated_len(&self) -> f64 {
+        let m = self.bit_vec_size as f64;
+        -m / self.hash_count as f64 * math::ln(1.0 - self.count_ones() as f64 / m)
+    }
+
+    /// The number of bits in the filter.
+    pub fn bit_vec_size(&self) -> u64 {
+        self.bit_vec_size
+    }
+
+    /// The number of hash functions.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The false positive probability the filter was sized for, if it was
+    /// created from one.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The number of items the filter was sized for, if it was created from
+    /// one.
+    pub fn capacity(&self) -> Option<usize> {
+        self.item_count
+    }
+
+    /// The storage holding the filter's bits.
+    pub fn storage(&self) -> &B {
+        &self.bits
+    }
+
+    /// The number of bytes of memory the filter occupies, including its
+    /// storage's heap allocations.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + self.bits.heap_bytes()
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    fn check_compatible(&self, other: &BackedBloomFilter<T, B, S>) -> Result<()> {
+        if self.bit_vec_size != other.bit_vec_size {
+            return Err(BloomError::Incompatible(format!(
+                "bit vector sizes differ ({} and {})",
+                self.bit_vec_size, other.bit_vec_size
+            )));
+        }
+        if self.hash_count != other.hash_count {
+            return Err(BloomError::Incompatible(format!(
+                "hash counts differ ({} and {})",
+                self.hash_count, other.hash_count
+            )));
+        }
+        if self.seed != other.seed {
+            return Err(BloomError::Incompatible(format!("seeds differ ({} and {})", self.seed, other.seed)));
+        }
+        Ok(())
+    }
+
+    /// The bits `item` maps to, as in a [`BloomFilter`](crate::BloomFilter).
+    fn probes<Q: ?Sized + Hash>(&self, item: &Q) -> Probes<S::Hasher> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let size = self.bit_vec_size;
+        Probes::new(hasher, size, self.hash_count, size > params::WIDE_HASH_THRESHOLD)
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<T: Hash, A: Allocator> BackedBloomFilter<T, AllocBits<A>> {
+    /// Like [`new`](BackedBloomFilter::new), but allocates the bits from
+    /// `alloc`.
+    ///
+    /// ```
+    /// use allocator_api2::alloc::Global;
+    /// use bloom::{AllocBits, BackedBloomFilter};
+    ///
+    /// let mut filter = BackedBloomFilter::<&str, AllocBits>::new_in(1000, 0.01, Global);
+    /// filter.insert(&"apple");
+    /// assert!(filter.contains(&"apple"));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0, if `false_positive_prob` is not strictly
+    /// between 0 and 1, or if `alloc` can't provide the bits.
+    pub fn new_in(item_count: usize, false_positive_prob: f64, alloc: A) -> BackedBloomFilter<T, AllocBits<A>> {
+        BackedBloomFilter::with_hasher_in(item_count, false_positive_prob, DefaultBuildHasher::default(), alloc)
+    }
+
+    /// Like [`from_params`](BackedBloomFilter::from_params), but allocates
+    /// the bits from `alloc`.
+    pub fn from_params_in(bits: u64, hashes: usize, alloc: A) -> BackedBloomFilter<T, AllocBits<A>> {
+        BackedBloomFilter::from_params_with_hasher_in(bits, hashes, DefaultBuildHasher::default(), alloc)
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<T: Hash, A: Allocator, S: BuildHasher> BackedBloomFilter<T, AllocBits<A>, S> {
+    /// Like [`new_in`](BackedBloomFilter::new_in), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher_in(
+        item_count: usize,
+        false_positive_prob: f64,
+        hash_builder: S,
+        alloc: A,
+    ) -> BackedBloomFilter<T, AllocBits<A>, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let bits = params::optimal_bits(item_count, false_positive_prob);
+        let hashes = params::optimal_hashes(bits, item_count);
+        let mut filter = BackedBloomFilter::from_params_with_hasher_in(bits, hashes, hash_builder, alloc);
+        filter.item_count = Some(item_count);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params_in`](BackedBloomFilter::from_params_in), but
+    /// hashes items with `hash_builder`.
+    pub fn from_params_with_hasher_in(
+        bits: u64,
+        hashes: usize,
+        hash_builder: S,
+        alloc: A,
+    ) -> BackedBloomFilter<T, AllocBits<A>, S> {
+        if let Err(e) = params::validate_layout(bits, hashes) {
+            panic!("{}", e);
+        }
+        match AllocBits::new_in(bits, alloc) {
+            Ok(storage) => BackedBloomFilter::from_storage(storage, bits, hashes, hash_builder),
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+impl<T, Q, B, S> ApproximateMembership<Q> for BackedBloomFilter<T, B, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    B: BitStorage,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(BackedBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        BackedBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        BackedBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        BackedBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        BackedBloomFilter::memory_bytes(self)
+    }
+}
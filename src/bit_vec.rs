@@ -0,0 +1,185 @@
+use alloc::alloc::{self as allocator, Layout};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec;
+use core::mem;
+use core::ptr;
+use core::slice;
+
+use crate::{BloomError, Result};
+
+/// Eight words filling one 64-byte cache line, aligned so that they never
+/// straddle two lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(C, align(64))]
+pub(crate) struct CacheLine(pub(crate) [u64; 8]);
+
+// Lines must tile memory exactly, with no padding between their words, for
+// the words to be viewed as one slice.
+const _: () = assert!(mem::size_of::<CacheLine>() == 64 && mem::align_of::<CacheLine>() == 64);
+
+/// A fixed-size array of bits stored in 64-bit words.
+///
+/// Bit `i` is bit `i % 64` of word `i / 64`. Bits past `len` in the last word
+/// are always zero, so whole words can be counted and compared.
+///
+/// The words are stored in whole cache lines, so the first word starts a
+/// line and a probe's word never straddles two. Words past those `len` bits
+/// need, up to the end of the last line, are always zero and never exposed.
+///
+/// Bit indices are `u64` so that the number of bits isn't limited by the
+/// width of `usize`; they are only converted to `usize` to index a word.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BitVec {
+    lines: Box<[CacheLine]>,
+    len: u64,
+}
+
+impl BitVec {
+    /// Creates `len` cleared bits.
+    ///
+    /// `len` must be at most [`MAX_BITS`](crate::params::MAX_BITS).
+    pub(crate) fn new(len: u64) -> BitVec {
+        BitVec {
+            lines: vec![CacheLine::default(); line_count(len)].into_boxed_slice(),
+            len,
+        }
+    }
+
+    /// Like [`new`](BitVec::new), but returns [`BloomError::Capacity`] if the
+    /// allocator can't provide the memory, rather than aborting.
+    pub(crate) fn try_new(len: u64) -> Result<BitVec> {
+        let count = line_count(len);
+        if count == 0 {
+            return Ok(BitVec::new(len));
+        }
+        let error = || BloomError::Capacity(format!("{} bytes of memory can't be allocated", heap_bytes(len)));
+        let layout = Layout::array::<CacheLine>(count).map_err(|_| error())?;
+        // SAFETY: the layout isn't zero-sized, since `count` isn't zero.
+        let lines = unsafe { allocator::alloc_zeroed(layout) } as *mut CacheLine;
+        if lines.is_null() {
+            return Err(error());
+        }
+        // SAFETY: the allocation came from the global allocator with the
+        // layout of `count` lines, which `Box` frees it with, and all-zero
+        // bytes are valid lines.
+        let lines = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(lines, count)) };
+        Ok(BitVec { lines, len })
+    }
+
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// The number of bytes the bits occupy on the heap, including the unused
+    /// words that round them up to whole cache lines.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        mem::size_of_val(&*self.lines)
+    }
+
+    /// The words holding the bits, starting at a cache line boundary.
+    pub(crate) fn words(&self) -> &[u64] {
+        // SAFETY: `CacheLine` is eight `u64`s with no padding, as asserted
+        // above, so the lines are `8 * lines.len()` contiguous words, of
+        // which `word_count(len)` are in use.
+        unsafe { slice::from_raw_parts(self.lines.as_ptr() as *const u64, word_count(self.len)) }
+    }
+
+    /// The words holding the bits, for filling them in place. Bits past `len`
+    /// in the last word must be left clear.
+    pub(crate) fn words_mut(&mut self) -> &mut [u64] {
+        // SAFETY: as for `words`, and the lines are borrowed exclusively.
+        unsafe { slice::from_raw_parts_mut(self.lines.as_mut_ptr() as *mut u64, word_count(self.len)) }
+    }
+
+    /// Moves the lines out, leaving none, so that they can be shared between
+    /// threads as atomics. They must be put back with
+    /// [`restore_lines`](BitVec::restore_lines) before the bits are used.
+    #[cfg(feature = "std")]
+    pub(crate) fn take_lines(&mut self) -> Box<[CacheLine]> {
+        mem::take(&mut self.lines)
+    }
+
+    /// Puts back the lines taken by [`take_lines`](BitVec::take_lines).
+    #[cfg(feature = "std")]
+    pub(crate) fn restore_lines(&mut self, lines: Box<[CacheLine]>) {
+        debug_assert_eq!(lines.len(), line_count(self.len));
+        self.lines = lines;
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, index: u64) -> bool {
+        debug_assert!(index < self.len);
+        self.lines[(index / 512) as usize].0[(index / 64 % 8) as usize] & (1 << (index % 64)) != 0
+    }
+
+    /// Sets bit `index`, returning whether it was already set.
+    #[inline]
+    pub(crate) fn set(&mut self, index: u64) -> bool {
+        debug_assert!(index < self.len);
+        let word = &mut self.lines[(index / 512) as usize].0[(index / 64 % 8) as usize];
+        let mask = 1 << (index % 64);
+        let was_set = *word & mask != 0;
+        *word |= mask;
+        was_set
+    }
+
+    pub(crate) fn clear(&mut self) {
+        for line in self.lines.iter_mut() {
+            *line = CacheLine::default();
+        }
+    }
+
+    pub(crate) fn none(&self) -> bool {
+        self.words().iter().all(|&word| word == 0)
+    }
+
+    pub(crate) fn count_ones(&self) -> u64 {
+        self.words().iter().map(|word| u64::from(word.count_ones())).sum()
+    }
+
+    /// Counts the bits set in `op` applied word by word to both vectors,
+    /// without building the combined vector.
+    pub(crate) fn count_ones_with<F: Fn(u64, u64) -> u64>(&self, other: &BitVec, op: F) -> u64 {
+        self.words()
+            .iter()
+            .zip(other.words().iter())
+            .map(|(&a, &b)| u64::from(op(a, b).count_ones()))
+            .sum()
+    }
+
+    /// Sets every bit that is set in `other`. Both must be the same length.
+    pub(crate) fn union(&mut self, other: &BitVec) {
+        debug_assert_eq!(self.len, other.len);
+        for (a, &b) in self.words_mut().iter_mut().zip(other.words().iter()) {
+            *a |= b;
+        }
+    }
+
+    /// Clears every bit that is clear in `other`. Both must be the same length.
+    pub(crate) fn intersect(&mut self, other: &BitVec) {
+        debug_assert_eq!(self.len, other.len);
+        for (a, &b) in self.words_mut().iter_mut().zip(other.words().iter()) {
+            *a &= b;
+        }
+    }
+}
+
+/// The number of words needed to hold `len` bits.
+///
+/// The conversion can't truncate for any `len` up to `MAX_BITS`, which is
+/// chosen so that the words fit in memory.
+pub(crate) fn word_count(len: u64) -> usize {
+    debug_assert!(len <= crate::params::MAX_BITS);
+    len.div_ceil(64) as usize
+}
+
+/// The number of cache lines needed to hold `len` bits.
+fn line_count(len: u64) -> usize {
+    word_count(len).div_ceil(8)
+}
+
+/// The number of bytes a bit vector of `len` bits occupies on the heap.
+pub(crate) fn heap_bytes(len: u64) -> u64 {
+    line_count(len) as u64 * mem::size_of::<CacheLine>() as u64
+}
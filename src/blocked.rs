@@ -0,0 +1,417 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp;
+use core::convert::TryFrom;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::probe;
+use crate::simd;
+use crate::{math, memory, params, ApproximateMembership, BloomError, DefaultBuildHasher, Result};
+
+/// The number of bits in a block: one 64-byte cache line.
+const BLOCK_BITS: u64 = 512;
+
+/// The most hash functions [`new`](BlockedBloomFilter::new) gives a filter.
+///
+/// Past this the bits an item sets crowd its block, so larger filters gain
+/// more from fewer hashes; the optimal `k` for very low targets would also
+/// grow with the filter and never let the sizing converge.
+const MAX_HASHES: usize = 16;
+
+/// The number of bits of hash that pick one bit of a block.
+const PROBE_BITS: u32 = 9;
+
+/// The number of items [`contains_many`](BlockedBloomFilter::contains_many)
+/// hashes before reading any blocks.
+const BATCH_SIZE: usize = 8;
+
+/// One cache line of bits, aligned so that it never straddles two lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(C, align(64))]
+struct Block([u64; 8]);
+
+// A block must be exactly one line, starting on a line boundary, for every
+// probe of an item to hit the same line.
+const _: () = assert!(mem::size_of::<Block>() == 64 && mem::align_of::<Block>() == 64);
+
+/// A bloom filter whose items each set all of their bits within a single
+/// 64-byte block (Putze et al., "Cache-, Hash- and Space-Efficient Bloom
+/// Filters").
+///
+/// The first hash of an item picks its block, and each probe within that
+/// block is a separate 9-bit slice of further hashes, so a lookup touches
+/// one cache line where a [`BloomFilter`](crate::BloomFilter) touches `k`.
+/// For filters much larger than the CPU caches that makes lookups several
+/// times faster.
+///
+/// The price is a higher false positive rate for the same number of bits,
+/// since some blocks receive more than their share of items.
+/// [`new`](BlockedBloomFilter::new) compensates by sizing the filter with
+/// [`expected_blocked_fpr`](params::expected_blocked_fpr), which typically
+/// takes 10 to 20% more memory.
+///
+/// ```
+/// use bloom::BlockedBloomFilter;
+///
+/// let mut filter = BlockedBloomFilter::<String>::new(1000, 0.01);
+/// filter.add("apple");
+/// assert!(filter.contains("apple"));
+/// ```
+#[derive(Debug)]
+pub struct BlockedBloomFilter<T, S = DefaultBuildHasher> {
+    blocks: Box<[Block]>,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    hash_count: usize,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for BlockedBloomFilter<T, S> {
+    fn clone(&self) -> BlockedBloomFilter<T, S> {
+        BlockedBloomFilter {
+            blocks: self.blocks.clone(),
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            hash_count: self.hash_count,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> BlockedBloomFilter<T> {
+    /// Creates a filter sized to hold `item_count` items with the given
+    /// false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0, if `false_positive_prob` is not strictly
+    /// between 0 and 1 or too low for any blocked filter to reach, or if the
+    /// blocks can't be addressed. Use [`try_new`](BlockedBloomFilter::try_new)
+    /// to get an error instead.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> BlockedBloomFilter<T> {
+        BlockedBloomFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](BlockedBloomFilter::new), but returns an error rather
+    /// than panicking.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::InvalidParams`] if the parameters are invalid or
+    /// the target can't be reached with 512-bit blocks, and
+    /// [`BloomError::Capacity`] if the blocks don't fit in memory.
+    pub fn try_new(item_count: usize, false_positive_prob: f64) -> Result<BlockedBloomFilter<T>> {
+        BlockedBloomFilter::try_with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](BlockedBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> BlockedBloomFilter<T> {
+        let mut filter = BlockedBloomFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter with `blocks` blocks of 512 bits and `hashes` hash
+    /// functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either is 0, or if the blocks can't be addressed.
+    pub fn from_params(blocks: u64, hashes: usize) -> BlockedBloomFilter<T> {
+        BlockedBloomFilter::from_params_with_hasher(blocks, hashes, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> BlockedBloomFilter<T, S> {
+    /// Like [`new`](BlockedBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> BlockedBloomFilter<T, S> {
+        match BlockedBloomFilter::try_with_hasher(item_count, false_positive_prob, hash_builder) {
+            Ok(filter) => filter,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like [`try_new`](BlockedBloomFilter::try_new), but hashes items with
+    /// `hash_builder`.
+    pub fn try_with_hasher(
+        item_count: usize,
+        false_positive_prob: f64,
+        hash_builder: S,
+    ) -> Result<BlockedBloomFilter<T, S>> {
+        params::validate(item_count, false_positive_prob)?;
+        // Grow the filter from the unblocked size until the blocked rate
+        // meets the target.
+        let hashes = |bits| cmp::min(params::optimal_hashes(bits, item_count), MAX_HASHES);
+        let start = params::optimal_bits(item_count, false_positive_prob);
+        let mut bits = start;
+        let mut hash_count = hashes(bits);
+        while params::expected_blocked_fpr(bits, item_count, hash_count, BLOCK_BITS) > false_positive_prob {
+            if bits == params::MAX_BITS && start == params::MAX_BITS {
+                return Err(BloomError::Capacity(format!("{} items can't fit in any filter", item_count)));
+            }
+            if bits == params::MAX_BITS {
+                return Err(BloomError::InvalidParams(format!(
+                    "a blocked filter of {} items can't reach a false positive probability of {}",
+                    item_count, false_positive_prob
+                )));
+            }
+            bits = cmp::min(math::ceil(bits as f64 * 1.05) as u64, params::MAX_BITS);
+            hash_count = hashes(bits);
+        }
+        let blocks = bits.div_ceil(BLOCK_BITS);
+        params::validate_layout(blocks.saturating_mul(BLOCK_BITS), hash_count)?;
+        memory::check(blocks.saturating_mul(mem::size_of::<Block>() as u64), None)?;
+        let len = usize::try_from(blocks)
+            .map_err(|_| BloomError::Capacity(format!("{} blocks can't be addressed", blocks)))?;
+        let mut vec = Vec::new();
+        vec.try_reserve_exact(len)
+            .map_err(|_| BloomError::Capacity(format!("{} blocks can't be allocated", blocks)))?;
+        vec.resize(len, Block::default());
+        Ok(BlockedBloomFilter {
+            blocks: vec.into_boxed_slice(),
+            item_count: Some(item_count),
+            false_positive_prob: Some(false_positive_prob),
+            hash_count,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Like [`from_params`](BlockedBloomFilter::from_params), but hashes
+    /// items with `hash_builder`.
+    pub fn from_params_with_hasher(blocks: u64, hashes: usize, hash_builder: S) -> BlockedBloomFilter<T, S> {
+        let len = params::validate_layout(blocks.saturating_mul(BLOCK_BITS), hashes).and_then(|()| {
+            usize::try_from(blocks)
+                .map_err(|_| BloomError::Capacity(format!("{} blocks can't be addressed", blocks)))
+        });
+        let len = match len {
+            Ok(len) => len,
+            Err(e) => panic!("{}", e),
+        };
+        BlockedBloomFilter {
+            blocks: vec![Block::default(); len].into_boxed_slice(),
+            item_count: None,
+            false_positive_prob: None,
+            hash_count: hashes,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item` in the filter.
+    pub fn add<Q: ?Sized + Hash>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+    {
+        self.insert(item);
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (block, mask) = self.locate(item);
+        let words = &mut self.blocks[block].0;
+        let present = simd::covers(words, &mask);
+        for (word, mask) in words.iter_mut().zip(mask.iter()) {
+            *word |= mask;
+        }
+        present
+    }
+
+    /// Returns `true` if `item` has probably been added, and `false` if it
+    /// definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (block, mask) = self.locate(item);
+        simd::covers(&self.blocks[block].0, &mask)
+    }
+
+    /// Checks each of `items`, returning whether each is probably present.
+    ///
+    /// Equivalent to calling [`contains`](BlockedBloomFilter::contains) on
+    /// each item, but a batch of eight items is hashed and their blocks
+    /// prefetched before any block is read, so hashing and memory accesses
+    /// can overlap. Each item is still hashed on its own; only the test of
+    /// its bits against the block uses SIMD.
+    pub fn contains_many(&self, items: &[T]) -> Vec<bool> {
+        let mut results = Vec::with_capacity(items.len());
+        for batch in items.chunks(BATCH_SIZE) {
+            let mut probed = [(0, [0; 8]); BATCH_SIZE];
+            for (probed, item) in probed.iter_mut().zip(batch) {
+                let (block, mask) = self.locate(item);
+                simd::prefetch(&self.blocks[block]);
+                *probed = (block, mask);
+            }
+            results.extend(
+                probed[..batch.len()]
+                    .iter()
+                    .map(|(block, mask)| simd::covers(&self.blocks[*block].0, mask)),
+            );
+        }
+        results
+    }
+
+    /// Removes every item from the filter, keeping its allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        for block in self.blocks.iter_mut() {
+            *block = Block::default();
+        }
+    }
+
+    /// Returns `true` if nothing has been added since the filter was created
+    /// or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.iter().all(|block| block.0.iter().all(|&word| word == 0))
+    }
+
+    /// The number of bits that are set.
+    pub fn count_ones(&self) -> u64 {
+        self.blocks.iter().map(count_ones).sum()
+    }
+
+    /// Estimates the false positive probability of the filter as it is now:
+    /// the mean over blocks of each block's fill ratio to the power `k`.
+    pub fn current_fpr(&self) -> f64 {
+        let k = self.hash_count as f64;
+        let sum: f64 = self
+            .blocks
+            .iter()
+            .map(|block| math::powf(count_ones(block) as f64 / BLOCK_BITS as f64, k))
+            .sum();
+        sum / self.blocks.len() as f64
+    }
+
+    /// Estimates how many distinct items have been added, from the number of
+    /// bits set in each block.
+    ///
+    /// Returns infinity once any block is full.
+    pub fn estimated_len(&self) -> f64 {
+        let m = BLOCK_BITS as f64;
+        self.blocks
+            .iter()
+            .map(|block| -m / self.hash_count as f64 * math::ln(1.0 - count_ones(block) as f64 / m))
+            .sum()
+    }
+
+    /// The number of bits in the filter.
+    pub fn bit_vec_size(&self) -> u64 {
+        self.blocks.len() as u64 * BLOCK_BITS
+    }
+
+    /// The number of blocks.
+    pub fn block_count(&self) -> u64 {
+        self.blocks.len() as u64
+    }
+
+    /// The number of hash functions applied to each item.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The false positive probability the filter was sized for, or `None`
+    /// if it was built from explicit parameters.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The number of items the filter was sized for, or `None` if it was
+    /// built from explicit parameters.
+    pub fn capacity(&self) -> Option<usize> {
+        self.item_count
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The block `item` maps to, and the bits it sets there, so that a
+    /// lookup can test them all at once.
+    ///
+    /// Each probe is its own 9-bit slice of hash, seven to a 64-bit hash, so
+    /// the probes are as independent as the hasher's output. Double hashing
+    /// within a block would allow only about 2^17 patterns of bits, and items
+    /// sharing a pattern collide whatever the filter's size.
+    fn locate<Q: ?Sized + Hash>(&self, item: &Q) -> (usize, [u64; 8]) {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let block = probe::reduce(hasher.finish(), self.blocks.len() as u64) as usize;
+        let mut mask = [0; 8];
+        let (mut hash, mut remaining) = (0, 0);
+        for i in 0..self.hash_count {
+            if remaining < PROBE_BITS {
+                // So that the probes don't reuse the hash that chose the
+                // block, or each other's.
+                hasher.write_u8(0xfc ^ i as u8);
+                hash = hasher.finish();
+                remaining = 64;
+            }
+            let index = hash % BLOCK_BITS;
+            mask[(index / 64) as usize] |= 1 << (index % 64);
+            hash >>= PROBE_BITS;
+            remaining -= PROBE_BITS;
+        }
+        (block, mask)
+    }
+}
+
+fn count_ones(block: &Block) -> u64 {
+    block.0.iter().map(|word| u64::from(word.count_ones())).sum()
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for BlockedBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(BlockedBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        BlockedBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        BlockedBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        BlockedBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(&*self.blocks)
+    }
+}
@@ -0,0 +1,321 @@
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::convert::TryFrom;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::hash::fmix64;
+use crate::packed::PackedVec;
+use crate::{math, probe, quotient, BloomError, DefaultBuildHasher, Result};
+
+/// The slots per key construction starts with: a little above the 1.222 at
+/// which a random 3-hypergraph stops being peelable.
+const INITIAL_OVERHEAD: f64 = 1.23;
+
+/// The extra slots per key construction adds each time it fails.
+const OVERHEAD_STEP: f64 = 0.02;
+
+/// How many times construction retries before giving up.
+const MAX_ATTEMPTS: u32 = 20;
+
+/// An immutable map from keys to small values, built once from every entry
+/// (Chazelle et al., "The Bloomier filter: an efficient data structure for
+/// static support lookup tables"), that never stores the keys themselves.
+///
+/// Each key maps to three slots of a table of `v + f`-bit words, one in each
+/// third of the table, and construction chooses the words so that the three
+/// XOR to the key's `v`-bit value beside an `f`-bit fingerprint of the key.
+/// As with a [`XorFilter`](crate::XorFilter), the table is solved by peeling
+/// keys off slots only they map to, and takes about `1.23 (v + f)` bits per
+/// key.
+///
+/// A lookup of a key returns its value. A lookup of anything else returns
+/// `None` unless its fingerprint happens to match, with probability `2^-f`,
+/// in which case it returns an arbitrary value. That suits routing and
+/// classification tables whose lookups are mostly of known keys, or whose
+/// callers can tolerate the odd misrouted stranger.
+///
+/// ```
+/// use bloom::BloomierFilter;
+///
+/// let routes = [("10.0.0.1", 3), ("10.0.0.2", 7), ("10.0.0.3", 3)];
+/// let table = BloomierFilter::<&str>::from_entries(routes.iter().copied(), 4, 0.001).unwrap();
+/// assert_eq!(table.get(&"10.0.0.2"), Some(7));
+/// ```
+#[derive(Debug)]
+pub struct BloomierFilter<K, S = DefaultBuildHasher> {
+    /// Each slot's value in the low `value_bits` bits, and fingerprint above.
+    slots: PackedVec,
+    value_bits: u32,
+    /// The number of slots in each third of the table.
+    block_len: u64,
+    len: u64,
+    salt: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(K)>,
+}
+
+// Implemented by hand because deriving would require `K: Clone`.
+impl<K, S: Clone> Clone for BloomierFilter<K, S> {
+    fn clone(&self) -> BloomierFilter<K, S> {
+        BloomierFilter {
+            slots: self.slots.clone(),
+            value_bits: self.value_bits,
+            block_len: self.block_len,
+            len: self.len,
+            salt: self.salt,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<K: Hash> BloomierFilter<K> {
+    /// Builds a map of `entries`, whose values must fit in `value_bits` bits,
+    /// looking up a key that isn't one of them with a probability of at most
+    /// `false_positive_prob` of returning a value.
+    ///
+    /// # Errors
+    ///
+    /// As for [`from_entries_with_hasher`](BloomierFilter::from_entries_with_hasher).
+    pub fn from_entries<I, Q>(entries: I, value_bits: u32, false_positive_prob: f64) -> Result<BloomierFilter<K>>
+    where
+        I: IntoIterator<Item = (Q, u64)>,
+        Q: Borrow<K>,
+    {
+        let hash_builder = DefaultBuildHasher::default();
+        BloomierFilter::from_entries_with_hasher(entries, value_bits, false_positive_prob, hash_builder)
+    }
+}
+
+impl<K: Hash, S: BuildHasher> BloomierFilter<K, S> {
+    /// Like [`from_entries`](BloomierFilter::from_entries), but hashes keys
+    /// with `hash_builder`.
+    ///
+    /// The false positive probability is rounded down to a power of two,
+    /// `2^-f` for `f` fingerprint bits. A key given more than once must have
+    /// the same value each time.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::InvalidParams`] unless `value_bits` is between 1
+    /// and 32 and `false_positive_prob` is strictly between 0 and 1, if a
+    /// value doesn't fit, or if a key is given two values.
+    /// [`BloomError::Capacity`] is returned if the table can't be addressed
+    /// or no table was found, which in practice only happens when the table
+    /// can't be allocated.
+    pub fn from_entries_with_hasher<I, Q>(
+        entries: I,
+        value_bits: u32,
+        false_positive_prob: f64,
+        hash_builder: S,
+    ) -> Result<BloomierFilter<K, S>>
+    where
+        I: IntoIterator<Item = (Q, u64)>,
+        Q: Borrow<K>,
+    {
+        if !(1..=32).contains(&value_bits) {
+            return Err(BloomError::InvalidParams(format!("values must be 1 to 32 bits (got {})", value_bits)));
+        }
+        if !(false_positive_prob > 0.0 && false_positive_prob < 1.0) {
+            return Err(BloomError::InvalidParams(format!(
+                "false positive probability must be strictly between 0 and 1 (got {})",
+                false_positive_prob
+            )));
+        }
+        let fingerprint_bits = (math::ceil(-math::log2(false_positive_prob)) as u32).clamp(1, 32);
+        let mut entries = entries
+            .into_iter()
+            .map(|(key, value)| {
+                if value > quotient::mask(value_bits) {
+                    return Err(BloomError::InvalidParams(format!(
+                        "value {} doesn't fit in {} bits",
+                        value, value_bits
+                    )));
+                }
+                let mut hasher = hash_builder.build_hasher();
+                hasher.write_u64(0);
+                key.borrow().hash(&mut hasher);
+                Ok((hasher.finish(), value))
+            })
+            .collect::<Result<Vec<(u64, u64)>>>()?;
+        entries.sort_unstable();
+        entries.dedup();
+        if let Some(pair) = entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(BloomError::InvalidParams(format!(
+                "a key has two values ({} and {})",
+                pair[0].1, pair[1].1
+            )));
+        }
+
+        let mut filter = BloomierFilter {
+            slots: PackedVec::new(0, 1)?,
+            value_bits,
+            block_len: 0,
+            len: 0,
+            salt: 0,
+            hash_builder,
+            phantom: PhantomData,
+        };
+        let mut overhead = INITIAL_OVERHEAD;
+        for attempt in 0..MAX_ATTEMPTS {
+            // The slack keeps small tables, which peel less reliably, from
+            // taking many attempts.
+            filter.block_len = math::ceil(entries.len() as f64 * overhead / 3.0) as u64 + 8;
+            filter.salt = u64::from(attempt);
+            if filter.solve(&entries, value_bits + fingerprint_bits)? {
+                return Ok(filter);
+            }
+            overhead += OVERHEAD_STEP;
+        }
+        Err(BloomError::Capacity(format!("no table found for {} keys", entries.len())))
+    }
+
+    /// The value of `key` if it is one of the keys the map was built from,
+    /// and otherwise probably `None`.
+    pub fn get<Q: ?Sized + Hash>(&self, key: &Q) -> Option<u64>
+    where
+        K: Borrow<Q>,
+    {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(0);
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+        let word = self
+            .positions(hash)
+            .iter()
+            .fold(self.fingerprint(hash) << self.value_bits, |word, &slot| word ^ self.slots.get(slot));
+        if word >> self.value_bits == 0 {
+            Some(word)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if `key` was probably one of the keys the map was built
+    /// from, and `false` if it definitely was not.
+    pub fn contains_key<Q: ?Sized + Hash>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Returns `true` if the map was built from no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of distinct keys the map was built from.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// The number of bits in each value.
+    pub fn value_bits(&self) -> u32 {
+        self.value_bits
+    }
+
+    /// The number of bits in each key's fingerprint, `f`.
+    pub fn fingerprint_bits(&self) -> u32 {
+        self.slots.width() - self.value_bits
+    }
+
+    /// The probability that `get` returns a value for a key that wasn't in
+    /// the map: `2^-f`.
+    pub fn false_positive_prob(&self) -> f64 {
+        math::powf(2.0, -(self.fingerprint_bits() as f64))
+    }
+
+    /// The number of slots in the table.
+    pub fn slot_count(&self) -> u64 {
+        self.slots.len()
+    }
+
+    /// The number of bytes of memory the map occupies, including its heap
+    /// allocation.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(self.slots.words())
+    }
+
+    /// The hasher keys are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Solves for a table of `slot_bits`-bit slots holding every entry of
+    /// `entries`, returning `false` if their slots couldn't all be peeled.
+    fn solve(&mut self, entries: &[(u64, u64)], slot_bits: u32) -> Result<bool> {
+        let slot_count = 3 * self.block_len;
+        self.slots = PackedVec::new(slot_count, slot_bits)?;
+        let len = usize::try_from(slot_count)
+            .map_err(|_| BloomError::Capacity(format!("{} slots can't be addressed", slot_count)))?;
+
+        // For each slot, the number of entries mapping to it and the XOR of
+        // their indices, so a slot with one entry left names it.
+        let mut counts = vec![0u32; len];
+        let mut indices = vec![0usize; len];
+        for (index, &(hash, _)) in entries.iter().enumerate() {
+            for &slot in self.positions(hash).iter() {
+                counts[slot as usize] += 1;
+                indices[slot as usize] ^= index;
+            }
+        }
+
+        // Peel entries off slots that only they map to, which may leave
+        // further slots with a single entry.
+        let mut stack = (0..len).filter(|&slot| counts[slot] == 1).collect::<Vec<usize>>();
+        let mut peeled = Vec::with_capacity(entries.len());
+        while let Some(slot) = stack.pop() {
+            if counts[slot] != 1 {
+                continue;
+            }
+            let index = indices[slot];
+            peeled.push((index, slot as u64));
+            for &other in self.positions(entries[index].0).iter() {
+                let other = other as usize;
+                counts[other] -= 1;
+                indices[other] ^= index;
+                if counts[other] == 1 {
+                    stack.push(other);
+                }
+            }
+        }
+        if peeled.len() != entries.len() {
+            return Ok(false);
+        }
+
+        // Assign words in the reverse of the order entries were peeled, so
+        // each entry's own slot is written after its other two are final.
+        for &(index, own) in peeled.iter().rev() {
+            let (hash, value) = entries[index];
+            let word = self
+                .positions(hash)
+                .iter()
+                .filter(|&&slot| slot != own)
+                .fold(self.fingerprint(hash) << self.value_bits | value, |word, &slot| word ^ self.slots.get(slot));
+            self.slots.set(own, word);
+        }
+        self.len = entries.len() as u64;
+        Ok(true)
+    }
+
+    /// The three slots `hash` maps to, one in each third of the table.
+    fn positions(&self, hash: u64) -> [u64; 3] {
+        let block = |i: u64, constant: u64| {
+            i * self.block_len + probe::reduce(fmix64(hash ^ self.salt ^ constant), self.block_len)
+        };
+        [
+            block(0, 0),
+            block(1, 0x5bd1_e995_9e37_79b9),
+            block(2, 0x2545_f491_4f6c_dd1d),
+        ]
+    }
+
+    fn fingerprint(&self, hash: u64) -> u64 {
+        fmix64(hash ^ self.salt ^ 0x9e37_79b9_7f4a_7c15) & quotient::mask(self.fingerprint_bits())
+    }
+}
@@ -0,0 +1,254 @@
+use alloc::format;
+use alloc::string::ToString;
+use core::cmp;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+
+use crate::bit_vec::{self, BitVec};
+use crate::filter::DefaultBuildHasher;
+#[cfg(feature = "hugepages")]
+use crate::madvise::{self, MemoryHints};
+use crate::{math, memory, params, BloomError, BloomFilter, Result};
+
+/// Configures and constructs a [`BloomFilter`].
+///
+/// The bit vector size is derived from the expected item count and false
+/// positive probability unless set explicitly; likewise the hash count is
+/// derived from the bit vector size unless set explicitly.
+///
+/// ```
+/// use bloom::BloomFilterBuilder;
+///
+/// let filter = BloomFilterBuilder::<String>::new()
+///     .item_count(10_000)
+///     .false_positive_prob(0.001)
+///     .seed(42)
+///     .build()
+///     .unwrap();
+/// assert_eq!(filter.seed(), 42);
+/// ```
+#[derive(Debug)]
+pub struct BloomFilterBuilder<T, S = DefaultBuildHasher> {
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    bit_vec_size: Option<u64>,
+    hash_count: Option<usize>,
+    power_of_two: bool,
+    wide_hashes: Option<bool>,
+    max_memory: Option<u64>,
+    seed: u64,
+    #[cfg(feature = "hugepages")]
+    memory_hints: MemoryHints,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+impl<T: Hash> BloomFilterBuilder<T> {
+    /// Creates a builder with nothing configured.
+    pub fn new() -> BloomFilterBuilder<T> {
+        BloomFilterBuilder {
+            item_count: None,
+            false_positive_prob: None,
+            bit_vec_size: None,
+            hash_count: None,
+            power_of_two: false,
+            wide_hashes: None,
+            max_memory: None,
+            seed: 0,
+            #[cfg(feature = "hugepages")]
+            memory_hints: MemoryHints::default(),
+            hash_builder: DefaultBuildHasher::default(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> Default for BloomFilterBuilder<T> {
+    fn default() -> BloomFilterBuilder<T> {
+        BloomFilterBuilder::new()
+    }
+}
+
+impl<T: Hash, S: BuildHasher> BloomFilterBuilder<T, S> {
+    /// The number of items the filter is expected to hold.
+    pub fn item_count(mut self, item_count: usize) -> Self {
+        self.item_count = Some(item_count);
+        self
+    }
+
+    /// The target false positive probability. Defaults to 0.01.
+    pub fn false_positive_prob(mut self, false_positive_prob: f64) -> Self {
+        self.false_positive_prob = Some(false_positive_prob);
+        self
+    }
+
+    /// Sets the number of bits explicitly instead of deriving it.
+    pub fn bit_vec_size(mut self, bit_vec_size: u64) -> Self {
+        self.bit_vec_size = Some(bit_vec_size);
+        self
+    }
+
+    /// Sets the number of hash functions explicitly instead of deriving it.
+    pub fn hash_count(mut self, hash_count: usize) -> Self {
+        self.hash_count = Some(hash_count);
+        self
+    }
+
+    /// Rounds the bit vector size up to a power of two, so that an item's
+    /// hashes are mapped onto bit indices with a mask rather than a
+    /// multiplication.
+    ///
+    /// This makes each lookup slightly cheaper at the cost of up to twice the
+    /// memory. The false positive rate never gets worse, since the filter
+    /// only gains bits (and the hash count is derived from the rounded size
+    /// unless set explicitly), but the extra memory would usually buy a lower
+    /// rate if spent on an exactly sized filter instead. Defaults to off.
+    pub fn power_of_two(mut self, power_of_two: bool) -> Self {
+        self.power_of_two = power_of_two;
+        self
+    }
+
+    /// Derives bit indices from 128 rather than 64 bits of hash output.
+    ///
+    /// An item's probes start from two hashes mapped onto the bit vector, and
+    /// a 64-bit hash mapped onto a multi-gigabit vector favours some bits
+    /// slightly over others. Wide hashes finish the hasher twice more per
+    /// item to map 128 bits instead, which is uniform at any size. Defaults
+    /// to on for filters of more than
+    /// [`WIDE_HASH_THRESHOLD`](params::WIDE_HASH_THRESHOLD) bits.
+    pub fn wide_hashes(mut self, wide_hashes: bool) -> Self {
+        self.wide_hashes = Some(wide_hashes);
+        self
+    }
+
+    /// The seed mixed into every hash. Defaults to 0.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Asks the kernel to back the bits with transparent huge pages
+    /// (`MADV_HUGEPAGE`).
+    ///
+    /// A lookup in a multi-gigabyte filter touches `k` random pages, and
+    /// with 4 KiB pages nearly every one misses the TLB; 2 MiB pages cover
+    /// the same memory with 512 times fewer entries. This only takes effect
+    /// if transparent huge pages are enabled in `always` or `madvise` mode.
+    /// Defaults to off.
+    #[cfg(feature = "hugepages")]
+    pub fn huge_pages(mut self, huge_pages: bool) -> Self {
+        self.memory_hints.huge_pages = huge_pages;
+        self
+    }
+
+    /// Tells the kernel the bits will be accessed in random order
+    /// (`MADV_RANDOM`), so it doesn't read ahead when paging them in.
+    /// Defaults to off.
+    #[cfg(feature = "hugepages")]
+    pub fn random_access(mut self, random_access: bool) -> Self {
+        self.memory_hints.random_access = random_access;
+        self
+    }
+
+    /// Tells the kernel the bits will be needed soon (`MADV_WILLNEED`), so
+    /// that it can page them in ahead of the first inserts. Defaults to off.
+    #[cfg(feature = "hugepages")]
+    pub fn will_need(mut self, will_need: bool) -> Self {
+        self.memory_hints.will_need = will_need;
+        self
+    }
+
+    /// The most memory, in bytes, the filter's bits may take. Defaults to no
+    /// limit beyond the memory the system has available.
+    ///
+    /// The available memory is `MemAvailable` from `/proc/meminfo`, so it is
+    /// only checked on Linux with the `std` feature, and there only for bits
+    /// of at least 64 MiB unless a limit is set. Elsewhere only the limit is
+    /// checked.
+    ///
+    /// ```
+    /// use bloom::{BloomError, BloomFilterBuilder};
+    ///
+    /// let result = BloomFilterBuilder::<u64>::new().item_count(1_000_000_000).max_memory(1 << 30).build();
+    /// assert!(matches!(result, Err(BloomError::Capacity(_))));
+    /// ```
+    pub fn max_memory(mut self, bytes: u64) -> Self {
+        self.max_memory = Some(bytes);
+        self
+    }
+
+    /// Uses `hash_builder` to hash items instead of the default hasher.
+    pub fn hasher<S2: BuildHasher>(self, hash_builder: S2) -> BloomFilterBuilder<T, S2> {
+        BloomFilterBuilder {
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            bit_vec_size: self.bit_vec_size,
+            hash_count: self.hash_count,
+            power_of_two: self.power_of_two,
+            wide_hashes: self.wide_hashes,
+            max_memory: self.max_memory,
+            seed: self.seed,
+            #[cfg(feature = "hugepages")]
+            memory_hints: self.memory_hints,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Builds the filter.
+    ///
+    /// Fails with [`BloomError::InvalidParams`] if neither an item count nor
+    /// a bit vector size was given, or if any of the parameters are out of
+    /// range, and with [`BloomError::Capacity`] if the bits would take more
+    /// than the [`max_memory`](BloomFilterBuilder::max_memory) limit or the
+    /// memory the system has available, or can't be allocated.
+    pub fn build(self) -> Result<BloomFilter<T, S>> {
+        let false_positive_prob = self.false_positive_prob.unwrap_or(params::DEFAULT_FALSE_POSITIVE_PROB);
+        params::validate(self.item_count.unwrap_or(1), false_positive_prob)?;
+        let derived = self.false_positive_prob.is_some()
+            || self.bit_vec_size.is_none()
+            || (self.hash_count.is_none() && self.item_count.is_none());
+        let bit_vec_size = match (self.bit_vec_size, self.item_count) {
+            (Some(m), _) => m,
+            (None, Some(n)) => params::optimal_bits(n, false_positive_prob),
+            (None, None) => {
+                return Err(BloomError::InvalidParams(
+                    "either an item count or a bit vector size is required".to_string(),
+                ))
+            }
+        };
+        let bit_vec_size = if self.power_of_two {
+            bit_vec_size.checked_next_power_of_two().ok_or_else(|| {
+                BloomError::Capacity(format!("{} bits can't be rounded up to a power of two", bit_vec_size))
+            })?
+        } else {
+            bit_vec_size
+        };
+        let hash_count = match (self.hash_count, self.item_count) {
+            (Some(k), _) => k,
+            (None, Some(n)) => params::optimal_hashes(bit_vec_size, n),
+            // Without an item count, use the hash count that is optimal for
+            // the target probability regardless of size: -log2(p).
+            (None, None) => cmp::max(math::ceil(-math::log2(false_positive_prob)) as usize, 1),
+        };
+        params::validate_layout(bit_vec_size, hash_count)?;
+        // Only report a target probability if one was actually used for sizing.
+        let false_positive_prob = if derived { Some(false_positive_prob) } else { None };
+        memory::check(bit_vec::heap_bytes(bit_vec_size), self.max_memory)?;
+        let bit_vec = BitVec::try_new(bit_vec_size)?;
+        #[cfg(feature = "hugepages")]
+        madvise::advise(bit_vec.words(), self.memory_hints);
+        let filter = BloomFilter::from_parts(
+            bit_vec,
+            hash_count,
+            self.item_count,
+            false_positive_prob,
+            self.seed,
+            self.hash_builder,
+        );
+        Ok(match self.wide_hashes {
+            Some(wide_hashes) => filter.with_wide_hashes(wide_hashes),
+            None => filter,
+        })
+    }
+}
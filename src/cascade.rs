@@ -0,0 +1,258 @@
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp;
+use core::convert::TryFrom;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use crate::hash::{read_le, xxh64};
+use crate::{math, ApproximateMembership, BloomError, BloomFilter, BloomFilterBuilder, DefaultBuildHasher, Result};
+
+/// The false positive probability of every level after the first.
+const LEVEL_FALSE_POSITIVE_PROB: f64 = 0.5;
+
+/// How many levels construction adds before concluding that it will never
+/// finish, because an item is in both sets.
+const MAX_LEVELS: usize = 64;
+
+const MAGIC: [u8; 4] = *b"BLMC";
+const FORMAT_VERSION: u16 = 1;
+const HEADER_LEN: usize = 8;
+const LEVEL_HEADER_LEN: usize = 24;
+
+/// A cascade of [`BloomFilter`]s that encodes a set exactly, as long as
+/// every item looked up is from a universe known when it is built
+/// (Larisch et al., "CRLite: A Scalable System for Pushing All TLS
+/// Revocations to All Browsers").
+///
+/// The first level holds the included items, and gives false positives for
+/// some of the excluded ones. The second level holds exactly those false
+/// positives, and gives false positives for some included items, which the
+/// third level holds, and so on until a level has no false positives among
+/// the items it must reject. A lookup walks down the levels until one
+/// doesn't contain the item: if that level holds included items the item is
+/// excluded, and otherwise included. Each level is about half the size of
+/// the one before, so the whole cascade takes little more than the first.
+///
+/// Items outside the universe get the same answers they would from the
+/// first level: no false negatives, but false positives.
+///
+/// ```
+/// use bloom::FilterCascade;
+///
+/// let revoked: Vec<u64> = (0..1000).collect();
+/// let valid: Vec<u64> = (1000..100_000).collect();
+/// let cascade = FilterCascade::<u64>::build(&revoked, &valid).unwrap();
+/// assert!(revoked.iter().all(|serial| cascade.contains(serial)));
+/// assert!(valid.iter().all(|serial| !cascade.contains(serial)));
+/// ```
+#[derive(Debug)]
+pub struct FilterCascade<T, S = DefaultBuildHasher> {
+    /// The levels, the first holding the included items.
+    levels: Vec<BloomFilter<T, S>>,
+    hash_builder: S,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for FilterCascade<T, S> {
+    fn clone(&self) -> FilterCascade<T, S> {
+        FilterCascade {
+            levels: self.levels.clone(),
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+impl<T: Hash> FilterCascade<T> {
+    /// Builds a cascade containing exactly the `included` items out of the
+    /// universe of `included` and `excluded`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`build_with_hasher`](FilterCascade::build_with_hasher).
+    pub fn build(included: &[T], excluded: &[T]) -> Result<FilterCascade<T>> {
+        FilterCascade::build_with_hasher(included, excluded, DefaultBuildHasher::default())
+    }
+
+    /// Decodes a cascade encoded by [`to_bytes`](FilterCascade::to_bytes).
+    ///
+    /// # Errors
+    ///
+    /// As for [`from_bytes_with_hasher`](FilterCascade::from_bytes_with_hasher).
+    pub fn from_bytes(bytes: &[u8]) -> Result<FilterCascade<T>> {
+        FilterCascade::from_bytes_with_hasher(bytes, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher + Clone> FilterCascade<T, S> {
+    /// Like [`build`](FilterCascade::build), but hashes items with
+    /// `hash_builder`.
+    ///
+    /// The first level's false positive probability is chosen from the
+    /// sizes of the two sets to make the cascade as small as it can be, and
+    /// every later level's is 1/2.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::InvalidParams`] if construction doesn't finish,
+    /// which happens when an item is in both sets, and
+    /// [`BloomError::Capacity`] if a level is too large to allocate.
+    pub fn build_with_hasher(included: &[T], excluded: &[T], hash_builder: S) -> Result<FilterCascade<T, S>> {
+        // Per Larisch et al., `r sqrt(p) / s` for `r` included and `s`
+        // excluded items and a later rate of `p`.
+        let first_false_positive_prob = if excluded.is_empty() {
+            LEVEL_FALSE_POSITIVE_PROB
+        } else {
+            let ratio = included.len() as f64 * math::sqrt(LEVEL_FALSE_POSITIVE_PROB) / excluded.len() as f64;
+            ratio.clamp(1e-9, LEVEL_FALSE_POSITIVE_PROB)
+        };
+        let mut insert: Vec<&T> = included.iter().collect();
+        let mut reject: Vec<&T> = excluded.iter().collect();
+        let mut levels: Vec<BloomFilter<T, S>> = Vec::new();
+        loop {
+            if levels.len() == MAX_LEVELS {
+                return Err(BloomError::InvalidParams(format!(
+                    "the cascade didn't finish within {} levels; is an item both included and excluded?",
+                    MAX_LEVELS
+                )));
+            }
+            let false_positive_prob = if levels.is_empty() {
+                first_false_positive_prob
+            } else {
+                LEVEL_FALSE_POSITIVE_PROB
+            };
+            // Each level has its own seed, so that its false positives are
+            // independent of the last one's.
+            let mut level = BloomFilterBuilder::new()
+                .hasher(hash_builder.clone())
+                .item_count(cmp::max(insert.len(), 1))
+                .false_positive_prob(false_positive_prob)
+                .seed(levels.len() as u64)
+                .build()?;
+            for item in &insert {
+                level.add(*item);
+            }
+            let false_positives: Vec<&T> = reject.into_iter().filter(|item| level.contains(*item)).collect();
+            levels.push(level);
+            if false_positives.is_empty() {
+                break;
+            }
+            reject = insert;
+            insert = false_positives;
+        }
+        Ok(FilterCascade { levels, hash_builder })
+    }
+
+    /// Like [`from_bytes`](FilterCascade::from_bytes), but hashes items with
+    /// `hash_builder`, which must be the hasher the cascade was built with.
+    ///
+    /// Fails with [`BloomError::CorruptFile`] if `bytes` is malformed or its
+    /// checksum doesn't match, and with [`BloomError::Incompatible`] if it
+    /// is from a newer format version.
+    pub fn from_bytes_with_hasher(bytes: &[u8], hash_builder: S) -> Result<FilterCascade<T, S>> {
+        if bytes.len() < HEADER_LEN + 8 || bytes[..4] != MAGIC {
+            return Err(BloomError::CorruptFile("not a filter cascade".to_string()));
+        }
+        let (contents, checksum) = bytes.split_at(bytes.len() - 8);
+        if xxh64(contents, 0) != read_le(checksum, 0, 8) {
+            return Err(BloomError::CorruptFile("checksum mismatch".to_string()));
+        }
+        if read_le(bytes, 4, 2) != u64::from(FORMAT_VERSION) {
+            return Err(BloomError::Incompatible("unsupported format version".to_string()));
+        }
+        let level_count = usize::from(bytes[6]);
+        if !(1..=MAX_LEVELS).contains(&level_count) {
+            return Err(BloomError::CorruptFile("invalid layout".to_string()));
+        }
+        let mut body = &contents[HEADER_LEN..];
+        let mut levels = Vec::with_capacity(level_count);
+        for _ in 0..level_count {
+            if body.len() < LEVEL_HEADER_LEN {
+                return Err(BloomError::CorruptFile("truncated level".to_string()));
+            }
+            let bit_len = read_le(body, 0, 8);
+            let hash_count = usize::try_from(read_le(body, 8, 8)).unwrap_or(usize::MAX);
+            let seed = read_le(body, 16, 8);
+            let byte_len = match usize::try_from(bit_len.div_ceil(8)) {
+                Ok(len) if len <= body.len() - LEVEL_HEADER_LEN => len,
+                _ => return Err(BloomError::CorruptFile("truncated level".to_string())),
+            };
+            let bits = &body[LEVEL_HEADER_LEN..LEVEL_HEADER_LEN + byte_len];
+            let level = BloomFilter::from_raw_parts_with_hasher(bits, bit_len, hash_count, seed, hash_builder.clone())
+                .map_err(|e| BloomError::CorruptFile(format!("invalid level: {}", e)))?;
+            levels.push(level);
+            body = &body[LEVEL_HEADER_LEN + byte_len..];
+        }
+        if !body.is_empty() {
+            return Err(BloomError::CorruptFile("trailing bytes".to_string()));
+        }
+        Ok(FilterCascade { levels, hash_builder })
+    }
+
+    /// Returns `true` if `item` was one of the included items, and `false`
+    /// if it was one of the excluded ones. Any other item gets `false` if
+    /// the first level doesn't contain it, and an arbitrary answer if it
+    /// does.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        match self.levels.iter().position(|level| !level.contains(item)) {
+            // Even levels hold included items.
+            Some(depth) => depth % 2 == 1,
+            None => self.levels.len() % 2 == 1,
+        }
+    }
+
+    /// The number of levels.
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// The levels, the first holding the included items.
+    pub fn levels(&self) -> &[BloomFilter<T, S>] {
+        &self.levels
+    }
+
+    /// The number of bytes of memory the cascade occupies, including every
+    /// level's bits.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self)
+            + self
+                .levels
+                .iter()
+                .map(|level| ApproximateMembership::<T>::memory_bytes(level))
+                .sum::<usize>()
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Encodes the cascade so that [`from_bytes`](FilterCascade::from_bytes)
+    /// can load it on any platform, given the same hasher.
+    ///
+    /// The layout is little-endian: the magic `b"BLMC"`, a 2-byte format
+    /// version (1), the number of levels as one byte and a zero byte. Each
+    /// level follows as its bit length, hash count and seed, 8 bytes each,
+    /// then its bits as [`BloomFilter::to_bytes`] packs them. Finally comes
+    /// the XXH64 (seed 0) of every preceding byte.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&[self.levels.len() as u8, 0]);
+        for level in &self.levels {
+            bytes.extend_from_slice(&level.bit_vec_size().to_le_bytes());
+            bytes.extend_from_slice(&(level.hash_count() as u64).to_le_bytes());
+            bytes.extend_from_slice(&level.seed().to_le_bytes());
+            bytes.extend_from_slice(&level.to_bytes());
+        }
+        let checksum = xxh64(&bytes, 0);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+}
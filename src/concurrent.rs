@@ -0,0 +1,366 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::bit_vec::{self, BitVec};
+use crate::probe::Probes;
+use crate::{math, params, ApproximateMembership, BloomFilter, DefaultBuildHasher, Result};
+
+/// A bloom filter that many threads can insert into and query at once,
+/// without locks, by keeping its bits in `AtomicU64`s.
+///
+/// Insertion sets each bit with a single atomic OR, so
+/// [`insert`](ConcurrentBloomFilter::insert) and
+/// [`contains`](ConcurrentBloomFilter::contains) both take `&self` and are
+/// wait-free: no thread ever waits on another. Bits are set with
+/// acquire-release ordering and read with acquire ordering. An item
+/// inserted while another thread looks it up may be reported either present
+/// or absent, but once `insert` returns it is present to every lookup that
+/// happens after.
+///
+/// Items map to the same bits as in a [`BloomFilter`] of the same
+/// parameters, and the two convert into each other.
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// use bloom::ConcurrentBloomFilter;
+///
+/// let filter = Arc::new(ConcurrentBloomFilter::<u64>::new(10_000, 0.01));
+/// let workers: Vec<_> = (0..4u64)
+///     .map(|worker| {
+///         let filter = Arc::clone(&filter);
+///         thread::spawn(move || {
+///             for i in 0..1000 {
+///                 filter.insert(&(worker * 1000 + i));
+///             }
+///         })
+///     })
+///     .collect();
+/// for worker in workers {
+///     worker.join().unwrap();
+/// }
+/// assert!((0..4000).all(|i| filter.contains(&i)));
+/// ```
+#[derive(Debug)]
+pub struct ConcurrentBloomFilter<T, S = DefaultBuildHasher> {
+    words: Box<[AtomicU64]>,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    bit_vec_size: u64,
+    hash_count: usize,
+    seed: u64,
+    wide_hashes: bool,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+impl<T: Hash> ConcurrentBloomFilter<T> {
+    /// Creates a filter sized to hold `item_count` items with the given
+    /// false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0 or `false_positive_prob` is not strictly
+    /// between 0 and 1.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> ConcurrentBloomFilter<T> {
+        ConcurrentBloomFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](ConcurrentBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> ConcurrentBloomFilter<T> {
+        let mut filter = ConcurrentBloomFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter with exactly `bits` bits and `hashes` hash functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` or `hashes` is 0, or if `bits` is more than
+    /// [`MAX_BITS`](crate::params::MAX_BITS).
+    pub fn from_params(bits: u64, hashes: usize) -> ConcurrentBloomFilter<T> {
+        ConcurrentBloomFilter::from_params_with_hasher(bits, hashes, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> ConcurrentBloomFilter<T, S> {
+    /// Like [`new`](ConcurrentBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> ConcurrentBloomFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let bits = params::optimal_bits(item_count, false_positive_prob);
+        let hashes = params::optimal_hashes(bits, item_count);
+        let mut filter = ConcurrentBloomFilter::from_params_with_hasher(bits, hashes, hash_builder);
+        filter.item_count = Some(item_count);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](ConcurrentBloomFilter::from_params), but hashes
+    /// items with `hash_builder`.
+    pub fn from_params_with_hasher(bits: u64, hashes: usize, hash_builder: S) -> ConcurrentBloomFilter<T, S> {
+        if let Err(e) = params::validate_layout(bits, hashes) {
+            panic!("{}", e);
+        }
+        let words = (0..bit_vec::word_count(bits)).map(|_| AtomicU64::new(0)).collect();
+        ConcurrentBloomFilter {
+            words,
+            item_count: None,
+            false_positive_prob: None,
+            bit_vec_size: bits,
+            hash_count: hashes,
+            seed: 0,
+            wide_hashes: bits > params::WIDE_HASH_THRESHOLD,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Converts a [`BloomFilter`] into a concurrent one holding the same
+    /// items.
+    pub fn from_bloom_filter(filter: BloomFilter<T, S>) -> ConcurrentBloomFilter<T, S> {
+        let item_count = filter.capacity();
+        let false_positive_prob = filter.false_positive_prob();
+        let bit_vec_size = filter.bit_vec_size();
+        let hash_count = filter.hash_count();
+        let seed = filter.seed();
+        let wide_hashes = filter.wide_hashes();
+        let (bit_vec, hash_builder) = filter.into_bits_and_hasher();
+        ConcurrentBloomFilter {
+            words: bit_vec.words().iter().map(|&word| AtomicU64::new(word)).collect(),
+            item_count,
+            false_positive_prob,
+            bit_vec_size,
+            hash_count,
+            seed,
+            wide_hashes,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present; the same as
+    /// [`contains_or_insert`](ConcurrentBloomFilter::contains_or_insert).
+    pub fn insert<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.contains_or_insert(item)
+    }
+
+    /// Checks for `item` and records it in one pass, returning `true` if it
+    /// was probably already present and `false` if this call added it.
+    ///
+    /// Each bit is tested and set by the same atomic `fetch_or`, so there is
+    /// no gap between the check and the insertion for another thread to add
+    /// the item in, as there is between
+    /// [`contains`](ConcurrentBloomFilter::contains) and
+    /// [`insert`](ConcurrentBloomFilter::insert) called one after the other.
+    /// This makes it suitable for deduplicating a stream across threads:
+    ///
+    /// - Of any number of threads adding the same new item at once, at least
+    ///   one gets `false`, since exactly one of them sets each of its bits
+    ///   that was clear.
+    /// - Once a call has returned, every call that happens after it gets
+    ///   `true`.
+    ///
+    /// Threads that overlap may still both get `false`, when each sets some
+    /// of the item's bits before seeing the others' work; guaranteeing a
+    /// single winner takes a lock, or an exact set.
+    pub fn contains_or_insert<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let mut present = true;
+        for index in self.probes(item) {
+            let mask = 1 << (index % 64);
+            if self.words[(index / 64) as usize].fetch_or(mask, Ordering::AcqRel) & mask == 0 {
+                present = false;
+            }
+        }
+        present
+    }
+
+    /// Returns `true` if `item` has probably been added, and `false` if it
+    /// definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.probes(item)
+            .all(|index| self.words[(index / 64) as usize].load(Ordering::Acquire) & 1 << (index % 64) != 0)
+    }
+
+    /// Removes every item from the filter.
+    ///
+    /// Words are cleared one at a time, so items inserted while the filter
+    /// is being cleared may be left partly set, and give false negatives.
+    /// Clear a filter only while nothing else is inserting into it.
+    pub fn clear(&self) {
+        for word in self.words.iter() {
+            word.store(0, Ordering::Release);
+        }
+    }
+
+    /// Returns `true` if nothing has been added since the filter was created
+    /// or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| word.load(Ordering::Acquire) == 0)
+    }
+
+    /// The number of bits that are set.
+    pub fn count_ones(&self) -> u64 {
+        self.words.iter().map(|word| u64::from(word.load(Ordering::Acquire).count_ones())).sum()
+    }
+
+    /// The fraction of bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.bit_vec_size as f64
+    }
+
+    /// Estimates the probability that a lookup of an absent item returns
+    /// `true`, as the filter is now.
+    pub fn current_fpr(&self) -> f64 {
+        math::powf(self.fill_ratio(), self.hash_count as f64)
+    }
+
+    /// Estimates how many distinct items have been added, from the number of
+    /// bits that are set.
+    pub fn estimated_len(&self) -> f64 {
+        let m = self.bit_vec_size as f64;
+        -m / self.hash_count as f64 * math::ln(1.0 - self.count_ones() as f64 / m)
+    }
+
+    /// The number of bits in the filter.
+    pub fn bit_vec_size(&self) -> u64 {
+        self.bit_vec_size
+    }
+
+    /// The number of hash functions.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The false positive probability the filter was sized for, if it was
+    /// created from one.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The number of items the filter was sized for, if it was created from
+    /// one.
+    pub fn capacity(&self) -> Option<usize> {
+        self.item_count
+    }
+
+    /// The number of bytes of memory the filter occupies, including its
+    /// bits.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(&*self.words)
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Copies the filter into a [`BloomFilter`] holding the same items.
+    ///
+    /// Words are copied one at a time, so an item being inserted meanwhile
+    /// may be only partly copied.
+    pub fn to_bloom_filter(&self) -> BloomFilter<T, S>
+    where
+        S: Clone,
+    {
+        let mut bit_vec = BitVec::new(self.bit_vec_size);
+        for (word, shared) in bit_vec.words_mut().iter_mut().zip(self.words.iter()) {
+            *word = shared.load(Ordering::Acquire);
+        }
+        BloomFilter::from_parts(
+            bit_vec,
+            self.hash_count,
+            self.item_count,
+            self.false_positive_prob,
+            self.seed,
+            self.hash_builder.clone(),
+        )
+        .with_wide_hashes(self.wide_hashes)
+    }
+
+    /// Converts the filter into a [`BloomFilter`] holding the same items.
+    pub fn into_bloom_filter(self) -> BloomFilter<T, S> {
+        let mut bit_vec = BitVec::new(self.bit_vec_size);
+        for (word, shared) in bit_vec.words_mut().iter_mut().zip(Vec::from(self.words)) {
+            *word = shared.into_inner();
+        }
+        BloomFilter::from_parts(
+            bit_vec,
+            self.hash_count,
+            self.item_count,
+            self.false_positive_prob,
+            self.seed,
+            self.hash_builder,
+        )
+        .with_wide_hashes(self.wide_hashes)
+    }
+
+    fn probes<Q: ?Sized + Hash>(&self, item: &Q) -> Probes<S::Hasher> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        Probes::new(hasher, self.bit_vec_size, self.hash_count, self.wide_hashes)
+    }
+}
+
+impl<T: Hash, S: BuildHasher> From<BloomFilter<T, S>> for ConcurrentBloomFilter<T, S> {
+    fn from(filter: BloomFilter<T, S>) -> ConcurrentBloomFilter<T, S> {
+        ConcurrentBloomFilter::from_bloom_filter(filter)
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for ConcurrentBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(ConcurrentBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        ConcurrentBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        ConcurrentBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        ConcurrentBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        ConcurrentBloomFilter::memory_bytes(self)
+    }
+}
@@ -0,0 +1,183 @@
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::mem;
+
+use crate::hash::HashScheme;
+use crate::probe::Probes;
+use crate::{math, params, ApproximateMembership, Result};
+
+/// A bloom filter with a fixed layout and inline storage, which never
+/// allocates.
+///
+/// The filter has `WORDS` 64-bit words, so [`BITS`](ConstBloomFilter::BITS)
+/// is `WORDS * 64`, and `K` hash functions. (Stable Rust can't yet size an
+/// array from an expression of a const parameter, so the size is given in
+/// words rather than bits.) Use [`params`] to pick them: for example
+/// `optimal_bits(1000, 0.01)` is 9586 bits, or 150 words, and
+/// `optimal_hashes(9600, 1000)` is 7.
+///
+/// Since it holds its bits directly, a `ConstBloomFilter` can live on the
+/// stack or in a `static`, and [`new`](ConstBloomFilter::new) is a `const fn`:
+///
+/// ```
+/// use bloom::ConstBloomFilter;
+///
+/// let mut filter = ConstBloomFilter::<150, 7>::new();
+/// filter.add("apple");
+/// assert!(filter.contains("apple"));
+/// ```
+///
+/// Items map to bits exactly as in a [`BloomFilter`](crate::BloomFilter)
+/// with the same size, hash count, seed and hasher, so their words can be
+/// exchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstBloomFilter<const WORDS: usize, const K: usize, S = HashScheme> {
+    words: [u64; WORDS],
+    seed: u64,
+    hash_builder: S,
+}
+
+impl<const WORDS: usize, const K: usize> ConstBloomFilter<WORDS, K> {
+    /// Creates an empty filter using the default hash scheme.
+    pub const fn new() -> ConstBloomFilter<WORDS, K> {
+        ConstBloomFilter::from_words(HashScheme::SipHash13, 0, [0; WORDS])
+    }
+}
+
+impl<const WORDS: usize, const K: usize> Default for ConstBloomFilter<WORDS, K> {
+    fn default() -> ConstBloomFilter<WORDS, K> {
+        ConstBloomFilter::new()
+    }
+}
+
+impl<const WORDS: usize, const K: usize, S> ConstBloomFilter<WORDS, K, S> {
+    /// The number of bits in the filter.
+    pub const BITS: u64 = WORDS as u64 * 64;
+
+    // Evaluated when a filter type is first used, so invalid layouts fail to
+    // compile rather than panicking.
+    const VALID: () = assert!(WORDS > 0 && K > 0, "a filter needs at least one word and one hash");
+
+    /// Creates a filter with the given hasher, seed and bits, where bit `i`
+    /// is bit `i % 64` of word `i / 64`.
+    ///
+    /// This is a `const fn`, so filters built ahead of time can be embedded
+    /// in a program as constants.
+    pub const fn from_words(hash_builder: S, seed: u64, words: [u64; WORDS]) -> ConstBloomFilter<WORDS, K, S> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID;
+        ConstBloomFilter {
+            words,
+            seed,
+            hash_builder,
+        }
+    }
+
+    /// The words backing the filter.
+    pub const fn as_words(&self) -> &[u64; WORDS] {
+        &self.words
+    }
+
+    /// The seed mixed into every hash.
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub const fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Clears every bit.
+    pub fn clear(&mut self) {
+        self.words = [0; WORDS];
+    }
+
+    /// Whether no bits are set.
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    /// The number of bits set.
+    pub fn count_ones(&self) -> u64 {
+        self.words.iter().map(|word| u64::from(word.count_ones())).sum()
+    }
+
+    /// Estimates the number of distinct items in the filter from the
+    /// number of bits set; see
+    /// [`BloomFilter::estimated_len`](crate::BloomFilter::estimated_len).
+    pub fn estimated_len(&self) -> f64 {
+        let m = Self::BITS as f64;
+        -m / K as f64 * math::ln(1.0 - self.count_ones() as f64 / m)
+    }
+
+    /// The probability that a lookup of an item not in the filter returns
+    /// true, given the bits currently set.
+    pub fn current_fpr(&self) -> f64 {
+        math::powf(self.count_ones() as f64 / Self::BITS as f64, K as f64)
+    }
+}
+
+impl<const WORDS: usize, const K: usize, S: BuildHasher> ConstBloomFilter<WORDS, K, S> {
+    /// Records `item` in the filter, returning whether it was probably
+    /// already present.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool {
+        let mut present = true;
+        for index in self.probes(item) {
+            let (word, bit) = ((index / 64) as usize, 1 << (index % 64));
+            if self.words[word] & bit == 0 {
+                self.words[word] |= bit;
+                present = false;
+            }
+        }
+        present
+    }
+
+    /// Records `item` in the filter.
+    pub fn add<Q: ?Sized + Hash>(&mut self, item: &Q) {
+        self.insert(item);
+    }
+
+    /// Whether `item` may be in the filter. False positives are possible,
+    /// false negatives are not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool {
+        self.probes(item)
+            .all(|index| self.words[(index / 64) as usize] & (1 << (index % 64)) != 0)
+    }
+
+    fn probes<Q: ?Sized + Hash>(&self, item: &Q) -> Probes<S::Hasher> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        Probes::new(hasher, Self::BITS, K, Self::BITS > params::WIDE_HASH_THRESHOLD)
+    }
+}
+
+/// A `ConstBloomFilter` accepts items of any type; `T` only fixes the type
+/// used through this trait.
+impl<T: ?Sized + Hash, const WORDS: usize, const K: usize, S: BuildHasher> ApproximateMembership<T>
+    for ConstBloomFilter<WORDS, K, S>
+{
+    fn insert(&mut self, item: &T) -> Result<bool> {
+        Ok(ConstBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &T) -> bool {
+        ConstBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        ConstBloomFilter::clear(self);
+    }
+
+    fn estimated_len(&self) -> f64 {
+        ConstBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self)
+    }
+}
@@ -0,0 +1,333 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use core::borrow::Borrow;
+use core::convert::TryFrom;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::probe::Probes;
+use crate::{math, BloomError, DefaultBuildHasher, Result};
+
+/// A count-min sketch (Cormode and Muthukrishnan, "An Improved Data Stream
+/// Summary: The Count-Min Sketch and its Applications"), which estimates how
+/// many times each item of a stream occurred in memory independent of the
+/// number of distinct items.
+///
+/// The sketch is `depth` rows of `width` counters. Each item maps to one
+/// counter per row, hashed as for a [`BloomFilter`](crate::BloomFilter), and
+/// inserting it increments all of them. A count is estimated as the smallest
+/// of its counters, which is never too low, and with probability at least
+/// `1 - delta` too high by at most `epsilon` times the total of all counts,
+/// for `width = e / epsilon` and `depth = ln(1 / delta)`.
+///
+/// [Conservative update](CountMinSketch::with_conservative_update) makes
+/// estimates of skewed streams tighter.
+///
+/// Unlike a [`SpectralBloomFilter`](crate::SpectralBloomFilter), which is
+/// sized for a number of distinct items, a sketch is sized for the error it
+/// may make relative to the whole stream, so it suits streams with an
+/// unknown number of items of which only the frequent ones matter.
+///
+/// ```
+/// use bloom::CountMinSketch;
+///
+/// let mut sketch = CountMinSketch::<&str>::new(0.001, 0.01);
+/// for word in "the cat saw the dog chase the cat".split(' ') {
+///     sketch.insert(&word);
+/// }
+/// assert_eq!(sketch.estimate_count(&"the"), 3);
+/// assert_eq!(sketch.estimate_count(&"cat"), 2);
+/// ```
+#[derive(Debug)]
+pub struct CountMinSketch<T, S = DefaultBuildHasher> {
+    /// The counters, row by row.
+    counters: Box<[u64]>,
+    width: u64,
+    depth: usize,
+    conservative: bool,
+    total_count: u64,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for CountMinSketch<T, S> {
+    fn clone(&self) -> CountMinSketch<T, S> {
+        CountMinSketch {
+            counters: self.counters.clone(),
+            width: self.width,
+            depth: self.depth,
+            conservative: self.conservative,
+            total_count: self.total_count,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> CountMinSketch<T> {
+    /// Creates a sketch whose estimates are, with probability at least
+    /// `1 - delta`, at most `epsilon` times the total of all counts too high.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `epsilon` or `delta` is not strictly between 0 and 1, or if
+    /// the counters can't be addressed.
+    pub fn new(epsilon: f64, delta: f64) -> CountMinSketch<T> {
+        CountMinSketch::with_hasher(epsilon, delta, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](CountMinSketch::new), but mixes `seed` into every hash.
+    pub fn with_seed(epsilon: f64, delta: f64, seed: u64) -> CountMinSketch<T> {
+        let mut sketch = CountMinSketch::new(epsilon, delta);
+        sketch.seed = seed;
+        sketch
+    }
+
+    /// Creates a sketch with exactly `depth` rows of `width` counters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either is 0, or if the counters can't be addressed.
+    pub fn from_params(width: u64, depth: usize) -> CountMinSketch<T> {
+        CountMinSketch::from_params_with_hasher(width, depth, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> CountMinSketch<T, S> {
+    /// Like [`new`](CountMinSketch::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(epsilon: f64, delta: f64, hash_builder: S) -> CountMinSketch<T, S> {
+        if !(epsilon > 0.0 && epsilon < 1.0 && delta > 0.0 && delta < 1.0) {
+            panic!(
+                "{}",
+                BloomError::InvalidParams(format!(
+                    "epsilon and delta must be strictly between 0 and 1 (got {} and {})",
+                    epsilon, delta
+                ))
+            );
+        }
+        let width = math::ceil(core::f64::consts::E / epsilon) as u64;
+        let depth = math::ceil(math::ln(1.0 / delta)) as usize;
+        CountMinSketch::from_params_with_hasher(width, depth, hash_builder)
+    }
+
+    /// Like [`from_params`](CountMinSketch::from_params), but hashes items
+    /// with `hash_builder`.
+    pub fn from_params_with_hasher(width: u64, depth: usize, hash_builder: S) -> CountMinSketch<T, S> {
+        if width == 0 || depth == 0 {
+            panic!(
+                "{}",
+                BloomError::InvalidParams(format!("width and depth must be nonzero (got {} and {})", width, depth))
+            );
+        }
+        let len = width
+            .checked_mul(depth as u64)
+            .and_then(|len| usize::try_from(len).ok())
+            .filter(|&len| len <= isize::MAX as usize / 8);
+        let counters = match len {
+            Some(len) => vec![0; len].into_boxed_slice(),
+            None => panic!(
+                "{}",
+                BloomError::Capacity(format!("{} rows of {} counters can't be addressed", depth, width))
+            ),
+        };
+        CountMinSketch {
+            counters,
+            width,
+            depth,
+            conservative: false,
+            total_count: 0,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Switches conservative update on or off. Defaults to off.
+    ///
+    /// With conservative update (Estan and Varghese), inserting an item only
+    /// raises its counters as far as its new estimate, the smallest counter
+    /// plus the count, rather than adding to all of them. Counters that
+    /// other items had already pushed higher are left alone, so estimates
+    /// stay upper bounds but overestimate less, most of all for the rare
+    /// items of a skewed stream.
+    ///
+    /// The caveats: an insertion reads every counter before writing, counts
+    /// can never be subtracted, and a sketch merged from two conservative
+    /// ones is only as tight as their sum, which is looser than a single
+    /// sketch of both streams would have been. It's still at least as tight
+    /// as merging plain sketches.
+    ///
+    /// # Panics
+    ///
+    /// Panics if anything has been inserted.
+    pub fn with_conservative_update(mut self, conservative: bool) -> CountMinSketch<T, S> {
+        assert!(self.is_empty(), "the sketch must be configured before inserting items");
+        self.conservative = conservative;
+        self
+    }
+
+    /// Records one occurrence of `item`.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+    {
+        self.insert_count(item, 1);
+    }
+
+    /// Records `count` occurrences of `item` at once. Counters saturate
+    /// rather than wrapping.
+    pub fn insert_count<Q: ?Sized + Hash>(&mut self, item: &Q, count: u64)
+    where
+        T: Borrow<Q>,
+    {
+        if self.conservative {
+            let estimate = self.estimate_count(item).saturating_add(count);
+            for index in self.indices(item) {
+                self.counters[index] = self.counters[index].max(estimate);
+            }
+        } else {
+            for index in self.indices(item) {
+                self.counters[index] = self.counters[index].saturating_add(count);
+            }
+        }
+        self.total_count = self.total_count.saturating_add(count);
+    }
+
+    /// Estimates how many times `item` has been inserted, as the smallest of
+    /// its counters. The estimate is never too low, and is 0 for an item
+    /// that definitely wasn't inserted.
+    pub fn estimate_count<Q: ?Sized + Hash>(&self, item: &Q) -> u64
+    where
+        T: Borrow<Q>,
+    {
+        self.indices(item).map(|index| self.counters[index]).min().unwrap_or(0)
+    }
+
+    /// Adds every count in `other` to this sketch, so that it summarizes both
+    /// streams.
+    ///
+    /// Both sketches must have the same width, depth, seed and update mode,
+    /// and should use the same hasher; otherwise
+    /// [`BloomError::Incompatible`] is returned and this sketch is
+    /// unchanged. The merged sketch keeps using the mode; see
+    /// [`with_conservative_update`](CountMinSketch::with_conservative_update)
+    /// for how tight a merged conservative sketch is.
+    pub fn try_merge(&mut self, other: &CountMinSketch<T, S>) -> Result<()> {
+        if (self.width, self.depth) != (other.width, other.depth) {
+            return Err(BloomError::Incompatible(format!(
+                "shapes differ ({}x{} and {}x{})",
+                self.depth, self.width, other.depth, other.width
+            )));
+        }
+        if self.seed != other.seed {
+            return Err(BloomError::Incompatible(format!("seeds differ ({} and {})", self.seed, other.seed)));
+        }
+        if self.conservative != other.conservative {
+            return Err(BloomError::Incompatible(
+                "only one of the sketches uses conservative update".to_string(),
+            ));
+        }
+        for (counter, &count) in self.counters.iter_mut().zip(other.counters.iter()) {
+            *counter = counter.saturating_add(count);
+        }
+        self.total_count = self.total_count.saturating_add(other.total_count);
+        Ok(())
+    }
+
+    /// Resets every count to zero, keeping the sketch's allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        for counter in self.counters.iter_mut() {
+            *counter = 0;
+        }
+        self.total_count = 0;
+    }
+
+    /// Halves every count, rounding down, so that what was inserted before
+    /// weighs half as much as what is inserted after: the periodic aging
+    /// step of a [`TinyLfu`](crate::TinyLfu) policy. Estimates stay upper
+    /// bounds on the halved counts.
+    pub fn halve(&mut self) {
+        for counter in self.counters.iter_mut() {
+            *counter /= 2;
+        }
+        self.total_count /= 2;
+    }
+
+    /// Returns `true` if nothing has been inserted since the sketch was
+    /// created or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.total_count == 0
+    }
+
+    /// The total of all counts inserted, the `N` that errors are relative
+    /// to.
+    pub fn total_count(&self) -> u64 {
+        self.total_count
+    }
+
+    /// The number of counters in each row.
+    pub fn width(&self) -> u64 {
+        self.width
+    }
+
+    /// The number of rows.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns `true` if the sketch uses conservative update.
+    pub fn conservative_update(&self) -> bool {
+        self.conservative
+    }
+
+    /// The error bound relative to the total count, `e / width`.
+    pub fn epsilon(&self) -> f64 {
+        core::f64::consts::E / self.width as f64
+    }
+
+    /// The probability of exceeding the error bound, `e^-depth`.
+    pub fn delta(&self) -> f64 {
+        math::exp(-(self.depth as f64))
+    }
+
+    /// The number of bytes of memory the sketch occupies, including its
+    /// counters.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(&*self.counters)
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Changes the seed of an empty sketch.
+    pub(crate) fn set_seed(&mut self, seed: u64) {
+        debug_assert!(self.is_empty());
+        self.seed = seed;
+    }
+
+    /// The index of `item`'s counter in each row.
+    fn indices<Q: ?Sized + Hash>(&self, item: &Q) -> impl Iterator<Item = usize> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let width = self.width;
+        Probes::new(hasher, width, self.depth, false)
+            .zip(0..)
+            .map(move |(index, row)| (row * width + index) as usize)
+    }
+}
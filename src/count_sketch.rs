@@ -0,0 +1,318 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::convert::TryFrom;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::probe::Probes;
+use crate::{math, BloomError, DefaultBuildHasher, Result};
+
+/// A count sketch (Charikar, Chen and Farach-Colton, "Finding Frequent Items
+/// in Data Streams"), which estimates item frequencies without bias, and the
+/// inner products of two streams' frequency vectors.
+///
+/// Like a [`CountMinSketch`](crate::CountMinSketch) the sketch is `depth`
+/// rows of `width` counters with one counter per row for each item, but each
+/// item also has a random sign per row, and inserting it adds the count
+/// times that sign. Other items in the same counter add noise that is as
+/// likely negative as positive, so each row's counter times the sign is an
+/// unbiased estimate, and the median of the rows is a robust one: within
+/// `epsilon` times the L2 norm of the stream's frequencies for
+/// `width = 3 / epsilon^2`.
+///
+/// Estimates can be too low as well as too high, and the L2 norm is much
+/// smaller than the total count on skewed streams, so the count sketch is
+/// the more accurate of the two for heavy hitters. Counts may be negative,
+/// so items can be removed.
+///
+/// ```
+/// use bloom::CountSketch;
+///
+/// let mut sketch = CountSketch::<&str>::new(0.01, 0.01);
+/// for word in "the cat saw the dog chase the cat".split(' ') {
+///     sketch.insert(&word);
+/// }
+/// assert_eq!(sketch.estimate_count(&"the"), 3);
+/// sketch.insert_count(&"the", -3);
+/// assert_eq!(sketch.estimate_count(&"the"), 0);
+/// ```
+#[derive(Debug)]
+pub struct CountSketch<T, S = DefaultBuildHasher> {
+    /// The counters, row by row.
+    counters: Box<[i64]>,
+    width: u64,
+    depth: usize,
+    total_count: i64,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for CountSketch<T, S> {
+    fn clone(&self) -> CountSketch<T, S> {
+        CountSketch {
+            counters: self.counters.clone(),
+            width: self.width,
+            depth: self.depth,
+            total_count: self.total_count,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> CountSketch<T> {
+    /// Creates a sketch whose estimates are within `epsilon` times the L2
+    /// norm of the frequencies, with `ln(1 / delta)` rows rounded up to an
+    /// odd number.
+    ///
+    /// That many rows keeps the failure rate below `delta` in practice; the
+    /// proven bound for the median needs about 18 times as many.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `epsilon` or `delta` is not strictly between 0 and 1, or if
+    /// the counters can't be addressed.
+    pub fn new(epsilon: f64, delta: f64) -> CountSketch<T> {
+        CountSketch::with_hasher(epsilon, delta, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](CountSketch::new), but mixes `seed` into every hash.
+    pub fn with_seed(epsilon: f64, delta: f64, seed: u64) -> CountSketch<T> {
+        let mut sketch = CountSketch::new(epsilon, delta);
+        sketch.seed = seed;
+        sketch
+    }
+
+    /// Creates a sketch with exactly `depth` rows of `width` counters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either is 0, or if the counters can't be addressed.
+    pub fn from_params(width: u64, depth: usize) -> CountSketch<T> {
+        CountSketch::from_params_with_hasher(width, depth, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> CountSketch<T, S> {
+    /// Like [`new`](CountSketch::new), but hashes items with `hash_builder`.
+    pub fn with_hasher(epsilon: f64, delta: f64, hash_builder: S) -> CountSketch<T, S> {
+        if !(epsilon > 0.0 && epsilon < 1.0 && delta > 0.0 && delta < 1.0) {
+            panic!(
+                "{}",
+                BloomError::InvalidParams(format!(
+                    "epsilon and delta must be strictly between 0 and 1 (got {} and {})",
+                    epsilon, delta
+                ))
+            );
+        }
+        let width = math::ceil(3.0 / (epsilon * epsilon)) as u64;
+        let depth = math::ceil(math::ln(1.0 / delta)) as usize | 1;
+        CountSketch::from_params_with_hasher(width, depth, hash_builder)
+    }
+
+    /// Like [`from_params`](CountSketch::from_params), but hashes items with
+    /// `hash_builder`.
+    pub fn from_params_with_hasher(width: u64, depth: usize, hash_builder: S) -> CountSketch<T, S> {
+        if width == 0 || depth == 0 {
+            panic!(
+                "{}",
+                BloomError::InvalidParams(format!("width and depth must be nonzero (got {} and {})", width, depth))
+            );
+        }
+        let len = width
+            .checked_mul(depth as u64)
+            .and_then(|len| usize::try_from(len).ok())
+            .filter(|&len| len <= isize::MAX as usize / 8);
+        let counters = match len {
+            Some(len) => vec![0; len].into_boxed_slice(),
+            None => panic!(
+                "{}",
+                BloomError::Capacity(format!("{} rows of {} counters can't be addressed", depth, width))
+            ),
+        };
+        CountSketch {
+            counters,
+            width,
+            depth,
+            total_count: 0,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records one occurrence of `item`.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+    {
+        self.insert_count(item, 1);
+    }
+
+    /// Adds `count` to `item`'s frequency, which may be negative to remove
+    /// occurrences. Counters saturate rather than wrapping.
+    pub fn insert_count<Q: ?Sized + Hash>(&mut self, item: &Q, count: i64)
+    where
+        T: Borrow<Q>,
+    {
+        for (index, sign) in self.indices(item) {
+            self.counters[index] = self.counters[index].saturating_add(sign * count);
+        }
+        self.total_count = self.total_count.saturating_add(count);
+    }
+
+    /// Estimates `item`'s frequency as the median of the rows' estimates.
+    pub fn estimate_count<Q: ?Sized + Hash>(&self, item: &Q) -> i64
+    where
+        T: Borrow<Q>,
+    {
+        let estimates = self.indices(item).map(|(index, sign)| sign * self.counters[index]).collect();
+        median(estimates)
+    }
+
+    /// Estimates the inner product of this sketch's and `other`'s frequency
+    /// vectors, the sum over all items of the product of their counts, as
+    /// the median of the rows' inner products.
+    ///
+    /// A sketch's inner product with itself estimates the second frequency
+    /// moment, the square of the L2 norm. Both sketches must be compatible,
+    /// as for [`try_merge`](CountSketch::try_merge).
+    pub fn inner_product(&self, other: &CountSketch<T, S>) -> Result<i64> {
+        self.check_compatible(other)?;
+        let width = self.width as usize;
+        let products = self
+            .counters
+            .chunks(width)
+            .zip(other.counters.chunks(width))
+            .map(|(row, other_row)| {
+                row.iter()
+                    .zip(other_row)
+                    .fold(0i64, |sum, (&a, &b)| sum.saturating_add(a.saturating_mul(b)))
+            })
+            .collect();
+        Ok(median(products))
+    }
+
+    /// Adds every count in `other` to this sketch, so that it summarizes both
+    /// streams.
+    ///
+    /// Both sketches must have the same width, depth and seed, and should
+    /// use the same hasher; otherwise [`BloomError::Incompatible`] is
+    /// returned and this sketch is unchanged.
+    pub fn try_merge(&mut self, other: &CountSketch<T, S>) -> Result<()> {
+        self.check_compatible(other)?;
+        for (counter, &count) in self.counters.iter_mut().zip(other.counters.iter()) {
+            *counter = counter.saturating_add(count);
+        }
+        self.total_count = self.total_count.saturating_add(other.total_count);
+        Ok(())
+    }
+
+    /// Resets every count to zero, keeping the sketch's allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        for counter in self.counters.iter_mut() {
+            *counter = 0;
+        }
+        self.total_count = 0;
+    }
+
+    /// Returns `true` if every counter is zero.
+    pub fn is_empty(&self) -> bool {
+        self.counters.iter().all(|&counter| counter == 0)
+    }
+
+    /// The total of all counts inserted, net of removals.
+    pub fn total_count(&self) -> i64 {
+        self.total_count
+    }
+
+    /// The number of counters in each row.
+    pub fn width(&self) -> u64 {
+        self.width
+    }
+
+    /// The number of rows.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The error bound relative to the L2 norm of the frequencies,
+    /// `sqrt(3 / width)`.
+    pub fn epsilon(&self) -> f64 {
+        math::sqrt(3.0 / self.width as f64)
+    }
+
+    /// The number of bytes of memory the sketch occupies, including its
+    /// counters.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(&*self.counters)
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    fn check_compatible(&self, other: &CountSketch<T, S>) -> Result<()> {
+        if (self.width, self.depth) != (other.width, other.depth) {
+            return Err(BloomError::Incompatible(format!(
+                "shapes differ ({}x{} and {}x{})",
+                self.depth, self.width, other.depth, other.width
+            )));
+        }
+        if self.seed != other.seed {
+            return Err(BloomError::Incompatible(format!("seeds differ ({} and {})", self.seed, other.seed)));
+        }
+        Ok(())
+    }
+
+    /// The index of `item`'s counter in each row, and its sign there.
+    fn indices<Q: ?Sized + Hash>(&self, item: &Q) -> impl Iterator<Item = (usize, i64)> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        // The signs come from a hash of their own: the probes' low bits
+        // follow a pattern from row to row.
+        let signs = hasher.finish();
+        hasher.write_u8(0xfc);
+        let width = self.width;
+        Probes::new(hasher, width, self.depth, false)
+            .zip(0..)
+            .map(move |(index, row)| ((row * width + index) as usize, sign(signs, row)))
+    }
+}
+
+/// The sign of row `row` from an item's sign hash: one bit per row, and a
+/// fresh 64 bits, mixed from the hash, for every 64 rows.
+fn sign(signs: u64, row: u64) -> i64 {
+    let bits = if row < 64 {
+        signs
+    } else {
+        (signs ^ (row / 64)).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    };
+    1 - 2 * ((bits >> (row % 64)) & 1) as i64
+}
+
+/// The median of `values`, rounding towards the lower of the middle two.
+fn median(mut values: Vec<i64>) -> i64 {
+    values.sort_unstable();
+    let middle = values.len() / 2;
+    if values.len() % 2 == 1 {
+        values[middle]
+    } else {
+        values[middle - 1] + (values[middle] - values[middle - 1]) / 2
+    }
+}
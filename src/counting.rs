@@ -0,0 +1,497 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec;
+use core::borrow::Borrow;
+use core::cmp;
+use core::convert::TryFrom;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::bit_vec::BitVec;
+use crate::probe::Probes;
+use crate::{math, params, ApproximateMembership, BloomError, BloomFilter, DefaultBuildHasher, Removable, Result};
+
+/// A bloom filter that keeps a small counter in each slot instead of a
+/// single bit, so items can be removed as well as added.
+///
+/// Adding an item increments the counters it maps to and removing it
+/// decrements them; an item is present while all of its counters are
+/// nonzero. Items map to the same slots as in a [`BloomFilter`] with the
+/// same parameters.
+///
+/// Counters are 8 bits by default, so a filter takes eight times the memory
+/// of that `BloomFilter`. [4-bit counters](CounterWidth::Four) halve that and
+/// are enough for almost every workload; see
+/// [`counter_overflow_prob`](params::counter_overflow_prob).
+///
+/// Only remove items that were added: removing an item that is merely a
+/// false positive decrements counters that belong to other items, and can
+/// make them false negatives. Adding an item twice means it must be removed
+/// twice.
+///
+/// A counter that reaches its maximum saturates: it no longer knows how many
+/// items map to it, so it stays at the maximum until the filter is cleared.
+/// Items mapping to it can then never be fully removed. [`stats`] reports how
+/// many counters have saturated.
+///
+/// [`stats`]: CountingBloomFilter::stats
+///
+/// ```
+/// use bloom::{CounterWidth, CountingBloomFilter};
+///
+/// let mut filter = CountingBloomFilter::<String>::new(1000, 0.01).with_counter_width(CounterWidth::Four);
+/// filter.add("session-1");
+/// assert!(filter.contains("session-1"));
+/// filter.remove("session-1");
+/// assert!(!filter.contains("session-1"));
+/// ```
+#[derive(Debug)]
+pub struct CountingBloomFilter<T, S = DefaultBuildHasher> {
+    counters: Counters,
+    saturated: u64,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    hash_count: usize,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for CountingBloomFilter<T, S> {
+    fn clone(&self) -> CountingBloomFilter<T, S> {
+        CountingBloomFilter {
+            counters: self.counters.clone(),
+            saturated: self.saturated,
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            hash_count: self.hash_count,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> CountingBloomFilter<T> {
+    /// Creates a filter sized to hold `item_count` items with the given
+    /// false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0 or `false_positive_prob` is not strictly
+    /// between 0 and 1, or if the counters can't be addressed.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> CountingBloomFilter<T> {
+        CountingBloomFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](CountingBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> CountingBloomFilter<T> {
+        let mut filter = CountingBloomFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter with exactly `slots` counters and `hashes` hash
+    /// functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either is 0, or if the counters can't be addressed.
+    pub fn from_params(slots: u64, hashes: usize) -> CountingBloomFilter<T> {
+        CountingBloomFilter::from_params_with_hasher(slots, hashes, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> CountingBloomFilter<T, S> {
+    /// Like [`new`](CountingBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> CountingBloomFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let slots = params::optimal_bits(item_count, false_positive_prob);
+        let hash_count = params::optimal_hashes(slots, item_count);
+        let mut filter = CountingBloomFilter::from_params_with_hasher(slots, hash_count, hash_builder);
+        filter.item_count = Some(item_count);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](CountingBloomFilter::from_params), but hashes
+    /// items with `hash_builder`.
+    pub fn from_params_with_hasher(slots: u64, hashes: usize, hash_builder: S) -> CountingBloomFilter<T, S> {
+        let counters =
+            params::validate_layout(slots, hashes).and_then(|()| Counters::new(slots, CounterWidth::default()));
+        let counters = match counters {
+            Ok(counters) => counters,
+            Err(e) => panic!("{}", e),
+        };
+        CountingBloomFilter {
+            counters,
+            saturated: 0,
+            item_count: None,
+            false_positive_prob: None,
+            hash_count: hashes,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Switches to counters of the given width, keeping the current counts.
+    ///
+    /// This is meant to be called on a new filter. Counts that don't fit in
+    /// a narrower width are capped at its maximum, and count as saturated.
+    pub fn with_counter_width(mut self, width: CounterWidth) -> CountingBloomFilter<T, S> {
+        if width != self.counters.width {
+            let mut counters = match Counters::new(self.counters.len, width) {
+                Ok(counters) => counters,
+                Err(e) => panic!("{}", e),
+            };
+            let mut saturated = 0;
+            for index in 0..self.counters.len {
+                let count = cmp::min(self.counters.get(index), width.max());
+                if count == width.max() {
+                    saturated += 1;
+                }
+                counters.set(index, count);
+            }
+            self.counters = counters;
+            self.saturated = saturated;
+        }
+        self
+    }
+
+    /// Records `item` in the filter.
+    pub fn add<Q: ?Sized + Hash>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+    {
+        self.insert(item);
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present, i.e. every counter it maps to was already nonzero.
+    ///
+    /// The counters are incremented either way, so an item inserted twice
+    /// must be removed twice. Counters saturate at their maximum rather than
+    /// wrapping.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let mut present = true;
+        let max = self.counters.width.max();
+        for index in self.probes(item) {
+            let count = self.counters.get(index);
+            if count == 0 {
+                present = false;
+            }
+            if count < max {
+                self.counters.set(index, count + 1);
+                if count + 1 == max {
+                    self.saturated += 1;
+                }
+            }
+        }
+        present
+    }
+
+    /// Returns `true` if `item` has probably been added and not removed, and
+    /// `false` if it definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.probes(item).all(|index| self.counters.get(index) != 0)
+    }
+
+    /// Removes one occurrence of `item`, returning `true` if it was probably
+    /// present.
+    ///
+    /// If any of `item`'s counters is zero it was definitely never added, and
+    /// nothing is changed. Saturated counters are left as they are.
+    pub fn remove<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        if !self.contains(item) {
+            return false;
+        }
+        let max = self.counters.width.max();
+        for index in self.probes(item) {
+            // Saturating, since a false positive's counters may reach zero
+            // before all of its probes have been applied.
+            let count = self.counters.get(index);
+            if count < max {
+                self.counters.set(index, count.saturating_sub(1));
+            }
+        }
+        true
+    }
+
+    /// A plain [`BloomFilter`] with a bit set for every nonzero counter, which
+    /// answers lookups the same way in a fraction of the memory.
+    pub fn to_bloom_filter(&self) -> BloomFilter<T, S>
+    where
+        S: Clone,
+    {
+        let mut bit_vec = BitVec::new(self.slots());
+        for index in (0..self.counters.len).filter(|&index| self.counters.get(index) != 0) {
+            bit_vec.set(index);
+        }
+        BloomFilter::from_parts(
+            bit_vec,
+            self.hash_count,
+            self.item_count,
+            self.false_positive_prob,
+            self.seed,
+            self.hash_builder.clone(),
+        )
+    }
+
+    /// Removes every item from the filter, keeping its allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        self.counters.clear();
+        self.saturated = 0;
+    }
+
+    /// Returns `true` if every counter is zero.
+    pub fn is_empty(&self) -> bool {
+        self.counters.bytes.iter().all(|&byte| byte == 0)
+    }
+
+    /// The number of counters that are nonzero.
+    pub fn count_nonzero(&self) -> u64 {
+        (0..self.counters.len)
+            .filter(|&index| self.counters.get(index) != 0)
+            .count() as u64
+    }
+
+    /// The number of nonzero and saturated counters.
+    ///
+    /// Once any counter has saturated, removing an item may leave it
+    /// reported as present.
+    pub fn stats(&self) -> CounterStats {
+        CounterStats {
+            slots: self.slots(),
+            nonzero: self.count_nonzero(),
+            saturated: self.saturated,
+        }
+    }
+
+    /// Estimates the false positive probability of the filter as it is now,
+    /// from the fraction of nonzero counters; see
+    /// [`BloomFilter::current_fpr`].
+    pub fn current_fpr(&self) -> f64 {
+        math::powf(
+            self.count_nonzero() as f64 / self.slots() as f64,
+            self.hash_count as f64,
+        )
+    }
+
+    /// Estimates how many distinct items the filter holds from the number of
+    /// nonzero counters; see [`BloomFilter::estimated_len`].
+    pub fn estimated_len(&self) -> f64 {
+        let m = self.slots() as f64;
+        -m / self.hash_count as f64 * math::ln(1.0 - self.count_nonzero() as f64 / m)
+    }
+
+    /// The number of counters.
+    pub fn slots(&self) -> u64 {
+        self.counters.len
+    }
+
+    /// The width of each counter.
+    pub fn counter_width(&self) -> CounterWidth {
+        self.counters.width
+    }
+
+    /// The probability that some counter overflows once the filter holds
+    /// [`capacity`](CountingBloomFilter::capacity) items; see
+    /// [`counter_overflow_prob`](params::counter_overflow_prob).
+    pub fn overflow_prob(&self) -> Option<f64> {
+        self.item_count
+            .map(|n| params::counter_overflow_prob(self.slots(), n, self.hash_count, self.counters.width))
+    }
+
+    /// The number of hash functions applied to each item.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The false positive probability the filter was sized for, or `None`
+    /// if it was built from explicit parameters.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The number of items the filter was sized for, or `None` if it was
+    /// built from explicit parameters.
+    pub fn capacity(&self) -> Option<usize> {
+        self.item_count
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The counter indices `item` maps to.
+    fn probes<Q: ?Sized + Hash>(&self, item: &Q) -> Probes<S::Hasher> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let slots = self.slots();
+        Probes::new(hasher, slots, self.hash_count, slots > params::WIDE_HASH_THRESHOLD)
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for CountingBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(CountingBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        CountingBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        CountingBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        CountingBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + self.counters.bytes.len()
+    }
+}
+
+impl<T, Q, S> Removable<Q> for CountingBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn remove(&mut self, item: &Q) -> bool {
+        CountingBloomFilter::remove(self, item)
+    }
+}
+
+/// Counter occupancy of a [`CountingBloomFilter`], from
+/// [`CountingBloomFilter::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CounterStats {
+    /// The number of counters.
+    pub slots: u64,
+    /// The number of counters that are nonzero.
+    pub nonzero: u64,
+    /// The number of counters stuck at their maximum. Removals can't clear
+    /// items that map to these.
+    pub saturated: u64,
+}
+
+/// The width of each counter in a [`CountingBloomFilter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CounterWidth {
+    /// 4-bit counters packed two to a byte, which count up to 15.
+    Four,
+    /// 8-bit counters, which count up to 255.
+    #[default]
+    Eight,
+}
+
+impl CounterWidth {
+    /// The number of bits in each counter.
+    pub const fn bits(self) -> u32 {
+        match self {
+            CounterWidth::Four => 4,
+            CounterWidth::Eight => 8,
+        }
+    }
+
+    /// The largest count a counter can hold.
+    pub const fn max(self) -> u8 {
+        match self {
+            CounterWidth::Four => 0xf,
+            CounterWidth::Eight => 0xff,
+        }
+    }
+}
+
+/// A fixed-size array of counters packed into bytes.
+///
+/// With 4-bit counters, counter `i` is the low nibble of byte `i / 2` if `i`
+/// is even and the high nibble otherwise.
+#[derive(Debug, Clone)]
+pub(crate) struct Counters {
+    pub(crate) bytes: Box<[u8]>,
+    pub(crate) len: u64,
+    pub(crate) width: CounterWidth,
+}
+
+impl Counters {
+    pub(crate) fn new(len: u64, width: CounterWidth) -> Result<Counters> {
+        let byte_len = match width {
+            CounterWidth::Four => len.div_ceil(2),
+            CounterWidth::Eight => len,
+        };
+        let byte_len = usize::try_from(byte_len)
+            .ok()
+            .filter(|&byte_len| byte_len <= isize::MAX as usize)
+            .ok_or_else(|| BloomError::Capacity(format!("{} counters can't be addressed", len)))?;
+        Ok(Counters {
+            bytes: vec![0; byte_len].into_boxed_slice(),
+            len,
+            width,
+        })
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, index: u64) -> u8 {
+        debug_assert!(index < self.len);
+        match self.width {
+            CounterWidth::Four => self.bytes[(index / 2) as usize] >> (4 * (index % 2)) & 0xf,
+            CounterWidth::Eight => self.bytes[index as usize],
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set(&mut self, index: u64, count: u8) {
+        debug_assert!(index < self.len && count <= self.width.max());
+        match self.width {
+            CounterWidth::Four => {
+                let shift = 4 * (index % 2);
+                let byte = &mut self.bytes[(index / 2) as usize];
+                *byte = *byte & !(0xf << shift) | count << shift;
+            }
+            CounterWidth::Eight => self.bytes[index as usize] = count,
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        for byte in self.bytes.iter_mut() {
+            *byte = 0;
+        }
+    }
+}
@@ -0,0 +1,522 @@
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::quotient::{self, QuotientTable, MAX_LOAD};
+use crate::{math, params, ApproximateMembership, BloomError, DefaultBuildHasher, Removable, Result};
+
+/// The counter width [`new`](CountingQuotientFilter::new) uses.
+const DEFAULT_COUNTER_BITS: u32 = 8;
+
+/// A counting quotient filter, which stores each distinct fingerprint once
+/// alongside a count of how many times it has been inserted (after Pandey et
+/// al., "A General-Purpose Counting Filter").
+///
+/// It is a [`QuotientFilter`](crate::QuotientFilter) whose slots hold a
+/// counter of `c` bits after each remainder. An item inserted a thousand
+/// times takes one slot rather than a thousand, which suits skewed
+/// workloads like k-mer counting. A count that doesn't fit in one counter
+/// continues in further slots with the same remainder, so counts are never
+/// capped, but counts much larger than `2^c` are better served by wider
+/// counters.
+///
+/// [`count`](CountingQuotientFilter::count) never underestimates: it may
+/// overestimate only when another item has the same fingerprint, which
+/// happens with the filter's false positive probability. Like the quotient
+/// filter, the table grows automatically, and filters with fingerprints of
+/// the same length can be merged.
+///
+/// ```
+/// use bloom::CountingQuotientFilter;
+///
+/// let mut filter = CountingQuotientFilter::<String>::new(1000, 0.01);
+/// filter.insert_count("apple", 3).unwrap();
+/// filter.insert("apple").unwrap();
+/// assert_eq!(filter.count("apple"), 4);
+/// filter.remove("apple");
+/// assert_eq!(filter.count("apple"), 3);
+/// ```
+#[derive(Debug)]
+pub struct CountingQuotientFilter<T, S = DefaultBuildHasher> {
+    table: QuotientTable,
+    counter_bits: u32,
+    distinct: u64,
+    total: u64,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for CountingQuotientFilter<T, S> {
+    fn clone(&self) -> CountingQuotientFilter<T, S> {
+        CountingQuotientFilter {
+            table: self.table.clone(),
+            counter_bits: self.counter_bits,
+            distinct: self.distinct,
+            total: self.total,
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> CountingQuotientFilter<T> {
+    /// Creates a filter sized to hold `item_count` distinct items with the
+    /// given false positive probability, with 8-bit counters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0 or `false_positive_prob` is not strictly
+    /// between 0 and 1, or if the table can't be addressed.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> CountingQuotientFilter<T> {
+        CountingQuotientFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](CountingQuotientFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> CountingQuotientFilter<T> {
+        let mut filter = CountingQuotientFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter with `2^quotient_bits` slots, each holding a
+    /// remainder of `remainder_bits` bits and a counter of `counter_bits`
+    /// bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of them is 0, if the quotient and remainder bits add up
+    /// to more than 64 or the remainder and counter bits to more than 61, or
+    /// if the table can't be addressed.
+    pub fn from_params(quotient_bits: u32, remainder_bits: u32, counter_bits: u32) -> CountingQuotientFilter<T> {
+        CountingQuotientFilter::from_params_with_hasher(
+            quotient_bits,
+            remainder_bits,
+            counter_bits,
+            DefaultBuildHasher::default(),
+        )
+    }
+}
+
+impl<T: Hash, S: BuildHasher> CountingQuotientFilter<T, S> {
+    /// Like [`new`](CountingQuotientFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> CountingQuotientFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let (quotient_bits, remainder_bits) = quotient::layout(item_count, false_positive_prob);
+        let remainder_bits = cmp::min(remainder_bits, 61 - DEFAULT_COUNTER_BITS);
+        let mut filter = CountingQuotientFilter::from_params_with_hasher(
+            quotient_bits,
+            remainder_bits,
+            DEFAULT_COUNTER_BITS,
+            hash_builder,
+        );
+        filter.item_count = Some(item_count);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](CountingQuotientFilter::from_params), but hashes
+    /// items with `hash_builder`.
+    pub fn from_params_with_hasher(
+        quotient_bits: u32,
+        remainder_bits: u32,
+        counter_bits: u32,
+        hash_builder: S,
+    ) -> CountingQuotientFilter<T, S> {
+        let table = if quotient_bits + remainder_bits > 64 {
+            Err(BloomError::InvalidParams(format!(
+                "fingerprints can be at most 64 bits (got {})",
+                quotient_bits + remainder_bits
+            )))
+        } else if remainder_bits == 0 || counter_bits == 0 {
+            Err(BloomError::InvalidParams("remainders and counters need at least one bit".to_string()))
+        } else {
+            QuotientTable::new(quotient_bits, remainder_bits.saturating_add(counter_bits))
+        };
+        let table = match table {
+            Ok(table) => table,
+            Err(e) => panic!("{}", e),
+        };
+        CountingQuotientFilter {
+            table,
+            counter_bits,
+            distinct: 0,
+            total: 0,
+            item_count: None,
+            false_positive_prob: None,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records one occurrence of `item`, returning `true` if it was probably
+    /// already present.
+    ///
+    /// # Errors
+    ///
+    /// As for [`insert_count`](CountingQuotientFilter::insert_count).
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> Result<bool>
+    where
+        T: Borrow<Q>,
+    {
+        self.insert_count(item, 1)
+    }
+
+    /// Records `count` occurrences of `item`, returning `true` if it was
+    /// probably already present.
+    ///
+    /// Once more than 95% of the slots would be full the table is first
+    /// [grown](CountingQuotientFilter::grow).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::Capacity`] if the count needs more slots than
+    /// are free and the table can't grow, because the remainders are down to
+    /// one bit or the table can't be allocated. The filter is unchanged.
+    pub fn insert_count<Q: ?Sized + Hash>(&mut self, item: &Q, count: u64) -> Result<bool>
+    where
+        T: Borrow<Q>,
+    {
+        let fingerprint = self.fingerprint(item);
+        let slots = self.slots_needed(fingerprint, count);
+        self.reserve(slots)?;
+        self.insert_fingerprint(fingerprint, count)
+    }
+
+    /// Returns how many times `item` has probably been inserted, net of
+    /// removals.
+    ///
+    /// The count is never too low, and is too high only if another item
+    /// has the same fingerprint.
+    pub fn count<Q: ?Sized + Hash>(&self, item: &Q) -> u64
+    where
+        T: Borrow<Q>,
+    {
+        let (quotient, remainder) = self.split(self.fingerprint(item));
+        let counter_bits = self.counter_bits;
+        self.table
+            .run(quotient)
+            .filter(|&(_, payload)| payload >> counter_bits == remainder)
+            .fold(0u64, |sum, (_, payload)| sum.saturating_add(payload & quotient::mask(counter_bits)))
+    }
+
+    /// Returns `true` if `item` has probably been inserted and not removed,
+    /// and `false` if it definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (quotient, remainder) = self.split(self.fingerprint(item));
+        let counter_bits = self.counter_bits;
+        self.table.find(quotient, |payload| payload >> counter_bits == remainder).is_some()
+    }
+
+    /// Removes one occurrence of `item`, returning `true` if it was probably
+    /// present.
+    pub fn remove<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.remove_count(item, 1) == 1
+    }
+
+    /// Removes up to `count` occurrences of `item`, returning how many were
+    /// removed.
+    pub fn remove_count<Q: ?Sized + Hash>(&mut self, item: &Q, count: u64) -> u64
+    where
+        T: Borrow<Q>,
+    {
+        let (quotient, remainder) = self.split(self.fingerprint(item));
+        let counter_bits = self.counter_bits;
+        let max = quotient::mask(counter_bits);
+        let mut removed = 0;
+        while removed < count {
+            // Take from the partly full slot first, so at most one slot per
+            // fingerprint is ever partly full.
+            let slot = self
+                .table
+                .find(quotient, |payload| payload >> counter_bits == remainder && payload & max != max)
+                .or_else(|| self.table.find(quotient, |payload| payload >> counter_bits == remainder));
+            let slot = match slot {
+                Some(slot) => slot,
+                None => break,
+            };
+            let payload = self.table.payload(slot);
+            let taken = cmp::min(count - removed, payload & max);
+            if taken == payload & max {
+                self.table.remove(quotient, slot);
+            } else {
+                self.table.set_payload(slot, payload - taken);
+            }
+            removed += taken;
+        }
+        if removed > 0 && self.table.find(quotient, |payload| payload >> counter_bits == remainder).is_none() {
+            self.distinct -= 1;
+        }
+        self.total -= cmp::min(self.total, removed);
+        removed
+    }
+
+    /// Doubles the number of slots, moving the top bit of every remainder
+    /// into its quotient.
+    ///
+    /// Items map to the same fingerprints as before, so no counts change,
+    /// but the shorter remainders double the false positive probability.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::Capacity`] if the remainders are already one
+    /// bit long or the new table can't be allocated. The filter is unchanged.
+    pub fn grow(&mut self) -> Result<()> {
+        if self.remainder_bits() <= 1 {
+            return Err(BloomError::Capacity("remainders can't be shortened any further".to_string()));
+        }
+        self.table = self.table.doubled()?;
+        Ok(())
+    }
+
+    /// Adds every fingerprint and count in `other` to this filter, so that
+    /// each item's count is the sum of its counts in both.
+    ///
+    /// The filters' tables and counters may differ in size, but their
+    /// fingerprints must be the same length and they must have the same
+    /// seed, and should use the same hasher; otherwise
+    /// [`BloomError::Incompatible`] is returned and this filter is
+    /// unchanged. This filter grows as needed.
+    pub fn try_union(&mut self, other: &CountingQuotientFilter<T, S>) -> Result<()> {
+        if self.fingerprint_bits() != other.fingerprint_bits() {
+            return Err(BloomError::Incompatible(format!(
+                "fingerprint lengths differ ({} and {})",
+                self.fingerprint_bits(),
+                other.fingerprint_bits()
+            )));
+        }
+        if self.seed != other.seed {
+            return Err(BloomError::Incompatible(format!("seeds differ ({} and {})", self.seed, other.seed)));
+        }
+        let mut entries = Vec::with_capacity(other.table.len() as usize);
+        let (remainder_bits, counter_bits) = (other.remainder_bits(), other.counter_bits);
+        other.table.for_each(|quotient, payload| {
+            let fingerprint = quotient << remainder_bits | payload >> counter_bits;
+            entries.push((fingerprint, payload & quotient::mask(counter_bits)));
+        });
+        // Assume no entry merges into an existing slot, which may grow the
+        // table once more than strictly needed but never leaves it half
+        // merged.
+        let max = quotient::mask(self.counter_bits);
+        let slots = entries.iter().map(|&(_, count)| count.div_ceil(max)).sum();
+        self.reserve(slots)?;
+        for (fingerprint, count) in entries {
+            self.insert_fingerprint(fingerprint, count)?;
+        }
+        Ok(())
+    }
+
+    /// Removes every item from the filter, keeping its allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.distinct = 0;
+        self.total = 0;
+    }
+
+    /// Returns `true` if the filter holds no fingerprints.
+    pub fn is_empty(&self) -> bool {
+        self.distinct == 0
+    }
+
+    /// The number of distinct fingerprints stored.
+    pub fn len(&self) -> u64 {
+        self.distinct
+    }
+
+    /// The sum of every stored count: the number of insertions net of
+    /// removals, saturating at `u64::MAX`.
+    pub fn total_count(&self) -> u64 {
+        self.total
+    }
+
+    /// The fraction of slots that are occupied.
+    pub fn load_factor(&self) -> f64 {
+        self.table.len() as f64 / self.table.size() as f64
+    }
+
+    /// Estimates the false positive probability of the filter as it is now:
+    /// the chance that an absent item's fingerprint matches a stored one.
+    pub fn current_fpr(&self) -> f64 {
+        let fingerprints = math::powf(2.0, self.fingerprint_bits() as f64);
+        1.0 - math::powf(1.0 - 1.0 / fingerprints, self.distinct as f64)
+    }
+
+    /// The number of quotient bits, so the table has `2^q` slots.
+    pub fn quotient_bits(&self) -> u32 {
+        self.table.quotient_bits()
+    }
+
+    /// The number of remainder bits stored per slot.
+    pub fn remainder_bits(&self) -> u32 {
+        self.table.payload_bits() - self.counter_bits
+    }
+
+    /// The number of counter bits stored per slot.
+    pub fn counter_bits(&self) -> u32 {
+        self.counter_bits
+    }
+
+    /// The number of bits in each fingerprint: quotient plus remainder.
+    pub fn fingerprint_bits(&self) -> u32 {
+        self.quotient_bits() + self.remainder_bits()
+    }
+
+    /// The false positive probability the filter was sized for, or `None`
+    /// if it was built from explicit parameters.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The number of distinct items the filter was sized for, or `None` if
+    /// it was built from explicit parameters.
+    pub fn capacity(&self) -> Option<usize> {
+        self.item_count
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The low `q + r` bits of `item`'s hash.
+    fn fingerprint<Q: ?Sized + Hash>(&self, item: &Q) -> u64 {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        hasher.finish() & quotient::mask(self.fingerprint_bits())
+    }
+
+    fn split(&self, fingerprint: u64) -> (u64, u64) {
+        let remainder_bits = self.remainder_bits();
+        (fingerprint >> remainder_bits, fingerprint & quotient::mask(remainder_bits))
+    }
+
+    /// The number of new slots adding `count` to `fingerprint` takes, after
+    /// topping up its partly full slot.
+    fn slots_needed(&self, fingerprint: u64, count: u64) -> u64 {
+        let (quotient, remainder) = self.split(fingerprint);
+        let counter_bits = self.counter_bits;
+        let max = quotient::mask(counter_bits);
+        let room = self
+            .table
+            .find(quotient, |payload| payload >> counter_bits == remainder && payload & max != max)
+            .map_or(0, |slot| max - (self.table.payload(slot) & max));
+        count.saturating_sub(room).div_ceil(max)
+    }
+
+    /// Grows the table until `slots` more fit under the maximum load, or
+    /// fails if they don't fit at all.
+    fn reserve(&mut self, slots: u64) -> Result<()> {
+        while (self.table.len() + slots) as f64 > self.table.size() as f64 * MAX_LOAD && self.remainder_bits() > 1 {
+            self.grow()?;
+        }
+        if self.table.len() + slots > self.table.size() {
+            return Err(BloomError::Capacity(format!(
+                "{} more slots don't fit in the {} free",
+                slots,
+                self.table.size() - self.table.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Adds `count` to `fingerprint`, which [`reserve`](Self::reserve) has
+    /// made room for, returning whether it was already present.
+    fn insert_fingerprint(&mut self, fingerprint: u64, mut count: u64) -> Result<bool> {
+        let (quotient, remainder) = self.split(fingerprint);
+        let counter_bits = self.counter_bits;
+        let max = quotient::mask(counter_bits);
+        let present = self.table.find(quotient, |payload| payload >> counter_bits == remainder).is_some();
+        if count == 0 {
+            return Ok(present);
+        }
+        self.total = self.total.saturating_add(count);
+        if let Some(slot) =
+            self.table.find(quotient, |payload| payload >> counter_bits == remainder && payload & max != max)
+        {
+            let payload = self.table.payload(slot);
+            let added = cmp::min(count, max - (payload & max));
+            self.table.set_payload(slot, payload + added);
+            count -= added;
+        }
+        while count > 0 {
+            let added = cmp::min(count, max);
+            self.table.insert(quotient, remainder << counter_bits | added)?;
+            count -= added;
+        }
+        if !present {
+            self.distinct += 1;
+        }
+        Ok(present)
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for CountingQuotientFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        CountingQuotientFilter::insert(self, item)
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        CountingQuotientFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        CountingQuotientFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        self.distinct as f64
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(self.table.words())
+    }
+}
+
+impl<T, Q, S> Removable<Q> for CountingQuotientFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn remove(&mut self, item: &Q) -> bool {
+        CountingQuotientFilter::remove(self, item)
+    }
+}
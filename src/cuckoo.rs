@@ -0,0 +1,377 @@
+use alloc::format;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::packed::PackedVec;
+use crate::{math, params, ApproximateMembership, BloomError, DefaultBuildHasher, Removable, Result};
+
+/// The number of fingerprints in a bucket.
+const BUCKET_SIZE: u64 = 4;
+
+/// The fraction of slots a filter is sized to fill. Tables of four-slot
+/// buckets rarely fail to insert below about 95% occupancy.
+const TARGET_LOAD: f64 = 0.95;
+
+/// How many fingerprints an insertion relocates before giving up.
+const MAX_KICKS: usize = 500;
+
+/// A cuckoo filter (Fan et al., "Cuckoo Filter: Practically Better Than
+/// Bloom"), which stores a short fingerprint of each item in one of two
+/// buckets.
+///
+/// An item's first bucket comes from its hash and its second from the first
+/// XORed with a hash of its fingerprint, so either bucket can be found from
+/// the other and the fingerprint alone. When both are full, a fingerprint is
+/// evicted to its other bucket to make room, and so on.
+///
+/// Unlike a bloom filter, items can be removed, and below a false positive
+/// probability of about 3% a cuckoo filter is also smaller. A lookup reads
+/// at most two buckets.
+///
+/// The table has a hard capacity: once relocations can't make room,
+/// inserting fails with [`BloomError::Capacity`]. As with a
+/// [`CountingBloomFilter`](crate::CountingBloomFilter), adding an item twice
+/// stores it twice, and only items that were added should be removed.
+///
+/// ```
+/// use bloom::CuckooFilter;
+///
+/// let mut filter = CuckooFilter::<String>::new(1000, 0.01);
+/// filter.insert("session-1").unwrap();
+/// assert!(filter.contains("session-1"));
+/// filter.remove("session-1");
+/// assert!(!filter.contains("session-1"));
+/// ```
+#[derive(Debug)]
+pub struct CuckooFilter<T, S = DefaultBuildHasher> {
+    // Fingerprints, `BUCKET_SIZE` per bucket; zero is an empty slot.
+    slots: PackedVec,
+    bucket_mask: u64,
+    // A fingerprint evicted by an insertion that ran out of kicks, and its
+    // bucket. Once set, the filter is full.
+    victim: Option<(u64, u64)>,
+    len: u64,
+    // State for choosing which fingerprint to evict.
+    rng: u64,
+    item_count: usize,
+    false_positive_prob: f64,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for CuckooFilter<T, S> {
+    fn clone(&self) -> CuckooFilter<T, S> {
+        CuckooFilter {
+            slots: self.slots.clone(),
+            bucket_mask: self.bucket_mask,
+            victim: self.victim,
+            len: self.len,
+            rng: self.rng,
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> CuckooFilter<T> {
+    /// Creates a filter sized to hold `item_count` items with the given
+    /// false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0 or `false_positive_prob` is not strictly
+    /// between 0 and 1, or if the table can't be addressed.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> CuckooFilter<T> {
+        CuckooFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](CuckooFilter::new), but mixes `seed` into every hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> CuckooFilter<T> {
+        let mut filter = CuckooFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+}
+
+impl<T: Hash, S: BuildHasher> CuckooFilter<T, S> {
+    /// Like [`new`](CuckooFilter::new), but hashes items with `hash_builder`.
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> CuckooFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        // The alternate bucket is found by XOR, so the bucket count must be a
+        // power of two.
+        let buckets = math::ceil(item_count as f64 / (BUCKET_SIZE as f64 * TARGET_LOAD)) as u64;
+        let buckets = cmp::max(buckets, 1).checked_next_power_of_two();
+        // A lookup compares against the fingerprints in two buckets, so the
+        // false positive probability is about `2 * BUCKET_SIZE / 2^f`.
+        let fingerprint_bits = math::ceil(math::log2(2.0 * BUCKET_SIZE as f64 / false_positive_prob)) as u32;
+        let fingerprint_bits = fingerprint_bits.clamp(1, 32);
+        let slots = buckets
+            .and_then(|buckets| buckets.checked_mul(BUCKET_SIZE))
+            .ok_or_else(|| BloomError::Capacity(format!("{} items can't be addressed", item_count)))
+            .and_then(|slots| PackedVec::new(slots, fingerprint_bits));
+        let slots = match slots {
+            Ok(slots) => slots,
+            Err(e) => panic!("{}", e),
+        };
+        CuckooFilter {
+            bucket_mask: slots.len() / BUCKET_SIZE - 1,
+            slots,
+            victim: None,
+            len: 0,
+            rng: 0x2545_f491_4f6c_dd1d,
+            item_count,
+            false_positive_prob,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::Capacity`] if the filter is full. The item is
+    /// not added, and the filter is unchanged.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> Result<bool>
+    where
+        T: Borrow<Q>,
+    {
+        if self.victim.is_some() {
+            return Err(BloomError::Capacity(format!("the filter is full ({} items)", self.len)));
+        }
+        let present = self.contains(item);
+        let (fingerprint, first) = self.locate(item);
+        let second = self.alternate(first, fingerprint);
+        if self.put(first, fingerprint) || self.put(second, fingerprint) {
+            self.len += 1;
+            return Ok(present);
+        }
+        // Evict fingerprints along a random path until one finds room. The
+        // last one evicted is kept aside rather than lost, so every item
+        // stays findable.
+        let mut bucket = if self.next_random() & 1 == 0 { first } else { second };
+        let mut fingerprint = fingerprint;
+        for _ in 0..MAX_KICKS {
+            let slot = bucket * BUCKET_SIZE + self.next_random() % BUCKET_SIZE;
+            let evicted = self.slots.get(slot);
+            self.slots.set(slot, fingerprint);
+            fingerprint = evicted;
+            bucket = self.alternate(bucket, fingerprint);
+            if self.put(bucket, fingerprint) {
+                self.len += 1;
+                return Ok(present);
+            }
+        }
+        self.victim = Some((bucket, fingerprint));
+        self.len += 1;
+        Ok(present)
+    }
+
+    /// Returns `true` if `item` has probably been inserted and not removed,
+    /// and `false` if it definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (fingerprint, first) = self.locate(item);
+        let second = self.alternate(first, fingerprint);
+        self.find(first, fingerprint).is_some()
+            || self.find(second, fingerprint).is_some()
+            || self.victim_matches(first, second, fingerprint)
+    }
+
+    /// Removes one copy of `item`, returning `true` if it was probably
+    /// present.
+    pub fn remove<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (fingerprint, first) = self.locate(item);
+        let second = self.alternate(first, fingerprint);
+        if self.victim_matches(first, second, fingerprint) {
+            self.victim = None;
+            self.len -= 1;
+            return true;
+        }
+        let slot = match self.find(first, fingerprint).or_else(|| self.find(second, fingerprint)) {
+            Some(slot) => slot,
+            None => return false,
+        };
+        self.slots.set(slot, 0);
+        self.len -= 1;
+        // Now that there is room, try to place the victim again.
+        if let Some((bucket, fingerprint)) = self.victim.take() {
+            let placed = self.put(bucket, fingerprint) || self.put(self.alternate(bucket, fingerprint), fingerprint);
+            if !placed {
+                self.victim = Some((bucket, fingerprint));
+            }
+        }
+        true
+    }
+
+    /// Removes every item from the filter, keeping its allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.victim = None;
+        self.len = 0;
+    }
+
+    /// Returns `true` if the filter holds no fingerprints.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of fingerprints stored, counting each copy of an item
+    /// inserted more than once.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// The fraction of slots that are occupied.
+    pub fn load_factor(&self) -> f64 {
+        self.len as f64 / self.slots.len() as f64
+    }
+
+    /// Estimates the false positive probability of the filter as it is now:
+    /// the chance that a fingerprint in either of an item's buckets matches.
+    pub fn current_fpr(&self) -> f64 {
+        let compared = 2.0 * self.len as f64 / self.bucket_count() as f64;
+        1.0 - math::powf(1.0 - 1.0 / self.slots.max() as f64, compared)
+    }
+
+    /// The number of buckets.
+    pub fn bucket_count(&self) -> u64 {
+        self.bucket_mask + 1
+    }
+
+    /// The number of bits in each fingerprint.
+    pub fn fingerprint_bits(&self) -> u32 {
+        64 - self.slots.max().leading_zeros()
+    }
+
+    /// The number of items the filter was sized for.
+    pub fn capacity(&self) -> usize {
+        self.item_count
+    }
+
+    /// The false positive probability the filter was sized for.
+    pub fn false_positive_prob(&self) -> f64 {
+        self.false_positive_prob
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// `item`'s fingerprint, which is never zero, and its first bucket.
+    fn locate<Q: ?Sized + Hash>(&self, item: &Q) -> (u64, u64) {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let hash = hasher.finish();
+        let fingerprint = cmp::max((hash >> 32) & self.slots.max(), 1);
+        (fingerprint, hash & self.bucket_mask)
+    }
+
+    /// The other bucket a fingerprint in `bucket` may be stored in.
+    fn alternate(&self, bucket: u64, fingerprint: u64) -> u64 {
+        let mut hash = fingerprint.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        hash ^= hash >> 32;
+        (bucket ^ hash) & self.bucket_mask
+    }
+
+    /// The slot in `bucket` holding `fingerprint`, if any.
+    fn find(&self, bucket: u64, fingerprint: u64) -> Option<u64> {
+        let start = bucket * BUCKET_SIZE;
+        (start..start + BUCKET_SIZE).find(|&slot| self.slots.get(slot) == fingerprint)
+    }
+
+    /// Stores `fingerprint` in an empty slot of `bucket`, returning `false`
+    /// if there is none.
+    fn put(&mut self, bucket: u64, fingerprint: u64) -> bool {
+        match self.find(bucket, 0) {
+            Some(slot) => {
+                self.slots.set(slot, fingerprint);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn victim_matches(&self, first: u64, second: u64, fingerprint: u64) -> bool {
+        match self.victim {
+            Some((bucket, victim)) => victim == fingerprint && (bucket == first || bucket == second),
+            None => false,
+        }
+    }
+
+    /// Steps an xorshift generator; evictions only need to avoid cycles, not
+    /// to be unpredictable.
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for CuckooFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        CuckooFilter::insert(self, item)
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        CuckooFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        CuckooFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        self.len as f64
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(self.slots.words())
+    }
+}
+
+impl<T, Q, S> Removable<Q> for CuckooFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn remove(&mut self, item: &Q) -> bool {
+        CuckooFilter::remove(self, item)
+    }
+}
@@ -0,0 +1,391 @@
+use alloc::format;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::packed::PackedVec;
+use crate::{
+    math, params, ApproximateMembership, BloomError, CounterStats, DefaultBuildHasher, Removable, Result,
+};
+
+/// The number of subtables.
+const TABLES: usize = 4;
+
+/// The number of bits in each cell's counter.
+const COUNTER_BITS: u32 = 4;
+
+/// Odd multipliers for the permutation of each subtable.
+const MULTIPLIERS: [u64; TABLES] = [
+    0x9e37_79b9_7f4a_7c15,
+    0xc2b2_ae3d_27d4_eb4f,
+    0x1656_67b1_9e37_79f9,
+    0xd6e8_feb8_6659_fd93,
+];
+
+/// A counting filter built from a d-left hash table of fingerprints
+/// (Bonomi et al., "An Improved Construction for Counting Bloom Filters").
+///
+/// Each item has a fingerprint, which is mapped to one bucket in each of
+/// four subtables by a different permutation. The remaining bits of the
+/// permuted fingerprint, its remainder, are stored in a cell of the least
+/// loaded of those buckets along with a small counter. Because the bucket
+/// and remainder are a permutation of the fingerprint, two items share a
+/// cell only if their fingerprints are equal, so removing an item always
+/// decrements the cell it was counted in.
+///
+/// For the same false positive probability this takes about half the
+/// memory of a [`CountingBloomFilter`](crate::CountingBloomFilter) with
+/// 4-bit counters, and a lookup reads four buckets rather than `k` scattered
+/// counters.
+///
+/// The table has a hard capacity: once every candidate bucket of an item is
+/// full, inserting it fails with [`BloomError::Capacity`]. Filters are sized
+/// with enough headroom that this is vanishingly unlikely below the item
+/// count they were created for.
+///
+/// ```
+/// use bloom::DLeftCountingFilter;
+///
+/// let mut filter = DLeftCountingFilter::<String>::new(1000, 0.01);
+/// filter.insert("session-1").unwrap();
+/// assert!(filter.contains("session-1"));
+/// filter.remove("session-1");
+/// assert!(!filter.contains("session-1"));
+/// ```
+#[derive(Debug)]
+pub struct DLeftCountingFilter<T, S = DefaultBuildHasher> {
+    // Each cell is a remainder followed by a counter; a zero counter is an
+    // empty cell.
+    cells: PackedVec,
+    bucket_bits: u32,
+    remainder_bits: u32,
+    bucket_size: u64,
+    occupied: u64,
+    saturated: u64,
+    item_count: usize,
+    false_positive_prob: f64,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for DLeftCountingFilter<T, S> {
+    fn clone(&self) -> DLeftCountingFilter<T, S> {
+        DLeftCountingFilter {
+            cells: self.cells.clone(),
+            bucket_bits: self.bucket_bits,
+            remainder_bits: self.remainder_bits,
+            bucket_size: self.bucket_size,
+            occupied: self.occupied,
+            saturated: self.saturated,
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> DLeftCountingFilter<T> {
+    /// Creates a filter sized to hold `item_count` items with the given
+    /// false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0 or `false_positive_prob` is not strictly
+    /// between 0 and 1, or if the table can't be addressed.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> DLeftCountingFilter<T> {
+        DLeftCountingFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](DLeftCountingFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> DLeftCountingFilter<T> {
+        let mut filter = DLeftCountingFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+}
+
+impl<T: Hash, S: BuildHasher> DLeftCountingFilter<T, S> {
+    /// Like [`new`](DLeftCountingFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> DLeftCountingFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        // A power of two buckets per subtable, so that bucket and remainder
+        // together are exactly the fingerprint's bits. That leaves between 4
+        // and 8 items per bucket on average; buckets get two spare cells,
+        // since with four choices the fullest bucket is rarely more than one
+        // above the average.
+        let per_table = item_count.div_ceil(TABLES) as u64;
+        let buckets = cmp::max(1, per_table / 4);
+        let buckets = if buckets.is_power_of_two() { buckets } else { buckets.next_power_of_two() / 2 };
+        let average = per_table.div_ceil(buckets);
+        let bucket_size = average + 2;
+        let bucket_bits = buckets.trailing_zeros();
+        // A lookup compares against about `TABLES * average` remainders.
+        let remainder_bits = math::ceil(math::log2(TABLES as f64 * average as f64 / false_positive_prob)) as u32;
+        let remainder_bits = cmp::max(1, cmp::min(remainder_bits, 64 - COUNTER_BITS - bucket_bits));
+        let cells = PackedVec::new(TABLES as u64 * buckets * bucket_size, remainder_bits + COUNTER_BITS);
+        let cells = match cells {
+            Ok(cells) => cells,
+            Err(e) => panic!("{}", e),
+        };
+        DLeftCountingFilter {
+            cells,
+            bucket_bits,
+            remainder_bits,
+            bucket_size,
+            occupied: 0,
+            saturated: 0,
+            item_count,
+            false_positive_prob,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present.
+    ///
+    /// Counters saturate at 15 like a [`CountingBloomFilter`](crate::CountingBloomFilter)'s
+    /// 4-bit counters, and a saturated cell is never removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::Capacity`] if `item` is new and all of its
+    /// buckets are full. The filter is unchanged.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> Result<bool>
+    where
+        T: Borrow<Q>,
+    {
+        let fingerprint = self.fingerprint(item);
+        if let Some(cell) = self.find(fingerprint) {
+            let count = self.count(cell);
+            if count < self.max_count() {
+                self.set_cell(cell, self.remainder(cell), count + 1);
+                if count + 1 == self.max_count() {
+                    self.saturated += 1;
+                }
+            }
+            return Ok(true);
+        }
+        // The least loaded bucket, preferring the leftmost on ties.
+        let mut best = None;
+        for table in 0..TABLES {
+            let (start, remainder) = self.bucket(table, fingerprint);
+            let empty = (start..start + self.bucket_size).filter(|&cell| self.count(cell) == 0);
+            let load = self.bucket_size - empty.clone().count() as u64;
+            if let Some(cell) = empty.min() {
+                if best.is_none_or(|(_, _, best_load)| load < best_load) {
+                    best = Some((cell, remainder, load));
+                }
+            }
+        }
+        match best {
+            Some((cell, remainder, _)) => {
+                self.set_cell(cell, remainder, 1);
+                self.occupied += 1;
+                Ok(false)
+            }
+            None => Err(BloomError::Capacity(format!(
+                "every candidate bucket is full ({} cells occupied)",
+                self.occupied
+            ))),
+        }
+    }
+
+    /// Returns `true` if `item` has probably been inserted and not removed,
+    /// and `false` if it definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.find(self.fingerprint(item)).is_some()
+    }
+
+    /// Removes one occurrence of `item`, returning `true` if it was probably
+    /// present.
+    ///
+    /// As with a [`CountingBloomFilter`](crate::CountingBloomFilter), only
+    /// remove items that were inserted: removing a false positive removes
+    /// the item it was mistaken for.
+    pub fn remove<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let cell = match self.find(self.fingerprint(item)) {
+            Some(cell) => cell,
+            None => return false,
+        };
+        let count = self.count(cell);
+        if count < self.max_count() {
+            self.set_cell(cell, self.remainder(cell), count - 1);
+            if count == 1 {
+                self.occupied -= 1;
+            }
+        }
+        true
+    }
+
+    /// Removes every item from the filter, keeping its allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.occupied = 0;
+        self.saturated = 0;
+    }
+
+    /// Returns `true` if no cell is occupied.
+    pub fn is_empty(&self) -> bool {
+        self.occupied == 0
+    }
+
+    /// The number of distinct fingerprints stored, which is the number of
+    /// distinct items held less the few whose fingerprints collided.
+    pub fn len(&self) -> u64 {
+        self.occupied
+    }
+
+    /// The number of occupied and saturated cells.
+    pub fn stats(&self) -> CounterStats {
+        CounterStats {
+            slots: self.cells.len(),
+            nonzero: self.occupied,
+            saturated: self.saturated,
+        }
+    }
+
+    /// Estimates the false positive probability of the filter as it is now:
+    /// the chance that one of the remainders in an item's buckets matches.
+    pub fn current_fpr(&self) -> f64 {
+        let compared = self.occupied as f64 / (1u64 << self.bucket_bits) as f64;
+        1.0 - math::powf(1.0 - math::powf(2.0, -(self.remainder_bits as f64)), compared)
+    }
+
+    /// The number of items the filter was sized for.
+    pub fn capacity(&self) -> usize {
+        self.item_count
+    }
+
+    /// The false positive probability the filter was sized for.
+    pub fn false_positive_prob(&self) -> f64 {
+        self.false_positive_prob
+    }
+
+    /// The number of remainder bits stored per item.
+    pub fn remainder_bits(&self) -> u32 {
+        self.remainder_bits
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The low `bucket_bits + remainder_bits` bits of `item`'s hash.
+    fn fingerprint<Q: ?Sized + Hash>(&self, item: &Q) -> u64 {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        hasher.finish() & self.fingerprint_mask()
+    }
+
+    fn fingerprint_mask(&self) -> u64 {
+        u64::MAX >> (64 - self.bucket_bits - self.remainder_bits)
+    }
+
+    /// The first cell of `fingerprint`'s bucket in `table`, and the
+    /// remainder it is stored as there.
+    ///
+    /// The fingerprint is permuted by an xorshift, a multiplication by an
+    /// odd constant and another xorshift, each of which is invertible on
+    /// fingerprint-sized values.
+    fn bucket(&self, table: usize, fingerprint: u64) -> (u64, u64) {
+        let shift = (self.bucket_bits + self.remainder_bits) / 2 + 1;
+        let mut x = fingerprint;
+        x ^= x >> shift;
+        x = x.wrapping_mul(MULTIPLIERS[table]) & self.fingerprint_mask();
+        x ^= x >> shift;
+        let bucket = table as u64 * (1 << self.bucket_bits) + (x >> self.remainder_bits);
+        (bucket * self.bucket_size, x & ((1 << self.remainder_bits) - 1))
+    }
+
+    /// The occupied cell holding `fingerprint`, if any.
+    fn find(&self, fingerprint: u64) -> Option<u64> {
+        (0..TABLES).find_map(|table| {
+            let (start, remainder) = self.bucket(table, fingerprint);
+            (start..start + self.bucket_size).find(|&cell| self.count(cell) != 0 && self.remainder(cell) == remainder)
+        })
+    }
+
+    fn max_count(&self) -> u64 {
+        (1 << COUNTER_BITS) - 1
+    }
+
+    fn count(&self, cell: u64) -> u64 {
+        self.cells.get(cell) & self.max_count()
+    }
+
+    fn remainder(&self, cell: u64) -> u64 {
+        self.cells.get(cell) >> COUNTER_BITS
+    }
+
+    fn set_cell(&mut self, cell: u64, remainder: u64, count: u64) {
+        self.cells.set(cell, remainder << COUNTER_BITS | count);
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for DLeftCountingFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        DLeftCountingFilter::insert(self, item)
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        DLeftCountingFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        DLeftCountingFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        self.occupied as f64
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(self.cells.words())
+    }
+}
+
+impl<T, Q, S> Removable<Q> for DLeftCountingFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn remove(&mut self, item: &Q) -> bool {
+        DLeftCountingFilter::remove(self, item)
+    }
+}
@@ -0,0 +1,377 @@
+use alloc::format;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::packed::PackedVec;
+use crate::probe::Probes;
+use crate::{math, params, ApproximateMembership, BloomError, DefaultBuildHasher, Result};
+
+/// The number of ticks [`new`](DecayingBloomFilter::new) divides the window
+/// into.
+const DEFAULT_SLICES: u32 = 8;
+
+/// A bloom filter whose items expire a fixed time after they were last
+/// inserted, for "seen recently" queries such as suppressing an alert that
+/// already fired within the last hour.
+///
+/// Time is whatever the caller passes in: seconds since the epoch,
+/// milliseconds from a monotonic clock, or a sequence number, as long as it
+/// doesn't go backwards. The window is divided into `slices` ticks, and each
+/// cell holds the tick it was last set in, in just enough bits to tell the
+/// last `2 * slices` ticks apart (4 bits for the default of 8). An item is
+/// present while all of its cells were set within the last `slices` ticks,
+/// so it's forgotten between `window - tick` and `slices * tick` after it
+/// was last inserted, where `tick` is `window / slices` rounded up.
+///
+/// When time moves on to a new tick, the next insertion sweeps the cells and
+/// zeroes the ones that have expired, so a stale tick can be reused. Lookups
+/// never modify the filter, and account for expiry themselves.
+///
+/// ```
+/// use bloom::DecayingBloomFilter;
+///
+/// // Remember alerts for an hour, with times in seconds.
+/// let mut fired = DecayingBloomFilter::<&str>::new(1000, 0.01, 3600);
+/// assert!(!fired.insert(&"disk full on db-1", 0));
+/// assert!(fired.insert(&"disk full on db-1", 600));
+/// assert!(!fired.contains(&"disk full on db-1", 600 + 3600));
+/// ```
+#[derive(Debug)]
+pub struct DecayingBloomFilter<T, S = DefaultBuildHasher> {
+    /// The tick each cell was last set in, modulo the largest cell value,
+    /// plus one; zero for a cell that's unset or expired.
+    cells: PackedVec,
+    hash_count: usize,
+    window: u64,
+    slices: u32,
+    tick: u64,
+    /// The latest time the filter has been given.
+    now: u64,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for DecayingBloomFilter<T, S> {
+    fn clone(&self) -> DecayingBloomFilter<T, S> {
+        DecayingBloomFilter {
+            cells: self.cells.clone(),
+            hash_count: self.hash_count,
+            window: self.window,
+            slices: self.slices,
+            tick: self.tick,
+            now: self.now,
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> DecayingBloomFilter<T> {
+    /// Creates a filter that remembers items for `window` units of time,
+    /// sized to hold `item_count` items inserted within any one window with
+    /// the given false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` or `window` is 0, if `false_positive_prob` is
+    /// not strictly between 0 and 1, or if the cells can't be addressed.
+    pub fn new(item_count: usize, false_positive_prob: f64, window: u64) -> DecayingBloomFilter<T> {
+        DecayingBloomFilter::with_hasher(item_count, false_positive_prob, window, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](DecayingBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, window: u64, seed: u64) -> DecayingBloomFilter<T> {
+        let mut filter = DecayingBloomFilter::new(item_count, false_positive_prob, window);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter of `cells` cells with `hashes` hash functions that
+    /// remembers items for `window` units of time, divided into `slices`
+    /// ticks.
+    ///
+    /// More slices make expiry more precise and take more bits per cell.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of them is 0, if `slices` is more than 128, or if the
+    /// cells can't be addressed.
+    pub fn from_params(cells: u64, hashes: usize, window: u64, slices: u32) -> DecayingBloomFilter<T> {
+        DecayingBloomFilter::from_params_with_hasher(cells, hashes, window, slices, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> DecayingBloomFilter<T, S> {
+    /// Like [`new`](DecayingBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(
+        item_count: usize,
+        false_positive_prob: f64,
+        window: u64,
+        hash_builder: S,
+    ) -> DecayingBloomFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let cells = params::optimal_bits(item_count, false_positive_prob);
+        let hash_count = params::optimal_hashes(cells, item_count);
+        let mut filter =
+            DecayingBloomFilter::from_params_with_hasher(cells, hash_count, window, DEFAULT_SLICES, hash_builder);
+        filter.item_count = Some(item_count);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](DecayingBloomFilter::from_params), but hashes
+    /// items with `hash_builder`.
+    pub fn from_params_with_hasher(
+        cells: u64,
+        hashes: usize,
+        window: u64,
+        slices: u32,
+        hash_builder: S,
+    ) -> DecayingBloomFilter<T, S> {
+        let cells = params::validate_layout(cells, hashes).and_then(|()| {
+            if window == 0 || !(1..=128).contains(&slices) {
+                Err(BloomError::InvalidParams(format!(
+                    "the window must be nonzero and divided into 1 to 128 slices (got {} in {} slices)",
+                    window, slices
+                )))
+            } else {
+                // Enough bits that ages up to twice the window stay distinct:
+                // the largest value must be at least `2 * slices - 1`.
+                PackedVec::new(cells, (2 * slices).next_power_of_two().trailing_zeros())
+            }
+        });
+        let cells = match cells {
+            Ok(cells) => cells,
+            Err(e) => panic!("{}", e),
+        };
+        DecayingBloomFilter {
+            cells,
+            hash_count: hashes,
+            window,
+            slices,
+            tick: window.div_ceil(u64::from(slices)),
+            now: 0,
+            item_count: None,
+            false_positive_prob: None,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item` as seen at time `now`.
+    pub fn add<Q: ?Sized + Hash>(&mut self, item: &Q, now: u64)
+    where
+        T: Borrow<Q>,
+    {
+        self.insert(item, now);
+    }
+
+    /// Records `item` as seen at time `now`, returning `true` if it was
+    /// probably seen within the window before that.
+    ///
+    /// A time earlier than one already given is treated as that later time.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q, now: u64) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.advance(now);
+        let present = self.contains(item, self.now);
+        let stamp = self.stamp(self.now / self.tick);
+        for cell in self.probes(item) {
+            self.cells.set(cell, stamp);
+        }
+        present
+    }
+
+    /// Returns `true` if `item` was probably inserted within the window
+    /// before time `now`, and `false` if it definitely was not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q, now: u64) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let tick = cmp::max(now, self.now) / self.tick;
+        if tick - self.now / self.tick >= u64::from(self.slices) {
+            return false;
+        }
+        self.probes(item).all(|cell| self.is_live(self.cells.get(cell), tick))
+    }
+
+    /// Moves the filter's clock on to `now`, expiring the items that are
+    /// older than the window by then. Insertions do this themselves.
+    pub fn advance(&mut self, now: u64) {
+        let (from, to) = (self.now / self.tick, now / self.tick);
+        if to <= from {
+            return;
+        }
+        self.now = now;
+        if to - from >= u64::from(self.slices) {
+            self.cells.clear();
+            return;
+        }
+        for cell in 0..self.cells.len() {
+            let stamp = self.cells.get(cell);
+            if stamp != 0 && !self.is_live(stamp, to) {
+                self.cells.set(cell, 0);
+            }
+        }
+    }
+
+    /// Removes every item from the filter, keeping its clock and
+    /// parameters.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// Returns `true` if no item was inserted within the window before the
+    /// latest time the filter has been given.
+    pub fn is_empty(&self) -> bool {
+        self.count_live() == 0
+    }
+
+    /// The number of cells set within the window before the latest time the
+    /// filter has been given.
+    pub fn count_live(&self) -> u64 {
+        let tick = self.now / self.tick;
+        (0..self.cells.len()).filter(|&cell| self.is_live(self.cells.get(cell), tick)).count() as u64
+    }
+
+    /// Estimates the false positive probability of the filter as it is now:
+    /// the fraction of live cells to the power `k`.
+    pub fn current_fpr(&self) -> f64 {
+        let fill = self.count_live() as f64 / self.cells.len() as f64;
+        math::powf(fill, self.hash_count as f64)
+    }
+
+    /// Estimates how many distinct items were inserted within the window,
+    /// from the fraction of live cells as for a bloom filter.
+    pub fn estimated_len(&self) -> f64 {
+        let m = self.cells.len() as f64;
+        -m / self.hash_count as f64 * math::ln(1.0 - self.count_live() as f64 / m)
+    }
+
+    /// How long items are remembered for.
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    /// The number of ticks the window is divided into.
+    pub fn slices(&self) -> u32 {
+        self.slices
+    }
+
+    /// The length of one tick, the precision items expire with.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// The latest time the filter has been given.
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// The number of cells.
+    pub fn cell_count(&self) -> u64 {
+        self.cells.len()
+    }
+
+    /// The number of bits in each cell.
+    pub fn cell_bits(&self) -> u32 {
+        self.cells.width()
+    }
+
+    /// The number of hash functions applied to each item.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The number of items per window the filter was sized for, or `None`
+    /// if it was built from explicit parameters.
+    pub fn item_count(&self) -> Option<usize> {
+        self.item_count
+    }
+
+    /// The false positive probability the filter was sized for, or `None` if
+    /// it was built from explicit parameters.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    fn probes<Q: ?Sized + Hash>(&self, item: &Q) -> Probes<S::Hasher> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let len = self.cells.len();
+        Probes::new(hasher, len, self.hash_count, len > params::WIDE_HASH_THRESHOLD)
+    }
+
+    /// The cell value for tick `tick`.
+    fn stamp(&self, tick: u64) -> u64 {
+        tick % self.cells.max() + 1
+    }
+
+    /// Returns `true` if a cell holding `stamp` was set within the window
+    /// before tick `tick`. Only valid for cells set less than twice the
+    /// window earlier, which sweeping guarantees.
+    fn is_live(&self, stamp: u64, tick: u64) -> bool {
+        let cycle = self.cells.max();
+        stamp != 0 && (self.stamp(tick) + cycle - stamp) % cycle < u64::from(self.slices)
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for DecayingBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    /// Inserts `item` at the latest time the filter has been given.
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(DecayingBloomFilter::insert(self, item, self.now))
+    }
+
+    /// Looks `item` up at the latest time the filter has been given.
+    fn contains(&self, item: &Q) -> bool {
+        DecayingBloomFilter::contains(self, item, self.now)
+    }
+
+    fn clear(&mut self) {
+        DecayingBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        DecayingBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(self.cells.words())
+    }
+}
@@ -0,0 +1,55 @@
+use alloc::string::String;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error::Error;
+#[cfg(feature = "std")]
+use std::io;
+
+/// The error type for fallible filter operations.
+#[derive(Debug)]
+pub enum BloomError {
+    /// Reading or writing a file failed.
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /// The requested filter parameters are out of range.
+    InvalidParams(String),
+    /// A serialized filter could not be decoded.
+    CorruptFile(String),
+    /// The filter would be too large to allocate or address.
+    Capacity(String),
+    /// Two filters can't be combined because their parameters differ.
+    Incompatible(String),
+}
+
+/// A `Result` whose error type is [`BloomError`].
+pub type Result<T> = core::result::Result<T, BloomError>;
+
+impl fmt::Display for BloomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            BloomError::Io(e) => write!(f, "I/O error: {}", e),
+            BloomError::InvalidParams(msg) => write!(f, "invalid parameters: {}", msg),
+            BloomError::CorruptFile(msg) => write!(f, "corrupt filter file: {}", msg),
+            BloomError::Capacity(msg) => write!(f, "capacity exceeded: {}", msg),
+            BloomError::Incompatible(msg) => write!(f, "incompatible filters: {}", msg),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for BloomError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BloomError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for BloomError {
+    fn from(e: io::Error) -> BloomError {
+        BloomError::Io(e)
+    }
+}
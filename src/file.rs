@@ -0,0 +1,104 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::panic;
+use std::sync::atomic::AtomicU64;
+use std::thread;
+
+use crate::parallel;
+use crate::{BloomError, BloomFilter, BloomFilterBuilder, Result};
+
+/// Builds a filter holding every (trimmed) line of the file at `path`.
+///
+/// The filter is sized for `capacity` lines, and its false positive rate
+/// climbs past `false_positive_prob` if the file has more. When the number of
+/// lines isn't known, add them to a [`ScalableBloomFilter`](crate::ScalableBloomFilter)
+/// instead.
+///
+/// The file is read on the calling thread. To read a large file on several,
+/// use [`filter_from_file_with_jobs`].
+pub fn filter_from_file(path: &str, capacity: usize, false_positive_prob: f64) -> Result<BloomFilter<String>> {
+    let mut filter = BloomFilterBuilder::new()
+        .item_count(capacity)
+        .false_positive_prob(false_positive_prob)
+        .build()?;
+
+    let mut file = BufReader::new(File::open(path)?);
+    let mut line = String::new();
+    while file.read_line(&mut line)? > 0 {
+        filter.add(line.trim());
+        line.clear();
+    }
+    Ok(filter)
+}
+
+/// Like [`filter_from_file`], but reads the file on `jobs` threads.
+///
+/// The file is split into `jobs` byte ranges of about the same size (or a
+/// byte each, if it is shorter than that), and each thread reads, trims and
+/// hashes the lines starting in its range. The threads set bits in the one
+/// filter, so it takes no more memory than with a single thread, and the
+/// filter is the same whatever `jobs` is.
+///
+/// # Errors
+///
+/// Returns [`BloomError::InvalidParams`] if `jobs` is 0, and
+/// [`BloomError::Io`] if the file can't be read or isn't UTF-8.
+pub fn filter_from_file_with_jobs(
+    path: &str,
+    capacity: usize,
+    false_positive_prob: f64,
+    jobs: usize,
+) -> Result<BloomFilter<String>> {
+    if jobs == 0 {
+        return Err(BloomError::InvalidParams("at least one job is needed".to_string()));
+    }
+    let mut filter = BloomFilterBuilder::new()
+        .item_count(capacity)
+        .false_positive_prob(false_positive_prob)
+        .build()?;
+
+    let len = File::open(path)?.metadata()?.len();
+    // Ranges need at least a byte each.
+    let jobs = (jobs as u64).min(len.max(1));
+    let mut result = Ok(());
+    filter.par_insert_with(|filter, words| {
+        result = thread::scope(|scope| {
+            let workers: Vec<_> = (0..jobs)
+                .map(|job| {
+                    let (start, end) = (len * job / jobs, len * (job + 1) / jobs);
+                    scope.spawn(move || insert_lines(path, start, end, filter, words))
+                })
+                .collect();
+            workers.into_iter().try_for_each(|worker| worker.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+        });
+    });
+    result?;
+    Ok(filter)
+}
+
+/// Inserts the lines that start at byte offsets from `start` up to `end`.
+///
+/// A range that starts inside a line leaves that line to the range before.
+fn insert_lines(path: &str, start: u64, end: u64, filter: &BloomFilter<String>, words: &[AtomicU64]) -> io::Result<()> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut offset = start;
+    let mut line = Vec::new();
+    if start > 0 {
+        // The line the byte before `start` belongs to, which is only the
+        // newline if a line starts exactly at `start`.
+        file.seek(SeekFrom::Start(start - 1))?;
+        offset += file.read_until(b'\n', &mut line)? as u64 - 1;
+    }
+    while offset < end {
+        line.clear();
+        let read = file.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        offset += read as u64;
+        let line = std::str::from_utf8(&line)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8"))?;
+        parallel::set_all(filter, words, line.trim());
+    }
+    Ok(())
+}
@@ -0,0 +1,864 @@
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::iter::FromIterator;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{BitAndAssign, BitOrAssign};
+
+use crate::bit_vec::BitVec;
+use crate::hash::{Fnv1a, HashScheme};
+use crate::probe::Probes;
+use crate::simd;
+use crate::{math, params, BloomError, BloomFilterBuilder, Result};
+
+/// The hasher used by filters that don't configure their own: a
+/// [`HashScheme`], which maps items to the same bits on every platform and in
+/// every release (see [`HASH_SCHEME_VERSION`](crate::hash::HASH_SCHEME_VERSION))
+/// and is recorded when the filter is saved. It defaults to SipHash-1-3.
+pub type DefaultBuildHasher = HashScheme;
+
+/// A bloom filter over items of type `T`.
+///
+/// Items are never stored; only their hashes are recorded in a bit vector,
+/// so `contains` may return false positives but never false negatives.
+///
+/// # Concurrency
+///
+/// Because no `T` is stored, a filter is `Send` and `Sync` whenever its
+/// hasher is, whatever `T` is: a `BloomFilter<Rc<String>>` can be sent to
+/// another thread. Lookups only need `&self`, so a filter can be shared
+/// between threads and queried concurrently, while insertion needs `&mut
+/// self` and so exclusive access. With the `concurrent` feature,
+/// `ConcurrentBloomFilter` lets threads insert at once too, and a
+/// [`CowBloomFilter`](crate::CowBloomFilter) lets one thread insert while
+/// others query snapshots of it.
+#[derive(Debug)]
+pub struct BloomFilter<T, S = DefaultBuildHasher> {
+    bit_vec: BitVec,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    bit_vec_size: u64,
+    hash_count: usize,
+    seed: u64,
+    wide_hashes: bool,
+    hash_builder: S,
+    // `fn(T)` rather than `T`, since items are only ever passed in: this
+    // keeps `T` from affecting `Send`, `Sync` or drop checking.
+    phantom: PhantomData<fn(T)>,
+}
+
+// Filters of non-thread-safe items must still be thread-safe.
+const _: () = {
+    use alloc::rc::Rc;
+    use alloc::string::String;
+
+    fn assert_send_sync<X: Send + Sync>() {}
+
+    #[allow(dead_code)]
+    fn assert_filters_send_sync() {
+        assert_send_sync::<BloomFilter<Rc<String>>>();
+        assert_send_sync::<BloomFilter<*const u8>>();
+        assert_send_sync::<BloomFilterBuilder<Rc<String>>>();
+    }
+};
+
+// Implemented by hand because deriving would require `T: Clone` and
+// `T: PartialEq`, though no `T` is ever stored.
+impl<T, S: Clone> Clone for BloomFilter<T, S> {
+    fn clone(&self) -> BloomFilter<T, S> {
+        BloomFilter {
+            bit_vec: self.bit_vec.clone(),
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            bit_vec_size: self.bit_vec_size,
+            hash_count: self.hash_count,
+            seed: self.seed,
+            wide_hashes: self.wide_hashes,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Filters are equal if they have the same bit vector size, hash count, seed
+/// and hash width, hash items the same way, and have exactly the same bits
+/// set. The sizing targets they were built for are not compared.
+impl<T, S: BuildHasher> PartialEq for BloomFilter<T, S> {
+    fn eq(&self, other: &BloomFilter<T, S>) -> bool {
+        self.bit_vec_size == other.bit_vec_size
+            && self.hash_count == other.hash_count
+            && self.seed == other.seed
+            && self.wide_hashes == other.wide_hashes
+            && hasher_id(&self.hash_builder) == hasher_id(&other.hash_builder)
+            && self.bit_vec == other.bit_vec
+    }
+}
+
+impl<T, S: BuildHasher> Eq for BloomFilter<T, S> {}
+
+impl<T: Hash> BloomFilter<T> {
+    /// Creates a filter sized to hold `item_count` items with the given
+    /// false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0 or `false_positive_prob` is not strictly
+    /// between 0 and 1, and aborts if the bits can't be allocated. Use
+    /// [`try_new`](BloomFilter::try_new) to get an error instead.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> BloomFilter<T> {
+        BloomFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](BloomFilter::new), but returns an error rather than
+    /// panicking or aborting.
+    ///
+    /// On Linux, bits of 64 MiB or more are only allocated if they fit in the
+    /// memory the system has available, so a mistyped capacity fails here
+    /// instead of getting the process killed later. Elsewhere, bits that
+    /// can't be allocated at all are still an error rather than an abort.
+    ///
+    /// ```
+    /// use bloom::{BloomError, BloomFilter};
+    ///
+    /// assert!(BloomFilter::<u64>::try_new(1000, 0.01).is_ok());
+    /// let result = BloomFilter::<u64>::try_new(1_000_000_000_000_000, 0.01);
+    /// assert!(matches!(result, Err(BloomError::Capacity(_))));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::InvalidParams`] if `item_count` is 0 or
+    /// `false_positive_prob` is not strictly between 0 and 1, and
+    /// [`BloomError::Capacity`] if the bits don't fit in memory.
+    pub fn try_new(item_count: usize, false_positive_prob: f64) -> Result<BloomFilter<T>> {
+        BloomFilterBuilder::new().item_count(item_count).false_positive_prob(false_positive_prob).build()
+    }
+
+    /// Like [`try_new`](BloomFilter::try_new), but also fails if the bits
+    /// would take more than `max_memory` bytes.
+    pub fn try_with_capacity(item_count: usize, false_positive_prob: f64, max_memory: u64) -> Result<BloomFilter<T>> {
+        BloomFilterBuilder::new()
+            .item_count(item_count)
+            .false_positive_prob(false_positive_prob)
+            .max_memory(max_memory)
+            .build()
+    }
+
+    /// Like [`new`](BloomFilter::new), but mixes `seed` into every hash.
+    ///
+    /// Filters built with the same parameters and seed from the same items
+    /// have identical bits, so independently built filters can be compared
+    /// or merged. Filters with different seeds map items to unrelated bits,
+    /// which is useful when the false positives of one filter must not be
+    /// correlated with another's.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> BloomFilter<T> {
+        let mut filter = BloomFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter with exactly `bits` bits and `hashes` hash functions.
+    ///
+    /// Use this to match the layout of a filter built elsewhere; to size a
+    /// filter for a workload, use [`BloomFilter::new`] instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` or `hashes` is 0.
+    pub fn from_params(bits: u64, hashes: usize) -> BloomFilter<T> {
+        BloomFilter::from_params_with_hasher(bits, hashes, DefaultBuildHasher::default())
+    }
+
+    /// Reconstructs a filter from bits in the layout produced by
+    /// [`to_bytes`](BloomFilter::to_bytes) or
+    /// [`into_raw_parts`](BloomFilter::into_raw_parts).
+    ///
+    /// `bits` must be exactly `ceil(bit_len / 8)` bytes long, with any bits
+    /// past `bit_len` in the last byte clear. The filter must have been built
+    /// with the same hasher for lookups to work, and filters of more than
+    /// [`WIDE_HASH_THRESHOLD`](params::WIDE_HASH_THRESHOLD) bits are assumed
+    /// to use wide hashes, as they do by default.
+    pub fn from_raw_parts(bits: &[u8], bit_len: u64, hash_count: usize, seed: u64) -> Result<BloomFilter<T>> {
+        BloomFilter::from_raw_parts_with_hasher(bits, bit_len, hash_count, seed, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> BloomFilter<T, S> {
+    /// Like [`new`](BloomFilter::new), but hashes items with `hash_builder`.
+    ///
+    /// Any `BuildHasher` works, including fast non-cryptographic ones from
+    /// crates like `ahash` or `rustc-hash`, which can make a large
+    /// difference for short keys:
+    ///
+    /// ```
+    /// use std::collections::hash_map::RandomState;
+    /// use bloom::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::with_hasher(1000, 0.01, RandomState::new());
+    /// filter.add("a key");
+    /// assert!(filter.contains("a key"));
+    /// # let _: &BloomFilter<&str, _> = &filter;
+    /// ```
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> BloomFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let bit_vec_size = params::optimal_bits(item_count, false_positive_prob);
+        let hash_count = params::optimal_hashes(bit_vec_size, item_count);
+        BloomFilter::from_parts(
+            BitVec::new(bit_vec_size),
+            hash_count,
+            Some(item_count),
+            Some(false_positive_prob),
+            0,
+            hash_builder,
+        )
+    }
+
+    /// Like [`from_params`](BloomFilter::from_params), but hashes items with
+    /// `hash_builder`.
+    pub fn from_params_with_hasher(bits: u64, hashes: usize, hash_builder: S) -> BloomFilter<T, S> {
+        if let Err(e) = params::validate_layout(bits, hashes) {
+            panic!("{}", e);
+        }
+        BloomFilter::from_parts(BitVec::new(bits), hashes, None, None, 0, hash_builder)
+    }
+
+    /// Like [`from_raw_parts`](BloomFilter::from_raw_parts), but hashes items
+    /// with `hash_builder`.
+    pub fn from_raw_parts_with_hasher(
+        bits: &[u8],
+        bit_len: u64,
+        hash_count: usize,
+        seed: u64,
+        hash_builder: S,
+    ) -> Result<BloomFilter<T, S>> {
+        params::validate_layout(bit_len, hash_count)?;
+        if bits.len() as u64 != bit_len.div_ceil(8) {
+            return Err(BloomError::InvalidParams(format!(
+                "{} bits need {} bytes (got {})",
+                bit_len,
+                bit_len.div_ceil(8),
+                bits.len()
+            )));
+        }
+        if !bit_len.is_multiple_of(8) && bits[bits.len() - 1] >> (bit_len % 8) != 0 {
+            return Err(BloomError::InvalidParams("bits past the end of the filter are set".to_string()));
+        }
+        let mut bit_vec = BitVec::try_new(bit_len)?;
+        let words = bit_vec.words_mut();
+        for (i, &byte) in bits.iter().enumerate() {
+            words[i / 8] |= u64::from(byte) << (8 * (i % 8));
+        }
+        Ok(BloomFilter::from_parts(bit_vec, hash_count, None, None, seed, hash_builder))
+    }
+
+    pub(crate) fn from_parts(
+        bit_vec: BitVec,
+        hash_count: usize,
+        item_count: Option<usize>,
+        false_positive_prob: Option<f64>,
+        seed: u64,
+        hash_builder: S,
+    ) -> BloomFilter<T, S> {
+        let bit_vec_size = bit_vec.len();
+        BloomFilter {
+            bit_vec_size,
+            bit_vec,
+            item_count,
+            false_positive_prob,
+            hash_count,
+            seed,
+            wide_hashes: bit_vec_size > params::WIDE_HASH_THRESHOLD,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Takes the filter apart into its bits and hasher, for wrappers that
+    /// keep the bits some other way.
+    pub(crate) fn into_bits_and_hasher(self) -> (BitVec, S) {
+        (self.bit_vec, self.hash_builder)
+    }
+
+    /// The filter's bits, for parallel insertion, which sets them in place.
+    #[cfg(feature = "std")]
+    pub(crate) fn bit_vec_mut(&mut self) -> &mut BitVec {
+        &mut self.bit_vec
+    }
+
+    pub(crate) fn with_wide_hashes(mut self, wide_hashes: bool) -> BloomFilter<T, S> {
+        self.wide_hashes = wide_hashes;
+        self
+    }
+
+    /// Sets the sizing targets reported by [`capacity`](BloomFilter::capacity)
+    /// and [`false_positive_prob`](BloomFilter::false_positive_prob).
+    #[cfg(feature = "serde")]
+    pub(crate) fn with_sizing(
+        mut self,
+        item_count: Option<usize>,
+        false_positive_prob: Option<f64>,
+    ) -> BloomFilter<T, S> {
+        self.item_count = item_count;
+        self.false_positive_prob = false_positive_prob;
+        self
+    }
+
+    /// Records `item` in the filter.
+    ///
+    /// `item` may be any borrowed form of `T`, as with `HashSet`, provided
+    /// it hashes the same way.
+    pub fn add<Q: ?Sized + Hash>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+    {
+        self.insert(item);
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present, i.e. every bit it maps to was already set.
+    ///
+    /// Note that this is the opposite sense to `HashSet::insert`: a `false`
+    /// return means the item is definitely new.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let probes = self.probes(item);
+        self.set_all(probes)
+    }
+
+    /// Returns `true` if `item` has probably been added, and `false` if it
+    /// definitely has not.
+    ///
+    /// `item` may be any borrowed form of `T`, so a `BloomFilter<String>` can
+    /// be queried with a `&str` without allocating.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.probes(item).all(|index| self.bit_vec.get(index))
+    }
+
+    /// Records the raw bytes of `key`, returning `true` if they were probably
+    /// already present.
+    ///
+    /// The bytes are written straight to the hasher rather than through the
+    /// `Hash` trait, so with the default hasher a key maps to bits that
+    /// depend only on its bytes: SipHash-1-3 under
+    /// [`DEFAULT_KEY`](crate::hash::DEFAULT_KEY) of the seed as 8
+    /// little-endian bytes followed by the key. That makes filters built this
+    /// way reproducible in other languages.
+    ///
+    /// Keys added with this method must be queried with
+    /// [`contains_bytes`](BloomFilter::contains_bytes): `Hash` adds framing
+    /// to most types (a `str` is followed by a `0xff` byte, for example), so
+    /// `contains("key")` won't find `insert_bytes("key")`.
+    pub fn insert_bytes<K: ?Sized + AsRef<[u8]>>(&mut self, key: &K) -> bool {
+        let probes = self.probes_bytes(key.as_ref());
+        self.set_all(probes)
+    }
+
+    /// Returns `true` if the raw bytes of `key` have probably been added with
+    /// [`insert_bytes`](BloomFilter::insert_bytes).
+    pub fn contains_bytes<K: ?Sized + AsRef<[u8]>>(&self, key: &K) -> bool {
+        self.probes_bytes(key.as_ref()).all(|index| self.bit_vec.get(index))
+    }
+
+    /// Checks each of `items`, returning whether each is probably present.
+    ///
+    /// Equivalent to calling [`contains`](BloomFilter::contains) on each item,
+    /// but the bit indices for a batch of items are computed and prefetched
+    /// up front before any bits are read, so that the cache misses of a
+    /// large filter overlap rather than each waiting on memory in turn. Items
+    /// are hashed one at a time; only the bit tests use SIMD, which with
+    /// AVX2 reads the bits four at a time.
+    pub fn contains_many(&self, items: &[T]) -> Vec<bool> {
+        self.contains_iter(items).collect()
+    }
+
+    /// Like [`contains_many`](BloomFilter::contains_many), but lazily over any
+    /// iterator of items.
+    pub fn contains_iter<'a, Q, I>(&'a self, items: I) -> ContainsIter<'a, T, S, I::IntoIter>
+    where
+        Q: ?Sized + Hash + 'a,
+        T: Borrow<Q>,
+        I: IntoIterator<Item = &'a Q>,
+    {
+        ContainsIter {
+            filter: self,
+            items: items.into_iter(),
+            indices: Vec::with_capacity(BATCH_SIZE * self.hash_count),
+            results: Vec::with_capacity(BATCH_SIZE),
+            position: 0,
+        }
+    }
+
+    /// Adds every item in `other` to this filter, so that it holds the union
+    /// of both sets.
+    ///
+    /// Both filters must have the same bit vector size, hash count, seed and
+    /// hash width, and hash items the same way, with the same scheme and
+    /// key; otherwise [`BloomError::Incompatible`] is returned and this
+    /// filter is unchanged.
+    pub fn try_union(&mut self, other: &BloomFilter<T, S>) -> Result<()> {
+        self.check_compatible(other)?;
+        self.bit_vec.union(&other.bit_vec);
+        Ok(())
+    }
+
+    /// Keeps only the bits set in both this filter and `other`, approximating
+    /// the intersection of their sets.
+    ///
+    /// Every item present in both sets is still reported as present, so
+    /// there are no false negatives. The result is not the same as a filter
+    /// built from the intersection, though: a bit can survive because one
+    /// item from each side happened to set it, so the false positive rate is
+    /// higher than that of a directly built filter, and
+    /// [`estimated_len`](BloomFilter::estimated_len) overestimates the
+    /// intersection's size. It is never higher than the rate of either input.
+    ///
+    /// Requires compatible filters, as for [`try_union`](BloomFilter::try_union).
+    pub fn try_intersect(&mut self, other: &BloomFilter<T, S>) -> Result<()> {
+        self.check_compatible(other)?;
+        self.bit_vec.intersect(&other.bit_vec);
+        Ok(())
+    }
+
+    /// Estimates the Jaccard similarity `|A ∩ B| / |A ∪ B|` of this filter's
+    /// set and `other`'s, from how much their set bits overlap.
+    ///
+    /// This uses the cardinality estimates rather than the raw ratio of
+    /// shared to total set bits, which would overstate the similarity of
+    /// unrelated sets as the filters fill up. Two empty filters have a
+    /// similarity of 1.
+    pub fn jaccard_similarity(&self, other: &BloomFilter<T, S>) -> Result<f64> {
+        let union = self.estimate_union_size(other)?;
+        if union == 0.0 {
+            return Ok(1.0);
+        }
+        let intersection = self.estimate_intersection_size(other)?;
+        Ok((intersection / union).min(1.0))
+    }
+
+    /// A digest of the filter's parameters and bits, for checking that two
+    /// copies of a filter are identical without comparing them bit by bit.
+    ///
+    /// The digest is 64-bit FNV-1a over the bit vector size, hash count,
+    /// seed, hash width (0 or 1) and the hasher's hash of a fixed value as
+    /// little-endian `u64`s, followed by the bits packed least-significant
+    /// first into `ceil(m / 8)` bytes. With a [`HashScheme`] it is stable
+    /// across platforms and releases, and equal filters always have equal
+    /// fingerprints.
+    pub fn fingerprint(&self) -> u64 {
+        let mut digest = Fnv1a::new();
+        digest.write(&self.bit_vec_size.to_le_bytes());
+        digest.write(&(self.hash_count as u64).to_le_bytes());
+        digest.write(&self.seed.to_le_bytes());
+        digest.write(&u64::from(self.wide_hashes).to_le_bytes());
+        digest.write(&hasher_id(&self.hash_builder).to_le_bytes());
+        digest.write(&self.to_bytes());
+        digest.finish()
+    }
+
+    /// The words backing the bit vector. Bit `i` is bit `i % 64` of word
+    /// `i / 64`, and any bits past the end of the filter are zero. The first
+    /// word is aligned to 64 bytes, the start of a cache line.
+    pub fn as_raw_slice(&self) -> &[u64] {
+        self.bit_vec.words()
+    }
+
+    /// The bits packed into `ceil(m / 8)` bytes, with bit `i` stored as bit
+    /// `i % 8` of byte `i / 8`. This layout doesn't depend on the platform's
+    /// byte order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // Can't truncate: the bytes are no longer than the words in memory.
+        let byte_len = self.bit_vec_size.div_ceil(8) as usize;
+        self.bit_vec
+            .words()
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .take(byte_len)
+            .collect()
+    }
+
+    /// Decomposes the filter into its packed bits, bit length, hash count and
+    /// seed, the arguments [`from_raw_parts`](BloomFilter::from_raw_parts)
+    /// takes to rebuild it.
+    pub fn into_raw_parts(self) -> (Vec<u8>, u64, usize, u64) {
+        (self.to_bytes(), self.bit_vec_size, self.hash_count, self.seed)
+    }
+
+    /// Iterates over the indices of the bits that are set, in increasing
+    /// order.
+    pub fn iter_ones(&self) -> Ones<'_> {
+        Ones {
+            words: self.bit_vec.words(),
+            word_index: 0,
+            current: self.bit_vec.words().first().copied().unwrap_or(0),
+        }
+    }
+
+    /// Removes every item from the filter, keeping its allocation and
+    /// parameters so it can be reused.
+    pub fn clear(&mut self) {
+        self.bit_vec.clear();
+    }
+
+    /// Returns `true` if nothing has been added since the filter was created
+    /// or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.bit_vec.none()
+    }
+
+    /// The number of bits that are set.
+    pub fn count_ones(&self) -> u64 {
+        self.bit_vec.count_ones()
+    }
+
+    /// The fraction of bits that are set, from 0.0 for an empty filter to 1.0
+    /// for a saturated one.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.bit_vec_size as f64
+    }
+
+    /// Estimates the false positive probability of the filter as it is now:
+    /// the chance that all `k` probes of an absent item land on set bits,
+    /// `fill_ratio^k`.
+    ///
+    /// Unlike [`achieved_false_positive_rate`](BloomFilter::achieved_false_positive_rate)
+    /// this reflects what has actually been added, so it can be compared
+    /// against the target to detect a filter that has been overfilled.
+    pub fn current_fpr(&self) -> f64 {
+        math::powf(self.fill_ratio(), self.hash_count as f64)
+    }
+
+    /// Estimates how many distinct items have been added, from the number of
+    /// bits that are set: `-m/k ln(1 - X/m)`.
+    ///
+    /// Returns infinity once every bit is set, since the filter can no longer
+    /// tell how many items it holds.
+    pub fn estimated_len(&self) -> f64 {
+        self.estimate_from_ones(self.count_ones())
+    }
+
+    /// Estimates the number of distinct items in the union of this filter's
+    /// set and `other`'s, from the bits set in either (Swamidass and Baldi).
+    pub fn estimate_union_size(&self, other: &BloomFilter<T, S>) -> Result<f64> {
+        self.check_compatible(other)?;
+        Ok(self.estimate_from_ones(self.bit_vec.count_ones_with(&other.bit_vec, |a, b| a | b)))
+    }
+
+    /// Estimates the number of distinct items in both this filter's set and
+    /// `other`'s, as `|A| + |B| - |A ∪ B|`.
+    ///
+    /// Unlike ANDing the filters and calling
+    /// [`estimated_len`](BloomFilter::estimated_len), this isn't inflated by
+    /// bits that two different items happened to share.
+    pub fn estimate_intersection_size(&self, other: &BloomFilter<T, S>) -> Result<f64> {
+        let union = self.estimate_union_size(other)?;
+        Ok((self.estimated_len() + other.estimated_len() - union).max(0.0))
+    }
+
+    /// Estimates the number of distinct items in this filter's set but not
+    /// in `other`'s, as `|A ∪ B| - |B|`.
+    pub fn estimate_difference_size(&self, other: &BloomFilter<T, S>) -> Result<f64> {
+        let union = self.estimate_union_size(other)?;
+        Ok((union - other.estimated_len()).max(0.0))
+    }
+
+    /// The number of bits in the underlying bit vector.
+    pub fn bit_vec_size(&self) -> u64 {
+        self.bit_vec_size
+    }
+
+    /// The number of bytes of memory the filter occupies: its bits, which
+    /// are rounded up to whole 64-byte cache lines, and its own fields.
+    ///
+    /// ```
+    /// use bloom::BloomFilter;
+    ///
+    /// let filter = BloomFilter::<u64>::new(1_000_000, 0.01);
+    /// assert!(filter.memory_bytes() as u64 >= filter.bit_vec_size() / 8);
+    /// ```
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + self.bit_vec.heap_bytes()
+    }
+
+    /// The number of hash functions applied to each item.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The false positive probability the filter was sized for, or `None`
+    /// if it was built from explicit parameters.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The number of items the filter was sized for, or `None` if it was
+    /// built from explicit parameters without one.
+    pub fn capacity(&self) -> Option<usize> {
+        self.item_count
+    }
+
+    /// The false positive probability the filter will actually have once it
+    /// holds [`capacity`](BloomFilter::capacity) items, given the bit vector
+    /// size and hash count that were chosen.
+    ///
+    /// This differs from [`false_positive_prob`](BloomFilter::false_positive_prob)
+    /// because sizes are rounded to whole bits and hash functions, and
+    /// clamped to [`MAX_BITS`](params::MAX_BITS).
+    pub fn achieved_false_positive_rate(&self) -> Option<f64> {
+        self.item_count
+            .map(|n| params::expected_fpr(self.bit_vec_size, n, self.hash_count))
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Whether bit indices are derived from 128-bit rather than 64-bit hashes;
+    /// see [`BloomFilterBuilder::wide_hashes`](crate::BloomFilterBuilder::wide_hashes).
+    pub fn wide_hashes(&self) -> bool {
+        self.wide_hashes
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Estimates the number of items that would set `ones` bits.
+    fn estimate_from_ones(&self, ones: u64) -> f64 {
+        let m = self.bit_vec_size as f64;
+        -m / self.hash_count as f64 * math::ln(1.0 - ones as f64 / m)
+    }
+
+    pub(crate) fn check_compatible(&self, other: &BloomFilter<T, S>) -> Result<()> {
+        if self.bit_vec_size != other.bit_vec_size {
+            return Err(BloomError::Incompatible(format!(
+                "bit vector sizes differ ({} and {})",
+                self.bit_vec_size, other.bit_vec_size
+            )));
+        }
+        if self.hash_count != other.hash_count {
+            return Err(BloomError::Incompatible(format!(
+                "hash counts differ ({} and {})",
+                self.hash_count, other.hash_count
+            )));
+        }
+        if self.seed != other.seed {
+            return Err(BloomError::Incompatible(format!("seeds differ ({} and {})", self.seed, other.seed)));
+        }
+        if self.wide_hashes != other.wide_hashes {
+            return Err(BloomError::Incompatible("hash widths differ".to_string()));
+        }
+        if hasher_id(&self.hash_builder) != hasher_id(&other.hash_builder) {
+            return Err(BloomError::Incompatible("hashers differ".to_string()));
+        }
+        Ok(())
+    }
+
+    /// The bit indices `item` maps to.
+    pub(crate) fn probes<Q: ?Sized + Hash>(&self, item: &Q) -> Probes<S::Hasher> {
+        let mut hasher = self.seeded_hasher();
+        item.hash(&mut hasher);
+        self.probes_from(hasher)
+    }
+
+    /// The bit indices the raw bytes `bytes` map to.
+    fn probes_bytes(&self, bytes: &[u8]) -> Probes<S::Hasher> {
+        let mut hasher = self.seeded_hasher();
+        hasher.write(bytes);
+        self.probes_from(hasher)
+    }
+
+    fn seeded_hasher(&self) -> S::Hasher {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        hasher
+    }
+
+    /// Derives probes from a hasher that the item has been written to.
+    fn probes_from(&self, hasher: S::Hasher) -> Probes<S::Hasher> {
+        Probes::new(hasher, self.bit_vec_size, self.hash_count, self.wide_hashes)
+    }
+
+    /// Sets every bit in `probes`, returning whether they were all set
+    /// already.
+    fn set_all(&mut self, probes: Probes<S::Hasher>) -> bool {
+        let mut present = true;
+        for index in probes {
+            if !self.bit_vec.set(index) {
+                present = false;
+            }
+        }
+        present
+    }
+}
+
+/// The hash of a fixed value by a filter's hasher, which tells apart hashers
+/// that would map items to different bits: different schemes or keys.
+fn hasher_id<S: BuildHasher>(hash_builder: &S) -> u64 {
+    let mut hasher = hash_builder.build_hasher();
+    hasher.write(b"bloom");
+    hasher.finish()
+}
+
+/// An iterator over the indices of set bits in a filter.
+///
+/// Created by [`BloomFilter::iter_ones`].
+#[derive(Debug, Clone)]
+pub struct Ones<'a> {
+    words: &'a [u64],
+    word_index: usize,
+    // The bits of the current word that haven't been yielded yet.
+    current: u64,
+}
+
+impl<'a> Iterator for Ones<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        while self.current == 0 {
+            self.word_index += 1;
+            self.current = *self.words.get(self.word_index)?;
+        }
+        let bit = u64::from(self.current.trailing_zeros());
+        // Clear the lowest set bit.
+        self.current &= self.current - 1;
+        Some(self.word_index as u64 * 64 + bit)
+    }
+}
+
+/// Adds every item in the right-hand filter to the left-hand one.
+///
+/// # Panics
+///
+/// Panics if the filters are incompatible; see [`BloomFilter::try_union`].
+impl<'a, T: Hash, S: BuildHasher> BitOrAssign<&'a BloomFilter<T, S>> for BloomFilter<T, S> {
+    fn bitor_assign(&mut self, other: &'a BloomFilter<T, S>) {
+        if let Err(e) = self.try_union(other) {
+            panic!("{}", e);
+        }
+    }
+}
+
+/// Keeps only the bits set in both filters.
+///
+/// # Panics
+///
+/// Panics if the filters are incompatible; see [`BloomFilter::try_intersect`].
+impl<'a, T: Hash, S: BuildHasher> BitAndAssign<&'a BloomFilter<T, S>> for BloomFilter<T, S> {
+    fn bitand_assign(&mut self, other: &'a BloomFilter<T, S>) {
+        if let Err(e) = self.try_intersect(other) {
+            panic!("{}", e);
+        }
+    }
+}
+
+/// The number of items [`ContainsIter`] hashes before reading any bits.
+const BATCH_SIZE: usize = 16;
+
+/// An iterator over membership results for a sequence of items.
+///
+/// Created by [`BloomFilter::contains_iter`].
+#[derive(Debug)]
+pub struct ContainsIter<'a, T, S, I> {
+    filter: &'a BloomFilter<T, S>,
+    items: I,
+    indices: Vec<u64>,
+    results: Vec<bool>,
+    position: usize,
+}
+
+impl<'a, T, S, Q, I> ContainsIter<'a, T, S, I>
+where
+    T: Hash + Borrow<Q>,
+    S: BuildHasher,
+    Q: ?Sized + Hash + 'a,
+    I: Iterator<Item = &'a Q>,
+{
+    fn fill(&mut self) {
+        let k = self.filter.hash_count;
+        self.indices.clear();
+        for item in self.items.by_ref().take(BATCH_SIZE) {
+            self.indices.extend(self.filter.probes(item));
+        }
+        // Start loading every word the batch reads, so that the loads
+        // overlap rather than each waiting on memory in turn.
+        let bit_vec = &self.filter.bit_vec;
+        for &index in &self.indices {
+            simd::prefetch(&bit_vec.words()[(index / 64) as usize]);
+        }
+        self.results.clear();
+        self.results.extend(self.indices.chunks(k).map(|indices| simd::all_set(bit_vec.words(), indices)));
+        self.position = 0;
+    }
+}
+
+impl<'a, T, S, Q, I> Iterator for ContainsIter<'a, T, S, I>
+where
+    T: Hash + Borrow<Q>,
+    S: BuildHasher,
+    Q: ?Sized + Hash + 'a,
+    I: Iterator<Item = &'a Q>,
+{
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        if self.position == self.results.len() {
+            self.fill();
+        }
+        let result = self.results.get(self.position).copied();
+        self.position += 1;
+        result
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let buffered = self.results.len().saturating_sub(self.position);
+        let (lower, upper) = self.items.size_hint();
+        (lower + buffered, upper.map(|upper| upper + buffered))
+    }
+}
+
+impl<T: Hash, S: BuildHasher> Extend<T> for BloomFilter<T, S> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.add(&item);
+        }
+    }
+}
+
+impl<'a, T: Hash + 'a, S: BuildHasher> Extend<&'a T> for BloomFilter<T, S> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        for item in iter {
+            self.add(item);
+        }
+    }
+}
+
+/// Collects items into a filter sized for exactly as many items as the
+/// iterator yields, at the default false positive probability.
+///
+/// The items are buffered so they can be counted before the filter is
+/// allocated; to avoid that, size a filter up front and use `extend`.
+impl<T: Hash, S: BuildHasher + Default> FromIterator<T> for BloomFilter<T, S> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> BloomFilter<T, S> {
+        let items: Vec<T> = iter.into_iter().collect();
+        let mut filter = BloomFilterBuilder::new()
+            .item_count(cmp::max(items.len(), 1))
+            .hasher(S::default())
+            .build()
+            .expect("the default parameters are valid");
+        filter.extend(items);
+        filter
+    }
+}
@@ -0,0 +1,35 @@
+//! A self-describing binary format for filters.
+//!
+//! Every field is little-endian:
+//!
+//! | Bytes | Field                                                    |
+//! |-------|----------------------------------------------------------|
+//! | 4     | Magic, `b"BLMF"`                                         |
+//! | 2     | Format version, currently 1                              |
+//! | 1     | [`HashScheme::id`](crate::hash::HashScheme::id)          |
+//! | 1     | Flags, described below                                   |
+//! | 4     | [Hash scheme version](crate::hash::HASH_SCHEME_VERSION)  |
+//! | 8     | Bit vector size `m`                                      |
+//! | 8     | Hash count                                               |
+//! | 8     | Seed                                                     |
+//! | 8     | Item count, or 0                                         |
+//! | 8     | Target false positive probability as `f64` bits, or 0    |
+//! | 8     | Key check value, for keyed schemes only                  |
+//! | m / 8 | The bits, as produced by [`BloomFilter::to_bytes`](crate::BloomFilter::to_bytes) |
+//! | 8     | XXH64 (seed 0) of every preceding byte                   |
+//!
+//! Flag bit 0 is set if the item count is, bit 1 if the target false
+//! positive probability is, and bit 2 if the filter uses
+//! [wide hashes](crate::BloomFilterBuilder::wide_hashes).
+
+pub(crate) const MAGIC: [u8; 4] = *b"BLMF";
+pub(crate) const FORMAT_VERSION: u16 = 1;
+
+#[cfg(feature = "std")]
+pub(crate) const HAS_ITEM_COUNT: u8 = 1;
+#[cfg(feature = "std")]
+pub(crate) const HAS_FALSE_POSITIVE_PROB: u8 = 2;
+pub(crate) const WIDE_HASHES: u8 = 4;
+
+/// The length of the fixed fields before the optional key check value.
+pub(crate) const HEADER_LEN: usize = 52;
@@ -0,0 +1,40 @@
+use core::hash::Hasher;
+
+const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64-bit FNV-1a.
+///
+/// Very fast for short keys, but with much weaker mixing than the other
+/// hashers here, so expect a somewhat higher false positive rate on
+/// structured keys.
+#[derive(Debug, Clone)]
+pub struct Fnv1a(u64);
+
+impl Fnv1a {
+    /// Creates a hasher in its initial state.
+    pub fn new() -> Fnv1a {
+        Fnv1a(OFFSET_BASIS)
+    }
+}
+
+impl Default for Fnv1a {
+    fn default() -> Fnv1a {
+        Fnv1a::new()
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(PRIME);
+        }
+    }
+
+    portable_integer_writes!();
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
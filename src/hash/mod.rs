@@ -0,0 +1,221 @@
+//! Hashing with output that is stable across platforms and releases.
+//!
+//! The standard library's `DefaultHasher` may change algorithm in any Rust
+//! release, which would silently change which bits a filter sets and break
+//! every filter saved by an older build. Filters in this crate instead hash
+//! with one of the fixed algorithms in [`HashScheme`], SipHash-1-3 under a
+//! fixed key by default, as described by [`HASH_SCHEME_VERSION`].
+
+use core::fmt;
+use core::hash::BuildHasher;
+#[cfg(feature = "std")]
+use core::hash::Hasher;
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+
+/// Implements `Hasher`'s integer methods so that they write little-endian
+/// bytes, and `usize`/`isize` as 64-bit values, on every platform.
+macro_rules! portable_integer_writes {
+    () => {
+        fn write_u8(&mut self, i: u8) {
+            self.write(&[i]);
+        }
+
+        fn write_u16(&mut self, i: u16) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_u32(&mut self, i: u32) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_u64(&mut self, i: u64) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_u128(&mut self, i: u128) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_usize(&mut self, i: usize) {
+            self.write_u64(i as u64);
+        }
+
+        fn write_i8(&mut self, i: i8) {
+            self.write_u8(i as u8);
+        }
+
+        fn write_i16(&mut self, i: i16) {
+            self.write_u16(i as u16);
+        }
+
+        fn write_i32(&mut self, i: i32) {
+            self.write_u32(i as u32);
+        }
+
+        fn write_i64(&mut self, i: i64) {
+            self.write_u64(i as u64);
+        }
+
+        fn write_i128(&mut self, i: i128) {
+            self.write_u128(i as u128);
+        }
+
+        fn write_isize(&mut self, i: isize) {
+            self.write_u64(i as i64 as u64);
+        }
+    };
+}
+
+mod fnv;
+mod scheme;
+mod sip;
+mod wy;
+mod xx;
+
+pub use self::fnv::Fnv1a;
+pub(crate) use self::scheme::KEYED_ID;
+pub use self::scheme::{HashScheme, SchemeHasher};
+pub use self::sip::SipHasher13;
+pub use self::wy::WyHash;
+pub use self::xx::XxHash64;
+pub(crate) use self::xx::{read_le, xxh64};
+
+/// The version of the scheme for mapping items to bits with a [`HashScheme`].
+///
+/// Version 1 is:
+///
+/// 1. Create the scheme's hasher: a [`SipHasher13`] keyed with
+///    [`DEFAULT_KEY`] or a [`SecretKey`], or an [`XxHash64`], [`WyHash`] or
+///    [`Fnv1a`] with seed 0.
+/// 2. Write the filter's seed with `write_u64`, then the item with its `Hash`
+///    implementation. Integers are written little-endian and `usize` as a
+///    `u64`, whatever the platform. Keys given to the `*_bytes` methods are
+///    written as they are, with no framing.
+/// 3. `h1` is the hasher's output. Write the byte `0xff`; `h2` is the new
+///    output.
+/// 4. Map `h1` and `h2` onto `0..m` as `x` and `y`: by masking their low
+///    bits if `m` is a power of two, and otherwise by taking the high 64
+///    bits of their 128-bit products with `m`.
+/// 5. Take up to `g = max(floor(log2(m)), 9) - 8` probes: probe `j` is `x`,
+///    after which `x` becomes `x + y` and `y` becomes `y + j + 1`, both
+///    modulo `m`.
+/// 6. While more probes are needed, write the byte `0xfc` and repeat from
+///    step 3.
+///
+/// With [wide hashes](crate::BloomFilterBuilder::wide_hashes), step 3 also
+/// writes the byte `0xfe` and then `0xfd` after computing `h2`, taking the
+/// output after each as `l1` and `l2`, and step 4 maps the 128-bit values
+/// `h1 << 64 | l1` and `h2 << 64 | l2` instead: by masking the low bits, or
+/// by taking the bits of the 192-bit products with `m` above the lowest 128.
+/// Whether a filter uses wide hashes is recorded separately when it is
+/// saved.
+///
+/// This number will be incremented if any of those steps ever change.
+pub const HASH_SCHEME_VERSION: u32 = 1;
+
+/// The fixed SipHash key used by default: the bytes `0x00..=0x0f`, as in the
+/// SipHash reference test vectors.
+pub const DEFAULT_KEY: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+/// A 128-bit key for keyed hashing, which should be kept secret.
+///
+/// Its `Debug` output doesn't show the key, so it can't leak into logs.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SecretKey([u8; 16]);
+
+impl SecretKey {
+    /// Wraps the given key bytes.
+    pub fn new(key: [u8; 16]) -> SecretKey {
+        SecretKey(key)
+    }
+
+    /// Generates a key from the randomness the standard library uses to seed
+    /// `HashMap`s.
+    #[cfg(feature = "std")]
+    pub fn random() -> SecretKey {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u8(0);
+        let k0 = hasher.finish();
+        hasher.write_u8(1);
+        let k1 = hasher.finish();
+        let mut key = [0; 16];
+        key[..8].copy_from_slice(&k0.to_le_bytes());
+        key[8..].copy_from_slice(&k1.to_le_bytes());
+        SecretKey(key)
+    }
+
+    /// The key bytes.
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// A value derived from the key that reveals nothing about it, but lets a
+    /// reader check that it was given the key a filter was built with.
+    #[cfg(feature = "std")]
+    pub(crate) fn check_value(&self) -> u64 {
+        let mut hasher = SipHashBuilder::with_key(self.0).build_hasher();
+        hasher.write(b"bloom key check");
+        hasher.finish()
+    }
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SecretKey(..)")
+    }
+}
+
+/// Builds [`SipHasher13`]s with a fixed key.
+///
+/// The default key is [`DEFAULT_KEY`]. Its `Debug` output doesn't show the
+/// key.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SipHashBuilder {
+    k0: u64,
+    k1: u64,
+}
+
+impl SipHashBuilder {
+    /// Creates a builder whose hashers use `key`.
+    pub fn with_key(key: [u8; 16]) -> SipHashBuilder {
+        let mut k0 = [0; 8];
+        let mut k1 = [0; 8];
+        k0.copy_from_slice(&key[..8]);
+        k1.copy_from_slice(&key[8..]);
+        SipHashBuilder {
+            k0: u64::from_le_bytes(k0),
+            k1: u64::from_le_bytes(k1),
+        }
+    }
+}
+
+impl fmt::Debug for SipHashBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SipHashBuilder(..)")
+    }
+}
+
+impl Default for SipHashBuilder {
+    fn default() -> SipHashBuilder {
+        SipHashBuilder::with_key(DEFAULT_KEY)
+    }
+}
+
+impl BuildHasher for SipHashBuilder {
+    type Hasher = SipHasher13;
+
+    fn build_hasher(&self) -> SipHasher13 {
+        SipHasher13::new_with_keys(self.k0, self.k1)
+    }
+}
+
+/// MurmurHash3's 64-bit finalizer, which mixes every bit of `h` into every
+/// bit of the result.
+pub(crate) fn fmix64(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
@@ -0,0 +1,130 @@
+use core::hash::{BuildHasher, Hasher};
+
+use super::{Fnv1a, SecretKey, SipHashBuilder, SipHasher13, WyHash, XxHash64};
+
+// Not in `HashScheme::ALL`, since a keyed scheme can't be made from its id.
+pub(crate) const KEYED_ID: u8 = 5;
+
+/// A hash algorithm chosen at run time, which can be recorded alongside a
+/// filter so it is always loaded with the algorithm it was built with.
+///
+/// Every scheme writes integers portably and has fixed parameters, so filters
+/// are reproducible across platforms and releases whichever is chosen. The
+/// default is [`HashScheme::SipHash13`].
+///
+/// All of the unkeyed schemes are public algorithms with public parameters,
+/// so anyone who knows a filter's size, hash count and seed can search
+/// offline for items that are false positives. Where that matters, for
+/// example when a filter gates expensive lookups made for untrusted input,
+/// use [`HashScheme::KeyedSipHash13`] with a key kept secret.
+///
+/// ```
+/// use bloom::hash::HashScheme;
+/// use bloom::BloomFilter;
+///
+/// let mut filter = BloomFilter::<String>::with_hasher(1000, 0.01, HashScheme::WyHash);
+/// filter.add("apple");
+///
+/// let mut bytes = Vec::new();
+/// filter.write_to(&mut bytes).unwrap();
+/// let loaded = BloomFilter::<String>::read_from(&bytes[..]).unwrap();
+/// assert_eq!(*loaded.hasher(), HashScheme::WyHash);
+/// assert!(loaded.contains("apple"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum HashScheme {
+    /// SipHash-1-3 keyed with [`DEFAULT_KEY`](super::DEFAULT_KEY). The best
+    /// distribution, and the slowest.
+    #[default]
+    SipHash13,
+    /// XXH64 with seed 0. Much faster than SipHash for longer keys.
+    XxHash64,
+    /// wyhash final4 with seed 0 and the default secret. The fastest here
+    /// for most key sizes.
+    WyHash,
+    /// 64-bit FNV-1a. Fast for very short keys but poorly mixed.
+    Fnv1a,
+    /// SipHash-1-3 keyed with a secret. The key is never serialized with the
+    /// filter; see [`BloomFilter::read_from_keyed`](crate::BloomFilter::read_from_keyed).
+    KeyedSipHash13(SecretKey),
+}
+
+impl HashScheme {
+    /// Every unkeyed scheme, in order of their ids.
+    pub const ALL: [HashScheme; 4] = [
+        HashScheme::SipHash13,
+        HashScheme::XxHash64,
+        HashScheme::WyHash,
+        HashScheme::Fnv1a,
+    ];
+
+    /// A stable identifier for the scheme, used in serialized filters.
+    pub const fn id(self) -> u8 {
+        match self {
+            HashScheme::SipHash13 => 1,
+            HashScheme::XxHash64 => 2,
+            HashScheme::WyHash => 3,
+            HashScheme::Fnv1a => 4,
+            HashScheme::KeyedSipHash13(_) => KEYED_ID,
+        }
+    }
+
+    /// The unkeyed scheme with the given [`id`](HashScheme::id), if there is
+    /// one.
+    pub const fn from_id(id: u8) -> Option<HashScheme> {
+        match id {
+            1 => Some(HashScheme::SipHash13),
+            2 => Some(HashScheme::XxHash64),
+            3 => Some(HashScheme::WyHash),
+            4 => Some(HashScheme::Fnv1a),
+            _ => None,
+        }
+    }
+}
+
+impl BuildHasher for HashScheme {
+    type Hasher = SchemeHasher;
+
+    fn build_hasher(&self) -> SchemeHasher {
+        match self {
+            HashScheme::SipHash13 => SchemeHasher::SipHash13(SipHashBuilder::default().build_hasher()),
+            HashScheme::XxHash64 => SchemeHasher::XxHash64(XxHash64::with_seed(0)),
+            HashScheme::WyHash => SchemeHasher::WyHash(WyHash::with_seed(0)),
+            HashScheme::Fnv1a => SchemeHasher::Fnv1a(Fnv1a::new()),
+            HashScheme::KeyedSipHash13(key) => {
+                SchemeHasher::SipHash13(SipHashBuilder::with_key(*key.as_bytes()).build_hasher())
+            }
+        }
+    }
+}
+
+/// The hasher for a [`HashScheme`].
+#[derive(Debug, Clone)]
+pub enum SchemeHasher {
+    SipHash13(SipHasher13),
+    XxHash64(XxHash64),
+    WyHash(WyHash),
+    Fnv1a(Fnv1a),
+}
+
+impl Hasher for SchemeHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        match self {
+            SchemeHasher::SipHash13(hasher) => hasher.write(bytes),
+            SchemeHasher::XxHash64(hasher) => hasher.write(bytes),
+            SchemeHasher::WyHash(hasher) => hasher.write(bytes),
+            SchemeHasher::Fnv1a(hasher) => hasher.write(bytes),
+        }
+    }
+
+    portable_integer_writes!();
+
+    fn finish(&self) -> u64 {
+        match self {
+            SchemeHasher::SipHash13(hasher) => hasher.finish(),
+            SchemeHasher::XxHash64(hasher) => hasher.finish(),
+            SchemeHasher::WyHash(hasher) => hasher.finish(),
+            SchemeHasher::Fnv1a(hasher) => hasher.finish(),
+        }
+    }
+}
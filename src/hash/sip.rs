@@ -0,0 +1,118 @@
+use core::fmt;
+use core::hash::Hasher;
+
+/// SipHash-1-3: one compression round per message block and three
+/// finalization rounds.
+///
+/// Unlike the standard library's hashers, integers are always written in
+/// little-endian order and `usize` is always written as a `u64`, so the same
+/// values hash the same way on every platform. Its `Debug` output doesn't
+/// show its state, which would reveal the key.
+#[derive(Clone)]
+pub struct SipHasher13 {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+    // Bytes written since the last full 8-byte block, in the low bytes.
+    tail: u64,
+    tail_len: usize,
+    // The total number of bytes written, modulo 256.
+    length: u8,
+}
+
+impl fmt::Debug for SipHasher13 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SipHasher13(..)")
+    }
+}
+
+impl SipHasher13 {
+    /// Creates a hasher keyed with `k0` and `k1`.
+    pub fn new_with_keys(k0: u64, k1: u64) -> SipHasher13 {
+        SipHasher13 {
+            v0: k0 ^ 0x736f_6d65_7073_6575,
+            v1: k1 ^ 0x646f_7261_6e64_6f6d,
+            v2: k0 ^ 0x6c79_6765_6e65_7261,
+            v3: k1 ^ 0x7465_6462_7974_6573,
+            tail: 0,
+            tail_len: 0,
+            length: 0,
+        }
+    }
+
+    #[inline]
+    fn round(&mut self) {
+        self.v0 = self.v0.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(13);
+        self.v1 ^= self.v0;
+        self.v0 = self.v0.rotate_left(32);
+        self.v2 = self.v2.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(16);
+        self.v3 ^= self.v2;
+        self.v0 = self.v0.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(21);
+        self.v3 ^= self.v0;
+        self.v2 = self.v2.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(17);
+        self.v1 ^= self.v2;
+        self.v2 = self.v2.rotate_left(32);
+    }
+
+    #[inline]
+    fn compress(&mut self, block: u64) {
+        self.v3 ^= block;
+        self.round();
+        self.v0 ^= block;
+    }
+}
+
+impl Hasher for SipHasher13 {
+    fn write(&mut self, mut bytes: &[u8]) {
+        self.length = self.length.wrapping_add(bytes.len() as u8);
+
+        // Top up a partial block left over from the last write.
+        if self.tail_len > 0 {
+            let needed = 8 - self.tail_len;
+            let taken = needed.min(bytes.len());
+            for (i, &byte) in bytes[..taken].iter().enumerate() {
+                self.tail |= u64::from(byte) << (8 * (self.tail_len + i));
+            }
+            self.tail_len += taken;
+            bytes = &bytes[taken..];
+            if self.tail_len < 8 {
+                return;
+            }
+            let block = self.tail;
+            self.compress(block);
+            self.tail = 0;
+            self.tail_len = 0;
+        }
+
+        let mut blocks = bytes.chunks_exact(8);
+        for block in &mut blocks {
+            let mut word = [0; 8];
+            word.copy_from_slice(block);
+            self.compress(u64::from_le_bytes(word));
+        }
+        for (i, &byte) in blocks.remainder().iter().enumerate() {
+            self.tail |= u64::from(byte) << (8 * i);
+        }
+        self.tail_len = blocks.remainder().len();
+    }
+
+    portable_integer_writes!();
+
+    fn finish(&self) -> u64 {
+        // Finishing doesn't consume the hasher, so work on a copy of the
+        // state; more can be written and the hasher finished again.
+        let mut state = self.clone();
+        let block = (u64::from(self.length) << 56) | self.tail;
+        state.compress(block);
+        state.v2 ^= 0xff;
+        state.round();
+        state.round();
+        state.round();
+        state.v0 ^ state.v1 ^ state.v2 ^ state.v3
+    }
+}
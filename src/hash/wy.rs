@@ -0,0 +1,143 @@
+use core::hash::Hasher;
+
+/// The default secret from the reference implementation.
+const SECRET: [u64; 4] = [
+    0xa076_1d64_78bd_642f,
+    0xe703_7ed1_a0b4_28db,
+    0x8ebc_6af0_9c88_c6e3,
+    0x5899_65cc_7537_4cc3,
+];
+
+// The buffer holds the 16 bytes before the unprocessed input, which the
+// final step may read back, followed by up to one 48-byte block of input.
+const HISTORY: usize = 16;
+const BLOCK: usize = 48;
+
+/// wyhash (version "final4"), one of the fastest hashes that passes SMHasher.
+///
+/// wyhash is defined over a whole message, so this buffers one 48-byte block
+/// at a time and produces the same result as hashing everything written as
+/// one byte string.
+#[derive(Debug, Clone)]
+pub struct WyHash {
+    seed: u64,
+    see1: u64,
+    see2: u64,
+    buffer: [u8; HISTORY + BLOCK],
+    buffered: usize,
+    total_len: u64,
+}
+
+impl WyHash {
+    /// Creates a hasher with the given seed and the default secret.
+    pub fn with_seed(seed: u64) -> WyHash {
+        let seed = seed ^ mix(seed ^ SECRET[0], SECRET[1]);
+        WyHash {
+            seed,
+            see1: seed,
+            see2: seed,
+            buffer: [0; HISTORY + BLOCK],
+            buffered: 0,
+            total_len: 0,
+        }
+    }
+
+    fn consume_block(&mut self) {
+        let block = &self.buffer[HISTORY..];
+        self.seed = mix(read_u64(block) ^ SECRET[1], read_u64(&block[8..]) ^ self.seed);
+        self.see1 = mix(read_u64(&block[16..]) ^ SECRET[2], read_u64(&block[24..]) ^ self.see1);
+        self.see2 = mix(read_u64(&block[32..]) ^ SECRET[3], read_u64(&block[40..]) ^ self.see2);
+        self.buffer.copy_within(BLOCK.., 0);
+        self.buffered = 0;
+    }
+}
+
+impl Default for WyHash {
+    fn default() -> WyHash {
+        WyHash::with_seed(0)
+    }
+}
+
+#[inline]
+fn multiply(a: u64, b: u64) -> (u64, u64) {
+    let product = u128::from(a) * u128::from(b);
+    (product as u64, (product >> 64) as u64)
+}
+
+#[inline]
+fn mix(a: u64, b: u64) -> u64 {
+    let (low, high) = multiply(a, b);
+    low ^ high
+}
+
+#[inline]
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut word = [0; 8];
+    word.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(word)
+}
+
+#[inline]
+fn read_u32(bytes: &[u8]) -> u64 {
+    let mut word = [0; 4];
+    word.copy_from_slice(&bytes[..4]);
+    u64::from(u32::from_le_bytes(word))
+}
+
+impl Hasher for WyHash {
+    fn write(&mut self, mut bytes: &[u8]) {
+        self.total_len = self.total_len.wrapping_add(bytes.len() as u64);
+        while !bytes.is_empty() {
+            // A full block is only consumed once more input follows it, since
+            // the last block of the message is handled differently.
+            if self.buffered == BLOCK {
+                self.consume_block();
+            }
+            let taken = (BLOCK - self.buffered).min(bytes.len());
+            let start = HISTORY + self.buffered;
+            self.buffer[start..start + taken].copy_from_slice(&bytes[..taken]);
+            self.buffered += taken;
+            bytes = &bytes[taken..];
+        }
+    }
+
+    portable_integer_writes!();
+
+    fn finish(&self) -> u64 {
+        let len = self.total_len;
+        let mut seed = self.seed;
+        let (a, b);
+        if len <= 16 {
+            let p = &self.buffer[HISTORY..HISTORY + self.buffered];
+            let n = p.len();
+            if n >= 4 {
+                let offset = (n >> 3) << 2;
+                a = (read_u32(p) << 32) | read_u32(&p[offset..]);
+                b = (read_u32(&p[n - 4..]) << 32) | read_u32(&p[n - 4 - offset..]);
+            } else if n > 0 {
+                a = (u64::from(p[0]) << 16) | (u64::from(p[n >> 1]) << 8) | u64::from(p[n - 1]);
+                b = 0;
+            } else {
+                a = 0;
+                b = 0;
+            }
+        } else {
+            if len > BLOCK as u64 {
+                seed ^= self.see1 ^ self.see2;
+            }
+            let mut start = HISTORY;
+            let end = HISTORY + self.buffered;
+            while end - start > 16 {
+                seed = mix(
+                    read_u64(&self.buffer[start..]) ^ SECRET[1],
+                    read_u64(&self.buffer[start + 8..]) ^ seed,
+                );
+                start += 16;
+            }
+            a = read_u64(&self.buffer[end - 16..]);
+            b = read_u64(&self.buffer[end - 8..]);
+        }
+        let (a, b) = multiply(a ^ SECRET[1], b ^ seed);
+        mix(a ^ SECRET[0] ^ len, b ^ SECRET[1])
+    }
+}
@@ -0,0 +1,216 @@
+use core::hash::Hasher;
+
+const PRIME_1: u64 = 0x9e37_79b1_85eb_ca87;
+const PRIME_2: u64 = 0xc2b2_ae3d_27d4_eb4f;
+const PRIME_3: u64 = 0x1656_67b1_9e37_79f9;
+const PRIME_4: u64 = 0x85eb_ca77_c2b2_ae63;
+const PRIME_5: u64 = 0x27d4_eb2f_1656_67c5;
+
+/// XXH64, a fast non-cryptographic hash with good distribution.
+#[derive(Debug, Clone)]
+pub struct XxHash64 {
+    seed: u64,
+    accumulators: [u64; 4],
+    // Bytes not yet folded into the accumulators; always fewer than 32.
+    buffer: [u8; 32],
+    buffer_len: usize,
+    total_len: u64,
+}
+
+impl XxHash64 {
+    /// Creates a hasher with the given seed.
+    pub fn with_seed(seed: u64) -> XxHash64 {
+        XxHash64 {
+            seed,
+            accumulators: [
+                seed.wrapping_add(PRIME_1).wrapping_add(PRIME_2),
+                seed.wrapping_add(PRIME_2),
+                seed,
+                seed.wrapping_sub(PRIME_1),
+            ],
+            buffer: [0; 32],
+            buffer_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn consume_stripe(&mut self, stripe: &[u8]) {
+        for (accumulator, lane) in self.accumulators.iter_mut().zip(stripe.chunks_exact(8)) {
+            *accumulator = round(*accumulator, read_u64(lane));
+        }
+    }
+}
+
+impl Default for XxHash64 {
+    fn default() -> XxHash64 {
+        XxHash64::with_seed(0)
+    }
+}
+
+#[inline]
+const fn round(accumulator: u64, lane: u64) -> u64 {
+    accumulator
+        .wrapping_add(lane.wrapping_mul(PRIME_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME_1)
+}
+
+#[inline]
+const fn merge(hash: u64, accumulator: u64) -> u64 {
+    (hash ^ round(0, accumulator)).wrapping_mul(PRIME_1).wrapping_add(PRIME_4)
+}
+
+#[inline]
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut word = [0; 8];
+    word.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(word)
+}
+
+#[inline]
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut word = [0; 4];
+    word.copy_from_slice(&bytes[..4]);
+    u32::from_le_bytes(word)
+}
+
+impl Hasher for XxHash64 {
+    fn write(&mut self, mut bytes: &[u8]) {
+        self.total_len = self.total_len.wrapping_add(bytes.len() as u64);
+
+        if self.buffer_len > 0 {
+            let taken = (32 - self.buffer_len).min(bytes.len());
+            self.buffer[self.buffer_len..self.buffer_len + taken].copy_from_slice(&bytes[..taken]);
+            self.buffer_len += taken;
+            bytes = &bytes[taken..];
+            if self.buffer_len < 32 {
+                return;
+            }
+            let stripe = self.buffer;
+            self.consume_stripe(&stripe);
+            self.buffer_len = 0;
+        }
+
+        let mut stripes = bytes.chunks_exact(32);
+        for stripe in &mut stripes {
+            self.consume_stripe(stripe);
+        }
+        let rest = stripes.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffer_len = rest.len();
+    }
+
+    portable_integer_writes!();
+
+    fn finish(&self) -> u64 {
+        let mut hash = if self.total_len >= 32 {
+            let [a, b, c, d] = self.accumulators;
+            let mut hash = a
+                .rotate_left(1)
+                .wrapping_add(b.rotate_left(7))
+                .wrapping_add(c.rotate_left(12))
+                .wrapping_add(d.rotate_left(18));
+            for &accumulator in &self.accumulators {
+                hash = merge(hash, accumulator);
+            }
+            hash
+        } else {
+            self.seed.wrapping_add(PRIME_5)
+        };
+        hash = hash.wrapping_add(self.total_len);
+
+        let mut rest = &self.buffer[..self.buffer_len];
+        while rest.len() >= 8 {
+            hash ^= round(0, read_u64(rest));
+            hash = hash.rotate_left(27).wrapping_mul(PRIME_1).wrapping_add(PRIME_4);
+            rest = &rest[8..];
+        }
+        if rest.len() >= 4 {
+            hash ^= u64::from(read_u32(rest)).wrapping_mul(PRIME_1);
+            hash = hash.rotate_left(23).wrapping_mul(PRIME_2).wrapping_add(PRIME_3);
+            rest = &rest[4..];
+        }
+        for &byte in rest {
+            hash ^= u64::from(byte).wrapping_mul(PRIME_5);
+            hash = hash.rotate_left(11).wrapping_mul(PRIME_1);
+        }
+
+        avalanche(hash)
+    }
+}
+
+const fn avalanche(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(PRIME_2);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(PRIME_3);
+    hash ^= hash >> 32;
+    hash
+}
+
+/// The little-endian integer of `len` bytes at `offset`, in a form usable in
+/// constant evaluation.
+pub(crate) const fn read_le(bytes: &[u8], offset: usize, len: usize) -> u64 {
+    let mut value = 0;
+    let mut i = 0;
+    while i < len {
+        value |= (bytes[offset + i] as u64) << (8 * i);
+        i += 1;
+    }
+    value
+}
+
+/// XXH64 of `bytes` in one shot, the same as an [`XxHash64`] fed `bytes`,
+/// but usable in constant evaluation.
+pub(crate) const fn xxh64(bytes: &[u8], seed: u64) -> u64 {
+    let len = bytes.len();
+    let mut i = 0;
+    let mut hash = if len >= 32 {
+        let mut accumulators = [
+            seed.wrapping_add(PRIME_1).wrapping_add(PRIME_2),
+            seed.wrapping_add(PRIME_2),
+            seed,
+            seed.wrapping_sub(PRIME_1),
+        ];
+        while i + 32 <= len {
+            let mut lane = 0;
+            while lane < 4 {
+                accumulators[lane] = round(accumulators[lane], read_le(bytes, i + 8 * lane, 8));
+                lane += 1;
+            }
+            i += 32;
+        }
+        let [a, b, c, d] = accumulators;
+        let mut hash = a
+            .rotate_left(1)
+            .wrapping_add(b.rotate_left(7))
+            .wrapping_add(c.rotate_left(12))
+            .wrapping_add(d.rotate_left(18));
+        let mut lane = 0;
+        while lane < 4 {
+            hash = merge(hash, accumulators[lane]);
+            lane += 1;
+        }
+        hash
+    } else {
+        seed.wrapping_add(PRIME_5)
+    };
+    hash = hash.wrapping_add(len as u64);
+
+    while i + 8 <= len {
+        hash ^= round(0, read_le(bytes, i, 8));
+        hash = hash.rotate_left(27).wrapping_mul(PRIME_1).wrapping_add(PRIME_4);
+        i += 8;
+    }
+    if i + 4 <= len {
+        hash ^= read_le(bytes, i, 4).wrapping_mul(PRIME_1);
+        hash = hash.rotate_left(23).wrapping_mul(PRIME_2).wrapping_add(PRIME_3);
+        i += 4;
+    }
+    while i < len {
+        hash ^= (bytes[i] as u64).wrapping_mul(PRIME_5);
+        hash = hash.rotate_left(11).wrapping_mul(PRIME_1);
+        i += 1;
+    }
+    avalanche(hash)
+}
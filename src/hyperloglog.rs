@@ -0,0 +1,236 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::{math, BloomError, DefaultBuildHasher, Result};
+
+/// The smallest and largest precisions, as the base-2 logarithm of the
+/// number of registers.
+const MIN_PRECISION: u32 = 4;
+const MAX_PRECISION: u32 = 18;
+
+/// A HyperLogLog sketch (Flajolet et al., "HyperLogLog: the analysis of a
+/// near-optimal cardinality estimation algorithm"), which estimates the
+/// number of distinct items in a stream in a few kilobytes.
+///
+/// Each item's hash picks one of `m = 2^p` registers with its first `p`
+/// bits, and the register keeps the longest run of leading zeros seen in
+/// the rest. A long run is evidence of many distinct hashes, and the
+/// harmonic mean over all registers turns that into an estimate with a
+/// standard error of `1.04 / sqrt(m)`. Small cardinalities are estimated by
+/// linear counting of the empty registers instead, which is more accurate
+/// while most registers are still empty.
+///
+/// Sketches with the same precision and seed can be merged, giving the
+/// sketch of the union of their streams.
+///
+/// ```
+/// use bloom::HyperLogLog;
+///
+/// let mut sketch = HyperLogLog::<u64>::new(0.01);
+/// for i in 0..100_000 {
+///     sketch.insert(&(i % 50_000));
+/// }
+/// let estimate = sketch.estimate();
+/// assert!(estimate > 48_000.0 && estimate < 52_000.0);
+/// ```
+#[derive(Debug)]
+pub struct HyperLogLog<T, S = DefaultBuildHasher> {
+    registers: Box<[u8]>,
+    precision: u32,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for HyperLogLog<T, S> {
+    fn clone(&self) -> HyperLogLog<T, S> {
+        HyperLogLog {
+            registers: self.registers.clone(),
+            precision: self.precision,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> HyperLogLog<T> {
+    /// Creates a sketch with enough registers for a standard error of at
+    /// most `relative_error`, or as close as 2^18 registers get.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `relative_error` is not strictly between 0 and 1.
+    pub fn new(relative_error: f64) -> HyperLogLog<T> {
+        HyperLogLog::with_hasher(relative_error, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](HyperLogLog::new), but mixes `seed` into every hash.
+    pub fn with_seed(relative_error: f64, seed: u64) -> HyperLogLog<T> {
+        let mut sketch = HyperLogLog::new(relative_error);
+        sketch.seed = seed;
+        sketch
+    }
+
+    /// Creates a sketch with `2^precision` registers.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `precision` is between 4 and 18.
+    pub fn from_params(precision: u32) -> HyperLogLog<T> {
+        HyperLogLog::from_params_with_hasher(precision, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> HyperLogLog<T, S> {
+    /// Like [`new`](HyperLogLog::new), but hashes items with `hash_builder`.
+    pub fn with_hasher(relative_error: f64, hash_builder: S) -> HyperLogLog<T, S> {
+        if !(relative_error > 0.0 && relative_error < 1.0) {
+            panic!(
+                "{}",
+                BloomError::InvalidParams(format!(
+                    "relative error must be strictly between 0 and 1 (got {})",
+                    relative_error
+                ))
+            );
+        }
+        let registers = (1.04 / relative_error) * (1.04 / relative_error);
+        let precision = (math::ceil(math::log2(registers)) as u32).clamp(MIN_PRECISION, MAX_PRECISION);
+        HyperLogLog::from_params_with_hasher(precision, hash_builder)
+    }
+
+    /// Like [`from_params`](HyperLogLog::from_params), but hashes items with
+    /// `hash_builder`.
+    pub fn from_params_with_hasher(precision: u32, hash_builder: S) -> HyperLogLog<T, S> {
+        if !(MIN_PRECISION..=MAX_PRECISION).contains(&precision) {
+            panic!(
+                "{}",
+                BloomError::InvalidParams(format!(
+                    "precision must be between {} and {} (got {})",
+                    MIN_PRECISION, MAX_PRECISION, precision
+                ))
+            );
+        }
+        HyperLogLog {
+            registers: vec![0; 1 << precision].into_boxed_slice(),
+            precision,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item`, returning `true` if the sketch changed. An item that
+    /// was already recorded never changes it.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let hash = hasher.finish();
+        let register = (hash >> (64 - self.precision)) as usize;
+        // The bit below the remaining ones caps the run at `64 - p`.
+        let rest = hash << self.precision | 1 << (self.precision - 1);
+        let rank = rest.leading_zeros() as u8 + 1;
+        if rank > self.registers[register] {
+            self.registers[register] = rank;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Estimates the number of distinct items recorded.
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let sum: f64 = self.registers.iter().map(|&rank| 1.0 / (1u64 << rank) as f64).sum();
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            m * math::ln(m / zeros as f64)
+        } else {
+            raw
+        }
+    }
+
+    /// Adds every item recorded in `other` to this sketch, so that it
+    /// estimates the number of distinct items in both streams.
+    ///
+    /// Both sketches must have the same precision and seed, and should use
+    /// the same hasher; otherwise [`BloomError::Incompatible`] is returned
+    /// and this sketch is unchanged.
+    pub fn try_merge(&mut self, other: &HyperLogLog<T, S>) -> Result<()> {
+        if self.precision != other.precision {
+            return Err(BloomError::Incompatible(format!(
+                "precisions differ ({} and {})",
+                self.precision, other.precision
+            )));
+        }
+        if self.seed != other.seed {
+            return Err(BloomError::Incompatible(format!("seeds differ ({} and {})", self.seed, other.seed)));
+        }
+        for (rank, &other_rank) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *rank = (*rank).max(other_rank);
+        }
+        Ok(())
+    }
+
+    /// Forgets every item, keeping the sketch's allocation and parameters.
+    pub fn clear(&mut self) {
+        for rank in self.registers.iter_mut() {
+            *rank = 0;
+        }
+    }
+
+    /// Returns `true` if nothing has been recorded since the sketch was
+    /// created or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.registers.iter().all(|&rank| rank == 0)
+    }
+
+    /// The base-2 logarithm of the number of registers.
+    pub fn precision(&self) -> u32 {
+        self.precision
+    }
+
+    /// The number of registers, `m`.
+    pub fn register_count(&self) -> usize {
+        self.registers.len()
+    }
+
+    /// The standard error of the estimate relative to the true count,
+    /// `1.04 / sqrt(m)`.
+    pub fn relative_error(&self) -> f64 {
+        1.04 / math::sqrt(self.registers.len() as f64)
+    }
+
+    /// The number of bytes of memory the sketch occupies, including its
+    /// registers.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + self.registers.len()
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+}
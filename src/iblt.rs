@@ -0,0 +1,353 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::mem;
+
+use crate::hash::{fmix64, read_le, xxh64};
+use crate::{math, probe, BloomError, Result};
+
+/// The number of cells each key is added to by [`new`](InvertibleBloomLookupTable::new).
+const DEFAULT_HASH_COUNT: u32 = 3;
+
+/// The cells per key [`new`](InvertibleBloomLookupTable::new) allocates: 3
+/// hashes decode reliably below about 1.22, and small tables need margin.
+const CELLS_PER_KEY: f64 = 1.5;
+
+const MAGIC: [u8; 4] = *b"BLMI";
+const FORMAT_VERSION: u16 = 1;
+const HEADER_LEN: usize = 24;
+const CELL_LEN: usize = 24;
+
+/// An invertible bloom lookup table (Goodrich and Mitzenmacher, "Invertible
+/// Bloom Lookup Tables"), which can list the keys it holds as long as there
+/// are not too many of them.
+///
+/// Each key is added to one cell in each of `k` parts of the table, and
+/// every cell keeps the number of keys added to it, the XOR of those keys
+/// and the XOR of a hash of each. A cell whose count is 1 or -1 and whose
+/// hash matches its key holds exactly one key, which can be removed from its
+/// other cells in turn, freeing up more; [`list_entries`] peels the table
+/// this way. Listing succeeds with high probability when the table has about
+/// 1.5 cells per key, however many keys were inserted and then removed.
+///
+/// That makes it suited to set reconciliation (Eppstein et al., "What's the
+/// Difference? Efficient Set Reconciliation without Prior Context"): two
+/// peers each insert their whole key set into a table sized for the number
+/// of keys they expect to differ in, and one sends theirs to the other, who
+/// [`subtract`]s it. The keys both hold cancel out, and listing the rest
+/// gives exactly the keys only one of them holds, in space proportional to
+/// the difference rather than the sets. A
+/// [`StrataEstimator`](crate::StrataEstimator) can estimate the difference
+/// first.
+///
+/// Keys are `u64`s, typically hashes of the items.
+///
+/// [`list_entries`]: InvertibleBloomLookupTable::list_entries
+/// [`subtract`]: InvertibleBloomLookupTable::subtract
+///
+/// ```
+/// use bloom::InvertibleBloomLookupTable;
+///
+/// let (mut ours, mut theirs) = (InvertibleBloomLookupTable::new(10), InvertibleBloomLookupTable::new(10));
+/// for key in 0..100_000 {
+///     ours.insert(key);
+///     theirs.insert(key + 3);
+/// }
+/// ours.subtract(&theirs).unwrap();
+/// let (mut only_ours, mut only_theirs) = ours.list_entries().unwrap();
+/// only_ours.sort();
+/// only_theirs.sort();
+/// assert_eq!(only_ours, [0, 1, 2]);
+/// assert_eq!(only_theirs, [100_000, 100_001, 100_002]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvertibleBloomLookupTable {
+    cells: Box<[Cell]>,
+    hash_count: u32,
+    seed: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Cell {
+    count: i64,
+    key_sum: u64,
+    hash_sum: u64,
+}
+
+impl InvertibleBloomLookupTable {
+    /// Creates a table that can list up to about `capacity` keys, with 3
+    /// hashes per key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cells can't be addressed.
+    pub fn new(capacity: u64) -> InvertibleBloomLookupTable {
+        let cells = math::ceil(capacity as f64 * CELLS_PER_KEY) as u64;
+        InvertibleBloomLookupTable::from_params(cells.max(1), DEFAULT_HASH_COUNT)
+    }
+
+    /// Like [`new`](InvertibleBloomLookupTable::new), but mixes `seed` into
+    /// every hash.
+    pub fn with_seed(capacity: u64, seed: u64) -> InvertibleBloomLookupTable {
+        let mut table = InvertibleBloomLookupTable::new(capacity);
+        table.seed = seed;
+        table
+    }
+
+    /// Creates a table of `cell_count` cells, rounded up to a multiple of
+    /// `hash_count`, adding each key to `hash_count` of them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cell_count` is 0 or `hash_count` isn't between 2 and 16,
+    /// or if the cells can't be addressed.
+    pub fn from_params(cell_count: u64, hash_count: u32) -> InvertibleBloomLookupTable {
+        if cell_count == 0 || !(2..=16).contains(&hash_count) {
+            panic!(
+                "{}",
+                BloomError::InvalidParams(format!(
+                    "need a nonzero cell count and 2 to 16 hashes (got {} and {})",
+                    cell_count, hash_count
+                ))
+            );
+        }
+        let len = cell_count
+            .div_ceil(u64::from(hash_count))
+            .checked_mul(u64::from(hash_count))
+            .and_then(|len| usize::try_from(len).ok())
+            .filter(|&len| len <= isize::MAX as usize / CELL_LEN);
+        let cells = match len {
+            Some(len) => vec![Cell::default(); len].into_boxed_slice(),
+            None => panic!("{}", BloomError::Capacity(format!("{} cells can't be addressed", cell_count))),
+        };
+        InvertibleBloomLookupTable {
+            cells,
+            hash_count,
+            seed: 0,
+        }
+    }
+
+    /// Adds `key` to the table.
+    pub fn insert(&mut self, key: u64) {
+        self.update(key, 1);
+    }
+
+    /// Removes `key` from the table. Removing a key that was never inserted
+    /// leaves it listed as removed.
+    pub fn remove(&mut self, key: u64) {
+        self.update(key, -1);
+    }
+
+    /// Removes every key of `other` from this table, so that it holds the
+    /// keys only this one holds, and, as removed keys, those only `other`
+    /// holds.
+    ///
+    /// Both tables must have the same cell count, hash count and seed;
+    /// otherwise [`BloomError::Incompatible`] is returned and this table is
+    /// unchanged.
+    pub fn subtract(&mut self, other: &InvertibleBloomLookupTable) -> Result<()> {
+        self.check_compatible(other)?;
+        for (cell, other) in self.cells.iter_mut().zip(other.cells.iter()) {
+            cell.count = cell.count.wrapping_sub(other.count);
+            cell.key_sum ^= other.key_sum;
+            cell.hash_sum ^= other.hash_sum;
+        }
+        Ok(())
+    }
+
+    /// Lists the table's keys: those inserted more often than removed, and
+    /// those removed more often than inserted. The table is unchanged.
+    ///
+    /// Fails with [`BloomError::Capacity`] if the table holds too many keys
+    /// to list, or a key more than once either way.
+    pub fn list_entries(&self) -> Result<(Vec<u64>, Vec<u64>)> {
+        let mut cells = self.cells.to_vec();
+        let (mut inserted, mut removed) = (Vec::new(), Vec::new());
+        let mut pure: Vec<usize> = (0..cells.len()).filter(|&i| self.is_pure(&cells[i])).collect();
+        while let Some(i) = pure.pop() {
+            // Peeling a neighbour may have changed the cell since.
+            if !self.is_pure(&cells[i]) {
+                continue;
+            }
+            let Cell { count, key_sum: key, .. } = cells[i];
+            if count == 1 {
+                inserted.push(key);
+            } else {
+                removed.push(key);
+            }
+            let hash = self.check_hash(key);
+            for index in self.indices(key) {
+                let cell = &mut cells[index];
+                cell.count = cell.count.wrapping_sub(count);
+                cell.key_sum ^= key;
+                cell.hash_sum ^= hash;
+                if self.is_pure(cell) {
+                    pure.push(index);
+                }
+            }
+        }
+        if cells.iter().any(|cell| *cell != Cell::default()) {
+            return Err(BloomError::Capacity(format!(
+                "only {} keys could be listed before decoding stalled",
+                inserted.len() + removed.len()
+            )));
+        }
+        Ok((inserted, removed))
+    }
+
+    /// Empties the table, keeping its parameters.
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = Cell::default();
+        }
+    }
+
+    /// Returns `true` if every key inserted has since been removed.
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(|cell| *cell == Cell::default())
+    }
+
+    /// The number of cells.
+    pub fn cell_count(&self) -> u64 {
+        self.cells.len() as u64
+    }
+
+    /// The number of cells each key is added to.
+    pub fn hash_count(&self) -> u32 {
+        self.hash_count
+    }
+
+    /// The number of bytes of memory the table occupies, including its
+    /// cells.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(&*self.cells)
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Encodes the table so that
+    /// [`from_bytes`](InvertibleBloomLookupTable::from_bytes) can load it on
+    /// any platform.
+    ///
+    /// The layout is little-endian: the magic `b"BLMI"`, a 2-byte format
+    /// version (1), the hash count as one byte, a zero byte, then as 8 bytes
+    /// each the seed and the cell count. Each cell follows as its count, key
+    /// sum and hash sum, 8 bytes each, and finally the XXH64 (seed 0) of
+    /// every preceding byte.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + CELL_LEN * self.cells.len() + 8);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&[self.hash_count as u8, 0]);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&(self.cells.len() as u64).to_le_bytes());
+        for cell in self.cells.iter() {
+            bytes.extend_from_slice(&cell.count.to_le_bytes());
+            bytes.extend_from_slice(&cell.key_sum.to_le_bytes());
+            bytes.extend_from_slice(&cell.hash_sum.to_le_bytes());
+        }
+        let checksum = xxh64(&bytes, 0);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes a table encoded by
+    /// [`to_bytes`](InvertibleBloomLookupTable::to_bytes).
+    ///
+    /// Fails with [`BloomError::CorruptFile`] if `bytes` is malformed or its
+    /// checksum doesn't match, and with [`BloomError::Incompatible`] if it
+    /// is from a newer format version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<InvertibleBloomLookupTable> {
+        if bytes.len() < HEADER_LEN + 8 || bytes[..4] != MAGIC {
+            return Err(BloomError::CorruptFile("not an invertible bloom lookup table".to_string()));
+        }
+        let (contents, checksum) = bytes.split_at(bytes.len() - 8);
+        if xxh64(contents, 0) != read_le(checksum, 0, 8) {
+            return Err(BloomError::CorruptFile("checksum mismatch".to_string()));
+        }
+        if read_le(bytes, 4, 2) != u64::from(FORMAT_VERSION) {
+            return Err(BloomError::Incompatible("unsupported format version".to_string()));
+        }
+        let hash_count = u32::from(bytes[6]);
+        let cell_count = read_le(bytes, 16, 8);
+        let body = &contents[HEADER_LEN..];
+        if !(2..=16).contains(&hash_count)
+            || cell_count == 0
+            || !cell_count.is_multiple_of(u64::from(hash_count))
+            || cell_count.checked_mul(CELL_LEN as u64) != Some(body.len() as u64)
+        {
+            return Err(BloomError::CorruptFile("invalid layout".to_string()));
+        }
+        let cells: Vec<Cell> = body
+            .chunks(CELL_LEN)
+            .map(|chunk| Cell {
+                count: read_le(chunk, 0, 8) as i64,
+                key_sum: read_le(chunk, 8, 8),
+                hash_sum: read_le(chunk, 16, 8),
+            })
+            .collect();
+        Ok(InvertibleBloomLookupTable {
+            cells: cells.into_boxed_slice(),
+            hash_count,
+            seed: read_le(bytes, 8, 8),
+        })
+    }
+
+    /// Changes the seed of an empty table.
+    pub(crate) fn set_seed(&mut self, seed: u64) {
+        debug_assert!(self.is_empty());
+        self.seed = seed;
+    }
+
+    fn update(&mut self, key: u64, count: i64) {
+        let hash = self.check_hash(key);
+        for index in self.indices(key) {
+            let cell = &mut self.cells[index];
+            cell.count = cell.count.wrapping_add(count);
+            cell.key_sum ^= key;
+            cell.hash_sum ^= hash;
+        }
+    }
+
+    fn check_compatible(&self, other: &InvertibleBloomLookupTable) -> Result<()> {
+        if (self.cells.len(), self.hash_count) != (other.cells.len(), other.hash_count) {
+            return Err(BloomError::Incompatible(format!(
+                "shapes differ ({} cells with {} hashes and {} with {})",
+                self.cells.len(),
+                self.hash_count,
+                other.cells.len(),
+                other.hash_count
+            )));
+        }
+        if self.seed != other.seed {
+            return Err(BloomError::Incompatible(format!("seeds differ ({} and {})", self.seed, other.seed)));
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if `cell` holds exactly one key, inserted or removed.
+    fn is_pure(&self, cell: &Cell) -> bool {
+        (cell.count == 1 || cell.count == -1) && cell.hash_sum == self.check_hash(cell.key_sum)
+    }
+
+    /// The cell `key` is added to in each part of the table.
+    fn indices(&self, key: u64) -> impl Iterator<Item = usize> {
+        let part_len = self.cells.len() as u64 / u64::from(self.hash_count);
+        let hash = fmix64(key ^ self.seed);
+        (0..u64::from(self.hash_count)).map(move |part| {
+            let part_hash = fmix64(hash.wrapping_add(part.wrapping_mul(0x9e37_79b9_7f4a_7c15)));
+            (part * part_len + probe::reduce(part_hash, part_len)) as usize
+        })
+    }
+
+    /// The hash that tells a cell holding one key from one holding several.
+    fn check_hash(&self, key: u64) -> u64 {
+        fmix64(key ^ self.seed ^ 0x2545_f491_4f6c_dd1d)
+    }
+}
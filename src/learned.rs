@@ -0,0 +1,275 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::cmp;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::{params, ApproximateMembership, BloomError, BloomFilter, BloomFilterBuilder, DefaultBuildHasher, Result};
+
+/// How many thresholds the builder tries, spreading the false positives it
+/// allows the model from none to nearly all of the target.
+const THRESHOLD_CANDIDATES: usize = 16;
+
+/// A filter that asks a learned model first and a [`BloomFilter`] second
+/// (Kraska et al., "The Case for Learned Index Structures").
+///
+/// The model scores items, higher meaning more likely a key, and accepts
+/// those scoring above a threshold. Keys it rejects, its false negatives,
+/// go in a backup filter, so that the whole has no false negatives. Where a
+/// model separates keys from other items well, say a classifier of
+/// malicious URLs, the backup holds only a fraction of the keys and the
+/// whole is far smaller than a plain filter of the same false positive
+/// probability, though every lookup pays for evaluating the model.
+///
+/// A [`LearnedBloomFilterBuilder`] picks the threshold and sizes the backup
+/// by measuring the model on keys and on a sample of the items that will be
+/// looked up. The model's false positive rate on the sample is only an
+/// estimate of its rate on later lookups, so the sample should be drawn
+/// from the same distribution.
+///
+/// ```
+/// use bloom::LearnedBloomFilterBuilder;
+///
+/// // A model that has learned that keys are mostly even.
+/// let model = |item: &u64| if item % 2 == 0 { 0.9 } else { 0.1 };
+/// let keys: Vec<u64> = (0..10_000).map(|i| i * 2).chain([7, 21]).collect();
+/// let others: Vec<u64> = (0..10_000).map(|i| i * 2 + 100_001).collect();
+/// let filter = LearnedBloomFilterBuilder::new(model).false_positive_prob(0.01).build(&keys, &others).unwrap();
+/// assert!(keys.iter().all(|key| filter.contains(key)));
+/// assert_eq!(filter.backup().capacity(), Some(2));
+/// ```
+pub struct LearnedBloomFilter<T, M, S = DefaultBuildHasher> {
+    model: M,
+    threshold: f64,
+    /// The fraction of the sampled non-keys the model accepted.
+    model_false_positive_prob: f64,
+    backup: BloomFilter<T, S>,
+}
+
+// Implemented by hand because models are usually closures, which aren't
+// `Debug`.
+impl<T: fmt::Debug, M, S: fmt::Debug> fmt::Debug for LearnedBloomFilter<T, M, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LearnedBloomFilter")
+            .field("threshold", &self.threshold)
+            .field("model_false_positive_prob", &self.model_false_positive_prob)
+            .field("backup", &self.backup)
+            .finish_non_exhaustive()
+    }
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, M: Clone, S: Clone> Clone for LearnedBloomFilter<T, M, S> {
+    fn clone(&self) -> LearnedBloomFilter<T, M, S> {
+        LearnedBloomFilter {
+            model: self.model.clone(),
+            threshold: self.threshold,
+            model_false_positive_prob: self.model_false_positive_prob,
+            backup: self.backup.clone(),
+        }
+    }
+}
+
+impl<T: Hash, M: Fn(&T) -> f64, S: BuildHasher> LearnedBloomFilter<T, M, S> {
+    /// Returns `true` if `item` is probably a key, and `false` if it
+    /// definitely isn't.
+    pub fn contains(&self, item: &T) -> bool {
+        (self.model)(item) > self.threshold || self.backup.contains(item)
+    }
+
+    /// Adds `item` as a key, returning `true` if it was probably one
+    /// already. The model can't learn, so a key it rejects goes in the
+    /// backup filter, which grows less accurate as it fills past the number
+    /// of false negatives it was sized for.
+    pub fn insert(&mut self, item: &T) -> bool {
+        if (self.model)(item) > self.threshold {
+            true
+        } else {
+            self.backup.insert(item)
+        }
+    }
+
+    /// The score above which the model's answer is taken to be a key.
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// The model.
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    /// The filter holding the keys the model rejects.
+    pub fn backup(&self) -> &BloomFilter<T, S> {
+        &self.backup
+    }
+
+    /// The fraction of the sampled non-keys that the model accepted.
+    pub fn model_false_positive_prob(&self) -> f64 {
+        self.model_false_positive_prob
+    }
+
+    /// The estimated probability that `contains` returns `true` for a
+    /// non-key: the model accepts it, or the backup filter does.
+    pub fn false_positive_prob(&self) -> f64 {
+        let model = self.model_false_positive_prob;
+        model + (1.0 - model) * self.backup.current_fpr()
+    }
+
+    /// The number of bytes of memory the filter occupies, including the
+    /// backup filter's bits but not anything the model owns.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) - mem::size_of::<BloomFilter<T, S>>()
+            + ApproximateMembership::<T>::memory_bytes(&self.backup)
+    }
+
+    /// The hasher the backup filter hashes items with.
+    pub fn hasher(&self) -> &S {
+        self.backup.hasher()
+    }
+}
+
+/// Measures a model and builds a [`LearnedBloomFilter`] around it.
+///
+/// Building scores every key and a sample of non-keys. For a range of
+/// thresholds, each letting the model accept a different share of the
+/// target false positive probability `p`, it works out the false positive
+/// probability the backup filter needs for the whole to meet `p`, and how
+/// large a backup of that probability holding the model's false negatives
+/// would be. It keeps the threshold that needs the smallest backup.
+///
+/// ```
+/// use bloom::LearnedBloomFilterBuilder;
+///
+/// let model = |name: &&str| if name.ends_with(".evil") { 1.0 } else { 0.0 };
+/// let filter = LearnedBloomFilterBuilder::new(model)
+///     .false_positive_prob(0.001)
+///     .seed(7)
+///     .build(&["a.evil", "b.evil", "c.example"], &["d.example", "e.example"])
+///     .unwrap();
+/// assert!(filter.contains(&"c.example"));
+/// assert_eq!(filter.threshold(), 0.0);
+/// ```
+pub struct LearnedBloomFilterBuilder<T, M, S = DefaultBuildHasher> {
+    model: M,
+    false_positive_prob: f64,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because models are usually closures, which aren't
+// `Debug`.
+impl<T, M, S: fmt::Debug> fmt::Debug for LearnedBloomFilterBuilder<T, M, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LearnedBloomFilterBuilder")
+            .field("false_positive_prob", &self.false_positive_prob)
+            .field("seed", &self.seed)
+            .field("hash_builder", &self.hash_builder)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Hash, M: Fn(&T) -> f64> LearnedBloomFilterBuilder<T, M> {
+    /// Creates a builder around `model`, which scores items higher the more
+    /// likely they are keys.
+    pub fn new(model: M) -> LearnedBloomFilterBuilder<T, M> {
+        LearnedBloomFilterBuilder {
+            model,
+            false_positive_prob: params::DEFAULT_FALSE_POSITIVE_PROB,
+            seed: 0,
+            hash_builder: DefaultBuildHasher::default(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash, M: Fn(&T) -> f64, S: BuildHasher> LearnedBloomFilterBuilder<T, M, S> {
+    /// The target false positive probability of the whole filter. Defaults
+    /// to 0.01.
+    pub fn false_positive_prob(mut self, false_positive_prob: f64) -> Self {
+        self.false_positive_prob = false_positive_prob;
+        self
+    }
+
+    /// The seed mixed into the backup filter's hashes. Defaults to 0.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Uses `hash_builder` to hash items in the backup filter instead of the
+    /// default hasher.
+    pub fn hasher<S2: BuildHasher>(self, hash_builder: S2) -> LearnedBloomFilterBuilder<T, M, S2> {
+        LearnedBloomFilterBuilder {
+            model: self.model,
+            false_positive_prob: self.false_positive_prob,
+            seed: self.seed,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Measures the model on `keys` and `non_keys`, and builds the filter
+    /// holding `keys`.
+    ///
+    /// Fails with [`BloomError::InvalidParams`] if `non_keys` is empty or
+    /// the false positive probability is not strictly between 0 and 1, and
+    /// with [`BloomError::Capacity`] if the backup filter is too large to
+    /// allocate.
+    pub fn build(self, keys: &[T], non_keys: &[T]) -> Result<LearnedBloomFilter<T, M, S>> {
+        let false_positive_prob = self.false_positive_prob;
+        params::validate(cmp::max(keys.len(), 1), false_positive_prob)?;
+        if non_keys.is_empty() {
+            return Err(BloomError::InvalidParams(
+                "non-keys are needed to measure the model's false positives".to_string(),
+            ));
+        }
+        let mut key_scores: Vec<f64> = keys.iter().map(|key| (self.model)(key)).collect();
+        key_scores.sort_unstable_by(f64::total_cmp);
+        let mut non_key_scores: Vec<f64> = non_keys.iter().map(|item| (self.model)(item)).collect();
+        non_key_scores.sort_unstable_by(|a, b| b.total_cmp(a));
+
+        // The candidate letting the model accept none of the non-keys, which
+        // leaves the backup the whole target, comes first.
+        let mut best: Option<(u64, f64, f64, f64)> = None;
+        for candidate in 0..THRESHOLD_CANDIDATES {
+            let allowed = (false_positive_prob * non_keys.len() as f64 * candidate as f64
+                / THRESHOLD_CANDIDATES as f64) as usize;
+            let threshold = non_key_scores[cmp::min(allowed, non_keys.len() - 1)];
+            let accepted = non_key_scores.partition_point(|&score| score > threshold);
+            let model_false_positive_prob = accepted as f64 / non_keys.len() as f64;
+            let backup_false_positive_prob =
+                (false_positive_prob - model_false_positive_prob) / (1.0 - model_false_positive_prob);
+            if backup_false_positive_prob <= 0.0 {
+                continue;
+            }
+            let false_negatives = key_scores.partition_point(|&score| score <= threshold);
+            let bits = params::optimal_bits(cmp::max(false_negatives, 1), backup_false_positive_prob);
+            if best.is_none_or(|(best_bits, ..)| bits < best_bits) {
+                best = Some((bits, threshold, model_false_positive_prob, backup_false_positive_prob));
+            }
+        }
+        // The first candidate always leaves the backup the whole target.
+        let (_, threshold, model_false_positive_prob, backup_false_positive_prob) =
+            best.unwrap_or((0, non_key_scores[0], 0.0, false_positive_prob));
+        let false_negatives: Vec<&T> = keys.iter().filter(|key| (self.model)(key) <= threshold).collect();
+        let mut backup = BloomFilterBuilder::new()
+            .hasher(self.hash_builder)
+            .item_count(cmp::max(false_negatives.len(), 1))
+            .false_positive_prob(backup_false_positive_prob)
+            .seed(self.seed)
+            .build()?;
+        for key in false_negatives {
+            backup.insert(key);
+        }
+        Ok(LearnedBloomFilter {
+            model: self.model,
+            threshold,
+            model_false_positive_prob,
+            backup,
+        })
+    }
+}
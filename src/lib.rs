@@ -0,0 +1,181 @@
+//! A bloom filter: a space-efficient probabilistic set that answers
+//! membership queries with no false negatives and a tunable rate of false
+//! positives.
+//!
+//! Most filters are sized from an item count and a target false positive
+//! probability. [`MortonFilter`] takes only the item count: its 8-bit
+//! fingerprints fix its rate at about 0.3% when full.
+//!
+//! # Features
+//!
+//! - `std` (default): file I/O, the binary file format,
+//!   [`SecretKey::random`](hash::SecretKey::random) and, on Unix and Windows,
+//!   `PagedBloomFilter` for querying saved filters larger than memory.
+//!   Without it the crate is `no_std` and needs only `alloc`.
+//! - `cli` (default): the `bloom` command-line tool.
+//! - `serde`: `Serialize` and `Deserialize` for filters using a
+//!   [`HashScheme`](hash::HashScheme).
+//! - `roaring`: [`BitStorage`] for `RoaringBitmap`, re-exported, to back a
+//!   [`BackedBloomFilter`] with compressed bits.
+//! - `async`: `add_stream` and `add_lines` for [`BloomFilter`], and
+//!   `filter_from_file_async` and `filter_from_async_reader`, to fill
+//!   filters from streams, files and sockets on a tokio runtime without
+//!   blocking it.
+//! - `stats`: `InstrumentedFilter`, which wraps any
+//!   [`ApproximateMembership`] filter and records the latency of each insert
+//!   and lookup in a `LatencyHistogram`.
+//! - `concurrent`: `ConcurrentBloomFilter`, which threads can insert into
+//!   at once without locks, and with `std`, `StripedCountingBloomFilter`,
+//!   which they can also remove from.
+//! - `allocator_api`: `AllocBits`, a [`BitStorage`] allocated from any
+//!   `allocator_api2` allocator, so a [`BackedBloomFilter`] can live in an
+//!   arena or shared memory. Enable `allocator-api2`'s `nightly` feature to
+//!   use the standard library's `Allocator` trait.
+//! - `rayon`: `par_insert_all` and `ParallelExtend` for [`BloomFilter`],
+//!   which hash and insert items on every core.
+//! - `hugepages`: [`BloomFilterBuilder`] options to back the bits with
+//!   transparent huge pages and to pass access pattern hints to the kernel,
+//!   on Linux.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod age_partitioned;
+mod attenuated;
+mod backed;
+mod bit_vec;
+mod blocked;
+mod bloomier;
+mod builder;
+mod cascade;
+#[cfg(feature = "concurrent")]
+mod concurrent;
+mod const_filter;
+mod count_min;
+mod count_sketch;
+mod counting;
+mod counting_quotient;
+mod cuckoo;
+mod d_left;
+mod decaying;
+mod error;
+#[cfg(feature = "std")]
+mod file;
+mod filter;
+mod format;
+mod golomb;
+pub mod hash;
+mod hyperloglog;
+mod iblt;
+mod learned;
+#[cfg(feature = "hugepages")]
+mod madvise;
+mod math;
+mod membership;
+mod memory;
+mod minhash;
+mod morton;
+mod multi_set;
+mod packed;
+#[cfg(all(feature = "std", any(unix, windows)))]
+mod paged;
+#[cfg(feature = "std")]
+mod parallel;
+pub mod params;
+mod partitioned;
+mod prefix;
+mod probe;
+mod quotient;
+mod range;
+mod ribbon;
+mod scalable;
+#[cfg(feature = "serde")]
+mod serde_impl;
+#[cfg(feature = "std")]
+mod serialize;
+mod sharded;
+mod simd;
+mod sliding;
+mod snapshot;
+mod sparse;
+mod spectral;
+mod split_block;
+mod stable;
+#[cfg(feature = "stats")]
+mod stats;
+mod strata;
+#[cfg(all(feature = "concurrent", feature = "std"))]
+mod striped;
+mod static_filter;
+mod storage;
+#[cfg(feature = "async")]
+mod stream;
+mod tiny_lfu;
+mod tombstone;
+mod top_k;
+mod weighted;
+mod xor;
+
+pub use crate::age_partitioned::AgePartitionedBloomFilter;
+pub use crate::attenuated::AttenuatedBloomFilter;
+pub use crate::backed::BackedBloomFilter;
+pub use crate::blocked::BlockedBloomFilter;
+pub use crate::bloomier::BloomierFilter;
+pub use crate::builder::BloomFilterBuilder;
+pub use crate::cascade::FilterCascade;
+#[cfg(feature = "concurrent")]
+pub use crate::concurrent::ConcurrentBloomFilter;
+pub use crate::const_filter::ConstBloomFilter;
+pub use crate::count_min::CountMinSketch;
+pub use crate::count_sketch::CountSketch;
+pub use crate::counting::{CounterStats, CounterWidth, CountingBloomFilter};
+pub use crate::counting_quotient::CountingQuotientFilter;
+pub use crate::cuckoo::CuckooFilter;
+pub use crate::d_left::DLeftCountingFilter;
+pub use crate::decaying::DecayingBloomFilter;
+pub use crate::error::{BloomError, Result};
+#[cfg(feature = "std")]
+pub use crate::file::{filter_from_file, filter_from_file_with_jobs};
+pub use crate::filter::{BloomFilter, ContainsIter, DefaultBuildHasher, Ones};
+pub use crate::golomb::GolombCodedSet;
+pub use crate::hyperloglog::HyperLogLog;
+pub use crate::iblt::InvertibleBloomLookupTable;
+pub use crate::learned::{LearnedBloomFilter, LearnedBloomFilterBuilder};
+pub use crate::membership::{ApproximateMembership, Removable};
+pub use crate::minhash::MinHash;
+pub use crate::morton::MortonFilter;
+pub use crate::multi_set::MultiSetBloomFilter;
+#[cfg(all(feature = "std", any(unix, windows)))]
+pub use crate::paged::PagedBloomFilter;
+pub use crate::partitioned::PartitionedBloomFilter;
+pub use crate::prefix::PrefixBloomFilter;
+pub use crate::quotient::QuotientFilter;
+pub use crate::range::RangeFilter;
+pub use crate::ribbon::RibbonFilter;
+pub use crate::scalable::ScalableBloomFilter;
+pub use crate::sharded::ShardedBloomFilter;
+pub use crate::sliding::{SlidingBloomFilter, Window};
+pub use crate::snapshot::{BloomSnapshot, CowBloomFilter};
+pub use crate::sparse::SparseBloomFilter;
+pub use crate::spectral::SpectralBloomFilter;
+pub use crate::split_block::SplitBlockBloomFilter;
+pub use crate::stable::StableBloomFilter;
+#[cfg(feature = "stats")]
+pub use crate::stats::{InstrumentedFilter, LatencyHistogram, LatencyStats};
+pub use crate::strata::StrataEstimator;
+#[cfg(all(feature = "concurrent", feature = "std"))]
+pub use crate::striped::StripedCountingBloomFilter;
+pub use crate::static_filter::StaticBloomFilter;
+#[cfg(feature = "allocator_api")]
+pub use crate::storage::AllocBits;
+pub use crate::storage::{BitStorage, DenseBits};
+#[cfg(feature = "async")]
+pub use crate::stream::{filter_from_async_reader, filter_from_file_async};
+pub use crate::tiny_lfu::TinyLfu;
+pub use crate::tombstone::TombstoneBloomFilter;
+pub use crate::top_k::TopK;
+pub use crate::weighted::WeightedBloomFilter;
+pub use crate::xor::XorFilter;
+#[cfg(feature = "roaring")]
+pub use roaring::RoaringBitmap;
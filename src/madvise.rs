@@ -0,0 +1,43 @@
+/// How the kernel should back and page a filter's bits, set through
+/// [`BloomFilterBuilder`](crate::BloomFilterBuilder).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct MemoryHints {
+    pub(crate) huge_pages: bool,
+    pub(crate) random_access: bool,
+    pub(crate) will_need: bool,
+}
+
+/// Applies `hints` to the pages holding `words`, before they are first
+/// touched.
+///
+/// The hints are advisory, so if the kernel rejects one (because transparent
+/// huge pages are disabled, say) the bits are used as they are.
+#[cfg(target_os = "linux")]
+pub(crate) fn advise(words: &[u64], hints: MemoryHints) {
+    // SAFETY: `sysconf` has no preconditions.
+    let page = match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        page if page > 0 => page as usize,
+        _ => return,
+    };
+    // madvise works on whole pages, so only advise the pages entirely
+    // within the words; large allocations are page-aligned anyway.
+    let start = (words.as_ptr() as usize).next_multiple_of(page);
+    let end = (words.as_ptr() as usize + core::mem::size_of_val(words)) / page * page;
+    if start >= end {
+        return;
+    }
+    let advice = [
+        (hints.huge_pages, libc::MADV_HUGEPAGE),
+        (hints.random_access, libc::MADV_RANDOM),
+        (hints.will_need, libc::MADV_WILLNEED),
+    ];
+    for &(_, advice) in advice.iter().filter(|(enabled, _)| *enabled) {
+        // SAFETY: the range lies within the words' allocation, and none of
+        // these advice values change its contents.
+        unsafe { libc::madvise(start as *mut libc::c_void, end - start, advice) };
+    }
+}
+
+/// Memory hints only apply on Linux.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn advise(_words: &[u64], _hints: MemoryHints) {}
@@ -1,81 +1,30 @@
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
-use std::marker::PhantomData;
-use std::fs::File;
-use std::io::{BufReader, BufRead};
 use std::env;
+use std::fmt::Write;
+use std::fs::File;
+use std::hint::black_box;
+use std::io::{self, BufReader, BufRead};
+use std::num::NonZeroUsize;
+use std::process;
+use std::thread;
+use std::time::Instant;
 
-extern crate bit_vec;
-extern crate time;
-
-use bit_vec::BitVec;
-use time::PreciseTime;
-
-#[derive(Debug)]
-struct BloomFilter<T> {
-    bit_vec: BitVec,
-    false_positive_prob: f64,
-    bit_vec_size: usize,
-    hash_count: usize,
-    phantom: PhantomData<T>,
-}
-
-impl<T: Hash> BloomFilter<T> {
-    fn new(item_count: usize, false_positive_prob: f64) -> BloomFilter<T> {
-        let bit_vec_size = BloomFilter::<T>::get_size(item_count, false_positive_prob);
-        BloomFilter {
-            false_positive_prob: false_positive_prob,
-            bit_vec_size: BloomFilter::<T>::get_size(item_count, false_positive_prob),
-            hash_count: BloomFilter::<T>::get_hash_count(bit_vec_size, item_count),
-            bit_vec: BitVec::from_elem(bit_vec_size, false),
-            phantom: PhantomData,
-        }
-    }
-
-    fn add(&mut self, item: &T) {
-        for i in 0..self.hash_count {
-            let index = BloomFilter::<T>::hash(i, item) % self.bit_vec_size;
-            self.bit_vec.set(index, true);
-        }
-    }
-
-    fn contains(&self, item: &T) -> bool {
-        for i in 0..self.hash_count {
-            let index = BloomFilter::<T>::hash(i, item) % self.bit_vec_size;
-            if self.bit_vec[index] == false {
-                return false;
-            }
-        }
-        true
-    }
-
-    fn hash(i: usize, t: &T) -> usize {
-        let mut s = DefaultHasher::new();
-        s.write_usize(i);
-        t.hash(&mut s);
-        s.finish() as usize
-    }
-
-    fn get_size(n: usize, p: f64) -> usize {
-        (n as f64 * p.ln() / ((2 as f64).ln() * (2 as f64).ln()) * (-1 as f64)) as usize
-    }
-
-    fn get_hash_count(m: usize, n: usize) -> usize {
-        std::cmp::max((m as f64 / n as f64 * (2 as f64).ln()) as usize, 1)
-    }
-}
+extern crate bloom;
 
-fn filter_from_file(path: &str, capacity: usize, false_positive_prob: f64) -> BloomFilter<String> {
-    let mut filter = BloomFilter::<String>::new(capacity, false_positive_prob);
+use bloom::{filter_from_file_with_jobs, BloomError, BloomFilter, BloomFilterBuilder, HyperLogLog};
 
-    let file = BufReader::new(File::open(path).expect(format!("Could not open file {}", path).as_str()));
-    for line in file.lines() {
-        filter.add(&line.unwrap().trim().to_string());
+// Calls `f` with each trimmed line of the file. The lines are read into one
+// buffer, so no string is allocated per line.
+fn for_each_line<F: FnMut(&str)>(path: &str, mut f: F) -> io::Result<()> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut line = String::new();
+    while file.read_line(&mut line)? > 0 {
+        f(line.trim());
+        line.clear();
     }
-    filter
+    Ok(())
 }
 
-fn check_from_file(path: &str, filter: &BloomFilter<String>) {
+fn check_from_file(path: &str, filter: &BloomFilter<String>) -> io::Result<()> {
 
     let mut true_positives = 0;
     let mut false_negatives = 0;
@@ -86,27 +35,26 @@ fn check_from_file(path: &str, filter: &BloomFilter<String>) {
     // We will also track the largest line in the file so that we can use that value
     // to generate strings that are definitely not in the file later.
     let mut longest_string: String = String::new();
-    let file = BufReader::new(File::open(path).expect(format!("Could not open file {}", path).as_str()));
-    for line in file.lines() {
-        let line = line.unwrap().trim().to_string();
-        if filter.contains(&line) {
+    for_each_line(path, |line| {
+        if filter.contains(line) {
             true_positives += 1;
         } else {
             false_negatives += 1;
         }
         if line.len() > longest_string.len() {
-            longest_string = line;
+            longest_string.clear();
+            longest_string.push_str(line);
         }
-        // max_line_len = std::cmp::max(max_line_len, line.unwrap().len());
-    }
+    })?;
 
     // Generate strings that are longer than the longest line in the file, and are
     // thus guaranteed not to be in the file, and check how well the filter correctly
     // identifies that they are not in the filter.
-    for i in 0..filter.bit_vec.len() {
-        let mut st = longest_string.clone();
-        st.push_str(&i.to_string());
-        if filter.contains(&st) {
+    let mut st = longest_string.clone();
+    for i in 0..filter.bit_vec_size() {
+        st.truncate(longest_string.len());
+        write_number(&mut st, i);
+        if filter.contains(st.as_str()) {
             false_positives += 1;
         } else {
             true_negatives += 1;
@@ -117,59 +65,249 @@ fn check_from_file(path: &str, filter: &BloomFilter<String>) {
     println!("False Negatives: {}", false_negatives);
     println!("False Positives: {}", false_positives);
     println!("True Negatives: {}", true_negatives);
-    println!("");
+    println!();
     println!("False Positives percentage: {}", false_positives as f64 / (false_positives + true_negatives) as f64);
+    Ok(())
 }
 
-fn main() {
-    // let mut b = BloomFilter::<String>::new(1000, 0.1);
-    let args: Vec<String> = env::args().collect();
+// Appends `n` in decimal to `buffer`, which only allocates if it has to grow.
+fn write_number(buffer: &mut String, n: u64) {
+    write!(buffer, "{}", n).expect("a String can always be written to");
+}
+
+// Estimates the number of distinct (trimmed) lines in the file, so that a filter
+// can be sized for them without holding them all in memory.
+fn distinct_lines(path: &str) -> io::Result<usize> {
+    let mut sketch = HyperLogLog::<String>::new(0.01);
+    for_each_line(path, |line| {
+        sketch.insert(line);
+    })?;
+    // Round up by two standard errors, so that the filter is rarely undersized.
+    let estimate = sketch.estimate() * (1.0 + 2.0 * sketch.relative_error());
+    Ok((estimate.ceil() as usize).max(1))
+}
+
+fn parse_arg<T: std::str::FromStr>(arg: &str, message: &str) -> Result<T, BloomError> {
+    arg.parse::<T>().map_err(|_| BloomError::InvalidParams(format!("{} (got {:?})", message, arg)))
+}
+
+// Removes a `<name> <value>` option from the arguments, returning the value,
+// if given.
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, BloomError> {
+    let position = match args.iter().position(|arg| arg == name) {
+        Some(position) => position,
+        None => return Ok(None),
+    };
+    if position + 1 == args.len() {
+        return Err(BloomError::InvalidParams(format!("{} needs a value", name)));
+    }
+    let value = args.remove(position + 1);
+    args.remove(position);
+    Ok(Some(value))
+}
+
+// Removes a `--jobs <n>` option from the arguments, returning the number of
+// threads to read the input file on: one per core unless given.
+fn take_jobs(args: &mut Vec<String>) -> Result<usize, BloomError> {
+    match take_option(args, "--jobs")? {
+        Some(arg) => parse_arg(&arg, "--jobs must be a positive integer"),
+        None => Ok(thread::available_parallelism().map_or(1, NonZeroUsize::get)),
+    }
+}
+
+// What `bloom bench` measures, and how it reports it.
+struct BenchOptions {
+    sizes: Vec<usize>,
+    iterations: usize,
+    json: bool,
+}
+
+// Removes the `bench` options from the arguments.
+fn take_bench_options(args: &mut Vec<String>) -> Result<BenchOptions, BloomError> {
+    let sizes = match take_option(args, "--sizes")? {
+        Some(sizes) => sizes
+            .split(',')
+            .map(|size| parse_arg(size, "--sizes must be a comma-separated list of positive integers"))
+            .collect::<Result<_, _>>()?,
+        None => vec![1_000, 100_000, 10_000_000],
+    };
+    let iterations = match take_option(args, "--iterations")? {
+        Some(arg) => parse_arg(&arg, "--iterations must be a positive integer")?,
+        None => 100,
+    };
+    if iterations == 0 {
+        return Err(BloomError::InvalidParams("--iterations must be a positive integer".to_string()));
+    }
+    let json = match args.iter().position(|arg| arg == "--json") {
+        Some(position) => {
+            args.remove(position);
+            true
+        },
+        None => false,
+    };
+    Ok(BenchOptions { sizes, iterations, json })
+}
+
+// The number of operations timed together as one sample, so that the clock's
+// resolution and overhead don't swamp the time of a single operation.
+const BATCH: u64 = 1000;
+
+// The number of samples run and discarded before measuring, to warm the
+// caches and let the CPU settle on a clock speed.
+const WARMUP_SAMPLES: usize = 20;
+
+// Nanoseconds per operation over a benchmark's samples.
+struct Stats {
+    mean: f64,
+    p50: f64,
+    p99: f64,
+}
+
+impl Stats {
+    fn new(mut samples: Vec<f64>) -> Stats {
+        samples.sort_by(f64::total_cmp);
+        let percentile = |p: f64| samples[((samples.len() - 1) as f64 * p).round() as usize];
+        Stats {
+            mean: samples.iter().sum::<f64>() / samples.len() as f64,
+            p50: percentile(0.5),
+            p99: percentile(0.99),
+        }
+    }
+
+    fn ops_per_sec(&self) -> f64 {
+        1e9 / self.mean
+    }
+}
+
+// Warms up, then times `iterations` batches of `op`, which is passed the
+// number of operations before it.
+fn measure<F: FnMut(u64)>(iterations: usize, mut op: F) -> Stats {
+    let mut next = 0;
+    let mut sample = || {
+        let start = Instant::now();
+        for i in next..next + BATCH {
+            op(i);
+        }
+        next += BATCH;
+        start.elapsed().as_nanos() as f64 / BATCH as f64
+    };
+    for _ in 0..WARMUP_SAMPLES {
+        sample();
+    }
+    Stats::new((0..iterations).map(|_| sample()).collect())
+}
+
+// Measures inserts into and lookups in a filter of each size, printing a
+// table, or with `--json` an array of results that later runs can be
+// compared against.
+fn run_bench(options: &BenchOptions) -> Result<(), BloomError> {
+    let mut results = Vec::new();
+    for &size in &options.sizes {
+        let mut filter = BloomFilterBuilder::<u64>::new().item_count(size).false_positive_prob(0.01).build()?;
+        let insert = measure(options.iterations, |i| {
+            black_box(filter.insert(black_box(&i)));
+        });
+        // Alternate between items that were inserted and items that weren't.
+        let inserted = (WARMUP_SAMPLES + options.iterations) as u64 * BATCH;
+        let query = measure(options.iterations, |i| {
+            let item = if i % 2 == 0 { i } else { inserted + i };
+            black_box(filter.contains(black_box(&item)));
+        });
+        results.push((size, "insert", insert));
+        results.push((size, "query", query));
+    }
+
+    if options.json {
+        let objects: Vec<String> = results
+            .iter()
+            .map(|(size, operation, stats)| {
+                format!(
+                    "  {{\"size\": {}, \"operation\": \"{}\", \"mean_ns\": {:.3}, \
+                     \"p50_ns\": {:.3}, \"p99_ns\": {:.3}, \"ops_per_sec\": {:.0}}}",
+                    size,
+                    operation,
+                    stats.mean,
+                    stats.p50,
+                    stats.p99,
+                    stats.ops_per_sec()
+                )
+            })
+            .collect();
+        println!("[\n{}\n]", objects.join(",\n"));
+    } else {
+        println!("{:>12}  {:<9}{:>10}{:>10}{:>10}{:>14}", "size", "operation", "ns/op", "p50", "p99", "ops/s");
+        for (size, operation, stats) in &results {
+            println!(
+                "{:>12}  {:<9}{:>10.1}{:>10.1}{:>10.1}{:>14.0}",
+                size,
+                operation,
+                stats.mean,
+                stats.p50,
+                stats.p99,
+                stats.ops_per_sec()
+            );
+        }
+    }
+    Ok(())
+}
+
+// Prints the layout and memory use of a filter sized for `capacity` items, so
+// that it can be planned for without building it from real input.
+fn print_info(capacity: usize, false_positive_prob: f64) -> Result<(), BloomError> {
+    let filter = BloomFilterBuilder::<String>::new()
+        .item_count(capacity)
+        .false_positive_prob(false_positive_prob)
+        .build()?;
+    println!("Bits: {}", filter.bit_vec_size());
+    println!("Hash functions: {}", filter.hash_count());
+    println!("Memory: {} bytes", filter.memory_bytes());
+    Ok(())
+}
+
+fn run(mut args: Vec<String>) -> Result<(), BloomError> {
+    if let Some(command @ ("bench" | "info")) = args.get(1).map(String::as_str) {
+        if args.iter().any(|arg| arg == "--jobs") {
+            return Err(BloomError::InvalidParams(format!("--jobs doesn't apply to {}", command)));
+        }
+    }
+    let jobs = take_jobs(&mut args)?;
+    if args.get(1).map(String::as_str) == Some("bench") {
+        let options = take_bench_options(&mut args)?;
+        if args.len() == 2 {
+            return run_bench(&options);
+        }
+    }
+    if args.len() == 4 && args[1] == "info" {
+        let capacity = parse_arg(&args[2], "filter capacity must be a positive integer")?;
+        let false_positive_prob = parse_arg(&args[3], "false positive probability must be between 0 and 1")?;
+        return print_info(capacity, false_positive_prob);
+    }
     match args.len() {
         4 => {
-            let filter = filter_from_file(
-                &args[1],
-                args[2].parse::<usize>().expect("Filter capacity must be a positive integer."),
-                args[3].parse::<f64>().expect("False positive probability must be between 0 and 1."));
-            check_from_file(&args[1], &filter);
-        },
-        2 => {
-
-            let sizes = [
-                1000,
-                10000,
-                100000,
-                1000000,
-                10000000,
-                100000000,
-                1000000000,
-            ];
-            let false_positive_prob = 0.1;
-
-            for size in &sizes {
-                let mut filter: BloomFilter<String> = BloomFilter::new(*size, false_positive_prob);
-
-                // Populate the filter
-                for i in 0..1000 {
-                    filter.add(&i.to_string());
-                }
-
-                // Check 1000 elements, half of which will be in the filter
-                let start = PreciseTime::now();
-                for i in 0..1000 {
-                    filter.contains(&(i + 500).to_string());
-                }
-                let end = PreciseTime::now();
-                println!("{} {:?}", size, start.to(end));
-
-                // println!("{} {}", size, filter.bit_vec.len());
-            }
+            let capacity = if args[2] == "auto" {
+                let capacity = distinct_lines(&args[1])?;
+                println!("Estimated {} distinct lines", capacity);
+                capacity
+            } else {
+                parse_arg(&args[2], "filter capacity must be a positive integer or \"auto\"")?
+            };
+            let false_positive_prob = parse_arg(&args[3], "false positive probability must be between 0 and 1")?;
+            let filter = filter_from_file_with_jobs(&args[1], capacity, false_positive_prob, jobs)?;
+            check_from_file(&args[1], &filter)?;
         },
         _ => {
-            println!("Usage: {} <input-file>", &args[0]);
+            println!("Usage: {} <input-file> [<capacity | auto> <false-positive-prob> [--jobs <n>]]", &args[0]);
+            println!("       {} info <capacity> <false-positive-prob>", &args[0]);
+            println!("       {} bench [--sizes <n,...>] [--iterations <n>] [--json]", &args[0]);
         },
     }
-    // println!("{:?}", b);
-    // b.add(&"Holle!".to_string());
-    // println!("{:?}", b.contains(&"Holle!".to_string()));
+    Ok(())
 }
 
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if let Err(e) = run(args) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
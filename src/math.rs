@@ -0,0 +1,40 @@
+//! The floating point functions the crate needs, which come from `std` when
+//! it's available and from `libm` otherwise.
+
+#[cfg(feature = "std")]
+mod imp {
+    pub(crate) fn ln(x: f64) -> f64 {
+        x.ln()
+    }
+
+    pub(crate) fn log2(x: f64) -> f64 {
+        x.log2()
+    }
+
+    pub(crate) fn exp(x: f64) -> f64 {
+        x.exp()
+    }
+
+    pub(crate) fn powf(x: f64, y: f64) -> f64 {
+        x.powf(y)
+    }
+
+    pub(crate) fn ceil(x: f64) -> f64 {
+        x.ceil()
+    }
+
+    pub(crate) fn round(x: f64) -> f64 {
+        x.round()
+    }
+
+    pub(crate) fn sqrt(x: f64) -> f64 {
+        x.sqrt()
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod imp {
+    pub(crate) use libm::{ceil, exp, log as ln, log2, pow as powf, round, sqrt};
+}
+
+pub(crate) use self::imp::*;
@@ -0,0 +1,77 @@
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+
+use crate::{BloomFilter, Result};
+
+/// The operations shared by every approximate membership filter in this
+/// crate, so that filter variants can be swapped for one another and tested
+/// generically.
+///
+/// `T` is the type items are passed as, which may be unsized: a filter of
+/// `String`s is also a filter of `str`s.
+pub trait ApproximateMembership<T: ?Sized> {
+    /// Records `item`, returning `true` if it was probably already present.
+    ///
+    /// Filters with a hard capacity return [`BloomError::Capacity`](crate::BloomError::Capacity)
+    /// when `item` can't be stored.
+    fn insert(&mut self, item: &T) -> Result<bool>;
+
+    /// Returns `true` if `item` has probably been inserted, and `false` if it
+    /// definitely has not.
+    fn contains(&self, item: &T) -> bool;
+
+    /// Removes every item, keeping the filter's parameters.
+    fn clear(&mut self);
+
+    /// Estimates the number of distinct items inserted.
+    fn estimated_len(&self) -> f64;
+
+    /// Estimates the probability that `contains` currently returns `true`
+    /// for an item that was never inserted.
+    fn fpr_estimate(&self) -> f64;
+
+    /// The number of bytes of memory the filter occupies, including its heap
+    /// allocations.
+    fn memory_bytes(&self) -> usize;
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for BloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(BloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        BloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        BloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        BloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        BloomFilter::memory_bytes(self)
+    }
+}
+
+/// An [`ApproximateMembership`] filter that items can also be removed from.
+pub trait Removable<T: ?Sized>: ApproximateMembership<T> {
+    /// Removes one occurrence of `item`, returning `true` if it was probably
+    /// present. Nothing is changed if it definitely wasn't.
+    ///
+    /// Only items that were inserted should be removed: removing a false
+    /// positive can cause false negatives for other items.
+    fn remove(&mut self, item: &T) -> bool;
+}
@@ -0,0 +1,59 @@
+use alloc::format;
+
+use crate::{BloomError, Result};
+
+/// Allocations smaller than this aren't checked against the available
+/// memory unless a limit was given too: 64 MiB.
+///
+/// Finding the available memory means reading and parsing
+/// `/proc/meminfo`, which would dominate building a small filter, and
+/// filters are built often as scalable, sharded and sliding filters grow
+/// and rotate. A filter this small can't be the mistyped capacity the check
+/// is there to catch.
+const CHECK_AVAILABLE_FROM: u64 = 64 << 20;
+
+/// Checks that `bytes` more bytes fit within `limit`, if one was given, and
+/// within the memory the system has available, where it can be found out.
+///
+/// A filter larger than the available memory would page or get the process
+/// killed once its bits are touched, so it's better refused up front. The
+/// available memory is only known on Linux with the `std` feature, and only
+/// consulted for allocations of at least 64 MiB or when a limit was given.
+pub(crate) fn check(bytes: u64, limit: Option<u64>) -> Result<()> {
+    if let Some(limit) = limit {
+        if bytes > limit {
+            return Err(BloomError::Capacity(format!(
+                "the filter needs {} bytes of memory, more than the limit of {}",
+                bytes, limit
+            )));
+        }
+    }
+    if bytes < CHECK_AVAILABLE_FROM && limit.is_none() {
+        return Ok(());
+    }
+    if let Some(available) = available() {
+        if bytes > available {
+            return Err(BloomError::Capacity(format!(
+                "the filter needs {} bytes of memory, but only {} are available",
+                bytes, available
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The memory the system can give the process without swapping: Linux's
+/// `MemAvailable`.
+#[cfg(all(feature = "std", target_os = "linux"))]
+fn available() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|line| line.starts_with("MemAvailable:"))?;
+    let kib: u64 = line.trim_start_matches("MemAvailable:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    kib.checked_mul(1024)
+}
+
+/// Available memory is only known on Linux.
+#[cfg(not(all(feature = "std", target_os = "linux")))]
+fn available() -> Option<u64> {
+    None
+}
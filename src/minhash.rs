@@ -0,0 +1,196 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::hash::fmix64;
+use crate::{math, BloomError, DefaultBuildHasher, Result};
+
+/// A MinHash signature of a set (Broder, "On the resemblance and
+/// containment of documents"), from which the Jaccard similarity of two sets
+/// can be estimated without either set.
+///
+/// The signature keeps, for each of `k` hash functions, the smallest hash of
+/// any item in the set. Two sets' minima under a hash function are equal
+/// exactly when the item of the union with the smallest hash is in both, so
+/// the fraction of positions where two signatures agree estimates
+/// `|A ∩ B| / |A ∪ B|` with a standard error of at most `1 / (2 sqrt(k))`.
+///
+/// A signature is a fixed `8 k` bytes however large the set, and comparing
+/// two is `k` comparisons, where comparing two
+/// [`BloomFilter`](crate::BloomFilter)s means comparing all of their bits
+/// and needs them sized alike.
+///
+/// ```
+/// use bloom::MinHash;
+///
+/// let (mut a, mut b) = (MinHash::<u64>::new(256), MinHash::<u64>::new(256));
+/// for i in 0..1000 {
+///     a.insert(&i);
+///     b.insert(&(i + 500));
+/// }
+/// // The sets share 500 of 1500 items.
+/// let similarity = a.jaccard(&b).unwrap();
+/// assert!((similarity - 1.0 / 3.0).abs() < 0.1);
+/// ```
+#[derive(Debug)]
+pub struct MinHash<T, S = DefaultBuildHasher> {
+    minima: Box<[u64]>,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for MinHash<T, S> {
+    fn clone(&self) -> MinHash<T, S> {
+        MinHash {
+            minima: self.minima.clone(),
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> MinHash<T> {
+    /// Creates the signature of an empty set, with `hash_count` hash
+    /// functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hash_count` is 0.
+    pub fn new(hash_count: usize) -> MinHash<T> {
+        MinHash::with_hasher(hash_count, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](MinHash::new), but mixes `seed` into every hash.
+    pub fn with_seed(hash_count: usize, seed: u64) -> MinHash<T> {
+        let mut signature = MinHash::new(hash_count);
+        signature.seed = seed;
+        signature
+    }
+}
+
+impl<T: Hash, S: BuildHasher> MinHash<T, S> {
+    /// Like [`new`](MinHash::new), but hashes items with `hash_builder`.
+    pub fn with_hasher(hash_count: usize, hash_builder: S) -> MinHash<T, S> {
+        if hash_count == 0 {
+            panic!("{}", BloomError::InvalidParams("hash count must be nonzero".into()));
+        }
+        MinHash {
+            minima: vec![u64::MAX; hash_count].into_boxed_slice(),
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Adds `item` to the set, returning `true` if the signature changed.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let hash = hasher.finish();
+        let mut changed = false;
+        for (i, minimum) in self.minima.iter_mut().enumerate() {
+            // Hash function `i` is a bijective mix of the item's hash offset
+            // by a multiple of the golden ratio.
+            let value = fmix64(hash.wrapping_add((i as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15)));
+            if value < *minimum {
+                *minimum = value;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Estimates the Jaccard similarity of this signature's set and
+    /// `other`'s, `|A ∩ B| / |A ∪ B|`. Two empty sets have a similarity of 1.
+    ///
+    /// Both signatures must have the same hash count and seed, and should
+    /// use the same hasher; otherwise [`BloomError::Incompatible`] is
+    /// returned.
+    pub fn jaccard(&self, other: &MinHash<T, S>) -> Result<f64> {
+        self.check_compatible(other)?;
+        let equal = self.minima.iter().zip(other.minima.iter()).filter(|(a, b)| a == b).count();
+        Ok(equal as f64 / self.minima.len() as f64)
+    }
+
+    /// Adds every item of `other`'s set to this one, so that it is the
+    /// signature of their union.
+    ///
+    /// Requires compatible signatures, as for [`jaccard`](MinHash::jaccard).
+    pub fn try_merge(&mut self, other: &MinHash<T, S>) -> Result<()> {
+        self.check_compatible(other)?;
+        for (minimum, &other_minimum) in self.minima.iter_mut().zip(other.minima.iter()) {
+            *minimum = (*minimum).min(other_minimum);
+        }
+        Ok(())
+    }
+
+    /// Empties the set, keeping the hash count.
+    pub fn clear(&mut self) {
+        for minimum in self.minima.iter_mut() {
+            *minimum = u64::MAX;
+        }
+    }
+
+    /// Returns `true` if nothing has been inserted since the signature was
+    /// created or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.minima.iter().all(|&minimum| minimum == u64::MAX)
+    }
+
+    /// The smallest hash under each hash function, `u64::MAX` for an empty
+    /// set. Signatures are equal exactly when these are.
+    pub fn signature(&self) -> &[u64] {
+        &self.minima
+    }
+
+    /// The number of hash functions, `k`.
+    pub fn hash_count(&self) -> usize {
+        self.minima.len()
+    }
+
+    /// The bound on the standard error of a similarity estimate,
+    /// `1 / (2 sqrt(k))`.
+    pub fn max_error(&self) -> f64 {
+        0.5 / math::sqrt(self.minima.len() as f64)
+    }
+
+    /// The number of bytes of memory the signature occupies.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(&*self.minima)
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    fn check_compatible(&self, other: &MinHash<T, S>) -> Result<()> {
+        if self.minima.len() != other.minima.len() {
+            return Err(BloomError::Incompatible(format!(
+                "hash counts differ ({} and {})",
+                self.minima.len(),
+                other.minima.len()
+            )));
+        }
+        if self.seed != other.seed {
+            return Err(BloomError::Incompatible(format!("seeds differ ({} and {})", self.seed, other.seed)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,535 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::convert::TryFrom;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::probe;
+use crate::{math, ApproximateMembership, BloomError, DefaultBuildHasher, Removable, Result};
+
+/// The number of fingerprints a block can hold.
+const BLOCK_SLOTS: usize = 46;
+
+/// The number of logical buckets in a block.
+const BLOCK_BUCKETS: u64 = 64;
+
+/// The number of fingerprints a logical bucket can hold.
+const BUCKET_SIZE: u32 = 3;
+
+/// The fraction of slots a filter is sized to fill.
+const TARGET_LOAD: f64 = 0.9;
+
+/// How many fingerprints an insertion relocates before giving up.
+const MAX_KICKS: usize = 500;
+
+const EVEN_BITS: u128 = 0x5555_5555_5555_5555_5555_5555_5555_5555;
+
+/// One cache line: the fingerprints of 64 logical buckets, stored end to end
+/// in bucket order, with a 2-bit count of each bucket's fingerprints and a
+/// 16-bit record of which buckets have overflowed to their other bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C, align(64))]
+struct Block {
+    fingerprints: [u8; BLOCK_SLOTS],
+    counts: [u8; 16],
+    overflow: u16,
+}
+
+const EMPTY_BLOCK: Block = Block {
+    fingerprints: [0; BLOCK_SLOTS],
+    counts: [0; 16],
+    overflow: 0,
+};
+
+impl Block {
+    fn counts(&self) -> u128 {
+        u128::from_le_bytes(self.counts)
+    }
+
+    fn count(&self, bucket: usize) -> usize {
+        (self.counts() >> (2 * bucket)) as usize & 3
+    }
+
+    /// The total count of the buckets before `bucket`, which is where its
+    /// fingerprints start.
+    fn offset(&self, bucket: usize) -> usize {
+        let counts = if bucket == 0 { 0 } else { self.counts() & (u128::MAX >> (128 - 2 * bucket)) };
+        ((counts & EVEN_BITS).count_ones() + 2 * (counts & !EVEN_BITS).count_ones()) as usize
+    }
+
+    fn used(&self) -> usize {
+        self.offset(BLOCK_BUCKETS as usize)
+    }
+
+    fn increment(&mut self, bucket: usize) {
+        self.counts = (self.counts() + (1 << (2 * bucket))).to_le_bytes();
+    }
+
+    fn decrement(&mut self, bucket: usize) {
+        self.counts = (self.counts() - (1 << (2 * bucket))).to_le_bytes();
+    }
+
+    fn find(&self, bucket: usize, fingerprint: u8) -> Option<usize> {
+        let start = self.offset(bucket);
+        (start..start + self.count(bucket)).find(|&slot| self.fingerprints[slot] == fingerprint)
+    }
+
+    /// Stores `fingerprint` at the end of `bucket`, returning `false` if the
+    /// bucket or the block is full.
+    fn put(&mut self, bucket: usize, fingerprint: u8) -> bool {
+        let used = self.used();
+        if self.count(bucket) == BUCKET_SIZE as usize || used == BLOCK_SLOTS {
+            return false;
+        }
+        let slot = self.offset(bucket) + self.count(bucket);
+        self.fingerprints.copy_within(slot..used, slot + 1);
+        self.fingerprints[slot] = fingerprint;
+        self.increment(bucket);
+        true
+    }
+
+    /// Removes the fingerprint in `slot`, which belongs to `bucket`.
+    fn remove(&mut self, bucket: usize, slot: usize) {
+        let used = self.used();
+        self.fingerprints.copy_within(slot + 1..used, slot);
+        self.fingerprints[used - 1] = 0;
+        self.decrement(bucket);
+    }
+
+    /// The bucket the fingerprint in `slot` belongs to.
+    fn owner(&self, slot: usize) -> usize {
+        let mut end = 0;
+        for bucket in 0..BLOCK_BUCKETS as usize {
+            end += self.count(bucket);
+            if slot < end {
+                return bucket;
+            }
+        }
+        unreachable!("slot {} is past the end of the block", slot)
+    }
+
+    fn overflow_bit(bucket: usize, fingerprint: u8) -> u16 {
+        1 << ((bucket ^ usize::from(fingerprint)) % 16)
+    }
+}
+
+/// A Morton filter (Breslow and Jayasena, "Morton Filters: Faster,
+/// Space-Efficient Cuckoo Filters via Biasing, Compression, and Decoupled
+/// Logical Sparsity"): a cuckoo filter whose buckets are compressed into
+/// cache-line blocks.
+///
+/// Each 64-byte block serves 64 logical buckets of up to three 8-bit
+/// fingerprints, but stores only 46 fingerprints end to end, with a 2-bit
+/// count per bucket to find them. Since most buckets are nearly empty at any
+/// realistic load, that fits far more buckets per cache line than
+/// uncompressed storage would, and tables reach high load factors.
+///
+/// Items are biased towards their first bucket: the second, which is always
+/// in another block, is only used once the first is full, and the first
+/// block then sets an overflow bit so that lookups know to check it. Most
+/// lookups therefore read a single cache line, and
+/// [`contains_batch`](MortonFilter::contains_batch) checks every first block
+/// of a batch before any second one so the reads overlap.
+///
+/// The false positive probability is fixed by the 8-bit fingerprints, at
+/// about 0.3% when full, and can't be tuned. Like a
+/// [`CuckooFilter`](crate::CuckooFilter), the table has a hard capacity,
+/// adding an item twice stores it twice, and only items that were added
+/// should be removed.
+///
+/// ```
+/// use bloom::MortonFilter;
+///
+/// let mut filter = MortonFilter::<String>::new(1000);
+/// filter.insert("request-1").unwrap();
+/// assert_eq!(filter.contains_batch(["request-1", "request-2"]), [true, false]);
+/// ```
+#[derive(Debug)]
+pub struct MortonFilter<T, S = DefaultBuildHasher> {
+    blocks: Box<[Block]>,
+    // A fingerprint evicted by an insertion that ran out of kicks, and its
+    // bucket. Once set, the filter is full.
+    victim: Option<(u64, u8)>,
+    len: u64,
+    // State for choosing which fingerprint to evict.
+    rng: u64,
+    item_count: usize,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for MortonFilter<T, S> {
+    fn clone(&self) -> MortonFilter<T, S> {
+        MortonFilter {
+            blocks: self.blocks.clone(),
+            victim: self.victim,
+            len: self.len,
+            rng: self.rng,
+            item_count: self.item_count,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> MortonFilter<T> {
+    /// Creates a filter sized to hold `item_count` items.
+    ///
+    /// There is no false positive target: with 8-bit fingerprints the rate
+    /// is about 0.3% once `item_count` items are in, and
+    /// [`current_fpr`](MortonFilter::current_fpr) estimates it as the
+    /// filter fills. For a lower rate, use a
+    /// [`CuckooFilter`](crate::CuckooFilter).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0, or if the table can't be addressed.
+    pub fn new(item_count: usize) -> MortonFilter<T> {
+        MortonFilter::with_hasher(item_count, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](MortonFilter::new), but mixes `seed` into every hash.
+    pub fn with_seed(item_count: usize, seed: u64) -> MortonFilter<T> {
+        let mut filter = MortonFilter::new(item_count);
+        filter.seed = seed;
+        filter
+    }
+}
+
+impl<T: Hash, S: BuildHasher> MortonFilter<T, S> {
+    /// Like [`new`](MortonFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(item_count: usize, hash_builder: S) -> MortonFilter<T, S> {
+        if item_count == 0 {
+            panic!("{}", BloomError::InvalidParams("item count must be greater than 0".to_string()));
+        }
+        // Alternate blocks pair even with odd, so the count must be even.
+        let blocks = math::ceil(item_count as f64 / (BLOCK_SLOTS as f64 * TARGET_LOAD)) as u64;
+        let blocks = usize::try_from(blocks.div_ceil(2) * 2)
+            .ok()
+            .filter(|&blocks| blocks <= isize::MAX as usize / mem::size_of::<Block>());
+        let blocks = match blocks {
+            Some(blocks) => blocks,
+            None => panic!("{}", BloomError::Capacity(format!("{} items can't be addressed", item_count))),
+        };
+        MortonFilter {
+            blocks: vec![EMPTY_BLOCK; blocks].into_boxed_slice(),
+            victim: None,
+            len: 0,
+            rng: 0x2545_f491_4f6c_dd1d,
+            item_count,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::Capacity`] if the filter is full. The item is
+    /// not added, and the filter is unchanged.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> Result<bool>
+    where
+        T: Borrow<Q>,
+    {
+        if self.victim.is_some() {
+            return Err(BloomError::Capacity(format!("the filter is full ({} items)", self.len)));
+        }
+        let present = self.contains(item);
+        let (fingerprint, first) = self.locate(item);
+        self.len += 1;
+        if self.put(first, fingerprint) {
+            return Ok(present);
+        }
+        self.mark_overflow(first, fingerprint);
+        let second = self.alternate(first, fingerprint);
+        if self.put(second, fingerprint) {
+            return Ok(present);
+        }
+        // Evict fingerprints along a random path until one finds room. The
+        // last one evicted is kept aside rather than lost, so every item
+        // stays findable.
+        let mut bucket = if self.next_random() & 1 == 0 { first } else { second };
+        let mut fingerprint = fingerprint;
+        for _ in 0..MAX_KICKS {
+            let random = self.next_random() as usize;
+            let (block, local) = split(bucket);
+            let block = &mut self.blocks[block];
+            // Evict from the bucket if it is full, and otherwise from
+            // anywhere in the full block.
+            let (owner, slot) = if block.count(local) == BUCKET_SIZE as usize {
+                (local, block.offset(local) + random % BUCKET_SIZE as usize)
+            } else {
+                let slot = random % BLOCK_SLOTS;
+                (block.owner(slot), slot)
+            };
+            let evicted = block.fingerprints[slot];
+            block.remove(owner, slot);
+            block.put(local, fingerprint);
+            let source = bucket - local as u64 + owner as u64;
+            self.mark_overflow(source, evicted);
+            bucket = self.alternate(source, evicted);
+            fingerprint = evicted;
+            if self.put(bucket, fingerprint) {
+                return Ok(present);
+            }
+        }
+        self.victim = Some((bucket, fingerprint));
+        Ok(present)
+    }
+
+    /// Returns `true` if `item` has probably been inserted and not removed,
+    /// and `false` if it definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (fingerprint, first) = self.locate(item);
+        self.find(first, fingerprint).is_some()
+            || self.overflowed(first, fingerprint) && self.find(self.alternate(first, fingerprint), fingerprint).is_some()
+            || self.victim_matches(first, fingerprint)
+    }
+
+    /// Looks up every item in `items`, returning whether each has probably
+    /// been inserted.
+    ///
+    /// Every item's first block is checked before any second block, so the
+    /// first pass's memory reads are independent of each other and can be
+    /// in flight at once; only items whose first block has overflowed need
+    /// a second pass.
+    pub fn contains_batch<'a, Q, I>(&self, items: I) -> Vec<bool>
+    where
+        T: Borrow<Q>,
+        Q: 'a + ?Sized + Hash,
+        I: IntoIterator<Item = &'a Q>,
+    {
+        let located: Vec<(u8, u64)> = items.into_iter().map(|item| self.locate(item)).collect();
+        let mut found: Vec<bool> = located
+            .iter()
+            .map(|&(fingerprint, first)| self.find(first, fingerprint).is_some())
+            .collect();
+        for (found, &(fingerprint, first)) in found.iter_mut().zip(&located) {
+            if !*found && self.overflowed(first, fingerprint) {
+                *found = self.find(self.alternate(first, fingerprint), fingerprint).is_some();
+            }
+            *found = *found || self.victim_matches(first, fingerprint);
+        }
+        found
+    }
+
+    /// Removes one copy of `item`, returning `true` if it was probably
+    /// present.
+    ///
+    /// Overflow bits stay set, since other items may share them, so lookups
+    /// that read a second block stay as frequent as at the filter's fullest.
+    pub fn remove<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (fingerprint, first) = self.locate(item);
+        if self.victim_matches(first, fingerprint) {
+            self.victim = None;
+            self.len -= 1;
+            return true;
+        }
+        let second = self.alternate(first, fingerprint);
+        let bucket = if self.find(first, fingerprint).is_some() {
+            first
+        } else if self.overflowed(first, fingerprint) && self.find(second, fingerprint).is_some() {
+            second
+        } else {
+            return false;
+        };
+        let (block, local) = split(bucket);
+        let slot = self.blocks[block].find(local, fingerprint).unwrap();
+        self.blocks[block].remove(local, slot);
+        self.len -= 1;
+        // Now that there is room, try to place the victim again.
+        if let Some((bucket, fingerprint)) = self.victim.take() {
+            let placed = self.put(bucket, fingerprint) || {
+                self.mark_overflow(bucket, fingerprint);
+                self.put(self.alternate(bucket, fingerprint), fingerprint)
+            };
+            if !placed {
+                self.victim = Some((bucket, fingerprint));
+            }
+        }
+        true
+    }
+
+    /// Removes every item from the filter, keeping its allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        for block in self.blocks.iter_mut() {
+            *block = EMPTY_BLOCK;
+        }
+        self.victim = None;
+        self.len = 0;
+    }
+
+    /// Returns `true` if the filter holds no fingerprints.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of fingerprints stored, counting each copy of an item
+    /// inserted more than once.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// The fraction of fingerprint slots that are occupied.
+    pub fn load_factor(&self) -> f64 {
+        self.len as f64 / (self.blocks.len() * BLOCK_SLOTS) as f64
+    }
+
+    /// Estimates the false positive probability of the filter as it is now:
+    /// the chance that a fingerprint in an item's first bucket matches, or
+    /// in its second when the first block's overflow bit is set.
+    pub fn current_fpr(&self) -> f64 {
+        let per_bucket = self.len as f64 / (self.blocks.len() as u64 * BLOCK_BUCKETS) as f64;
+        let overflowed: u32 = self.blocks.iter().map(|block| block.overflow.count_ones()).sum();
+        let overflowed = f64::from(overflowed) / (self.blocks.len() * 16) as f64;
+        1.0 - math::powf(1.0 - 1.0 / 256.0, per_bucket * (1.0 + overflowed))
+    }
+
+    /// The number of 64-byte blocks.
+    pub fn block_count(&self) -> u64 {
+        self.blocks.len() as u64
+    }
+
+    /// The number of items the filter was sized for.
+    pub fn capacity(&self) -> usize {
+        self.item_count
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// `item`'s fingerprint and its first bucket.
+    fn locate<Q: ?Sized + Hash>(&self, item: &Q) -> (u8, u64) {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let hash = hasher.finish();
+        (hash as u8, probe::reduce(hash, self.blocks.len() as u64 * BLOCK_BUCKETS))
+    }
+
+    /// The other bucket a fingerprint in `bucket` may be stored in: the
+    /// same bucket of a block an odd distance away, forwards from an even
+    /// block and backwards from an odd one, so that the mapping is its own
+    /// inverse.
+    fn alternate(&self, bucket: u64, fingerprint: u8) -> u64 {
+        let blocks = self.blocks.len() as u64;
+        let mut hash = u64::from(fingerprint).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        hash ^= hash >> 32;
+        let distance = hash % (blocks / 2) * 2 + 1;
+        let block = bucket / BLOCK_BUCKETS;
+        let block = if block.is_multiple_of(2) { (block + distance) % blocks } else { (block + blocks - distance) % blocks };
+        block * BLOCK_BUCKETS + bucket % BLOCK_BUCKETS
+    }
+
+    fn find(&self, bucket: u64, fingerprint: u8) -> Option<usize> {
+        let (block, local) = split(bucket);
+        self.blocks[block].find(local, fingerprint)
+    }
+
+    fn put(&mut self, bucket: u64, fingerprint: u8) -> bool {
+        let (block, local) = split(bucket);
+        self.blocks[block].put(local, fingerprint)
+    }
+
+    /// Records that `fingerprint` may be in the other bucket of `bucket`.
+    fn mark_overflow(&mut self, bucket: u64, fingerprint: u8) {
+        let (block, local) = split(bucket);
+        self.blocks[block].overflow |= Block::overflow_bit(local, fingerprint);
+    }
+
+    fn overflowed(&self, bucket: u64, fingerprint: u8) -> bool {
+        let (block, local) = split(bucket);
+        self.blocks[block].overflow & Block::overflow_bit(local, fingerprint) != 0
+    }
+
+    fn victim_matches(&self, first: u64, fingerprint: u8) -> bool {
+        match self.victim {
+            Some((bucket, victim)) => {
+                victim == fingerprint && (bucket == first || bucket == self.alternate(first, fingerprint))
+            }
+            None => false,
+        }
+    }
+
+    /// Steps an xorshift generator; evictions only need to avoid cycles, not
+    /// to be unpredictable.
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+}
+
+/// The block of `bucket` and its index within the block.
+fn split(bucket: u64) -> (usize, usize) {
+    ((bucket / BLOCK_BUCKETS) as usize, (bucket % BLOCK_BUCKETS) as usize)
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for MortonFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        MortonFilter::insert(self, item)
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        MortonFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        MortonFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        self.len as f64
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(&*self.blocks)
+    }
+}
+
+impl<T, Q, S> Removable<Q> for MortonFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn remove(&mut self, item: &Q) -> bool {
+        MortonFilter::remove(self, item)
+    }
+}
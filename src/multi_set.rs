@@ -0,0 +1,265 @@
+use alloc::format;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::packed::PackedVec;
+use crate::probe::Probes;
+use crate::{math, params, BloomError, DefaultBuildHasher};
+
+/// The most sets a filter can tell apart, one per bit of the masks it
+/// answers with.
+const MAX_SETS: u32 = 64;
+
+/// A bloom filter that records which of up to 64 sets each item belongs
+/// to, for routing an item to the shards, classes or peers that probably
+/// hold it.
+///
+/// Each cell holds one bit per set, so the filter is in effect one bloom
+/// filter per set laid side by side: an item's probes pick the same cells
+/// in every set, and looking it up reads all of its sets at once. An
+/// item's sets are the bits set in every one of its cells, returned as a
+/// mask with bit `i` standing for set `i`. Each set has its own false
+/// positives, as a bloom filter sized for the items in that set would,
+/// and no false negatives.
+///
+/// ```
+/// use bloom::MultiSetBloomFilter;
+///
+/// // Which of three datacenters holds a user's data.
+/// let mut filter = MultiSetBloomFilter::<&str>::new(3, 1000, 0.01);
+/// filter.insert(&"alice", 0);
+/// filter.insert(&"alice", 2);
+/// filter.insert(&"bob", 1);
+/// assert_eq!(filter.which_sets(&"alice"), 0b101);
+/// assert_eq!(filter.which_sets(&"bob"), 0b010);
+/// assert!(filter.contains(&"bob", 1));
+/// ```
+#[derive(Debug)]
+pub struct MultiSetBloomFilter<T, S = DefaultBuildHasher> {
+    /// One bit per set in each cell.
+    cells: PackedVec,
+    set_count: u32,
+    item_count: usize,
+    false_positive_prob: f64,
+    hash_count: usize,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for MultiSetBloomFilter<T, S> {
+    fn clone(&self) -> MultiSetBloomFilter<T, S> {
+        MultiSetBloomFilter {
+            cells: self.cells.clone(),
+            set_count: self.set_count,
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            hash_count: self.hash_count,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> MultiSetBloomFilter<T> {
+    /// Creates a filter of `set_count` sets, each sized to hold
+    /// `item_count` items with the given false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `set_count` is between 1 and 64, if `item_count` is 0,
+    /// if `false_positive_prob` is not strictly between 0 and 1, or if the
+    /// filter is too large to allocate.
+    pub fn new(set_count: u32, item_count: usize, false_positive_prob: f64) -> MultiSetBloomFilter<T> {
+        MultiSetBloomFilter::with_hasher(set_count, item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](MultiSetBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(
+        set_count: u32,
+        item_count: usize,
+        false_positive_prob: f64,
+        seed: u64,
+    ) -> MultiSetBloomFilter<T> {
+        let mut filter = MultiSetBloomFilter::new(set_count, item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+}
+
+impl<T: Hash, S: BuildHasher> MultiSetBloomFilter<T, S> {
+    /// Like [`new`](MultiSetBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(
+        set_count: u32,
+        item_count: usize,
+        false_positive_prob: f64,
+        hash_builder: S,
+    ) -> MultiSetBloomFilter<T, S> {
+        if !(1..=MAX_SETS).contains(&set_count) {
+            panic!(
+                "{}",
+                BloomError::InvalidParams(format!("need 1 to {} sets (got {})", MAX_SETS, set_count))
+            );
+        }
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let cell_count = params::optimal_bits(item_count, false_positive_prob);
+        let hash_count = params::optimal_hashes(cell_count, item_count);
+        let cells = match PackedVec::new(cell_count, set_count) {
+            Ok(cells) => cells,
+            Err(e) => panic!("{}", e),
+        };
+        MultiSetBloomFilter {
+            cells,
+            set_count,
+            item_count,
+            false_positive_prob,
+            hash_count,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records that `item` belongs to set `set`, returning `true` if it
+    /// probably did already.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `set` is not less than the number of sets.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q, set: u32) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        assert!(set < self.set_count, "set {} is out of range for {} sets", set, self.set_count);
+        let bit = 1 << set;
+        let mut present = true;
+        for index in self.probes(item) {
+            let cell = self.cells.get(index);
+            if cell & bit == 0 {
+                self.cells.set(index, cell | bit);
+                present = false;
+            }
+        }
+        present
+    }
+
+    /// The sets `item` probably belongs to, as a mask with bit `i` set for
+    /// set `i`. Bits for sets it definitely doesn't belong to are clear.
+    pub fn which_sets<Q: ?Sized + Hash>(&self, item: &Q) -> u64
+    where
+        T: Borrow<Q>,
+    {
+        let mut sets = self.cells.max();
+        for index in self.probes(item) {
+            sets &= self.cells.get(index);
+            if sets == 0 {
+                break;
+            }
+        }
+        sets
+    }
+
+    /// Returns `true` if `item` probably belongs to set `set`, and `false`
+    /// if it definitely doesn't.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q, set: u32) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        set < self.set_count && self.which_sets(item) & 1 << set != 0
+    }
+
+    /// Returns `true` if `item` probably belongs to any set.
+    pub fn contains_any<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.which_sets(item) != 0
+    }
+
+    /// Removes every item from every set, keeping the filter's allocation
+    /// and parameters.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// Returns `true` if nothing has been added since the filter was created
+    /// or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.cells.words().iter().all(|&word| word == 0)
+    }
+
+    /// The fraction of cells with set `set`'s bit set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `set` is not less than the number of sets.
+    pub fn fill_ratio(&self, set: u32) -> f64 {
+        assert!(set < self.set_count, "set {} is out of range for {} sets", set, self.set_count);
+        let ones = (0..self.cells.len()).filter(|&index| self.cells.get(index) & 1 << set != 0).count();
+        ones as f64 / self.cells.len() as f64
+    }
+
+    /// Estimates the probability that an item not in set `set` is reported
+    /// in it, as the set is now.
+    pub fn current_fpr(&self, set: u32) -> f64 {
+        math::powf(self.fill_ratio(set), self.hash_count as f64)
+    }
+
+    /// The number of sets.
+    pub fn set_count(&self) -> u32 {
+        self.set_count
+    }
+
+    /// The number of cells, each holding a bit per set.
+    pub fn cell_count(&self) -> u64 {
+        self.cells.len()
+    }
+
+    /// The number of hash functions.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The number of items each set was sized for.
+    pub fn capacity(&self) -> usize {
+        self.item_count
+    }
+
+    /// The false positive probability each set was sized for.
+    pub fn false_positive_prob(&self) -> f64 {
+        self.false_positive_prob
+    }
+
+    /// The number of bytes of memory the filter occupies, including its
+    /// cells.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(self.cells.words())
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The cells `item` maps to.
+    fn probes<Q: ?Sized + Hash>(&self, item: &Q) -> Probes<S::Hasher> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let size = self.cells.len();
+        Probes::new(hasher, size, self.hash_count, size > params::WIDE_HASH_THRESHOLD)
+    }
+}
@@ -0,0 +1,94 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec;
+use core::convert::TryFrom;
+
+use crate::{BloomError, Result};
+
+/// A fixed-size array of `width`-bit unsigned values packed end to end into
+/// 64-bit words.
+///
+/// Value `i` occupies bits `i * width..(i + 1) * width` of the array, where
+/// bit `j` is bit `j % 64` of word `j / 64`, so a value may straddle two
+/// words.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PackedVec {
+    words: Box<[u64]>,
+    len: u64,
+    width: u32,
+}
+
+impl PackedVec {
+    /// Creates `len` zeroed values of `width` bits each, where `width` is
+    /// between 1 and 64.
+    pub(crate) fn new(len: u64, width: u32) -> Result<PackedVec> {
+        debug_assert!((1..=64).contains(&width));
+        let words = len
+            .checked_mul(u64::from(width))
+            .map(|bits| bits.div_ceil(64))
+            .and_then(|words| usize::try_from(words).ok())
+            .filter(|&words| words <= isize::MAX as usize / 8)
+            .ok_or_else(|| BloomError::Capacity(format!("{} values of {} bits can't be addressed", len, width)))?;
+        Ok(PackedVec {
+            words: vec![0; words].into_boxed_slice(),
+            len,
+            width,
+        })
+    }
+
+    /// Wraps existing words, which must be exactly as many as `len` values of
+    /// `width` bits need.
+    pub(crate) fn from_words(words: Box<[u64]>, len: u64, width: u32) -> PackedVec {
+        debug_assert_eq!(words.len() as u64, (len * u64::from(width)).div_ceil(64));
+        PackedVec { words, len, width }
+    }
+
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub(crate) fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The largest value that fits in `width` bits.
+    pub(crate) fn max(&self) -> u64 {
+        u64::MAX >> (64 - self.width)
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, index: u64) -> u64 {
+        debug_assert!(index < self.len);
+        let bit = index * u64::from(self.width);
+        let (word, shift) = ((bit / 64) as usize, (bit % 64) as u32);
+        let mut value = self.words[word] >> shift;
+        if shift + self.width > 64 {
+            value |= self.words[word + 1] << (64 - shift);
+        }
+        value & self.max()
+    }
+
+    #[inline]
+    pub(crate) fn set(&mut self, index: u64, value: u64) {
+        debug_assert!(index < self.len && value <= self.max());
+        let bit = index * u64::from(self.width);
+        let (word, shift) = ((bit / 64) as usize, (bit % 64) as u32);
+        let mask = self.max();
+        self.words[word] = self.words[word] & !(mask << shift) | value << shift;
+        if shift + self.width > 64 {
+            let spill = 64 - shift;
+            self.words[word + 1] = self.words[word + 1] & !(mask >> spill) | value >> spill;
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        for word in self.words.iter_mut() {
+            *word = 0;
+        }
+    }
+
+    /// The words backing the array.
+    pub(crate) fn words(&self) -> &[u64] {
+        &self.words
+    }
+}
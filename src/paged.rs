@@ -0,0 +1,284 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io::BufReader;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::hash::{HashScheme, SecretKey, XxHash64};
+use crate::probe::Probes;
+use crate::serialize::Header;
+use crate::{BloomError, Result};
+
+/// The number of bytes of bits read from the file at a time.
+const PAGE_BYTES: u64 = 4096;
+
+/// The number of pages a filter caches unless told otherwise: 4 MiB.
+const DEFAULT_CACHE_PAGES: usize = 1024;
+
+/// A read-only view of a filter saved with [`BloomFilter::save`](crate::BloomFilter::save)
+/// that reads its bits from the file only as lookups touch them.
+///
+/// Opening the filter reads just the header, and each lookup reads the
+/// 4 KiB pages holding its `k` bits, keeping the most recently used pages
+/// in a small cache. A filter far larger than memory can be queried this
+/// way, at the cost of up to `k` positioned reads per lookup that misses the
+/// cache; with the file in the operating system's page cache those are
+/// cheap.
+///
+/// ```
+/// use bloom::{BloomFilter, PagedBloomFilter};
+/// use bloom::hash::HashScheme;
+///
+/// let path = std::env::temp_dir().join(format!("bloom-paged-doc-{}.blm", std::process::id()));
+/// let mut filter = BloomFilter::<u64, _>::with_hasher(10_000, 0.01, HashScheme::XxHash64);
+/// filter.insert(&42);
+/// filter.save(&path).unwrap();
+///
+/// let paged = PagedBloomFilter::<u64>::open(&path).unwrap().with_cache_pages(16);
+/// assert!(paged.contains(&42).unwrap());
+/// assert!(paged.pages_read() > 0);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+///
+/// Only the header is checked on opening; the checksum covers the whole
+/// file, so checking it means reading every bit with
+/// [`verify`](PagedBloomFilter::verify).
+#[derive(Debug)]
+pub struct PagedBloomFilter<T> {
+    file: File,
+    header: Header,
+    cache: Mutex<PageCache>,
+    pages_read: AtomicU64,
+    phantom: PhantomData<fn(T)>,
+}
+
+impl<T: Hash> PagedBloomFilter<T> {
+    /// Opens the filter saved at `path`, reading only its header.
+    ///
+    /// # Errors
+    ///
+    /// Fails as [`BloomFilter::read_from`](crate::BloomFilter::read_from)
+    /// does if the header is malformed or unsupported, and with
+    /// [`BloomError::CorruptFile`] if the file isn't as long as the header
+    /// says.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<PagedBloomFilter<T>> {
+        PagedBloomFilter::open_with_key(path.as_ref(), None)
+    }
+
+    /// Opens a keyed filter saved at `path`; see
+    /// [`BloomFilter::read_from_keyed`](crate::BloomFilter::read_from_keyed).
+    pub fn open_keyed<P: AsRef<Path>>(path: P, key: SecretKey) -> Result<PagedBloomFilter<T>> {
+        PagedBloomFilter::open_with_key(path.as_ref(), Some(key))
+    }
+
+    fn open_with_key(path: &Path, key: Option<SecretKey>) -> Result<PagedBloomFilter<T>> {
+        let file = File::open(path)?;
+        let header = Header::read(BufReader::new(&file), key)?;
+        let expected_len = header.len() + header.bit_vec_size.div_ceil(8) + 8;
+        if file.metadata()?.len() != expected_len {
+            return Err(BloomError::CorruptFile("wrong file length".to_string()));
+        }
+        Ok(PagedBloomFilter {
+            file,
+            header,
+            cache: Mutex::new(PageCache::new(DEFAULT_CACHE_PAGES)),
+            pages_read: AtomicU64::new(0),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Keeps at most `pages` pages of 4 KiB cached, instead of 1024, and
+    /// empties the cache.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pages` is 0.
+    pub fn with_cache_pages(self, pages: usize) -> PagedBloomFilter<T> {
+        assert!(pages > 0, "the cache must hold at least one page");
+        PagedBloomFilter { cache: Mutex::new(PageCache::new(pages)), ..self }
+    }
+
+    /// Returns `true` if `item` has probably been added, and `false` if it
+    /// definitely has not, reading any pages it needs that aren't cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::Io`] if a page can't be read.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> Result<bool>
+    where
+        T: Borrow<Q>,
+    {
+        let mut hasher = self.header.scheme.build_hasher();
+        hasher.write_u64(self.header.seed);
+        item.hash(&mut hasher);
+        let probes = Probes::new(hasher, self.header.bit_vec_size, self.header.hash_count, self.header.wide_hashes);
+        for index in probes {
+            if !self.get(index)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Reads every byte of the file and checks its checksum.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::CorruptFile`] if the checksum doesn't match.
+    pub fn verify(&self) -> Result<()> {
+        let len = self.file.metadata()?.len() - 8;
+        let mut digest = XxHash64::with_seed(0);
+        let mut chunk = vec![0; 64 * PAGE_BYTES as usize];
+        let mut offset = 0;
+        while offset < len {
+            let chunk = &mut chunk[..(len - offset).min(64 * PAGE_BYTES) as usize];
+            read_at(&self.file, chunk, offset)?;
+            digest.write(chunk);
+            offset += chunk.len() as u64;
+        }
+        let mut checksum = [0; 8];
+        read_at(&self.file, &mut checksum, len)?;
+        if u64::from_le_bytes(checksum) != digest.finish() {
+            return Err(BloomError::CorruptFile("checksum mismatch".to_string()));
+        }
+        Ok(())
+    }
+
+    /// The number of bits in the filter.
+    pub fn bit_vec_size(&self) -> u64 {
+        self.header.bit_vec_size
+    }
+
+    /// The number of bits each item sets.
+    pub fn hash_count(&self) -> usize {
+        self.header.hash_count
+    }
+
+    /// The number of items the filter was sized for, if it was sized for a
+    /// number of items.
+    pub fn capacity(&self) -> Option<usize> {
+        self.header.item_count
+    }
+
+    /// The false positive probability the filter was sized for, if any.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.header.false_positive_prob
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.header.seed
+    }
+
+    /// The scheme items are hashed with.
+    pub fn hasher(&self) -> &HashScheme {
+        &self.header.scheme
+    }
+
+    /// The number of pages the cache holds at most.
+    pub fn cache_pages(&self) -> usize {
+        self.lock_cache().capacity
+    }
+
+    /// The number of pages read from the file so far, counting pages read
+    /// again after being evicted from the cache.
+    pub fn pages_read(&self) -> u64 {
+        self.pages_read.load(Ordering::Relaxed)
+    }
+
+    /// Returns bit `index`, from the cache or from the file.
+    fn get(&self, index: u64) -> Result<bool> {
+        let byte = index / 8;
+        let (page, offset) = (byte / PAGE_BYTES, (byte % PAGE_BYTES) as usize);
+        if let Some(bytes) = self.lock_cache().get(page) {
+            return Ok(bytes[offset] & (1 << (index % 8)) != 0);
+        }
+        // Read without holding the lock, so lookups of cached pages carry on
+        // meanwhile. Two threads may read the same page; the second copy
+        // simply replaces the first.
+        let start = page * PAGE_BYTES;
+        let mut bytes = vec![0; PAGE_BYTES.min(self.header.bit_vec_size.div_ceil(8) - start) as usize];
+        read_at(&self.file, &mut bytes, self.header.len() + start)?;
+        self.pages_read.fetch_add(1, Ordering::Relaxed);
+        let set = bytes[offset] & (1 << (index % 8)) != 0;
+        self.lock_cache().insert(page, bytes.into_boxed_slice());
+        Ok(set)
+    }
+
+    fn lock_cache(&self) -> MutexGuard<'_, PageCache> {
+        // The cache is only a copy of the file, so one left behind by a
+        // panicking thread is still good.
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The most recently used pages of a filter's bits.
+#[derive(Debug)]
+struct PageCache {
+    capacity: usize,
+    pages: HashMap<u64, CachedPage>,
+    // Incremented on every access, to order the pages by when they were
+    // last used.
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct CachedPage {
+    bytes: Box<[u8]>,
+    last_used: u64,
+}
+
+impl PageCache {
+    fn new(capacity: usize) -> PageCache {
+        PageCache { capacity, pages: HashMap::new(), clock: 0 }
+    }
+
+    fn get(&mut self, page: u64) -> Option<&[u8]> {
+        self.clock += 1;
+        let cached = self.pages.get_mut(&page)?;
+        cached.last_used = self.clock;
+        Some(&cached.bytes)
+    }
+
+    /// Caches `page`, evicting the least recently used page if the cache is
+    /// full. The cache is small, so finding that page by scanning is cheap
+    /// next to the read that missed it.
+    fn insert(&mut self, page: u64, bytes: Box<[u8]>) {
+        if self.pages.len() == self.capacity && !self.pages.contains_key(&page) {
+            let oldest = self.pages.iter().min_by_key(|(_, cached)| cached.last_used).map(|(&page, _)| page);
+            if let Some(oldest) = oldest {
+                self.pages.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.pages.insert(page, CachedPage { bytes, last_used: self.clock });
+    }
+}
+
+/// Fills `bytes` from `file` at `offset`, without moving a shared cursor.
+#[cfg(unix)]
+fn read_at(file: &File, bytes: &mut [u8], offset: u64) -> Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(bytes, offset)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, mut bytes: &mut [u8], mut offset: u64) -> Result<()> {
+    use std::io;
+    use std::os::windows::fs::FileExt;
+    while !bytes.is_empty() {
+        match file.seek_read(bytes, offset)? {
+            0 => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            read => {
+                bytes = &mut bytes[read..];
+                offset += read as u64;
+            },
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use alloc::boxed::Box;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use core::slice;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "rayon")]
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelExtend, ParallelIterator};
+
+use crate::bit_vec::CacheLine;
+use crate::BloomFilter;
+
+impl<T: Hash, S: BuildHasher + Sync> BloomFilter<T, S> {
+    /// Records every item in `items`, hashing and inserting them on all of
+    /// rayon's threads at once.
+    ///
+    /// The threads set bits in the filter's own bit vector with atomic ORs,
+    /// so no per-thread copies of the filter are built or merged and the
+    /// filter needs no extra memory. The result is exactly the filter that
+    /// inserting the items one by one would give.
+    ///
+    /// ```
+    /// use bloom::BloomFilter;
+    ///
+    /// let lines: Vec<String> = (0..10_000).map(|i| format!("line {}", i)).collect();
+    /// let mut filter = BloomFilter::<String>::new(lines.len(), 0.01);
+    /// filter.par_insert_all(&lines);
+    /// assert!(filter.contains("line 42"));
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_insert_all<Q>(&mut self, items: &[Q])
+    where
+        T: Borrow<Q>,
+        Q: Hash + Sync,
+    {
+        self.par_insert_with(|filter, words| {
+            items.par_iter().for_each(|item| set_all(filter, words, item));
+        });
+    }
+
+    /// Runs `insert` with the filter and its bits as atomics, which it may
+    /// set from many threads, then puts the bits back.
+    pub(crate) fn par_insert_with<F>(&mut self, insert: F)
+    where
+        F: FnOnce(&BloomFilter<T, S>, &[AtomicU64]),
+    {
+        let lines = self.bit_vec_mut().take_lines();
+        let mut shared = SharedLines { filter: self, lines };
+        let len = 8 * shared.lines.len();
+        // SAFETY: `AtomicU64` has the size of a `u64` and an alignment the
+        // cache lines exceed, the lines are eight words with no padding, and
+        // the lines are borrowed exclusively until `insert` returns.
+        let words = unsafe { slice::from_raw_parts(shared.lines.as_mut_ptr() as *const AtomicU64, len) };
+        insert(shared.filter, words);
+    }
+}
+
+/// The filter's bits while they are shared between threads.
+///
+/// Dropping it puts the bits back in the filter, so the filter stays whole
+/// even if hashing an item panics.
+struct SharedLines<'a, T: Hash, S: BuildHasher> {
+    filter: &'a mut BloomFilter<T, S>,
+    lines: Box<[CacheLine]>,
+}
+
+impl<'a, T: Hash, S: BuildHasher> Drop for SharedLines<'a, T, S> {
+    fn drop(&mut self) {
+        let lines = mem::take(&mut self.lines);
+        self.filter.bit_vec_mut().restore_lines(lines);
+    }
+}
+
+/// Sets the bits `item` maps to in `words`.
+///
+/// Relaxed ordering is enough: joining the threads synchronizes them with
+/// the calling thread before the bits are read again.
+pub(crate) fn set_all<T, S, Q>(filter: &BloomFilter<T, S>, words: &[AtomicU64], item: &Q)
+where
+    T: Hash + Borrow<Q>,
+    S: BuildHasher,
+    Q: ?Sized + Hash,
+{
+    for index in filter.probes(item) {
+        words[(index / 64) as usize].fetch_or(1 << (index % 64), Ordering::Relaxed);
+    }
+}
+
+/// Inserts the items on all of rayon's threads, as
+/// [`par_insert_all`](BloomFilter::par_insert_all) does.
+#[cfg(feature = "rayon")]
+impl<T: Hash + Send, S: BuildHasher + Sync> ParallelExtend<T> for BloomFilter<T, S> {
+    fn par_extend<I: IntoParallelIterator<Item = T>>(&mut self, par_iter: I) {
+        self.par_insert_with(|filter, words| {
+            par_iter.into_par_iter().for_each(|item| set_all(filter, words, &item));
+        });
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, T: Hash + Sync + 'a, S: BuildHasher + Sync> ParallelExtend<&'a T> for BloomFilter<T, S> {
+    fn par_extend<I: IntoParallelIterator<Item = &'a T>>(&mut self, par_iter: I) {
+        self.par_insert_with(|filter, words| {
+            par_iter.into_par_iter().for_each(|item| set_all(filter, words, item));
+        });
+    }
+}
@@ -0,0 +1,148 @@
+//! Sizing math for planning filter capacity without building a filter.
+//!
+//! `n` is the number of items, `p` the false positive probability, `m` the
+//! number of bits and `k` the number of hash functions.
+
+use alloc::format;
+use alloc::string::ToString;
+use core::cmp;
+use core::f64::consts::{E, LN_2};
+
+use crate::{math, BloomError, CounterWidth, Result};
+
+/// The false positive probability used when none is configured.
+pub const DEFAULT_FALSE_POSITIVE_PROB: f64 = 0.01;
+
+/// The largest bit vector size a filter can have: the largest whole number of
+/// 64-bit words that fit in the address space, and at most 2^63 bits, so
+/// that the sum of two bit indices can't overflow a `u64`.
+///
+/// On 32-bit targets this is 2^34 bits, so filters aren't limited to
+/// `usize::MAX` bits there.
+pub const MAX_BITS: u64 = if isize::MAX as u64 >= 1 << 60 {
+    1 << 63
+} else {
+    (isize::MAX as u64 * 8) & !63
+};
+
+/// Filters with more bits than this use [wide hashes](crate::BloomFilterBuilder::wide_hashes)
+/// unless configured otherwise: 2^32 bits, or 512 MiB.
+pub const WIDE_HASH_THRESHOLD: u64 = 1 << 32;
+
+/// The number of bits needed to hold `n` items with false positive
+/// probability `p`: `m = -n ln(p) / ln(2)^2`.
+///
+/// The result is rounded up, so the filter is never smaller than the
+/// formula asks for, and clamped to `1..=MAX_BITS`.
+pub fn optimal_bits(n: usize, p: f64) -> u64 {
+    // The math is done in floating point so it can't overflow; the cast below
+    // is only reached once the value is known to be in range.
+    let bits = math::ceil(-(n as f64) * math::ln(p) / (LN_2 * LN_2));
+    if bits.is_nan() || bits < 1.0 {
+        1
+    } else if bits >= MAX_BITS as f64 {
+        MAX_BITS
+    } else {
+        bits as u64
+    }
+}
+
+/// The number of hash functions that minimizes the false positive
+/// probability of `m` bits holding `n` items: `k = m/n ln(2)`, rounded to
+/// the nearest integer and at least 1.
+pub fn optimal_hashes(m: u64, n: usize) -> usize {
+    let hashes = math::round(m as f64 / cmp::max(n, 1) as f64 * LN_2);
+    if hashes < 1.0 {
+        1
+    } else {
+        hashes as usize
+    }
+}
+
+/// The expected false positive probability of `m` bits and `k` hash
+/// functions holding `n` items: `(1 - e^(-kn/m))^k`.
+pub fn expected_fpr(m: u64, n: usize, k: usize) -> f64 {
+    if m == 0 {
+        return 1.0;
+    }
+    math::powf(1.0 - math::exp(-(k as f64) * n as f64 / m as f64), k as f64)
+}
+
+/// The expected false positive probability of a blocked filter of `m` bits
+/// in blocks of `block_bits` bits, with all `k` probes of an item in one
+/// block, holding `n` items.
+///
+/// The number of items landing in a block is approximately Poisson with mean
+/// `λ = n * block_bits / m`, and a block holding `i` items has false positive
+/// probability `expected_fpr(block_bits, i, k)`, so the result is the sum of
+/// those weighted by the Poisson probabilities (Putze et al., "Cache-,
+/// Hash- and Space-Efficient Bloom Filters"). Blocks that happen to be
+/// overloaded make this higher than [`expected_fpr`] for the same `m`.
+pub fn expected_blocked_fpr(m: u64, n: usize, k: usize, block_bits: u64) -> f64 {
+    if m == 0 || block_bits == 0 {
+        return 1.0;
+    }
+    let mean = n as f64 * block_bits as f64 / m as f64;
+    // Sum far enough past the mean that the remaining terms are negligible.
+    let terms = (mean + 10.0 * math::ceil(math::sqrt(mean)) + 10.0) as usize;
+    let mut weight = math::exp(-mean);
+    let mut fpr = 0.0;
+    for i in 0..terms {
+        fpr += weight * expected_fpr(block_bits, i, k);
+        weight *= mean / (i + 1) as f64;
+    }
+    fpr
+}
+
+/// An upper bound on the probability that any counter of a counting filter
+/// with `m` counters of the given width and `k` hash functions overflows
+/// while holding `n` items.
+///
+/// A counter overflows once it reaches `j = 2^bits`. Each of the `nk`
+/// increments lands on a given counter with probability `1/m`, so by the
+/// union bound over counters the probability is at most
+/// `m (e n k / (j m))^j` (Fan et al., "Summary Cache"). For a filter with the
+/// optimal `k`, 4-bit counters give `m * 1.37e-15`: overflow is negligible
+/// even for billions of counters.
+pub fn counter_overflow_prob(m: u64, n: usize, k: usize, width: CounterWidth) -> f64 {
+    if m == 0 {
+        return 1.0;
+    }
+    let j = f64::from(1u32 << width.bits());
+    let bound = m as f64 * math::powf(E * n as f64 * k as f64 / (j * m as f64), j);
+    if bound < 1.0 {
+        bound
+    } else {
+        1.0
+    }
+}
+
+/// Checks that `n` items at false positive probability `p` describe a
+/// filter that can actually be built.
+pub(crate) fn validate(n: usize, p: f64) -> Result<()> {
+    if n == 0 {
+        return Err(BloomError::InvalidParams("item count must be greater than 0".to_string()));
+    }
+    // Written so that NaN fails too.
+    if !(p > 0.0 && p < 1.0) {
+        return Err(BloomError::InvalidParams(format!(
+            "false positive probability must be strictly between 0 and 1 (got {})",
+            p
+        )));
+    }
+    Ok(())
+}
+
+/// Checks that a filter of `m` bits and `k` hash functions is usable.
+pub(crate) fn validate_layout(m: u64, k: usize) -> Result<()> {
+    if m == 0 {
+        return Err(BloomError::InvalidParams("bit vector size must be greater than 0".to_string()));
+    }
+    if m > MAX_BITS {
+        return Err(BloomError::Capacity(format!("bit vector size must be at most {} (got {})", MAX_BITS, m)));
+    }
+    if k == 0 {
+        return Err(BloomError::InvalidParams("hash count must be greater than 0".to_string()));
+    }
+    Ok(())
+}
@@ -0,0 +1,282 @@
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::bit_vec::BitVec;
+use crate::probe::Probes;
+use crate::{math, params, ApproximateMembership, DefaultBuildHasher, Result};
+
+/// A bloom filter whose bit vector is split into one partition per hash
+/// function, with probe `i` always landing in partition `i`.
+///
+/// Every item sets exactly one bit in each partition, so the partitions
+/// fill evenly and an item's probes never collide with each other. The
+/// false positive probability is the product of the partitions' fill
+/// ratios, which is marginally higher than a [`BloomFilter`](crate::BloomFilter)
+/// of the same size but much more predictable. Partitions start on 64-bit
+/// word boundaries, so they can be probed, counted or merged independently.
+///
+/// ```
+/// use bloom::PartitionedBloomFilter;
+///
+/// let mut filter = PartitionedBloomFilter::<String>::new(1000, 0.01);
+/// filter.add("apple");
+/// assert!(filter.contains("apple"));
+/// ```
+#[derive(Debug)]
+pub struct PartitionedBloomFilter<T, S = DefaultBuildHasher> {
+    bit_vec: BitVec,
+    partition_size: u64,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    hash_count: usize,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for PartitionedBloomFilter<T, S> {
+    fn clone(&self) -> PartitionedBloomFilter<T, S> {
+        PartitionedBloomFilter {
+            bit_vec: self.bit_vec.clone(),
+            partition_size: self.partition_size,
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            hash_count: self.hash_count,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> PartitionedBloomFilter<T> {
+    /// Creates a filter sized to hold `item_count` items with the given
+    /// false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0 or `false_positive_prob` is not strictly
+    /// between 0 and 1.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> PartitionedBloomFilter<T> {
+        PartitionedBloomFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](PartitionedBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> PartitionedBloomFilter<T> {
+        let mut filter = PartitionedBloomFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter with at least `bits` bits split between `hashes`
+    /// partitions.
+    ///
+    /// Each partition is rounded up to a whole number of 64-bit words, so
+    /// the filter may be slightly larger than asked for.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either is 0, or if `bits` is more than
+    /// [`MAX_BITS`](params::MAX_BITS).
+    pub fn from_params(bits: u64, hashes: usize) -> PartitionedBloomFilter<T> {
+        PartitionedBloomFilter::from_params_with_hasher(bits, hashes, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> PartitionedBloomFilter<T, S> {
+    /// Like [`new`](PartitionedBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> PartitionedBloomFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let bits = params::optimal_bits(item_count, false_positive_prob);
+        let hash_count = params::optimal_hashes(bits, item_count);
+        let mut filter = PartitionedBloomFilter::from_params_with_hasher(bits, hash_count, hash_builder);
+        filter.item_count = Some(item_count);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](PartitionedBloomFilter::from_params), but hashes
+    /// items with `hash_builder`.
+    pub fn from_params_with_hasher(bits: u64, hashes: usize, hash_builder: S) -> PartitionedBloomFilter<T, S> {
+        if let Err(e) = params::validate_layout(bits, hashes) {
+            panic!("{}", e);
+        }
+        let partition_size = bits.div_ceil(hashes as u64).next_multiple_of(64);
+        let bit_vec_size = partition_size.saturating_mul(hashes as u64);
+        if let Err(e) = params::validate_layout(bit_vec_size, hashes) {
+            panic!("{}", e);
+        }
+        PartitionedBloomFilter {
+            bit_vec: BitVec::new(bit_vec_size),
+            partition_size,
+            item_count: None,
+            false_positive_prob: None,
+            hash_count: hashes,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item` in the filter.
+    pub fn add<Q: ?Sized + Hash>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+    {
+        self.insert(item);
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let mut present = true;
+        for index in self.probes(item) {
+            if !self.bit_vec.set(index) {
+                present = false;
+            }
+        }
+        present
+    }
+
+    /// Returns `true` if `item` has probably been added, and `false` if it
+    /// definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.probes(item).all(|index| self.bit_vec.get(index))
+    }
+
+    /// Removes every item from the filter, keeping its allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        self.bit_vec.clear();
+    }
+
+    /// Returns `true` if nothing has been added since the filter was created
+    /// or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.bit_vec.none()
+    }
+
+    /// The number of bits that are set.
+    pub fn count_ones(&self) -> u64 {
+        self.bit_vec.count_ones()
+    }
+
+    /// The number of bits set in each partition.
+    pub fn partition_ones(&self) -> impl Iterator<Item = u64> + '_ {
+        self.bit_vec
+            .words()
+            .chunks((self.partition_size / 64) as usize)
+            .map(|words| words.iter().map(|word| u64::from(word.count_ones())).sum())
+    }
+
+    /// Estimates the false positive probability of the filter as it is now:
+    /// the product of the partitions' fill ratios.
+    pub fn current_fpr(&self) -> f64 {
+        let size = self.partition_size as f64;
+        self.partition_ones().map(|ones| ones as f64 / size).product()
+    }
+
+    /// Estimates how many distinct items have been added, from the mean
+    /// number `X` of bits set per partition: `-m ln(1 - X/m)`, where `m` is
+    /// the partition size.
+    ///
+    /// Returns infinity once every bit is set.
+    pub fn estimated_len(&self) -> f64 {
+        let size = self.partition_size as f64;
+        let mean = self.count_ones() as f64 / self.hash_count as f64;
+        -size * math::ln(1.0 - mean / size)
+    }
+
+    /// The number of bits in the filter.
+    pub fn bit_vec_size(&self) -> u64 {
+        self.bit_vec.len()
+    }
+
+    /// The number of bits in each partition.
+    pub fn partition_size(&self) -> u64 {
+        self.partition_size
+    }
+
+    /// The number of hash functions, which is also the number of partitions.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The false positive probability the filter was sized for, or `None`
+    /// if it was built from explicit parameters.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The number of items the filter was sized for, or `None` if it was
+    /// built from explicit parameters.
+    pub fn capacity(&self) -> Option<usize> {
+        self.item_count
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The bit indices `item` maps to: probe `i` is offset into partition
+    /// `i`.
+    fn probes<Q: ?Sized + Hash>(&self, item: &Q) -> impl Iterator<Item = u64> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let size = self.partition_size;
+        Probes::new(hasher, size, self.hash_count, size > params::WIDE_HASH_THRESHOLD)
+            .zip(0..)
+            .map(move |(index, partition)| partition * size + index)
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for PartitionedBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(PartitionedBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        PartitionedBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        PartitionedBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        PartitionedBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + self.bit_vec.heap_bytes()
+    }
+}
@@ -0,0 +1,258 @@
+use core::cmp;
+use core::hash::{BuildHasher, Hasher};
+use core::mem;
+
+use crate::bit_vec::BitVec;
+use crate::probe::Probes;
+use crate::{math, params, DefaultBuildHasher};
+
+/// Written before a key's prefix when it is hashed, so that the prefix and
+/// the whole key map to different bits even when they are the same bytes.
+const PREFIX_TAG: u8 = 0;
+const WHOLE_KEY_TAG: u8 = 1;
+
+/// A bloom filter over byte-string keys that also records each key's first
+/// `prefix_len` bytes, like RocksDB's fixed-length prefix extractor, so that
+/// a range scan can ask whether any key with a given prefix exists before
+/// touching storage.
+///
+/// Keys are hashed as raw bytes, as by
+/// [`BloomFilter::insert_bytes`](crate::BloomFilter::insert_bytes). A key
+/// shorter than `prefix_len` is its own prefix. Prefix lookups must be at
+/// least `prefix_len` bytes long: a longer prefix is checked by its first
+/// `prefix_len` bytes, which every key starting with it shares, and a
+/// shorter one can't be ruled out at all, so it is always reported
+/// possibly present.
+///
+/// By default both prefixes and whole keys are recorded, so point lookups
+/// are answered as precisely as by a plain filter; a filter made with
+/// [`prefixes_only`](PrefixBloomFilter::prefixes_only) records just the
+/// prefixes, in about half the space, and answers point lookups by the
+/// key's prefix.
+///
+/// ```
+/// use bloom::PrefixBloomFilter;
+///
+/// // Keys are "user:NNNN:field", and scans are per user.
+/// let mut filter = PrefixBloomFilter::new(9, 1000, 0.01);
+/// filter.insert("user:0042:name");
+/// filter.insert("user:0042:email");
+/// assert!(filter.contains_prefix("user:0042"));
+/// assert!(filter.contains_prefix("user:0042:em"));
+/// assert!(filter.contains("user:0042:email"));
+/// assert!(!filter.contains_prefix("user:0043"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct PrefixBloomFilter<S = DefaultBuildHasher> {
+    bit_vec: BitVec,
+    prefix_len: usize,
+    whole_keys: bool,
+    item_count: usize,
+    false_positive_prob: f64,
+    hash_count: usize,
+    seed: u64,
+    hash_builder: S,
+}
+
+impl PrefixBloomFilter {
+    /// Creates a filter recording the first `prefix_len` bytes and the whole
+    /// of up to `item_count` keys with the given false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `prefix_len` or `item_count` is 0, or if
+    /// `false_positive_prob` is not strictly between 0 and 1.
+    pub fn new(prefix_len: usize, item_count: usize, false_positive_prob: f64) -> PrefixBloomFilter {
+        PrefixBloomFilter::with_hasher(prefix_len, item_count, false_positive_prob, true, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](PrefixBloomFilter::new), but records only the keys'
+    /// prefixes.
+    pub fn prefixes_only(prefix_len: usize, item_count: usize, false_positive_prob: f64) -> PrefixBloomFilter {
+        let hash_builder = DefaultBuildHasher::default();
+        PrefixBloomFilter::with_hasher(prefix_len, item_count, false_positive_prob, false, hash_builder)
+    }
+
+    /// Like [`new`](PrefixBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(prefix_len: usize, item_count: usize, false_positive_prob: f64, seed: u64) -> PrefixBloomFilter {
+        let mut filter = PrefixBloomFilter::new(prefix_len, item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+}
+
+impl<S: BuildHasher> PrefixBloomFilter<S> {
+    /// Creates a filter like [`new`](PrefixBloomFilter::new), or like
+    /// [`prefixes_only`](PrefixBloomFilter::prefixes_only) unless
+    /// `whole_keys` is set, that hashes keys with `hash_builder`.
+    ///
+    /// Every key may add a distinct prefix, so a filter of whole keys is
+    /// sized for twice `item_count` entries.
+    pub fn with_hasher(
+        prefix_len: usize,
+        item_count: usize,
+        false_positive_prob: f64,
+        whole_keys: bool,
+        hash_builder: S,
+    ) -> PrefixBloomFilter<S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        assert!(prefix_len > 0, "the prefix length must be nonzero");
+        let entries = if whole_keys { item_count.saturating_mul(2) } else { item_count };
+        let bits = params::optimal_bits(entries, false_positive_prob);
+        let hash_count = params::optimal_hashes(bits, entries);
+        if let Err(e) = params::validate_layout(bits, hash_count) {
+            panic!("{}", e);
+        }
+        PrefixBloomFilter {
+            bit_vec: BitVec::new(bits),
+            prefix_len,
+            whole_keys,
+            item_count,
+            false_positive_prob,
+            hash_count,
+            seed: 0,
+            hash_builder,
+        }
+    }
+
+    /// Records `key` and its prefix, returning `true` if the key was
+    /// probably already present: by its whole bytes, or if only prefixes
+    /// are recorded, by its prefix.
+    pub fn insert<K: ?Sized + AsRef<[u8]>>(&mut self, key: &K) -> bool {
+        let key = key.as_ref();
+        let prefix_present = self.set_all(PREFIX_TAG, self.prefix(key));
+        if self.whole_keys {
+            self.set_all(WHOLE_KEY_TAG, key)
+        } else {
+            prefix_present
+        }
+    }
+
+    /// Returns `true` if `key` has probably been added, and `false` if it
+    /// definitely has not. If only prefixes are recorded, any key sharing
+    /// a prefix with one that was added is reported present.
+    pub fn contains<K: ?Sized + AsRef<[u8]>>(&self, key: &K) -> bool {
+        let key = key.as_ref();
+        if self.whole_keys {
+            self.all_set(WHOLE_KEY_TAG, key)
+        } else {
+            self.all_set(PREFIX_TAG, self.prefix(key))
+        }
+    }
+
+    /// Returns `true` if a key starting with `prefix` has probably been
+    /// added, and `false` if none definitely has.
+    ///
+    /// Prefixes shorter than the filter's prefix length always return
+    /// `true`, since the filter has nothing to check them against.
+    pub fn contains_prefix<K: ?Sized + AsRef<[u8]>>(&self, prefix: &K) -> bool {
+        let prefix = prefix.as_ref();
+        prefix.len() < self.prefix_len || self.all_set(PREFIX_TAG, &prefix[..self.prefix_len])
+    }
+
+    /// Removes every key from the filter, keeping its allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        self.bit_vec.clear();
+    }
+
+    /// Returns `true` if nothing has been added since the filter was created
+    /// or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.bit_vec.none()
+    }
+
+    /// The number of bits that are set.
+    pub fn count_ones(&self) -> u64 {
+        self.bit_vec.count_ones()
+    }
+
+    /// The fraction of bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.bit_vec.len() as f64
+    }
+
+    /// Estimates the probability that a lookup of an absent key or prefix
+    /// returns `true`, as the filter is now.
+    pub fn current_fpr(&self) -> f64 {
+        math::powf(self.fill_ratio(), self.hash_count as f64)
+    }
+
+    /// The number of bytes of every key recorded as its prefix.
+    pub fn prefix_len(&self) -> usize {
+        self.prefix_len
+    }
+
+    /// Returns `true` if whole keys are recorded as well as prefixes.
+    pub fn whole_keys(&self) -> bool {
+        self.whole_keys
+    }
+
+    /// The number of bits in the filter.
+    pub fn bit_vec_size(&self) -> u64 {
+        self.bit_vec.len()
+    }
+
+    /// The number of hash functions.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The false positive probability the filter was sized for.
+    pub fn false_positive_prob(&self) -> f64 {
+        self.false_positive_prob
+    }
+
+    /// The number of keys the filter was sized for.
+    pub fn capacity(&self) -> usize {
+        self.item_count
+    }
+
+    /// The number of bytes of memory the filter occupies, including its
+    /// bits.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + self.bit_vec.heap_bytes()
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher keys are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    fn prefix<'a>(&self, key: &'a [u8]) -> &'a [u8] {
+        &key[..cmp::min(key.len(), self.prefix_len)]
+    }
+
+    /// Sets the bits of `bytes` hashed after `tag`, returning whether they
+    /// were all set already.
+    fn set_all(&mut self, tag: u8, bytes: &[u8]) -> bool {
+        let mut present = true;
+        for index in self.probes(tag, bytes) {
+            if !self.bit_vec.set(index) {
+                present = false;
+            }
+        }
+        present
+    }
+
+    fn all_set(&self, tag: u8, bytes: &[u8]) -> bool {
+        self.probes(tag, bytes).all(|index| self.bit_vec.get(index))
+    }
+
+    fn probes(&self, tag: u8, bytes: &[u8]) -> Probes<S::Hasher> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        hasher.write_u8(tag);
+        hasher.write(bytes);
+        let size = self.bit_vec.len();
+        Probes::new(hasher, size, self.hash_count, size > params::WIDE_HASH_THRESHOLD)
+    }
+}
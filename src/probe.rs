@@ -0,0 +1,153 @@
+use core::cmp;
+use core::hash::Hasher;
+
+/// The bit indices an item maps to, derived by enhanced double hashing
+/// (Dillinger and Manolios, "Fast and Accurate Bitstate Verification for
+/// SPIN").
+///
+/// Two hashes are mapped onto `0..m` separately, as `x` and `y`. Probe `i` is
+/// `x`, after which `y` is added to `x` and then `i + 1` to `y`, all modulo
+/// `m`. When `m` is a power of two the mapping is a mask of the low bits;
+/// otherwise it is [`reduce`]. Unlike plain double hashing, `h1 + i * h2`,
+/// this never crowds an item's probes into a run of neighbouring bits.
+///
+/// Double hashing of any kind fixes an item's probes by `x` and `y`, so
+/// there are only `m^2` patterns of probes, and a lookup whose pattern
+/// matches an added item's is a false positive however few bits are set.
+/// With `n` items that puts a floor of about `n / m^2` under the false
+/// positive rate, far above the target in a small filter with a low one. So
+/// the probes are taken in groups of [`group_len`] for `m`, each group from a
+/// fresh `x` and `y`: large filters take all their probes from one group and
+/// hash the item just once, and the floor stays far below the target.
+///
+/// Wide probes map 128 bits of hash onto `0..m` rather than 64, which
+/// matters once `m` is large enough for the bias of a 64-bit reduction to
+/// show.
+pub(crate) struct Probes<H> {
+    hasher: H,
+    index: u64,
+    step: u64,
+    increment: u64,
+    bit_vec_size: u64,
+    wide_hashes: bool,
+    group_len: usize,
+    // Probes left in the current group.
+    group_remaining: usize,
+    remaining: usize,
+}
+
+impl<H: Hasher> Probes<H> {
+    /// Derives probes from a hasher that the item has been written to.
+    ///
+    /// The item is only fed to the hasher once: further hashes come from
+    /// finishing the hasher again after writing one more byte each time.
+    pub(crate) fn new(hasher: H, bit_vec_size: u64, hash_count: usize, wide_hashes: bool) -> Probes<H> {
+        let mut probes = Probes {
+            hasher,
+            index: 0,
+            step: 0,
+            increment: 0,
+            bit_vec_size,
+            wide_hashes,
+            group_len: group_len(bit_vec_size),
+            group_remaining: 0,
+            remaining: hash_count,
+        };
+        probes.start_group();
+        probes
+    }
+
+    /// Starts a group of probes from the hasher's next two outputs.
+    fn start_group(&mut self) {
+        let h1 = self.hasher.finish();
+        self.hasher.write_u8(0xff);
+        let h2 = self.hasher.finish();
+        let m = self.bit_vec_size;
+        let (index, step) = if self.wide_hashes {
+            self.hasher.write_u8(0xfe);
+            let low1 = self.hasher.finish();
+            self.hasher.write_u8(0xfd);
+            let low2 = self.hasher.finish();
+            (reduce_wide(h1, low1, m), reduce_wide(h2, low2, m))
+        } else if m.is_power_of_two() {
+            (h1 & (m - 1), h2 & (m - 1))
+        } else {
+            (reduce(h1, m), reduce(h2, m))
+        };
+        self.index = index;
+        self.step = step;
+        self.increment = 1;
+        self.group_remaining = self.group_len;
+    }
+}
+
+impl<H: Hasher> Iterator for Probes<H> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.remaining == 0 {
+            return None;
+        }
+        if self.group_remaining == 0 {
+            // So that the group doesn't start from the previous group's
+            // hashes.
+            self.hasher.write_u8(0xfc);
+            self.start_group();
+        }
+        self.remaining -= 1;
+        self.group_remaining -= 1;
+        let index = self.index;
+        // Each term is below `m`, which is at most `MAX_BITS`, or 2^63, so
+        // the sums can't overflow and one subtraction brings them back into
+        // range.
+        self.index = wrap(self.index + self.step, self.bit_vec_size);
+        self.step = wrap(self.step + self.increment, self.bit_vec_size);
+        self.increment = wrap(self.increment + 1, self.bit_vec_size);
+        Some(index)
+    }
+}
+
+/// The number of probes taken from each `x` and `y` in a filter of `m`
+/// bits: `log2(m) - 8`, and at least 1.
+///
+/// The optimal filter for `k` hashes has a false positive rate of about
+/// `2^-k`, and `m / k ln 2` items. A group of `g` probes sees a pattern
+/// collision about `ln 2 / (g m)` of the time, against `2^-g` for a group
+/// whose bits are set by chance, so with `2^g` at most `m / 256` collisions
+/// add well under 1% to the rate.
+fn group_len(m: u64) -> usize {
+    cmp::max(63 - m.leading_zeros() as usize, 9) - 8
+}
+
+/// Reduces `value`, which is below `2 * m`, modulo `m`.
+#[inline]
+fn wrap(value: u64, m: u64) -> u64 {
+    if value >= m {
+        value - m
+    } else {
+        value
+    }
+}
+
+/// Maps `hash` uniformly onto `0..range` with a multiply and shift rather
+/// than a division (Lemire's "fastrange"): the result is the high 64 bits of
+/// `hash * range`.
+///
+/// Unlike `hash % range` this has no bias towards low indices when `range`
+/// isn't a power of two. It relies on the high bits of `hash` being well
+/// mixed, which is true of any reasonable hasher's output.
+#[inline]
+pub(crate) fn reduce(hash: u64, range: u64) -> u64 {
+    ((u128::from(hash) * u128::from(range)) >> 64) as u64
+}
+
+/// Like [`reduce`], but maps the 128-bit hash `high << 64 | low` onto
+/// `0..range`, or masks `low` if `range` is a power of two.
+#[inline]
+fn reduce_wide(high: u64, low: u64, range: u64) -> u64 {
+    if range.is_power_of_two() {
+        return low & (range - 1);
+    }
+    let carry = (u128::from(low) * u128::from(range)) >> 64;
+    ((u128::from(high) * u128::from(range) + carry) >> 64) as u64
+}
@@ -0,0 +1,793 @@
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::hash::{read_le, xxh64, HashScheme, KEYED_ID};
+use crate::packed::PackedVec;
+use crate::{math, params, ApproximateMembership, BloomError, DefaultBuildHasher, Removable, Result};
+
+/// Set on slot `q` if some stored fingerprint has quotient `q`.
+const OCCUPIED: u64 = 1;
+/// Set if the slot's entry belongs to the same run as the previous slot's.
+const CONTINUATION: u64 = 2;
+/// Set if the slot's entry isn't in its quotient's slot.
+const SHIFTED: u64 = 4;
+const METADATA_BITS: u32 = 3;
+
+/// The fraction of slots a filter is sized to fill.
+const TARGET_LOAD: f64 = 0.75;
+
+/// The fraction of slots past which a filter doubles its table.
+pub(crate) const MAX_LOAD: f64 = 0.95;
+
+const MAGIC: [u8; 4] = *b"BLMQ";
+const FORMAT_VERSION: u16 = 1;
+const HEADER_LEN: usize = 44;
+
+/// The table shared by the quotient filters: `2^q` slots, each holding three
+/// metadata bits and a payload.
+///
+/// Entries are grouped into runs by quotient, each run sorted by payload,
+/// and a run starts at its quotient's slot or as soon after it as earlier
+/// runs allow (Bender et al., "Don't Thrash: How to Cache Your Hash on
+/// Flash"). A slot is empty exactly when all of its bits are zero: an entry
+/// that hasn't been shifted is in its quotient's slot, whose occupied bit
+/// is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct QuotientTable {
+    slots: PackedVec,
+    quotient_bits: u32,
+    payload_bits: u32,
+    len: u64,
+}
+
+impl QuotientTable {
+    pub(crate) fn new(quotient_bits: u32, payload_bits: u32) -> Result<QuotientTable> {
+        if quotient_bits == 0 || quotient_bits >= 64 || payload_bits == 0 || payload_bits > 64 - METADATA_BITS {
+            return Err(BloomError::InvalidParams(format!(
+                "a quotient table needs 1 to 63 quotient bits and 1 to {} payload bits (got {} and {})",
+                64 - METADATA_BITS,
+                quotient_bits,
+                payload_bits
+            )));
+        }
+        Ok(QuotientTable {
+            slots: PackedVec::new(1 << quotient_bits, payload_bits + METADATA_BITS)?,
+            quotient_bits,
+            payload_bits,
+            len: 0,
+        })
+    }
+
+    pub(crate) fn quotient_bits(&self) -> u32 {
+        self.quotient_bits
+    }
+
+    pub(crate) fn payload_bits(&self) -> u32 {
+        self.payload_bits
+    }
+
+    pub(crate) fn size(&self) -> u64 {
+        self.slots.len()
+    }
+
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub(crate) fn words(&self) -> &[u64] {
+        self.slots.words()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.slots.clear();
+        self.len = 0;
+    }
+
+    pub(crate) fn payload(&self, slot: u64) -> u64 {
+        self.slots.get(slot) >> METADATA_BITS
+    }
+
+    /// Replaces the payload of the entry in `slot`.
+    pub(crate) fn set_payload(&mut self, slot: u64, payload: u64) {
+        let metadata = self.slots.get(slot) & (OCCUPIED | CONTINUATION | SHIFTED);
+        self.slots.set(slot, metadata | payload << METADATA_BITS);
+    }
+
+    /// The slots and payloads of the entries in `quotient`'s run.
+    pub(crate) fn run(&self, quotient: u64) -> Run<'_> {
+        Run {
+            table: self,
+            slot: if self.has(quotient, OCCUPIED) { Some(self.run_start(quotient)) } else { None },
+        }
+    }
+
+    /// The slot of the first entry in `quotient`'s run satisfying `pred`.
+    pub(crate) fn find<P: Fn(u64) -> bool>(&self, quotient: u64, pred: P) -> Option<u64> {
+        self.run(quotient).find(|&(_, payload)| pred(payload)).map(|(slot, _)| slot)
+    }
+
+    /// Adds an entry to `quotient`'s run, before the first entry with a
+    /// larger payload.
+    pub(crate) fn insert(&mut self, quotient: u64, payload: u64) -> Result<()> {
+        if self.len == self.size() {
+            return Err(BloomError::Capacity(format!("all {} slots are full", self.size())));
+        }
+        self.len += 1;
+        if self.slots.get(quotient) == 0 {
+            self.slots.set(quotient, OCCUPIED | payload << METADATA_BITS);
+            return Ok(());
+        }
+        let was_occupied = self.has(quotient, OCCUPIED);
+        self.slots.set(quotient, self.slots.get(quotient) | OCCUPIED);
+        let start = self.run_start(quotient);
+        let mut slot = start;
+        if was_occupied {
+            loop {
+                if self.payload(slot) > payload {
+                    break;
+                }
+                slot = self.next(slot);
+                if !self.has(slot, CONTINUATION) {
+                    break;
+                }
+            }
+        }
+        // An entry inserted at the head of an existing run pushes the old
+        // head along, which becomes a continuation.
+        let mut demote_head = was_occupied && slot == start;
+        let mut entry = payload << METADATA_BITS;
+        if was_occupied && slot != start {
+            entry |= CONTINUATION;
+        }
+        if slot != quotient {
+            entry |= SHIFTED;
+        }
+        // Shift entries along until one lands in an empty slot. Occupied bits
+        // stay where they are.
+        loop {
+            let old = self.slots.get(slot);
+            self.slots.set(slot, old & OCCUPIED | entry);
+            if old == 0 {
+                return Ok(());
+            }
+            entry = old & !OCCUPIED | SHIFTED;
+            if demote_head {
+                entry |= CONTINUATION;
+                demote_head = false;
+            }
+            slot = self.next(slot);
+        }
+    }
+
+    /// Removes the entry in `slot`, which is in `quotient`'s run.
+    pub(crate) fn remove(&mut self, quotient: u64, slot: u64) {
+        let was_head = !self.has(slot, CONTINUATION);
+        let was_only = was_head && !self.has(self.next(slot), CONTINUATION);
+        // Shift the rest of the cluster back by one, stopping at an empty
+        // slot or an entry already in its quotient's slot.
+        let mut run_quotient = quotient;
+        let mut current = slot;
+        loop {
+            let next = self.next(current);
+            let moved = self.slots.get(next);
+            if moved == 0 || moved & SHIFTED == 0 {
+                self.slots.set(current, self.slots.get(current) & OCCUPIED);
+                break;
+            }
+            let mut continuation = moved & CONTINUATION != 0;
+            if !continuation {
+                run_quotient = self.next_occupied(run_quotient);
+            } else if current == slot && was_head {
+                // The next entry in the run becomes its head.
+                continuation = false;
+            }
+            let mut entry = self.slots.get(current) & OCCUPIED | moved >> METADATA_BITS << METADATA_BITS;
+            if continuation {
+                entry |= CONTINUATION;
+            }
+            if current != run_quotient {
+                entry |= SHIFTED;
+            }
+            self.slots.set(current, entry);
+            current = next;
+        }
+        if was_only {
+            self.slots.set(quotient, self.slots.get(quotient) & !OCCUPIED);
+        }
+        self.len -= 1;
+    }
+
+    /// Calls `f` with the quotient and payload of every entry.
+    pub(crate) fn for_each<F: FnMut(u64, u64)>(&self, mut f: F) {
+        // Start at the head of a cluster, so quotients can be tracked from
+        // there around the whole table.
+        let start = match (0..self.size()).find(|&slot| self.slots.get(slot) != 0 && !self.has(slot, SHIFTED)) {
+            Some(start) => start,
+            None => return,
+        };
+        let mut quotient = start;
+        for step in 0..self.size() {
+            let slot = (start + step) & (self.size() - 1);
+            let value = self.slots.get(slot);
+            if value == 0 {
+                continue;
+            }
+            if value & SHIFTED == 0 {
+                quotient = slot;
+            } else if value & CONTINUATION == 0 {
+                quotient = self.next_occupied(quotient);
+            }
+            f(quotient, value >> METADATA_BITS);
+        }
+    }
+
+    /// A table with twice the slots, holding the same entries with the top
+    /// bit of each payload moved to the bottom of its quotient.
+    pub(crate) fn doubled(&self) -> Result<QuotientTable> {
+        let payload_bits = self.payload_bits - 1;
+        let mut table = QuotientTable::new(self.quotient_bits + 1, payload_bits)?;
+        let mut result = Ok(());
+        self.for_each(|quotient, payload| {
+            let quotient = quotient << 1 | payload >> payload_bits;
+            if let Err(e) = table.insert(quotient, payload & mask(payload_bits)) {
+                result = Err(e);
+            }
+        });
+        result.map(|()| table)
+    }
+
+    /// Rebuilds the table from its words and entry count, as returned by
+    /// [`words`](QuotientTable::words) and [`len`](QuotientTable::len).
+    pub(crate) fn from_words(quotient_bits: u32, payload_bits: u32, words: Vec<u64>, len: u64) -> Result<QuotientTable> {
+        let mut table = QuotientTable::new(quotient_bits, payload_bits)?;
+        if words.len() != table.words().len() || len > table.size() {
+            return Err(BloomError::CorruptFile("wrong table length".to_string()));
+        }
+        let width = payload_bits + METADATA_BITS;
+        table.slots = PackedVec::from_words(words.into_boxed_slice(), table.size(), width);
+        table.len = len;
+        Ok(table)
+    }
+
+    fn has(&self, slot: u64, flag: u64) -> bool {
+        self.slots.get(slot) & flag != 0
+    }
+
+    fn next(&self, slot: u64) -> u64 {
+        (slot + 1) & (self.size() - 1)
+    }
+
+    fn prev(&self, slot: u64) -> u64 {
+        slot.wrapping_sub(1) & (self.size() - 1)
+    }
+
+    /// The first occupied quotient after `quotient`.
+    fn next_occupied(&self, quotient: u64) -> u64 {
+        let mut quotient = self.next(quotient);
+        while !self.has(quotient, OCCUPIED) {
+            quotient = self.next(quotient);
+        }
+        quotient
+    }
+
+    /// The slot where `quotient`'s run starts, or would start.
+    ///
+    /// Walks back to the head of the cluster, then forward over one run for
+    /// each occupied quotient until reaching `quotient`.
+    fn run_start(&self, quotient: u64) -> u64 {
+        let mut canonical = quotient;
+        while self.has(canonical, SHIFTED) {
+            canonical = self.prev(canonical);
+        }
+        let mut slot = canonical;
+        while canonical != quotient {
+            loop {
+                slot = self.next(slot);
+                if !self.has(slot, CONTINUATION) {
+                    break;
+                }
+            }
+            loop {
+                canonical = self.next(canonical);
+                if self.has(canonical, OCCUPIED) {
+                    break;
+                }
+            }
+        }
+        slot
+    }
+}
+
+/// An iterator over a run of a [`QuotientTable`], from
+/// [`QuotientTable::run`].
+pub(crate) struct Run<'a> {
+    table: &'a QuotientTable,
+    slot: Option<u64>,
+}
+
+impl Iterator for Run<'_> {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<(u64, u64)> {
+        let slot = self.slot?;
+        let next = self.table.next(slot);
+        self.slot = if self.table.has(next, CONTINUATION) { Some(next) } else { None };
+        Some((slot, self.table.payload(slot)))
+    }
+}
+
+/// A quotient filter, which stores a fingerprint of each item in a compact
+/// hash table (Bender et al., "Don't Thrash: How to Cache Your Hash on
+/// Flash").
+///
+/// Each fingerprint is split into a quotient, which picks one of `2^q`
+/// slots, and a remainder of `r` bits, which is what's stored. Remainders
+/// with the same quotient are kept together in a run near their slot, with
+/// three bits per slot to find runs again, so a lookup reads a few adjacent
+/// slots.
+///
+/// Because the table holds the fingerprints themselves, rather than bits
+/// they have set, it can do what a bloom filter can't:
+///
+/// - Items can be [removed](QuotientFilter::remove).
+/// - Filters with fingerprints of the same length can be [merged](QuotientFilter::try_union)
+///   even if their tables are different sizes.
+/// - The table [doubles](QuotientFilter::grow) by moving one bit from each
+///   remainder to its quotient, which it does automatically when nearly
+///   full. Each doubling doubles the false positive probability.
+///
+/// As with a [`CountingBloomFilter`](crate::CountingBloomFilter), adding an
+/// item twice stores it twice, and only items that were added should be
+/// removed.
+///
+/// ```
+/// use bloom::QuotientFilter;
+///
+/// let mut filter = QuotientFilter::<String>::new(1000, 0.01);
+/// filter.insert("apple").unwrap();
+/// assert!(filter.contains("apple"));
+/// filter.remove("apple");
+/// assert!(!filter.contains("apple"));
+/// ```
+#[derive(Debug)]
+pub struct QuotientFilter<T, S = DefaultBuildHasher> {
+    table: QuotientTable,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for QuotientFilter<T, S> {
+    fn clone(&self) -> QuotientFilter<T, S> {
+        QuotientFilter {
+            table: self.table.clone(),
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> QuotientFilter<T> {
+    /// Creates a filter sized to hold `item_count` items with the given
+    /// false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0 or `false_positive_prob` is not strictly
+    /// between 0 and 1, or if the table can't be addressed.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> QuotientFilter<T> {
+        QuotientFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](QuotientFilter::new), but mixes `seed` into every hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> QuotientFilter<T> {
+        let mut filter = QuotientFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter with `2^quotient_bits` slots and remainders of
+    /// `remainder_bits` bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either is 0 or their sum is more than 64, if
+    /// `remainder_bits` is more than 61, or if the table can't be addressed.
+    pub fn from_params(quotient_bits: u32, remainder_bits: u32) -> QuotientFilter<T> {
+        QuotientFilter::from_params_with_hasher(quotient_bits, remainder_bits, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> QuotientFilter<T, S> {
+    /// Like [`new`](QuotientFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> QuotientFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let (quotient_bits, remainder_bits) = layout(item_count, false_positive_prob);
+        let mut filter = QuotientFilter::from_params_with_hasher(quotient_bits, remainder_bits, hash_builder);
+        filter.item_count = Some(item_count);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](QuotientFilter::from_params), but hashes items
+    /// with `hash_builder`.
+    pub fn from_params_with_hasher(quotient_bits: u32, remainder_bits: u32, hash_builder: S) -> QuotientFilter<T, S> {
+        let table = if quotient_bits + remainder_bits > 64 {
+            Err(BloomError::InvalidParams(format!(
+                "fingerprints can be at most 64 bits (got {})",
+                quotient_bits + remainder_bits
+            )))
+        } else {
+            QuotientTable::new(quotient_bits, remainder_bits)
+        };
+        let table = match table {
+            Ok(table) => table,
+            Err(e) => panic!("{}", e),
+        };
+        QuotientFilter {
+            table,
+            item_count: None,
+            false_positive_prob: None,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present.
+    ///
+    /// Once more than 95% of the slots are full the table is first
+    /// [grown](QuotientFilter::grow).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::Capacity`] if the table is full and can't grow,
+    /// because the remainders are down to one bit or the table can't be
+    /// allocated. The filter is unchanged.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> Result<bool>
+    where
+        T: Borrow<Q>,
+    {
+        let fingerprint = self.fingerprint(item);
+        let present = self.contains_fingerprint(fingerprint);
+        if (self.table.len() + 1) as f64 > self.table.size() as f64 * MAX_LOAD && self.remainder_bits() > 1 {
+            self.grow()?;
+        }
+        self.insert_fingerprint(fingerprint)?;
+        Ok(present)
+    }
+
+    /// Returns `true` if `item` has probably been inserted and not removed,
+    /// and `false` if it definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.contains_fingerprint(self.fingerprint(item))
+    }
+
+    /// Removes one copy of `item`, returning `true` if it was probably
+    /// present.
+    pub fn remove<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (quotient, remainder) = self.split(self.fingerprint(item));
+        match self.table.find(quotient, |payload| payload == remainder) {
+            Some(slot) => {
+                self.table.remove(quotient, slot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Doubles the number of slots, moving the top bit of every remainder
+    /// into its quotient.
+    ///
+    /// Items map to the same fingerprints as before, so nothing is lost, but
+    /// the shorter remainders double the false positive probability.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::Capacity`] if the remainders are already one
+    /// bit long or the new table can't be allocated. The filter is unchanged.
+    pub fn grow(&mut self) -> Result<()> {
+        if self.remainder_bits() <= 1 {
+            return Err(BloomError::Capacity("remainders can't be shortened any further".to_string()));
+        }
+        self.table = self.table.doubled()?;
+        Ok(())
+    }
+
+    /// Adds every fingerprint in `other` to this filter, so that it holds
+    /// the union of both sets.
+    ///
+    /// The filters' tables may differ in size, but their fingerprints must
+    /// be the same length and they must have the same seed, and should use
+    /// the same hasher; otherwise [`BloomError::Incompatible`] is returned
+    /// and this filter is unchanged. This filter grows as needed.
+    pub fn try_union(&mut self, other: &QuotientFilter<T, S>) -> Result<()> {
+        if self.fingerprint_bits() != other.fingerprint_bits() {
+            return Err(BloomError::Incompatible(format!(
+                "fingerprint lengths differ ({} and {})",
+                self.fingerprint_bits(),
+                other.fingerprint_bits()
+            )));
+        }
+        if self.seed != other.seed {
+            return Err(BloomError::Incompatible(format!("seeds differ ({} and {})", self.seed, other.seed)));
+        }
+        let fingerprints = other.fingerprints();
+        while (self.table.len() + fingerprints.len() as u64) as f64 > self.table.size() as f64 * MAX_LOAD
+            && self.remainder_bits() > 1
+        {
+            self.grow()?;
+        }
+        if self.table.len() + fingerprints.len() as u64 > self.table.size() {
+            return Err(BloomError::Capacity("the union doesn't fit in the table".to_string()));
+        }
+        for fingerprint in fingerprints {
+            self.insert_fingerprint(fingerprint)?;
+        }
+        Ok(())
+    }
+
+    /// Removes every item from the filter, keeping its allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    /// Returns `true` if the filter holds no fingerprints.
+    pub fn is_empty(&self) -> bool {
+        self.table.len() == 0
+    }
+
+    /// The number of fingerprints stored, counting each copy of an item
+    /// inserted more than once.
+    pub fn len(&self) -> u64 {
+        self.table.len()
+    }
+
+    /// The fraction of slots that are occupied.
+    pub fn load_factor(&self) -> f64 {
+        self.table.len() as f64 / self.table.size() as f64
+    }
+
+    /// Estimates the false positive probability of the filter as it is now:
+    /// the chance that an absent item's fingerprint matches a stored one.
+    pub fn current_fpr(&self) -> f64 {
+        let fingerprints = math::powf(2.0, self.fingerprint_bits() as f64);
+        1.0 - math::powf(1.0 - 1.0 / fingerprints, self.table.len() as f64)
+    }
+
+    /// The number of quotient bits, so the table has `2^q` slots.
+    pub fn quotient_bits(&self) -> u32 {
+        self.table.quotient_bits()
+    }
+
+    /// The number of remainder bits stored per item.
+    pub fn remainder_bits(&self) -> u32 {
+        self.table.payload_bits()
+    }
+
+    /// The number of bits in each fingerprint: quotient plus remainder.
+    pub fn fingerprint_bits(&self) -> u32 {
+        self.quotient_bits() + self.remainder_bits()
+    }
+
+    /// The false positive probability the filter was sized for, or `None`
+    /// if it was built from explicit parameters.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The number of items the filter was sized for, or `None` if it was
+    /// built from explicit parameters.
+    pub fn capacity(&self) -> Option<usize> {
+        self.item_count
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The low `q + r` bits of `item`'s hash.
+    fn fingerprint<Q: ?Sized + Hash>(&self, item: &Q) -> u64 {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        hasher.finish() & mask(self.fingerprint_bits())
+    }
+
+    /// The fingerprints of every item in the filter, in table order.
+    pub(crate) fn fingerprints(&self) -> Vec<u64> {
+        let mut fingerprints = Vec::with_capacity(self.table.len() as usize);
+        let remainder_bits = self.remainder_bits();
+        self.table
+            .for_each(|quotient, remainder| fingerprints.push(quotient << remainder_bits | remainder));
+        fingerprints
+    }
+
+    fn split(&self, fingerprint: u64) -> (u64, u64) {
+        let remainder_bits = self.remainder_bits();
+        (fingerprint >> remainder_bits, fingerprint & mask(remainder_bits))
+    }
+
+    fn contains_fingerprint(&self, fingerprint: u64) -> bool {
+        let (quotient, remainder) = self.split(fingerprint);
+        self.table.find(quotient, |payload| payload == remainder).is_some()
+    }
+
+    fn insert_fingerprint(&mut self, fingerprint: u64) -> Result<()> {
+        let (quotient, remainder) = self.split(fingerprint);
+        self.table.insert(quotient, remainder)
+    }
+}
+
+impl<T: Hash> QuotientFilter<T, HashScheme> {
+    /// Encodes the filter, including its hash scheme, so that
+    /// [`from_bytes`](QuotientFilter::from_bytes) can load it on any
+    /// platform.
+    ///
+    /// The layout is little-endian: the magic `b"BLMQ"`, a 2-byte format
+    /// version (1), the [hash scheme id](HashScheme::id), the quotient and
+    /// remainder bits as one byte each, 3 zero bytes, then as 8 bytes each
+    /// the seed, the number of fingerprints, the item count or 0 and the
+    /// target false positive probability's `f64` bits or 0. The table's
+    /// words follow, and finally the XXH64 (seed 0) of every preceding byte.
+    ///
+    /// The key of a [`HashScheme::KeyedSipHash13`] filter isn't written, and
+    /// such a filter can't be loaded again.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + 8 * self.table.words().len() + 8);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&[self.hasher().id(), self.quotient_bits() as u8, self.remainder_bits() as u8, 0, 0, 0]);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.table.len().to_le_bytes());
+        bytes.extend_from_slice(&(self.item_count.unwrap_or(0) as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.false_positive_prob.map_or(0, f64::to_bits).to_le_bytes());
+        for word in self.table.words() {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        let checksum = xxh64(&bytes, 0);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes a filter encoded by [`to_bytes`](QuotientFilter::to_bytes).
+    ///
+    /// Fails with [`BloomError::CorruptFile`] if `bytes` is malformed or its
+    /// checksum doesn't match, and with [`BloomError::Incompatible`] if it
+    /// is from a newer format version or was built with a secret key.
+    pub fn from_bytes(bytes: &[u8]) -> Result<QuotientFilter<T, HashScheme>> {
+        if bytes.len() < HEADER_LEN + 8 || bytes[..4] != MAGIC {
+            return Err(BloomError::CorruptFile("not a quotient filter".to_string()));
+        }
+        let (contents, checksum) = bytes.split_at(bytes.len() - 8);
+        if xxh64(contents, 0) != read_le(checksum, 0, 8) {
+            return Err(BloomError::CorruptFile("checksum mismatch".to_string()));
+        }
+        if read_le(bytes, 4, 2) != u64::from(FORMAT_VERSION) {
+            return Err(BloomError::Incompatible("unsupported format version".to_string()));
+        }
+        let hash_builder = match HashScheme::from_id(bytes[6]) {
+            Some(scheme) => scheme,
+            None if bytes[6] == KEYED_ID => {
+                return Err(BloomError::Incompatible("the filter needs a secret key".to_string()))
+            }
+            None => return Err(BloomError::CorruptFile("unknown hash scheme".to_string())),
+        };
+        let (quotient_bits, remainder_bits) = (u32::from(bytes[7]), u32::from(bytes[8]));
+        if quotient_bits + remainder_bits > 64 {
+            return Err(BloomError::CorruptFile("fingerprints are longer than 64 bits".to_string()));
+        }
+        let words = contents[HEADER_LEN..]
+            .chunks(8)
+            .map(|chunk| if chunk.len() == 8 { Ok(read_le(chunk, 0, 8)) } else { Err(()) })
+            .collect::<core::result::Result<Vec<u64>, ()>>()
+            .map_err(|()| BloomError::CorruptFile("wrong table length".to_string()))?;
+        let table = QuotientTable::from_words(quotient_bits, remainder_bits, words, read_le(bytes, 20, 8))
+            .map_err(|e| match e {
+                BloomError::InvalidParams(message) | BloomError::Capacity(message) => BloomError::CorruptFile(message),
+                e => e,
+            })?;
+        let item_count = read_le(bytes, 28, 8) as usize;
+        let false_positive_prob = f64::from_bits(read_le(bytes, 36, 8));
+        Ok(QuotientFilter {
+            table,
+            item_count: if item_count == 0 { None } else { Some(item_count) },
+            false_positive_prob: if false_positive_prob == 0.0 { None } else { Some(false_positive_prob) },
+            seed: read_le(bytes, 12, 8),
+            hash_builder,
+            phantom: PhantomData,
+        })
+    }
+}
+
+/// The quotient and remainder bits for `n` items at false positive
+/// probability `p`: enough slots to fill `TARGET_LOAD` of them, and
+/// remainders long enough that `n` fingerprints collide with an absent item's
+/// with probability `p`.
+pub(crate) fn layout(n: usize, p: f64) -> (u32, u32) {
+    let slots = math::ceil(n as f64 / TARGET_LOAD) as u64;
+    let quotient_bits = cmp::max(cmp::max(slots, 2).next_power_of_two().trailing_zeros(), 1);
+    // With `2^q` slots at load `α`, the probability is about `α 2^-r`.
+    let remainder_bits = math::ceil(math::log2(n as f64 / (p * (1u64 << quotient_bits) as f64))) as i64;
+    let remainder_bits = remainder_bits.clamp(1, i64::from(cmp::min(64 - METADATA_BITS, 64 - quotient_bits)));
+    (quotient_bits, remainder_bits as u32)
+}
+
+pub(crate) fn mask(bits: u32) -> u64 {
+    if bits == 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for QuotientFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        QuotientFilter::insert(self, item)
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        QuotientFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        QuotientFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        self.table.len() as f64
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(self.table.words())
+    }
+}
+
+impl<T, Q, S> Removable<Q> for QuotientFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn remove(&mut self, item: &Q) -> bool {
+        QuotientFilter::remove(self, item)
+    }
+}
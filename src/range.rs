@@ -0,0 +1,445 @@
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::mem;
+use core::ops::Range;
+
+use crate::bit_vec::BitVec;
+use crate::packed::PackedVec;
+use crate::BloomError;
+
+/// The number of words each cumulative count in [`RankedBits`] covers.
+const RANK_BLOCK_WORDS: usize = 8;
+
+/// A filter over byte-string keys that answers range queries, "is there a
+/// key between `low` and `high`?", as well as point lookups: a succinct
+/// range filter in the style of SuRF (Zhang et al., "SuRF: Practical Range
+/// Query Filtering with Fast Succinct Tries").
+///
+/// The keys are stored in a trie cut off at each key's shortest prefix that
+/// tells it apart from every other key, followed by `suffix_bits` more bits
+/// of the key. The trie is encoded level by level as LOUDS-Sparse: a byte
+/// label per edge and two bits per edge marking whether it leads to a node
+/// and whether it starts one, navigated by rank and select over those bits.
+/// Because the prefixes keep the keys' order, a lookup can find the first
+/// key at or above `low` and compare it to `high`; the stored suffix bits
+/// make that comparison exact more often.
+///
+/// Like any filter, it has false positives and no false negatives: a range
+/// holding a key always gets `true`, and a range holding none gets `true`
+/// only when a truncated key can't be told apart from its ends. Keys that
+/// share long prefixes, such as URLs, take more space than random ones. To
+/// store integers, give them as big-endian bytes so that their byte order
+/// is their numeric order.
+///
+/// ```
+/// use bloom::RangeFilter;
+///
+/// let keys: Vec<[u8; 8]> = [10u64, 2000, 35_000, 1 << 40].iter().map(|key| key.to_be_bytes()).collect();
+/// let filter = RangeFilter::from_keys(&keys, 8);
+/// assert!(filter.contains(&2000u64.to_be_bytes()));
+/// assert!(filter.contains_range(&1000u64.to_be_bytes(), &3000u64.to_be_bytes()));
+/// assert!(!filter.contains_range(&3000u64.to_be_bytes(), &30_000u64.to_be_bytes()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RangeFilter {
+    /// The label of each edge, nodes in level order and each node's edges in
+    /// label order.
+    labels: Box<[u8]>,
+    /// Whether each edge leads to a node rather than ending a key.
+    has_child: RankedBits,
+    /// Whether each edge is its node's first.
+    louds: RankedBits,
+    /// Whether each node, in level order, ends a key: the key is a prefix of
+    /// others.
+    prefix_keys: BitVec,
+    /// The suffix of each edge that ends a key, in edge order.
+    suffixes: Option<PackedVec>,
+    suffix_bits: u32,
+    len: u64,
+}
+
+/// The first key at or above a bound, as far as the filter knows it.
+struct Found {
+    /// The key's stored prefix.
+    path: Vec<u8>,
+    /// The stored suffix bits, or `None` if the key is exactly `path`.
+    suffix: Option<u64>,
+}
+
+impl RangeFilter {
+    /// Builds a filter of `keys`, storing `suffix_bits` bits of each after
+    /// its distinguishing prefix. Each suffix bit halves the chance of a
+    /// false positive from a key near the ends of a range, or for a point
+    /// lookup sharing a key's prefix.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `suffix_bits` is more than 64, or if the filter is too
+    /// large to allocate.
+    pub fn from_keys<I>(keys: I, suffix_bits: u32) -> RangeFilter
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        if suffix_bits > 64 {
+            panic!(
+                "{}",
+                BloomError::InvalidParams(format!("suffixes can be at most 64 bits (got {})", suffix_bits))
+            );
+        }
+        let mut keys: Vec<I::Item> = keys.into_iter().collect();
+        keys.sort_unstable_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        keys.dedup_by(|a, b| a.as_ref() == b.as_ref());
+        let key = |i: usize| keys[i].as_ref();
+
+        let mut labels = Vec::new();
+        let mut has_child = Vec::new();
+        let mut louds = Vec::new();
+        let mut prefix_keys = Vec::new();
+        let mut suffixes = Vec::new();
+        // Each node is the keys sharing its path, which is `depth` bytes
+        // long; visiting them first in first out numbers them level by level.
+        let mut queue = VecDeque::new();
+        queue.push_back((0, 0..keys.len()));
+        while let Some((depth, range)) = queue.pop_front() {
+            let mut start = range.start;
+            // A key ending here sorts before every key continuing past it.
+            let is_prefix_key = start < range.end && key(start).len() == depth;
+            prefix_keys.push(is_prefix_key);
+            if is_prefix_key {
+                start += 1;
+            }
+            let mut first = true;
+            while start < range.end {
+                let label = key(start)[depth];
+                let end = start + keys[start..range.end].partition_point(|key| key.as_ref()[depth] == label);
+                louds.push(first);
+                first = false;
+                labels.push(label);
+                // A key alone under its label is told apart by it.
+                if end - start == 1 {
+                    has_child.push(false);
+                    suffixes.push(suffix(key(start), depth + 1, suffix_bits));
+                } else {
+                    has_child.push(true);
+                    queue.push_back((depth + 1, start..end));
+                }
+                start = end;
+            }
+        }
+
+        let suffixes = if suffix_bits == 0 {
+            None
+        } else {
+            let mut packed = match PackedVec::new(suffixes.len() as u64, suffix_bits) {
+                Ok(packed) => packed,
+                Err(e) => panic!("{}", e),
+            };
+            for (i, &suffix) in suffixes.iter().enumerate() {
+                packed.set(i as u64, suffix);
+            }
+            Some(packed)
+        };
+        let mut prefix_key_bits = BitVec::new(prefix_keys.len() as u64);
+        for (node, _) in prefix_keys.iter().enumerate().filter(|&(_, &is_prefix_key)| is_prefix_key) {
+            prefix_key_bits.set(node as u64);
+        }
+        RangeFilter {
+            labels: labels.into_boxed_slice(),
+            has_child: RankedBits::new(&has_child),
+            louds: RankedBits::new(&louds),
+            prefix_keys: prefix_key_bits,
+            suffixes,
+            suffix_bits,
+            len: keys.len() as u64,
+        }
+    }
+
+    /// Returns `true` if `key` is probably one of the keys, and `false` if
+    /// it definitely isn't.
+    pub fn contains<K: ?Sized + AsRef<[u8]>>(&self, key: &K) -> bool {
+        let key = key.as_ref();
+        let mut node = 0;
+        let mut depth = 0;
+        loop {
+            if depth == key.len() {
+                return self.prefix_keys.get(node);
+            }
+            let edges = self.edges(node);
+            let labels = &self.labels[edges.start as usize..edges.end as usize];
+            let pos = match labels.binary_search(&key[depth]) {
+                Ok(i) => edges.start + i as u64,
+                Err(_) => return false,
+            };
+            if !self.has_child.get(pos) {
+                return self.suffix_at(pos) == suffix(key, depth + 1, self.suffix_bits);
+            }
+            node = self.child(pos);
+            depth += 1;
+        }
+    }
+
+    /// Returns `true` if there is probably a key `k` with
+    /// `low <= k <= high`, and `false` if there definitely isn't.
+    pub fn contains_range<L, H>(&self, low: &L, high: &H) -> bool
+    where
+        L: ?Sized + AsRef<[u8]>,
+        H: ?Sized + AsRef<[u8]>,
+    {
+        let (low, high) = (low.as_ref(), high.as_ref());
+        if low > high {
+            return false;
+        }
+        let found = match self.seek(low) {
+            Some(found) => found,
+            None => return false,
+        };
+        let path = found.path.as_slice();
+        match found.suffix {
+            None => path <= high,
+            // The key continues past its path, so if `high` is a prefix of
+            // the path the key is above it.
+            Some(_) if high.len() < path.len() => path[..high.len()] < *high,
+            Some(stored) => match path.cmp(&high[..path.len()]) {
+                Ordering::Less => true,
+                Ordering::Greater => false,
+                Ordering::Equal => stored <= suffix(high, path.len(), self.suffix_bits),
+            },
+        }
+    }
+
+    /// The number of distinct keys.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the filter holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of bits stored of each key after its distinguishing
+    /// prefix.
+    pub fn suffix_bits(&self) -> u32 {
+        self.suffix_bits
+    }
+
+    /// The number of nodes in the trie.
+    pub fn node_count(&self) -> u64 {
+        self.prefix_keys.len()
+    }
+
+    /// The number of edges in the trie, each taking a byte and two bits.
+    pub fn edge_count(&self) -> u64 {
+        self.labels.len() as u64
+    }
+
+    /// The number of bytes of memory the filter occupies, including the
+    /// trie and suffixes.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self)
+            + self.labels.len()
+            + self.has_child.memory_bytes()
+            + self.louds.memory_bytes()
+            + self.prefix_keys.heap_bytes()
+            + self.suffixes.as_ref().map_or(0, |suffixes| mem::size_of_val(suffixes.words()))
+    }
+
+    /// The positions of `node`'s edges.
+    fn edges(&self, node: u64) -> Range<u64> {
+        let edge_count = self.labels.len() as u64;
+        // Only an empty root has no edges, and so no start mark.
+        if edge_count == 0 {
+            return 0..0;
+        }
+        let start = self.louds.select(node);
+        let end = if node + 1 < self.louds.ones() {
+            self.louds.select(node + 1)
+        } else {
+            edge_count
+        };
+        start..end
+    }
+
+    /// The node edge `pos` leads to: nodes after the root are numbered in
+    /// the order of the edges leading to them.
+    fn child(&self, pos: u64) -> u64 {
+        self.has_child.rank(pos + 1)
+    }
+
+    /// The suffix stored for edge `pos`, which ends a key.
+    fn suffix_at(&self, pos: u64) -> u64 {
+        match &self.suffixes {
+            Some(suffixes) => suffixes.get(pos - self.has_child.rank(pos)),
+            None => 0,
+        }
+    }
+
+    /// Finds the first key that is at or above `low`, or that can't be told
+    /// apart from it.
+    fn seek(&self, low: &[u8]) -> Option<Found> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut path = Vec::new();
+        // The edges followed from the root, each with the end of its node's
+        // edges.
+        let mut stack: Vec<(u64, u64)> = Vec::new();
+        let mut node = 0;
+        loop {
+            let depth = path.len();
+            if depth == low.len() {
+                // Every key below here starts with `low`.
+                return Some(self.leftmost(node, path));
+            }
+            let edges = self.edges(node);
+            let labels = &self.labels[edges.start as usize..edges.end as usize];
+            let pos = edges.start + labels.partition_point(|&label| label < low[depth]) as u64;
+            if pos == edges.end {
+                break;
+            }
+            path.push(self.labels[pos as usize]);
+            if self.labels[pos as usize] > low[depth] {
+                return Some(self.leftmost_from(pos, path));
+            }
+            if self.has_child.get(pos) {
+                stack.push((pos, edges.end));
+                node = self.child(pos);
+                continue;
+            }
+            let stored = self.suffix_at(pos);
+            if stored >= suffix(low, depth + 1, self.suffix_bits) {
+                return Some(Found {
+                    path,
+                    suffix: Some(stored),
+                });
+            }
+            // The key here is below `low`, so the answer is the next one.
+            stack.push((pos, edges.end));
+            break;
+        }
+        while let Some((pos, end)) = stack.pop() {
+            path.pop();
+            if pos + 1 < end {
+                path.push(self.labels[pos as usize + 1]);
+                return Some(self.leftmost_from(pos + 1, path));
+            }
+        }
+        None
+    }
+
+    /// The first key at or below `node`, whose path is `path`.
+    fn leftmost(&self, node: u64, mut path: Vec<u8>) -> Found {
+        if self.prefix_keys.get(node) {
+            return Found { path, suffix: None };
+        }
+        let pos = self.edges(node).start;
+        path.push(self.labels[pos as usize]);
+        self.leftmost_from(pos, path)
+    }
+
+    /// The first key through edge `pos`, whose label ends `path`.
+    fn leftmost_from(&self, pos: u64, path: Vec<u8>) -> Found {
+        if self.has_child.get(pos) {
+            self.leftmost(self.child(pos), path)
+        } else {
+            Found {
+                path,
+                suffix: Some(self.suffix_at(pos)),
+            }
+        }
+    }
+}
+
+/// The `bits` bits of `key` after its first `start` bytes, read big-endian
+/// so that suffixes order as the keys do, and zero past the key's end.
+fn suffix(key: &[u8], start: usize, bits: u32) -> u64 {
+    if bits == 0 {
+        return 0;
+    }
+    let mut window = [0; 8];
+    let rest = key.get(start..).unwrap_or(&[]);
+    let len = rest.len().min(8);
+    window[..len].copy_from_slice(&rest[..len]);
+    u64::from_be_bytes(window) >> (64 - bits)
+}
+
+/// Bits with cumulative counts of their ones, for rank and select in
+/// constant and logarithmic time.
+#[derive(Debug, Clone)]
+struct RankedBits {
+    bits: BitVec,
+    /// The ones before each block of `RANK_BLOCK_WORDS` words.
+    blocks: Box<[u64]>,
+    ones: u64,
+}
+
+impl RankedBits {
+    fn new(bits: &[bool]) -> RankedBits {
+        let mut bit_vec = BitVec::new(bits.len() as u64);
+        for (i, _) in bits.iter().enumerate().filter(|&(_, &bit)| bit) {
+            bit_vec.set(i as u64);
+        }
+        let mut blocks = Vec::new();
+        let mut ones = 0;
+        for words in bit_vec.words().chunks(RANK_BLOCK_WORDS) {
+            blocks.push(ones);
+            ones += words.iter().map(|word| u64::from(word.count_ones())).sum::<u64>();
+        }
+        RankedBits {
+            bits: bit_vec,
+            blocks: blocks.into_boxed_slice(),
+            ones,
+        }
+    }
+
+    fn get(&self, index: u64) -> bool {
+        self.bits.get(index)
+    }
+
+    fn ones(&self) -> u64 {
+        self.ones
+    }
+
+    /// The number of ones before `index`.
+    fn rank(&self, index: u64) -> u64 {
+        let words = self.bits.words();
+        let word = (index / 64) as usize;
+        let block = word / RANK_BLOCK_WORDS;
+        let mut rank = self.blocks.get(block).copied().unwrap_or(self.ones);
+        rank += words[block * RANK_BLOCK_WORDS..word]
+            .iter()
+            .map(|word| u64::from(word.count_ones()))
+            .sum::<u64>();
+        if !index.is_multiple_of(64) {
+            rank += u64::from((words[word] & ((1 << (index % 64)) - 1)).count_ones());
+        }
+        rank
+    }
+
+    /// The index of the one with `rank` ones before it, which must exist.
+    fn select(&self, rank: u64) -> u64 {
+        let block = self.blocks.partition_point(|&before| before <= rank) - 1;
+        let mut remaining = rank - self.blocks[block];
+        for (i, &word) in self.bits.words()[block * RANK_BLOCK_WORDS..].iter().enumerate() {
+            let ones = u64::from(word.count_ones());
+            if remaining < ones {
+                let mut word = word;
+                for _ in 0..remaining {
+                    // Clear the lowest one.
+                    word &= word - 1;
+                }
+                return ((block * RANK_BLOCK_WORDS + i) * 64) as u64 + u64::from(word.trailing_zeros());
+            }
+            remaining -= ones;
+        }
+        unreachable!("select past the last one")
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.bits.heap_bytes() + mem::size_of_val(&*self.blocks)
+    }
+}
@@ -0,0 +1,297 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use crate::{params, ApproximateMembership, BloomFilter, BloomFilterBuilder, DefaultBuildHasher, Result};
+
+/// The growth factor used unless configured otherwise.
+const DEFAULT_GROWTH_FACTOR: usize = 2;
+
+/// The tightening ratio used unless configured otherwise.
+const DEFAULT_TIGHTENING_RATIO: f64 = 0.85;
+
+/// A bloom filter that grows as items are added, so it needn't be sized up
+/// front (Almeida et al., "Scalable Bloom Filters").
+///
+/// The filter is a chain of [`BloomFilter`]s. Items are added to the newest
+/// one until it holds as many items as it was sized for, and then a new
+/// filter is started with [`growth_factor`](ScalableBloomFilter::with_growth_factor)
+/// times the capacity. Each new filter's false positive probability is the
+/// previous one's times the [`tightening_ratio`](ScalableBloomFilter::with_tightening_ratio)
+/// `r`, and the first's is `p (1 - r)`, so the geometric series keeps the
+/// probability for the whole chain below `p` however many items are added.
+///
+/// ```
+/// use bloom::ScalableBloomFilter;
+///
+/// let mut filter = ScalableBloomFilter::<u64>::new(100, 0.01);
+/// for i in 0..10_000 {
+///     filter.insert(&i).unwrap();
+/// }
+/// assert!(filter.contains(&1234));
+/// assert!(filter.filter_count() > 1);
+/// ```
+#[derive(Debug)]
+pub struct ScalableBloomFilter<T, S = DefaultBuildHasher> {
+    // Created when the first item is added, so that the configuration can
+    // still be changed until then.
+    filters: Vec<BloomFilter<T, S>>,
+    // The number of items added to the newest filter.
+    newest_len: usize,
+    initial_capacity: usize,
+    false_positive_prob: f64,
+    growth_factor: usize,
+    tightening_ratio: f64,
+    seed: u64,
+    hash_builder: S,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for ScalableBloomFilter<T, S> {
+    fn clone(&self) -> ScalableBloomFilter<T, S> {
+        ScalableBloomFilter {
+            filters: self.filters.clone(),
+            newest_len: self.newest_len,
+            initial_capacity: self.initial_capacity,
+            false_positive_prob: self.false_positive_prob,
+            growth_factor: self.growth_factor,
+            tightening_ratio: self.tightening_ratio,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+impl<T: Hash> ScalableBloomFilter<T> {
+    /// Creates a filter whose first sub-filter holds `initial_capacity`
+    /// items, and whose overall false positive probability stays below
+    /// `false_positive_prob`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initial_capacity` is 0 or `false_positive_prob` is not
+    /// strictly between 0 and 1.
+    pub fn new(initial_capacity: usize, false_positive_prob: f64) -> ScalableBloomFilter<T> {
+        ScalableBloomFilter::with_hasher(initial_capacity, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](ScalableBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(initial_capacity: usize, false_positive_prob: f64, seed: u64) -> ScalableBloomFilter<T> {
+        let mut filter = ScalableBloomFilter::new(initial_capacity, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+}
+
+impl<T: Hash, S: BuildHasher + Clone> ScalableBloomFilter<T, S> {
+    /// Like [`new`](ScalableBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(initial_capacity: usize, false_positive_prob: f64, hash_builder: S) -> ScalableBloomFilter<T, S> {
+        if let Err(e) = params::validate(initial_capacity, false_positive_prob) {
+            panic!("{}", e);
+        }
+        ScalableBloomFilter {
+            filters: Vec::new(),
+            newest_len: 0,
+            initial_capacity,
+            false_positive_prob,
+            growth_factor: DEFAULT_GROWTH_FACTOR,
+            tightening_ratio: DEFAULT_TIGHTENING_RATIO,
+            seed: 0,
+            hash_builder,
+        }
+    }
+
+    /// Sets how many times larger each sub-filter is than the one before.
+    /// Defaults to 2.
+    ///
+    /// Larger factors mean fewer sub-filters to check on each lookup, at
+    /// the cost of more memory reserved ahead of need.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `growth_factor` is 0, or if items have already been added.
+    pub fn with_growth_factor(mut self, growth_factor: usize) -> ScalableBloomFilter<T, S> {
+        assert!(growth_factor > 0, "growth factor must be greater than 0");
+        assert!(self.filters.is_empty(), "the filter must be configured before adding items");
+        self.growth_factor = growth_factor;
+        self
+    }
+
+    /// Sets the ratio between the false positive probabilities of
+    /// successive sub-filters. Defaults to 0.85.
+    ///
+    /// Lower ratios make later sub-filters larger, but leave more of the
+    /// overall probability to the first ones, which keeps them small.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tightening_ratio` is not strictly between 0 and 1, or if
+    /// items have already been added.
+    pub fn with_tightening_ratio(mut self, tightening_ratio: f64) -> ScalableBloomFilter<T, S> {
+        assert!(
+            tightening_ratio > 0.0 && tightening_ratio < 1.0,
+            "tightening ratio must be strictly between 0 and 1 (got {})",
+            tightening_ratio
+        );
+        assert!(self.filters.is_empty(), "the filter must be configured before adding items");
+        self.tightening_ratio = tightening_ratio;
+        self
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present.
+    ///
+    /// Items that are already present aren't added again, so they don't
+    /// count towards filling the newest sub-filter.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::Capacity`](crate::BloomError::Capacity) if a new
+    /// sub-filter is needed and would be too large to allocate.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> Result<bool>
+    where
+        T: Borrow<Q>,
+    {
+        if self.contains(item) {
+            return Ok(true);
+        }
+        let newest_capacity = self.filters.last().and_then(BloomFilter::capacity);
+        if newest_capacity.is_none_or(|capacity| self.newest_len >= capacity) {
+            self.grow()?;
+        }
+        // The newest filter exists now.
+        if let Some(newest) = self.filters.last_mut() {
+            newest.add(item);
+        }
+        self.newest_len += 1;
+        Ok(false)
+    }
+
+    /// Returns `true` if `item` has probably been added, and `false` if it
+    /// definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        // Newest first, since it holds the most items.
+        self.filters.iter().rev().any(|filter| filter.contains(item))
+    }
+
+    /// Removes every item and sub-filter, keeping the configuration.
+    pub fn clear(&mut self) {
+        self.filters.clear();
+        self.newest_len = 0;
+    }
+
+    /// Returns `true` if nothing has been added since the filter was created
+    /// or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// The number of distinct items added, less any that were mistaken for
+    /// items already present.
+    pub fn len(&self) -> usize {
+        let full: usize = self.filters.iter().rev().skip(1).filter_map(BloomFilter::capacity).sum();
+        full + self.newest_len
+    }
+
+    /// Estimates the false positive probability of the filter as it is now:
+    /// the chance that any sub-filter gives a false positive.
+    pub fn current_fpr(&self) -> f64 {
+        1.0 - self
+            .filters
+            .iter()
+            .map(|filter| 1.0 - filter.current_fpr())
+            .product::<f64>()
+    }
+
+    /// The number of sub-filters.
+    pub fn filter_count(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// The sub-filters, oldest first.
+    pub fn filters(&self) -> &[BloomFilter<T, S>] {
+        &self.filters
+    }
+
+    /// The number of items the first sub-filter holds.
+    pub fn initial_capacity(&self) -> usize {
+        self.initial_capacity
+    }
+
+    /// The bound on the false positive probability of the whole filter.
+    pub fn false_positive_prob(&self) -> f64 {
+        self.false_positive_prob
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Starts a new sub-filter.
+    fn grow(&mut self) -> Result<()> {
+        let (capacity, false_positive_prob) = match self.filters.last() {
+            Some(newest) => (
+                newest.capacity().unwrap_or(self.initial_capacity).saturating_mul(self.growth_factor),
+                newest.false_positive_prob().unwrap_or(self.false_positive_prob) * self.tightening_ratio,
+            ),
+            None => (self.initial_capacity, self.false_positive_prob * (1.0 - self.tightening_ratio)),
+        };
+        let filter = BloomFilterBuilder::new()
+            .hasher(self.hash_builder.clone())
+            .item_count(capacity)
+            .false_positive_prob(false_positive_prob)
+            .seed(self.seed)
+            .build()?;
+        self.filters.push(filter);
+        self.newest_len = 0;
+        Ok(())
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for ScalableBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher + Clone,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        ScalableBloomFilter::insert(self, item)
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        ScalableBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        ScalableBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        self.filters.iter().map(BloomFilter::estimated_len).sum()
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self)
+            + self
+                .filters
+                .iter()
+                .map(|filter| ApproximateMembership::<Q>::memory_bytes(filter))
+                .sum::<usize>()
+    }
+}
@@ -0,0 +1,244 @@
+//! `serde` support for filters using a [`HashScheme`].
+//!
+//! A filter is represented as a struct holding its hash scheme id, its layout
+//! and sizing targets, and its bits packed as by [`BloomFilter::to_bytes`].
+//! Filters with a [`HashScheme::KeyedSipHash13`] scheme can't be serialized,
+//! since their key would have to be written out with them.
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::hash::Hash;
+use core::marker::PhantomData;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Serialize, SerializeStruct, Serializer};
+
+use crate::hash::HashScheme;
+use crate::BloomFilter;
+
+const FIELDS: &[&str] = &[
+    "scheme",
+    "wide_hashes",
+    "bit_vec_size",
+    "hash_count",
+    "seed",
+    "item_count",
+    "false_positive_prob",
+    "bits",
+];
+
+impl<T: Hash> Serialize for BloomFilter<T, HashScheme> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if let HashScheme::KeyedSipHash13(_) = self.hasher() {
+            return Err(ser::Error::custom("filters with a secret key can't be serialized"));
+        }
+        let mut state = serializer.serialize_struct("BloomFilter", FIELDS.len())?;
+        state.serialize_field("scheme", &self.hasher().id())?;
+        state.serialize_field("wide_hashes", &self.wide_hashes())?;
+        state.serialize_field("bit_vec_size", &self.bit_vec_size())?;
+        state.serialize_field("hash_count", &(self.hash_count() as u64))?;
+        state.serialize_field("seed", &self.seed())?;
+        state.serialize_field("item_count", &self.capacity().map(|n| n as u64))?;
+        state.serialize_field("false_positive_prob", &self.false_positive_prob())?;
+        state.serialize_field("bits", &Bytes(&self.to_bytes()))?;
+        state.end()
+    }
+}
+
+/// Serializes as bytes rather than as a sequence of integers, which is far
+/// more compact in most formats.
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> Serialize for Bytes<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// Accepts bytes, or a sequence of integers from formats without a bytes type.
+struct ByteBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ByteBuf, D::Error> {
+        deserializer.deserialize_bytes(ByteBufVisitor)
+    }
+}
+
+struct ByteBufVisitor;
+
+impl<'de> Visitor<'de> for ByteBufVisitor {
+    type Value = ByteBuf;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a byte array")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(bytes.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(bytes))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ByteBuf, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(ByteBuf(bytes))
+    }
+}
+
+enum Field {
+    Scheme,
+    WideHashes,
+    BitVecSize,
+    HashCount,
+    Seed,
+    ItemCount,
+    FalsePositiveProb,
+    Bits,
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Field, D::Error> {
+        deserializer.deserialize_identifier(FieldVisitor)
+    }
+}
+
+struct FieldVisitor;
+
+impl<'de> Visitor<'de> for FieldVisitor {
+    type Value = Field;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a filter field")
+    }
+
+    fn visit_u64<E: de::Error>(self, index: u64) -> Result<Field, E> {
+        match FIELDS.get(index as usize) {
+            Some(name) => self.visit_str(name),
+            None => Err(de::Error::invalid_value(de::Unexpected::Unsigned(index), &self)),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<Field, E> {
+        match name {
+            "scheme" => Ok(Field::Scheme),
+            "wide_hashes" => Ok(Field::WideHashes),
+            "bit_vec_size" => Ok(Field::BitVecSize),
+            "hash_count" => Ok(Field::HashCount),
+            "seed" => Ok(Field::Seed),
+            "item_count" => Ok(Field::ItemCount),
+            "false_positive_prob" => Ok(Field::FalsePositiveProb),
+            "bits" => Ok(Field::Bits),
+            _ => Err(de::Error::unknown_field(name, FIELDS)),
+        }
+    }
+}
+
+/// The fields of a filter, before they have been checked.
+struct Parts {
+    scheme: u8,
+    wide_hashes: bool,
+    bit_vec_size: u64,
+    hash_count: u64,
+    seed: u64,
+    item_count: Option<u64>,
+    false_positive_prob: Option<f64>,
+    bits: Vec<u8>,
+}
+
+impl Parts {
+    fn into_filter<T: Hash, E: de::Error>(self) -> Result<BloomFilter<T, HashScheme>, E> {
+        let scheme = HashScheme::from_id(self.scheme)
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Unsigned(self.scheme.into()), &"a hash scheme id"))?;
+        let hash_count = usize::try_from(self.hash_count).map_err(|_| E::custom("hash count out of range"))?;
+        let item_count = match self.item_count {
+            Some(n) => Some(usize::try_from(n).map_err(|_| E::custom("item count out of range"))?),
+            None => None,
+        };
+        let filter =
+            BloomFilter::from_raw_parts_with_hasher(&self.bits, self.bit_vec_size, hash_count, self.seed, scheme)
+                .map_err(E::custom)?;
+        Ok(filter
+            .with_wide_hashes(self.wide_hashes)
+            .with_sizing(item_count, self.false_positive_prob))
+    }
+}
+
+struct FilterVisitor<T>(PhantomData<fn(T)>);
+
+impl<'de, T: Hash> Visitor<'de> for FilterVisitor<T> {
+    type Value = BloomFilter<T, HashScheme>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a bloom filter")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let missing = |index: usize| de::Error::invalid_length(index, &self);
+        let parts = Parts {
+            scheme: seq.next_element()?.ok_or_else(|| missing(0))?,
+            wide_hashes: seq.next_element()?.ok_or_else(|| missing(1))?,
+            bit_vec_size: seq.next_element()?.ok_or_else(|| missing(2))?,
+            hash_count: seq.next_element()?.ok_or_else(|| missing(3))?,
+            seed: seq.next_element()?.ok_or_else(|| missing(4))?,
+            item_count: seq.next_element()?.ok_or_else(|| missing(5))?,
+            false_positive_prob: seq.next_element()?.ok_or_else(|| missing(6))?,
+            bits: seq.next_element::<ByteBuf>()?.ok_or_else(|| missing(7))?.0,
+        };
+        parts.into_filter()
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut scheme = None;
+        let mut wide_hashes = None;
+        let mut bit_vec_size = None;
+        let mut hash_count = None;
+        let mut seed = None;
+        let mut item_count = None;
+        let mut false_positive_prob = None;
+        let mut bits = None;
+        while let Some(field) = map.next_key()? {
+            match field {
+                Field::Scheme => set(&mut scheme, "scheme", map.next_value()?)?,
+                Field::WideHashes => set(&mut wide_hashes, "wide_hashes", map.next_value()?)?,
+                Field::BitVecSize => set(&mut bit_vec_size, "bit_vec_size", map.next_value()?)?,
+                Field::HashCount => set(&mut hash_count, "hash_count", map.next_value()?)?,
+                Field::Seed => set(&mut seed, "seed", map.next_value()?)?,
+                Field::ItemCount => set(&mut item_count, "item_count", map.next_value()?)?,
+                Field::FalsePositiveProb => set(&mut false_positive_prob, "false_positive_prob", map.next_value()?)?,
+                Field::Bits => set(&mut bits, "bits", map.next_value::<ByteBuf>()?.0)?,
+            }
+        }
+        let parts = Parts {
+            scheme: scheme.ok_or_else(|| de::Error::missing_field("scheme"))?,
+            wide_hashes: wide_hashes.ok_or_else(|| de::Error::missing_field("wide_hashes"))?,
+            bit_vec_size: bit_vec_size.ok_or_else(|| de::Error::missing_field("bit_vec_size"))?,
+            hash_count: hash_count.ok_or_else(|| de::Error::missing_field("hash_count"))?,
+            seed: seed.ok_or_else(|| de::Error::missing_field("seed"))?,
+            item_count: item_count.unwrap_or(None),
+            false_positive_prob: false_positive_prob.unwrap_or(None),
+            bits: bits.ok_or_else(|| de::Error::missing_field("bits"))?,
+        };
+        parts.into_filter()
+    }
+}
+
+/// Stores a field's value, rejecting duplicates.
+fn set<V, E: de::Error>(slot: &mut Option<V>, name: &'static str, value: V) -> Result<(), E> {
+    if slot.is_some() {
+        return Err(E::duplicate_field(name));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+impl<'de, T: Hash> Deserialize<'de> for BloomFilter<T, HashScheme> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<BloomFilter<T, HashScheme>, D::Error> {
+        deserializer.deserialize_struct("BloomFilter", FIELDS, FilterVisitor(PhantomData))
+    }
+}
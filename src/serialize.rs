@@ -0,0 +1,301 @@
+//! Reading and writing filters in the [binary format](crate::format).
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::bit_vec::{self, BitVec};
+use crate::format::{
+    FORMAT_VERSION, HAS_FALSE_POSITIVE_PROB, HAS_ITEM_COUNT, HEADER_LEN, MAGIC, WIDE_HASHES,
+};
+use crate::hash::{HashScheme, SecretKey, XxHash64, HASH_SCHEME_VERSION, KEYED_ID};
+use crate::{memory, params, BloomError, BloomFilter, Result};
+
+// How many words of bits are encoded or decoded at a time.
+const CHUNK_WORDS: usize = 1024;
+
+/// Hashes everything that passes through it.
+struct Checksummed<I> {
+    inner: I,
+    digest: XxHash64,
+}
+
+impl<I> Checksummed<I> {
+    fn new(inner: I) -> Checksummed<I> {
+        Checksummed {
+            inner,
+            digest: XxHash64::with_seed(0),
+        }
+    }
+}
+
+impl<W: Write> Checksummed<W> {
+    fn put(&mut self, bytes: &[u8]) -> Result<()> {
+        self.digest.write(bytes);
+        self.inner.write_all(bytes)?;
+        Ok(())
+    }
+}
+
+impl<R: Read> Checksummed<R> {
+    fn take(&mut self, bytes: &mut [u8]) -> Result<()> {
+        self.inner.read_exact(bytes)?;
+        self.digest.write(bytes);
+        Ok(())
+    }
+
+    fn take_u64(&mut self) -> Result<u64> {
+        let mut bytes = [0; 8];
+        self.take(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+fn corrupt<T>(message: &str) -> Result<T> {
+    Err(BloomError::CorruptFile(message.to_string()))
+}
+
+/// The fields of a serialized filter that come before its bits.
+#[derive(Debug)]
+pub(crate) struct Header {
+    pub(crate) scheme: HashScheme,
+    pub(crate) wide_hashes: bool,
+    pub(crate) bit_vec_size: u64,
+    pub(crate) hash_count: usize,
+    pub(crate) seed: u64,
+    pub(crate) item_count: Option<usize>,
+    pub(crate) false_positive_prob: Option<f64>,
+}
+
+impl Header {
+    /// Reads and checks the header at the start of `reader`, leaving it at
+    /// the first byte of the bits. The checksum can only be checked once
+    /// the bits have been read too.
+    pub(crate) fn read<R: Read>(reader: R, key: Option<SecretKey>) -> Result<Header> {
+        read_header(&mut Checksummed::new(reader), key)
+    }
+
+    /// The number of bytes the header takes, which is where the bits start.
+    pub(crate) fn len(&self) -> u64 {
+        match self.scheme {
+            HashScheme::KeyedSipHash13(_) => HEADER_LEN as u64 + 8,
+            _ => HEADER_LEN as u64,
+        }
+    }
+}
+
+fn read_header<R: Read>(reader: &mut Checksummed<R>, key: Option<SecretKey>) -> Result<Header> {
+    let mut header = [0; 12];
+    reader.take(&mut header)?;
+    if header[..4] != MAGIC {
+        return corrupt("not a bloom filter file");
+    }
+    let format_version = u16::from_le_bytes([header[4], header[5]]);
+    if format_version != FORMAT_VERSION {
+        return Err(BloomError::Incompatible(format!(
+            "unsupported format version {}",
+            format_version
+        )));
+    }
+    let scheme = match (HashScheme::from_id(header[6]), key) {
+        (Some(scheme), None) => scheme,
+        (Some(_), Some(_)) => return Err(BloomError::Incompatible("the filter isn't keyed".to_string())),
+        (None, Some(key)) if header[6] == KEYED_ID => HashScheme::KeyedSipHash13(key),
+        (None, None) if header[6] == KEYED_ID => {
+            return Err(BloomError::Incompatible("the filter needs a secret key".to_string()))
+        }
+        (None, _) => return corrupt("unknown hash scheme"),
+    };
+    let flags = header[7];
+    let scheme_version = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+    if scheme_version != HASH_SCHEME_VERSION {
+        return Err(BloomError::Incompatible(format!(
+            "unsupported hash scheme version {}",
+            scheme_version
+        )));
+    }
+    let bit_vec_size = reader.take_u64()?;
+    let hash_count = match usize::try_from(reader.take_u64()?) {
+        Ok(hash_count) => hash_count,
+        Err(_) => return corrupt("hash count out of range"),
+    };
+    let seed = reader.take_u64()?;
+    let item_count = reader.take_u64()?;
+    let false_positive_prob = f64::from_bits(reader.take_u64()?);
+    if let HashScheme::KeyedSipHash13(key) = scheme {
+        if reader.take_u64()? != key.check_value() {
+            return Err(BloomError::Incompatible("wrong secret key".to_string()));
+        }
+    }
+    if let Err(error) = params::validate_layout(bit_vec_size, hash_count) {
+        return corrupt(&error.to_string());
+    }
+    let item_count = if flags & HAS_ITEM_COUNT != 0 {
+        match usize::try_from(item_count) {
+            Ok(item_count) => Some(item_count),
+            Err(_) => return corrupt("item count out of range"),
+        }
+    } else {
+        None
+    };
+    let false_positive_prob = if flags & HAS_FALSE_POSITIVE_PROB != 0 {
+        Some(false_positive_prob)
+    } else {
+        None
+    };
+    Ok(Header {
+        scheme,
+        wide_hashes: flags & WIDE_HASHES != 0,
+        bit_vec_size,
+        hash_count,
+        seed,
+        item_count,
+        false_positive_prob,
+    })
+}
+
+impl<T: Hash> BloomFilter<T, HashScheme> {
+    /// Writes the filter, including its hash scheme, in a self-describing
+    /// binary format that [`read_from`](BloomFilter::read_from) can load on
+    /// any platform.
+    ///
+    /// The key of a [`HashScheme::KeyedSipHash13`] filter isn't written, so
+    /// the output can be stored where the key can't; it must be loaded with
+    /// [`read_from_keyed`](BloomFilter::read_from_keyed).
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        let mut writer = Checksummed::new(writer);
+        let mut flags = 0;
+        if self.capacity().is_some() {
+            flags |= HAS_ITEM_COUNT;
+        }
+        if self.false_positive_prob().is_some() {
+            flags |= HAS_FALSE_POSITIVE_PROB;
+        }
+        if self.wide_hashes() {
+            flags |= WIDE_HASHES;
+        }
+        writer.put(&MAGIC)?;
+        writer.put(&FORMAT_VERSION.to_le_bytes())?;
+        writer.put(&[self.hasher().id(), flags])?;
+        writer.put(&HASH_SCHEME_VERSION.to_le_bytes())?;
+        writer.put(&self.bit_vec_size().to_le_bytes())?;
+        writer.put(&(self.hash_count() as u64).to_le_bytes())?;
+        writer.put(&self.seed().to_le_bytes())?;
+        writer.put(&(self.capacity().unwrap_or(0) as u64).to_le_bytes())?;
+        writer.put(&self.false_positive_prob().unwrap_or(0.0).to_bits().to_le_bytes())?;
+        if let HashScheme::KeyedSipHash13(key) = self.hasher() {
+            writer.put(&key.check_value().to_le_bytes())?;
+        }
+
+        let mut remaining = self.bit_vec_size().div_ceil(8);
+        let mut chunk = Vec::with_capacity(CHUNK_WORDS * 8);
+        for words in self.as_raw_slice().chunks(CHUNK_WORDS) {
+            chunk.clear();
+            for word in words {
+                chunk.extend_from_slice(&word.to_le_bytes());
+            }
+            // The last word may extend past the last byte.
+            let len = std::cmp::min(chunk.len() as u64, remaining);
+            writer.put(&chunk[..len as usize])?;
+            remaining -= len;
+        }
+
+        let checksum = writer.digest.finish();
+        writer.inner.write_all(&checksum.to_le_bytes())?;
+        Ok(())
+    }
+
+    /// Reads a filter written by [`write_to`](BloomFilter::write_to), using
+    /// the hash scheme it was built with.
+    ///
+    /// Fails with [`BloomError::CorruptFile`] if the data is malformed or its
+    /// checksum doesn't match, with [`BloomError::Incompatible`] if it was
+    /// written by a newer version of this crate or with a secret key, and
+    /// with [`BloomError::Capacity`] if the filter it describes is too large
+    /// to allocate.
+    pub fn read_from<R: Read>(reader: R) -> Result<BloomFilter<T, HashScheme>> {
+        BloomFilter::read_with_key(reader, None)
+    }
+
+    /// Reads a filter written by [`write_to`](BloomFilter::write_to) that
+    /// was built with [`HashScheme::KeyedSipHash13`] and `key`.
+    ///
+    /// Fails with [`BloomError::Incompatible`] if the filter wasn't built
+    /// with that key, or wasn't keyed at all.
+    pub fn read_from_keyed<R: Read>(reader: R, key: SecretKey) -> Result<BloomFilter<T, HashScheme>> {
+        BloomFilter::read_with_key(reader, Some(key))
+    }
+
+    fn read_with_key<R: Read>(reader: R, key: Option<SecretKey>) -> Result<BloomFilter<T, HashScheme>> {
+        let mut reader = Checksummed::new(reader);
+        let Header {
+            scheme,
+            wide_hashes,
+            bit_vec_size,
+            hash_count,
+            seed,
+            item_count,
+            false_positive_prob,
+        } = read_header(&mut reader, key)?;
+
+        // The size comes from the header, so refuse a filter too large for
+        // memory before the checksum can show whether the header is sound.
+        memory::check(bit_vec::heap_bytes(bit_vec_size), None)?;
+        let mut bit_vec = BitVec::try_new(bit_vec_size)?;
+        let words = bit_vec.words_mut();
+        let mut remaining = bit_vec_size.div_ceil(8);
+        let mut chunk = vec![0; CHUNK_WORDS * 8];
+        for words in words.chunks_mut(CHUNK_WORDS) {
+            let len = std::cmp::min(words.len() as u64 * 8, remaining) as usize;
+            reader.take(&mut chunk[..len])?;
+            // The last word may extend past the last byte.
+            chunk[len..words.len() * 8].fill(0);
+            for (word, bytes) in words.iter_mut().zip(chunk.chunks(8)) {
+                let mut word_bytes = [0; 8];
+                word_bytes.copy_from_slice(bytes);
+                *word = u64::from_le_bytes(word_bytes);
+            }
+            remaining -= len as u64;
+        }
+        if !bit_vec_size.is_multiple_of(64) && words[words.len() - 1] >> (bit_vec_size % 64) != 0 {
+            return corrupt("bits past the end of the filter are set");
+        }
+
+        let expected = reader.digest.finish();
+        let mut checksum = [0; 8];
+        reader.inner.read_exact(&mut checksum)?;
+        if u64::from_le_bytes(checksum) != expected {
+            return corrupt("checksum mismatch");
+        }
+        Ok(BloomFilter::from_parts(
+            bit_vec,
+            hash_count,
+            item_count,
+            false_positive_prob,
+            seed,
+            scheme,
+        )
+        .with_wide_hashes(wide_hashes))
+    }
+
+    /// Writes the filter to the file at `path`, replacing it if it exists.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a filter saved with [`save`](BloomFilter::save).
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<BloomFilter<T, HashScheme>> {
+        BloomFilter::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads a keyed filter saved with [`save`](BloomFilter::save); see
+    /// [`read_from_keyed`](BloomFilter::read_from_keyed).
+    pub fn load_keyed<P: AsRef<Path>>(path: P, key: SecretKey) -> Result<BloomFilter<T, HashScheme>> {
+        BloomFilter::read_from_keyed(BufReader::new(File::open(path)?), key)
+    }
+}
@@ -0,0 +1,307 @@
+use alloc::format;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::mem;
+
+use crate::{
+    params, probe, ApproximateMembership, BloomError, BloomFilter, BloomFilterBuilder, DefaultBuildHasher, Result,
+};
+
+/// A filter split into independent [`BloomFilter`] shards, each holding the
+/// items that a separate hash routes to it.
+///
+/// Every item lives in exactly one shard, so a lookup touches one shard, and
+/// each shard is sized for its share of the items at the full false positive
+/// probability. Because the shards share nothing, threads can each own one
+/// (through [`shards_mut`](ShardedBloomFilter::shards_mut)) and insert into
+/// it without synchronization, routing items with
+/// [`shard_index`](ShardedBloomFilter::shard_index). With a shard per core
+/// or NUMA node, each core's inserts and lookups stay in memory no other
+/// core writes to.
+///
+/// ```
+/// use bloom::ShardedBloomFilter;
+///
+/// let mut filter = ShardedBloomFilter::<u64>::new(10_000, 0.01, 4);
+/// for i in 0..10_000 {
+///     filter.insert(&i);
+/// }
+/// assert!(filter.contains(&42));
+/// assert!(!filter.contains(&10_042));
+/// ```
+#[derive(Debug)]
+pub struct ShardedBloomFilter<T, S = DefaultBuildHasher> {
+    shards: Vec<BloomFilter<T, S>>,
+    item_count: usize,
+    false_positive_prob: f64,
+    seed: u64,
+    hash_builder: S,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for ShardedBloomFilter<T, S> {
+    fn clone(&self) -> ShardedBloomFilter<T, S> {
+        ShardedBloomFilter {
+            shards: self.shards.clone(),
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+impl<T: Hash> ShardedBloomFilter<T> {
+    /// Creates a filter of `shards` shards, sized to hold `item_count` items
+    /// between them with the given false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` or `shards` is 0, if `false_positive_prob` is
+    /// not strictly between 0 and 1, or if the shards are too large to
+    /// allocate.
+    pub fn new(item_count: usize, false_positive_prob: f64, shards: usize) -> ShardedBloomFilter<T> {
+        ShardedBloomFilter::with_hasher(item_count, false_positive_prob, shards, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](ShardedBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, shards: usize, seed: u64) -> ShardedBloomFilter<T> {
+        let mut filter = ShardedBloomFilter::new(item_count, false_positive_prob, shards);
+        filter.seed = seed;
+        filter.shards = filter.build(shards);
+        filter
+    }
+}
+
+impl<T: Hash, S: BuildHasher + Clone> ShardedBloomFilter<T, S> {
+    /// Like [`new`](ShardedBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(
+        item_count: usize,
+        false_positive_prob: f64,
+        shards: usize,
+        hash_builder: S,
+    ) -> ShardedBloomFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        assert!(shards > 0, "there must be at least one shard");
+        let mut filter = ShardedBloomFilter {
+            shards: Vec::new(),
+            item_count,
+            false_positive_prob,
+            seed: 0,
+            hash_builder,
+        };
+        filter.shards = filter.build(shards);
+        filter
+    }
+
+    /// Records `item` in its shard, returning `true` if it was probably
+    /// already present.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let index = self.shard_index(item);
+        self.shards[index].insert(item)
+    }
+
+    /// Returns `true` if `item` has probably been added, and `false` if it
+    /// definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.shards[self.shard_index(item)].contains(item)
+    }
+
+    /// The index of the shard `item` belongs in.
+    ///
+    /// The routing hash is independent of the bits the item sets within its
+    /// shard, so each shard's bits are used evenly.
+    pub fn shard_index<Q: ?Sized + Hash>(&self, item: &Q) -> usize
+    where
+        T: Borrow<Q>,
+    {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        hasher.write_u8(0xfb);
+        probe::reduce(hasher.finish(), self.shards.len() as u64) as usize
+    }
+
+    /// The shards, in routing order.
+    pub fn shards(&self) -> &[BloomFilter<T, S>] {
+        &self.shards
+    }
+
+    /// The shards, mutably, so that each can be handed to a different
+    /// thread. Only insert into a shard the items
+    /// [`shard_index`](ShardedBloomFilter::shard_index) routes to it, or
+    /// lookups through this filter won't find them.
+    pub fn shards_mut(&mut self) -> &mut [BloomFilter<T, S>] {
+        &mut self.shards
+    }
+
+    /// Adds every item in `other` to this filter, shard by shard, so that it
+    /// holds the union of both sets.
+    ///
+    /// Both filters must have the same number of shards, and their shards
+    /// must be compatible as for [`BloomFilter::try_union`]; otherwise
+    /// [`BloomError::Incompatible`] is returned and this filter is unchanged.
+    pub fn try_union(&mut self, other: &ShardedBloomFilter<T, S>) -> Result<()> {
+        self.check_compatible(other)?;
+        for (shard, other) in self.shards.iter_mut().zip(&other.shards) {
+            shard.try_union(other)?;
+        }
+        Ok(())
+    }
+
+    /// Keeps only the bits set in both this filter and `other`, shard by
+    /// shard, approximating the intersection of their sets.
+    pub fn try_intersect(&mut self, other: &ShardedBloomFilter<T, S>) -> Result<()> {
+        self.check_compatible(other)?;
+        for (shard, other) in self.shards.iter_mut().zip(&other.shards) {
+            shard.try_intersect(other)?;
+        }
+        Ok(())
+    }
+
+    /// Removes every item from every shard, keeping their parameters.
+    pub fn clear(&mut self) {
+        for shard in &mut self.shards {
+            shard.clear();
+        }
+    }
+
+    /// Returns `true` if no shard holds any items.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(BloomFilter::is_empty)
+    }
+
+    /// The number of bits set across all shards.
+    pub fn count_ones(&self) -> u64 {
+        self.shards.iter().map(BloomFilter::count_ones).sum()
+    }
+
+    /// The fraction of all the shards' bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.bit_vec_size() as f64
+    }
+
+    /// Estimates the false positive probability of the filter as it is now:
+    /// the mean of the shards', since a lookup is routed to each equally
+    /// often.
+    pub fn current_fpr(&self) -> f64 {
+        let sum: f64 = self.shards.iter().map(BloomFilter::current_fpr).sum();
+        sum / self.shards.len() as f64
+    }
+
+    /// Estimates how many distinct items have been added, summed over the
+    /// shards.
+    pub fn estimated_len(&self) -> f64 {
+        self.shards.iter().map(BloomFilter::estimated_len).sum()
+    }
+
+    /// The number of bits in all the shards.
+    pub fn bit_vec_size(&self) -> u64 {
+        self.shards.iter().map(BloomFilter::bit_vec_size).sum()
+    }
+
+    /// The number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The number of items the filter was sized for.
+    pub fn capacity(&self) -> usize {
+        self.item_count
+    }
+
+    /// The false positive probability the filter was sized for.
+    pub fn false_positive_prob(&self) -> f64 {
+        self.false_positive_prob
+    }
+
+    /// The number of bytes of memory the filter and its shards occupy.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + self.shards.iter().map(BloomFilter::memory_bytes).sum::<usize>()
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Creates `count` empty shards, each holding its share of the items.
+    fn build(&self, count: usize) -> Vec<BloomFilter<T, S>> {
+        let item_count = self.item_count.div_ceil(count);
+        (0..count)
+            .map(|_| {
+                let shard = BloomFilterBuilder::new()
+                    .hasher(self.hash_builder.clone())
+                    .item_count(item_count)
+                    .false_positive_prob(self.false_positive_prob)
+                    .seed(self.seed)
+                    .build();
+                match shard {
+                    Ok(shard) => shard,
+                    Err(e) => panic!("{}", e),
+                }
+            })
+            .collect()
+    }
+
+    fn check_compatible(&self, other: &ShardedBloomFilter<T, S>) -> Result<()> {
+        if self.shards.len() != other.shards.len() {
+            return Err(BloomError::Incompatible(format!(
+                "shard counts differ ({} and {})",
+                self.shards.len(),
+                other.shards.len()
+            )));
+        }
+        for (shard, other) in self.shards.iter().zip(&other.shards) {
+            shard.check_compatible(other)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for ShardedBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher + Clone,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(ShardedBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        ShardedBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        ShardedBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        ShardedBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        ShardedBloomFilter::memory_bytes(self)
+    }
+}
@@ -0,0 +1,253 @@
+//! Bit testing kernels with explicit SIMD paths: AVX2 on x86-64 when the CPU
+//! has it, NEON on AArch64, and portable scalar code everywhere else. Also
+//! prefetch hints, so batch lookups can start loading bits early.
+//!
+//! Only the bit tests are vectorized. Items are still hashed one at a time,
+//! since they go through the filter's own `BuildHasher`, which has no batch
+//! interface.
+//!
+//! With `std`, AVX2 is detected at runtime (the result is cached by the
+//! standard library); without it, only when the crate is compiled for a CPU
+//! that has it. Every path gives exactly the same results.
+
+use crate::split_block::SALT;
+
+/// Returns `true` if every bit in `indices` is set in `words`.
+///
+/// Every index must be less than `64 * words.len()`.
+#[inline]
+pub(crate) fn all_set(words: &[u64], indices: &[u64]) -> bool {
+    debug_assert!(indices.iter().all(|&index| index / 64 < words.len() as u64));
+    #[cfg(target_arch = "x86_64")]
+    {
+        if has_avx2() {
+            // SAFETY: AVX2 is available, and the indices are in bounds.
+            return unsafe { avx2::all_set(words, indices) };
+        }
+    }
+    indices.iter().all(|&index| words[(index / 64) as usize] & (1 << (index % 64)) != 0)
+}
+
+/// Returns `true` if every bit set in `mask` is set in `words`.
+#[inline]
+pub(crate) fn covers(words: &[u64; 8], mask: &[u64; 8]) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if has_avx2() {
+            // SAFETY: AVX2 is available.
+            return unsafe { avx2::covers(words, mask) };
+        }
+    }
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    {
+        // SAFETY: NEON is enabled at compile time.
+        return unsafe { neon::covers(words, mask) };
+    }
+    #[allow(unreachable_code)]
+    words.iter().zip(mask.iter()).all(|(&word, &mask)| word & mask == mask)
+}
+
+/// Returns `true` if every bit `key` maps to in a split block is set.
+#[inline]
+pub(crate) fn split_block_contains(block: &[u32; 8], key: u32) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if has_avx2() {
+            // SAFETY: AVX2 is available.
+            return unsafe { avx2::split_block_contains(block, key) };
+        }
+    }
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    {
+        // SAFETY: NEON is enabled at compile time.
+        return unsafe { neon::split_block_contains(block, key) };
+    }
+    #[allow(unreachable_code)]
+    block.iter().zip(split_block_mask(key).iter()).all(|(&word, &mask)| word & mask != 0)
+}
+
+/// Sets every bit `key` maps to in a split block, returning whether they
+/// were all set already.
+#[inline]
+pub(crate) fn split_block_insert(block: &mut [u32; 8], key: u32) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if has_avx2() {
+            // SAFETY: AVX2 is available.
+            return unsafe { avx2::split_block_insert(block, key) };
+        }
+    }
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    {
+        // SAFETY: NEON is enabled at compile time.
+        return unsafe { neon::split_block_insert(block, key) };
+    }
+    #[allow(unreachable_code)]
+    {
+        let mut present = true;
+        for (word, mask) in block.iter_mut().zip(split_block_mask(key).iter()) {
+            if *word & mask == 0 {
+                *word |= mask;
+                present = false;
+            }
+        }
+        present
+    }
+}
+
+/// Hints to the CPU that the cache line holding `value` will be read soon,
+/// so that it can start loading it. Does nothing on architectures without a
+/// prefetch instruction.
+#[inline]
+pub(crate) fn prefetch<T>(value: &T) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        // SAFETY: prefetching never faults, and SSE is part of x86-64.
+        unsafe { _mm_prefetch::<_MM_HINT_T0>(value as *const T as *const i8) };
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        // SAFETY: prefetching never faults or changes any state the program
+        // can observe.
+        unsafe {
+            core::arch::asm!(
+                "prfm pldl1keep, [{}]",
+                in(reg) value as *const T,
+                options(nostack, readonly, preserves_flags)
+            )
+        };
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let _ = value;
+}
+
+/// The bit `key` sets in each word of a split block: the top 5 bits of
+/// `key` times that word's salt.
+fn split_block_mask(key: u32) -> [u32; 8] {
+    let mut mask = [0; 8];
+    for (bit, salt) in mask.iter_mut().zip(SALT.iter()) {
+        *bit = 1 << (key.wrapping_mul(*salt) >> 27);
+    }
+    mask
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline]
+fn has_avx2() -> bool {
+    #[cfg(feature = "std")]
+    {
+        std::is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        cfg!(target_feature = "avx2")
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use core::arch::x86_64::*;
+
+    use super::SALT;
+
+    /// Tests four indices at a time, gathering the words they fall in.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn all_set(words: &[u64], indices: &[u64]) -> bool {
+        let chunks = indices.chunks_exact(4);
+        let rest = chunks.remainder();
+        let low_bits = _mm256_set1_epi64x(63);
+        let one = _mm256_set1_epi64x(1);
+        for chunk in chunks {
+            let indices = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+            let word_indices = _mm256_srli_epi64(indices, 6);
+            let gathered = _mm256_i64gather_epi64(words.as_ptr() as *const i64, word_indices, 8);
+            let bits = _mm256_and_si256(_mm256_srlv_epi64(gathered, _mm256_and_si256(indices, low_bits)), one);
+            // All four bits are set exactly when `bits` covers `one`.
+            if _mm256_testc_si256(bits, one) == 0 {
+                return false;
+            }
+        }
+        rest.iter().all(|&index| words[(index / 64) as usize] & (1 << (index % 64)) != 0)
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn covers(words: &[u64; 8], mask: &[u64; 8]) -> bool {
+        let words = words.as_ptr() as *const __m256i;
+        let mask = mask.as_ptr() as *const __m256i;
+        _mm256_testc_si256(_mm256_loadu_si256(words), _mm256_loadu_si256(mask)) != 0
+            && _mm256_testc_si256(_mm256_loadu_si256(words.add(1)), _mm256_loadu_si256(mask.add(1))) != 0
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn split_block_contains(block: &[u32; 8], key: u32) -> bool {
+        let block = _mm256_loadu_si256(block.as_ptr() as *const __m256i);
+        _mm256_testc_si256(block, split_block_mask(key)) != 0
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn split_block_insert(block: &mut [u32; 8], key: u32) -> bool {
+        let ptr = block.as_mut_ptr() as *mut __m256i;
+        let words = _mm256_loadu_si256(ptr);
+        let mask = split_block_mask(key);
+        _mm256_storeu_si256(ptr, _mm256_or_si256(words, mask));
+        _mm256_testc_si256(words, mask) != 0
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn split_block_mask(key: u32) -> __m256i {
+        let salt = _mm256_loadu_si256(SALT.as_ptr() as *const __m256i);
+        let shifts = _mm256_srli_epi32(_mm256_mullo_epi32(_mm256_set1_epi32(key as i32), salt), 27);
+        _mm256_sllv_epi32(_mm256_set1_epi32(1), shifts)
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+mod neon {
+    use core::arch::aarch64::*;
+
+    use super::SALT;
+
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn covers(words: &[u64; 8], mask: &[u64; 8]) -> bool {
+        let mut covered = vdupq_n_u64(u64::MAX);
+        for i in (0..8).step_by(2) {
+            let mask = vld1q_u64(mask.as_ptr().add(i));
+            let words = vld1q_u64(words.as_ptr().add(i));
+            covered = vandq_u64(covered, vceqq_u64(vandq_u64(words, mask), mask));
+        }
+        vminvq_u32(vreinterpretq_u32_u64(covered)) == u32::MAX
+    }
+
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn split_block_contains(block: &[u32; 8], key: u32) -> bool {
+        let (low, high) = split_block_mask(key);
+        covers_halves(vld1q_u32(block.as_ptr()), vld1q_u32(block.as_ptr().add(4)), low, high)
+    }
+
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn split_block_insert(block: &mut [u32; 8], key: u32) -> bool {
+        let (low, high) = split_block_mask(key);
+        let ptr = block.as_mut_ptr();
+        let (words_low, words_high) = (vld1q_u32(ptr), vld1q_u32(ptr.add(4)));
+        vst1q_u32(ptr, vorrq_u32(words_low, low));
+        vst1q_u32(ptr.add(4), vorrq_u32(words_high, high));
+        covers_halves(words_low, words_high, low, high)
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn covers_halves(words_low: uint32x4_t, words_high: uint32x4_t, low: uint32x4_t, high: uint32x4_t) -> bool {
+        let covered_low = vceqq_u32(vandq_u32(words_low, low), low);
+        let covered_high = vceqq_u32(vandq_u32(words_high, high), high);
+        vminvq_u32(vandq_u32(covered_low, covered_high)) == u32::MAX
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn split_block_mask(key: u32) -> (uint32x4_t, uint32x4_t) {
+        let key = vdupq_n_u32(key);
+        let one = vdupq_n_u32(1);
+        let low = vshrq_n_u32::<27>(vmulq_u32(key, vld1q_u32(SALT.as_ptr())));
+        let high = vshrq_n_u32::<27>(vmulq_u32(key, vld1q_u32(SALT.as_ptr().add(4))));
+        (vshlq_u32(one, vreinterpretq_s32_u32(low)), vshlq_u32(one, vreinterpretq_s32_u32(high)))
+    }
+}
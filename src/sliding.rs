@@ -0,0 +1,382 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use crate::{math, params, ApproximateMembership, BloomFilter, BloomFilterBuilder, DefaultBuildHasher, Result};
+
+/// The number of generations used unless configured otherwise.
+const DEFAULT_GENERATIONS: usize = 4;
+
+/// How much of a stream a [`SlidingBloomFilter`] remembers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Window {
+    /// The last `n` insertions, counting repeats.
+    Items(u64),
+    /// The last `d` units of time, in whatever unit the caller passes times
+    /// in.
+    Time(u64),
+}
+
+impl Window {
+    fn len(self) -> u64 {
+        match self {
+            Window::Items(len) | Window::Time(len) => len,
+        }
+    }
+}
+
+/// A filter answering "seen within the last `W` items" or "seen within the
+/// last `W` minutes", built from a ring of [`BloomFilter`] generations.
+///
+/// Insertions go to the newest generation, and lookups check them all. Once
+/// the newest generation has covered `W / (N - 1)` insertions or units of
+/// time for `N` generations, the oldest one is cleared and becomes the
+/// newest. Every item inserted within the window is therefore present, and
+/// items are forgotten at most `W / (N - 1)` past it. More generations make
+/// expiry more precise at the cost of more lookups and, since each one has
+/// its own share of the false positive probability, a little more memory.
+///
+/// Time is whatever the caller passes to [`insert_at`](SlidingBloomFilter::insert_at)
+/// and [`advance`](SlidingBloomFilter::advance), as long as it doesn't go
+/// backwards; [`insert`](SlidingBloomFilter::insert) and
+/// [`contains`](SlidingBloomFilter::contains) use the latest time given.
+///
+/// ```
+/// use bloom::{SlidingBloomFilter, Window};
+///
+/// let mut recent = SlidingBloomFilter::<u64>::new(1000, 0.01, Window::Items(1000));
+/// for i in 0..10_000 {
+///     recent.insert(&i);
+/// }
+/// assert!(recent.contains(&9000));
+/// assert!(!recent.contains(&5000));
+/// ```
+#[derive(Debug)]
+pub struct SlidingBloomFilter<T, S = DefaultBuildHasher> {
+    /// A ring of generations, the newest at index `newest`.
+    generations: Vec<BloomFilter<T, S>>,
+    newest: usize,
+    /// The number of insertions into the newest generation.
+    newest_len: u64,
+    /// The latest time the filter has been given, and the index of the time
+    /// span the newest generation covers.
+    now: u64,
+    epoch: u64,
+    window: Window,
+    span: u64,
+    item_count: usize,
+    false_positive_prob: f64,
+    seed: u64,
+    hash_builder: S,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for SlidingBloomFilter<T, S> {
+    fn clone(&self) -> SlidingBloomFilter<T, S> {
+        SlidingBloomFilter {
+            generations: self.generations.clone(),
+            newest: self.newest,
+            newest_len: self.newest_len,
+            now: self.now,
+            epoch: self.epoch,
+            window: self.window,
+            span: self.span,
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+impl<T: Hash> SlidingBloomFilter<T> {
+    /// Creates a filter remembering `window`, sized for `item_count`
+    /// distinct items within any one window with an overall false positive
+    /// probability of `false_positive_prob`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` or the window is 0, if `false_positive_prob`
+    /// is not strictly between 0 and 1, or if the generations are too large
+    /// to allocate.
+    pub fn new(item_count: usize, false_positive_prob: f64, window: Window) -> SlidingBloomFilter<T> {
+        SlidingBloomFilter::with_hasher(item_count, false_positive_prob, window, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](SlidingBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, window: Window, seed: u64) -> SlidingBloomFilter<T> {
+        let mut filter = SlidingBloomFilter::new(item_count, false_positive_prob, window);
+        filter.seed = seed;
+        filter.generations = filter.build(filter.generations.len());
+        filter
+    }
+}
+
+impl<T: Hash, S: BuildHasher + Clone> SlidingBloomFilter<T, S> {
+    /// Like [`new`](SlidingBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(
+        item_count: usize,
+        false_positive_prob: f64,
+        window: Window,
+        hash_builder: S,
+    ) -> SlidingBloomFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        assert!(window.len() > 0, "the window must be nonzero");
+        let mut filter = SlidingBloomFilter {
+            generations: Vec::new(),
+            newest: 0,
+            newest_len: 0,
+            now: 0,
+            epoch: 0,
+            window,
+            span: 0,
+            item_count,
+            false_positive_prob,
+            seed: 0,
+            hash_builder,
+        };
+        filter.generations = filter.build(DEFAULT_GENERATIONS);
+        filter
+    }
+
+    /// Sets the number of generations the window is split across. Defaults
+    /// to 4.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `generations` is less than 2, or if items have already
+    /// been added.
+    pub fn with_generations(mut self, generations: usize) -> SlidingBloomFilter<T, S> {
+        assert!(generations >= 2, "there must be at least 2 generations (got {})", generations);
+        assert!(self.is_empty(), "the filter must be configured before adding items");
+        self.generations = self.build(generations);
+        self.newest = 0;
+        self
+    }
+
+    /// Records `item` at the latest time the filter has been given,
+    /// returning `true` if it was probably seen within the window before.
+    ///
+    /// An item that was already present is still recorded, so that it stays
+    /// for another full window.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let present = self.contains(item);
+        if let Window::Items(_) = self.window {
+            if self.newest_len >= self.span {
+                self.rotate();
+            }
+        }
+        self.generations[self.newest].add(item);
+        self.newest_len += 1;
+        present
+    }
+
+    /// Like [`insert`](SlidingBloomFilter::insert), but first moves the
+    /// filter's clock on to `now`.
+    pub fn insert_at<Q: ?Sized + Hash>(&mut self, item: &Q, now: u64) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.advance(now);
+        self.insert(item)
+    }
+
+    /// Returns `true` if `item` was probably inserted within the window, and
+    /// `false` if it definitely was not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.contains_at(item, self.now)
+    }
+
+    /// Like [`contains`](SlidingBloomFilter::contains), but as of time
+    /// `now`, skipping the generations that would have expired by then.
+    pub fn contains_at<Q: ?Sized + Hash>(&self, item: &Q, now: u64) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let live = match self.window {
+            Window::Items(_) => self.generations.len(),
+            Window::Time(_) => {
+                let passed = cmp::max(now, self.now) / self.span - self.epoch;
+                self.generations.len().saturating_sub(cmp::min(passed, usize::MAX as u64) as usize)
+            }
+        };
+        // Newest first, since recent items are the likeliest to be looked up.
+        (0..live).any(|age| self.generation(age).contains(item))
+    }
+
+    /// Moves the filter's clock on to `now`, expiring the generations that
+    /// are older than the window by then. Has no effect on a window of
+    /// items, or if `now` is earlier than a time already given.
+    pub fn advance(&mut self, now: u64) {
+        if now <= self.now {
+            return;
+        }
+        self.now = now;
+        if let Window::Time(_) = self.window {
+            let epoch = now / self.span;
+            let passed = cmp::min(epoch - self.epoch, self.generations.len() as u64);
+            for _ in 0..passed {
+                self.rotate();
+            }
+            self.epoch = epoch;
+        }
+    }
+
+    /// Removes every item, keeping the clock and configuration.
+    pub fn clear(&mut self) {
+        for generation in &mut self.generations {
+            generation.clear();
+        }
+        self.newest_len = 0;
+    }
+
+    /// Returns `true` if no generation holds any items.
+    pub fn is_empty(&self) -> bool {
+        self.generations.iter().all(BloomFilter::is_empty)
+    }
+
+    /// Estimates the false positive probability of the filter as it is now:
+    /// the chance that any generation gives a false positive.
+    pub fn current_fpr(&self) -> f64 {
+        1.0 - self
+            .generations
+            .iter()
+            .map(|generation| 1.0 - generation.current_fpr())
+            .product::<f64>()
+    }
+
+    /// Estimates the number of distinct items in the filter, counting an
+    /// item once for each generation it was inserted in.
+    pub fn estimated_len(&self) -> f64 {
+        self.generations.iter().map(BloomFilter::estimated_len).sum()
+    }
+
+    /// The number of generations.
+    pub fn generation_count(&self) -> usize {
+        self.generations.len()
+    }
+
+    /// The generations, newest first.
+    pub fn generations(&self) -> impl Iterator<Item = &BloomFilter<T, S>> {
+        (0..self.generations.len()).map(move |age| self.generation(age))
+    }
+
+    /// How much of the stream the filter remembers.
+    pub fn window(&self) -> Window {
+        self.window
+    }
+
+    /// The number of insertions or units of time each generation covers.
+    pub fn span(&self) -> u64 {
+        self.span
+    }
+
+    /// The latest time the filter has been given.
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// The number of distinct items per window the filter was sized for.
+    pub fn item_count(&self) -> usize {
+        self.item_count
+    }
+
+    /// The false positive probability the filter was sized for.
+    pub fn false_positive_prob(&self) -> f64 {
+        self.false_positive_prob
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Creates `count` empty generations, each holding its share of the
+    /// window's items and of the false positive probability.
+    fn build(&mut self, count: usize) -> Vec<BloomFilter<T, S>> {
+        self.span = self.window.len().div_ceil(count as u64 - 1);
+        self.epoch = self.now / self.span;
+        let item_count = self.item_count.div_ceil(count - 1);
+        let false_positive_prob = 1.0 - math::powf(1.0 - self.false_positive_prob, 1.0 / count as f64);
+        (0..count)
+            .map(|_| {
+                let generation = BloomFilterBuilder::new()
+                    .hasher(self.hash_builder.clone())
+                    .item_count(item_count)
+                    .false_positive_prob(false_positive_prob)
+                    .seed(self.seed)
+                    .build();
+                match generation {
+                    Ok(generation) => generation,
+                    Err(e) => panic!("{}", e),
+                }
+            })
+            .collect()
+    }
+
+    /// The generation `age` rotations older than the newest.
+    fn generation(&self, age: usize) -> &BloomFilter<T, S> {
+        let count = self.generations.len();
+        &self.generations[(self.newest + count - age) % count]
+    }
+
+    /// Clears the oldest generation and makes it the newest.
+    fn rotate(&mut self) {
+        self.newest = (self.newest + 1) % self.generations.len();
+        self.generations[self.newest].clear();
+        self.newest_len = 0;
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for SlidingBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher + Clone,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(SlidingBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        SlidingBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        SlidingBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        SlidingBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self)
+            + self
+                .generations
+                .iter()
+                .map(|generation| ApproximateMembership::<Q>::memory_bytes(generation))
+                .sum::<usize>()
+    }
+}
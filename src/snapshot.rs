@@ -0,0 +1,462 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::bit_vec::{self, BitVec};
+use crate::probe::Probes;
+use crate::{math, params, ApproximateMembership, BloomFilter, DefaultBuildHasher, Result};
+
+/// The number of words in a page: 8 KiB, copied whole the first time it is
+/// written after a snapshot.
+const PAGE_WORDS: usize = 1024;
+
+type Page = [u64; PAGE_WORDS];
+
+/// A bloom filter whose [`snapshot`](CowBloomFilter::snapshot)s are cheap,
+/// immutable and unaffected by later inserts.
+///
+/// The bits are kept in 8 KiB pages behind reference counts. A snapshot
+/// shares every page with the filter rather than copying the bits, and an
+/// insert copies a page only if a live snapshot still shares it, so each
+/// page is copied at most once per snapshot. A writer thread can keep
+/// inserting while reader threads query their own [`BloomSnapshot`]s, with
+/// no locks and no torn reads, and publish a fresh snapshot whenever
+/// readers should see new items.
+///
+/// Items map to the same bits as in a [`BloomFilter`] of the same
+/// parameters.
+///
+/// ```
+/// use bloom::CowBloomFilter;
+///
+/// let mut filter = CowBloomFilter::<&str>::new(1000, 0.01);
+/// filter.insert(&"apple");
+/// let snapshot = filter.snapshot();
+/// filter.insert(&"pear");
+/// assert!(snapshot.contains(&"apple"));
+/// assert!(!snapshot.contains(&"pear"));
+/// assert!(filter.contains(&"pear"));
+/// ```
+#[derive(Debug)]
+pub struct CowBloomFilter<T, S = DefaultBuildHasher> {
+    pages: Vec<Arc<Page>>,
+    layout: Layout,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+/// An immutable view of a [`CowBloomFilter`] at the moment
+/// [`snapshot`](CowBloomFilter::snapshot) was called.
+///
+/// A snapshot holds references to the filter's pages, so it takes almost no
+/// memory of its own until the filter is written to, and it can be cloned
+/// and sent to other threads cheaply.
+#[derive(Debug)]
+pub struct BloomSnapshot<T, S = DefaultBuildHasher> {
+    pages: Vec<Arc<Page>>,
+    layout: Layout,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+/// The parameters a filter and its snapshots share.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    bit_vec_size: u64,
+    hash_count: usize,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    seed: u64,
+    wide_hashes: bool,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for CowBloomFilter<T, S> {
+    fn clone(&self) -> CowBloomFilter<T, S> {
+        CowBloomFilter {
+            pages: self.pages.clone(),
+            layout: self.layout,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for BloomSnapshot<T, S> {
+    fn clone(&self) -> BloomSnapshot<T, S> {
+        BloomSnapshot {
+            pages: self.pages.clone(),
+            layout: self.layout,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> CowBloomFilter<T> {
+    /// Creates a filter sized to hold `item_count` items with the given
+    /// false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0 or `false_positive_prob` is not strictly
+    /// between 0 and 1.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> CowBloomFilter<T> {
+        CowBloomFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](CowBloomFilter::new), but mixes `seed` into every hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> CowBloomFilter<T> {
+        let mut filter = CowBloomFilter::new(item_count, false_positive_prob);
+        filter.layout.seed = seed;
+        filter
+    }
+
+    /// Creates a filter with exactly `bits` bits and `hashes` hash functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` or `hashes` is 0, or if `bits` is more than
+    /// [`MAX_BITS`](crate::params::MAX_BITS).
+    pub fn from_params(bits: u64, hashes: usize) -> CowBloomFilter<T> {
+        CowBloomFilter::from_params_with_hasher(bits, hashes, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> CowBloomFilter<T, S> {
+    /// Like [`new`](CowBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> CowBloomFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let bits = params::optimal_bits(item_count, false_positive_prob);
+        let hashes = params::optimal_hashes(bits, item_count);
+        let mut filter = CowBloomFilter::from_params_with_hasher(bits, hashes, hash_builder);
+        filter.layout.item_count = Some(item_count);
+        filter.layout.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](CowBloomFilter::from_params), but hashes items
+    /// with `hash_builder`.
+    pub fn from_params_with_hasher(bits: u64, hashes: usize, hash_builder: S) -> CowBloomFilter<T, S> {
+        if let Err(e) = params::validate_layout(bits, hashes) {
+            panic!("{}", e);
+        }
+        let pages = bit_vec::word_count(bits).div_ceil(PAGE_WORDS);
+        CowBloomFilter {
+            pages: (0..pages).map(|_| Arc::new([0; PAGE_WORDS])).collect(),
+            layout: Layout {
+                bit_vec_size: bits,
+                hash_count: hashes,
+                item_count: None,
+                false_positive_prob: None,
+                seed: 0,
+                wide_hashes: bits > params::WIDE_HASH_THRESHOLD,
+            },
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Converts a [`BloomFilter`] into one holding the same items whose
+    /// snapshots are cheap.
+    pub fn from_bloom_filter(filter: BloomFilter<T, S>) -> CowBloomFilter<T, S> {
+        let layout = Layout {
+            bit_vec_size: filter.bit_vec_size(),
+            hash_count: filter.hash_count(),
+            item_count: filter.capacity(),
+            false_positive_prob: filter.false_positive_prob(),
+            seed: filter.seed(),
+            wide_hashes: filter.wide_hashes(),
+        };
+        let (bit_vec, hash_builder) = filter.into_bits_and_hasher();
+        let pages = bit_vec
+            .words()
+            .chunks(PAGE_WORDS)
+            .map(|words| {
+                let mut page = [0; PAGE_WORDS];
+                page[..words.len()].copy_from_slice(words);
+                Arc::new(page)
+            })
+            .collect();
+        CowBloomFilter {
+            pages,
+            layout,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present.
+    ///
+    /// Each page the item's bits fall in is copied first if a snapshot still
+    /// shares it.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let mut present = true;
+        for index in self.layout.probes(&self.hash_builder, item) {
+            let (page, word, mask) = locate(index);
+            if self.pages[page][word] & mask == 0 {
+                Arc::make_mut(&mut self.pages[page])[word] |= mask;
+                present = false;
+            }
+        }
+        present
+    }
+
+    /// Returns `true` if `item` has probably been added, and `false` if it
+    /// definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.layout.contains(&self.pages, &self.hash_builder, item)
+    }
+
+    /// Takes an immutable view of the filter as it is now, sharing its bits.
+    pub fn snapshot(&self) -> BloomSnapshot<T, S>
+    where
+        S: Clone,
+    {
+        BloomSnapshot {
+            pages: self.pages.clone(),
+            layout: self.layout,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Removes every item from the filter, keeping its parameters.
+    /// Snapshots keep the items they had.
+    pub fn clear(&mut self) {
+        for page in &mut self.pages {
+            match Arc::get_mut(page) {
+                Some(page) => *page = [0; PAGE_WORDS],
+                None => *page = Arc::new([0; PAGE_WORDS]),
+            }
+        }
+    }
+
+    /// Returns `true` if nothing has been added since the filter was created
+    /// or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.count_ones() == 0
+    }
+
+    /// The number of bits that are set.
+    pub fn count_ones(&self) -> u64 {
+        count_ones(&self.pages)
+    }
+
+    /// The fraction of bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.layout.bit_vec_size as f64
+    }
+
+    /// Estimates the probability that a lookup of an absent item returns
+    /// `true`, as the filter is now.
+    pub fn current_fpr(&self) -> f64 {
+        self.layout.current_fpr(self.count_ones())
+    }
+
+    /// Estimates how many distinct items have been added, from the number of
+    /// bits that are set.
+    pub fn estimated_len(&self) -> f64 {
+        self.layout.estimated_len(self.count_ones())
+    }
+
+    /// The number of bits in the filter.
+    pub fn bit_vec_size(&self) -> u64 {
+        self.layout.bit_vec_size
+    }
+
+    /// The number of hash functions.
+    pub fn hash_count(&self) -> usize {
+        self.layout.hash_count
+    }
+
+    /// The false positive probability the filter was sized for, if it was
+    /// created from one.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.layout.false_positive_prob
+    }
+
+    /// The number of items the filter was sized for, if it was created from
+    /// one.
+    pub fn capacity(&self) -> Option<usize> {
+        self.layout.item_count
+    }
+
+    /// The number of pages a snapshot still shares with the filter, which
+    /// the next writes to them will copy.
+    pub fn shared_pages(&self) -> usize {
+        self.pages.iter().filter(|page| Arc::strong_count(page) > 1).count()
+    }
+
+    /// The number of bytes of memory the filter occupies, counting pages it
+    /// shares with snapshots.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(&*self.pages) + self.pages.len() * mem::size_of::<Page>()
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.layout.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+}
+
+impl<T: Hash, S: BuildHasher> BloomSnapshot<T, S> {
+    /// Returns `true` if `item` had probably been added when the snapshot was
+    /// taken, and `false` if it definitely had not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.layout.contains(&self.pages, &self.hash_builder, item)
+    }
+
+    /// The number of bits that are set.
+    pub fn count_ones(&self) -> u64 {
+        count_ones(&self.pages)
+    }
+
+    /// Estimates the probability that a lookup of an absent item returns
+    /// `true`.
+    pub fn current_fpr(&self) -> f64 {
+        self.layout.current_fpr(self.count_ones())
+    }
+
+    /// Estimates how many distinct items had been added when the snapshot
+    /// was taken.
+    pub fn estimated_len(&self) -> f64 {
+        self.layout.estimated_len(self.count_ones())
+    }
+
+    /// The number of bits in the filter.
+    pub fn bit_vec_size(&self) -> u64 {
+        self.layout.bit_vec_size
+    }
+
+    /// The number of hash functions.
+    pub fn hash_count(&self) -> usize {
+        self.layout.hash_count
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.layout.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Copies the snapshot into a [`BloomFilter`] holding the same items.
+    pub fn to_bloom_filter(&self) -> BloomFilter<T, S>
+    where
+        S: Clone,
+    {
+        let mut bit_vec = BitVec::new(self.layout.bit_vec_size);
+        let words = self.pages.iter().flat_map(|page| page.iter());
+        for (word, &page_word) in bit_vec.words_mut().iter_mut().zip(words) {
+            *word = page_word;
+        }
+        BloomFilter::from_parts(
+            bit_vec,
+            self.layout.hash_count,
+            self.layout.item_count,
+            self.layout.false_positive_prob,
+            self.layout.seed,
+            self.hash_builder.clone(),
+        )
+        .with_wide_hashes(self.layout.wide_hashes)
+    }
+}
+
+impl Layout {
+    /// The bit indices `item` maps to, as in a [`BloomFilter`].
+    fn probes<Q: ?Sized + Hash, S: BuildHasher>(&self, hash_builder: &S, item: &Q) -> Probes<S::Hasher> {
+        let mut hasher = hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        Probes::new(hasher, self.bit_vec_size, self.hash_count, self.wide_hashes)
+    }
+
+    fn contains<Q: ?Sized + Hash, S: BuildHasher>(&self, pages: &[Arc<Page>], hash_builder: &S, item: &Q) -> bool {
+        self.probes(hash_builder, item).all(|index| {
+            let (page, word, mask) = locate(index);
+            pages[page][word] & mask != 0
+        })
+    }
+
+    fn current_fpr(&self, ones: u64) -> f64 {
+        math::powf(ones as f64 / self.bit_vec_size as f64, self.hash_count as f64)
+    }
+
+    fn estimated_len(&self, ones: u64) -> f64 {
+        let m = self.bit_vec_size as f64;
+        -m / self.hash_count as f64 * math::ln(1.0 - ones as f64 / m)
+    }
+}
+
+/// The page, word within it and mask of bit `index`.
+fn locate(index: u64) -> (usize, usize, u64) {
+    let word = (index / 64) as usize;
+    (word / PAGE_WORDS, word % PAGE_WORDS, 1 << (index % 64))
+}
+
+fn count_ones(pages: &[Arc<Page>]) -> u64 {
+    pages.iter().flat_map(|page| page.iter()).map(|word| u64::from(word.count_ones())).sum()
+}
+
+impl<T: Hash, S: BuildHasher> From<BloomFilter<T, S>> for CowBloomFilter<T, S> {
+    fn from(filter: BloomFilter<T, S>) -> CowBloomFilter<T, S> {
+        CowBloomFilter::from_bloom_filter(filter)
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for CowBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(CowBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        CowBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        CowBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        CowBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        CowBloomFilter::memory_bytes(self)
+    }
+}
@@ -0,0 +1,333 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::bit_vec::{self, BitVec};
+use crate::probe::Probes;
+use crate::{math, params, ApproximateMembership, BloomFilter, DefaultBuildHasher, Result};
+
+/// How a filter's set bits are stored.
+#[derive(Debug, Clone)]
+enum Bits {
+    /// The indices of the set bits, sorted.
+    Sparse(Vec<u64>),
+    Dense(BitVec),
+}
+
+/// A bloom filter that stores its set bits as a sorted list of indices while
+/// few are set, and switches to a bitmap once the list takes half as much
+/// memory, for keeping many filters that are sized generously but mostly
+/// hold few items.
+///
+/// A filter at 1e-6 sized for a million items has 29 million bits, 3.6MB,
+/// however little it holds; the same filter holding a thousand items takes
+/// 160KB while sparse. Items map to the same bits as in a [`BloomFilter`]
+/// of the same parameters, so answers are exactly the same, and
+/// [`into_bloom_filter`](SparseBloomFilter::into_bloom_filter) converts
+/// one into the other. Inserting into a sparse filter takes time linear in
+/// the number of bits set, since the list is kept sorted for binary search.
+///
+/// ```
+/// use bloom::SparseBloomFilter;
+///
+/// let mut filter = SparseBloomFilter::<u64>::new(1_000_000, 1e-6);
+/// for i in 0..1000 {
+///     filter.insert(&i);
+/// }
+/// assert!(filter.is_sparse());
+/// assert!(filter.contains(&42));
+/// assert!(filter.memory_bytes() < filter.bit_vec_size() as usize / 8 / 10);
+/// ```
+#[derive(Debug)]
+pub struct SparseBloomFilter<T, S = DefaultBuildHasher> {
+    bits: Bits,
+    bit_vec_size: u64,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    hash_count: usize,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for SparseBloomFilter<T, S> {
+    fn clone(&self) -> SparseBloomFilter<T, S> {
+        SparseBloomFilter {
+            bits: self.bits.clone(),
+            bit_vec_size: self.bit_vec_size,
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            hash_count: self.hash_count,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> SparseBloomFilter<T> {
+    /// Creates a filter sized to hold `item_count` items with the given
+    /// false positive probability, starting out sparse.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0 or `false_positive_prob` is not strictly
+    /// between 0 and 1.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> SparseBloomFilter<T> {
+        SparseBloomFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](SparseBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> SparseBloomFilter<T> {
+        let mut filter = SparseBloomFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter with exactly `bits` bits and `hashes` hash functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` or `hashes` is 0, or if `bits` is more than
+    /// [`MAX_BITS`](crate::params::MAX_BITS).
+    pub fn from_params(bits: u64, hashes: usize) -> SparseBloomFilter<T> {
+        SparseBloomFilter::from_params_with_hasher(bits, hashes, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> SparseBloomFilter<T, S> {
+    /// Like [`new`](SparseBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> SparseBloomFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let bits = params::optimal_bits(item_count, false_positive_prob);
+        let mut filter =
+            SparseBloomFilter::from_params_with_hasher(bits, params::optimal_hashes(bits, item_count), hash_builder);
+        filter.item_count = Some(item_count);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](SparseBloomFilter::from_params), but hashes
+    /// items with `hash_builder`.
+    pub fn from_params_with_hasher(bits: u64, hashes: usize, hash_builder: S) -> SparseBloomFilter<T, S> {
+        if let Err(e) = params::validate_layout(bits, hashes) {
+            panic!("{}", e);
+        }
+        SparseBloomFilter {
+            bits: Bits::Sparse(Vec::new()),
+            bit_vec_size: bits,
+            item_count: None,
+            false_positive_prob: None,
+            hash_count: hashes,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let mut present = true;
+        for index in self.probes(item) {
+            let was_set = match &mut self.bits {
+                Bits::Sparse(indices) => match indices.binary_search(&index) {
+                    Ok(_) => true,
+                    Err(position) => {
+                        indices.insert(position, index);
+                        false
+                    }
+                },
+                Bits::Dense(bit_vec) => bit_vec.set(index),
+            };
+            if !was_set {
+                present = false;
+            }
+        }
+        if let Bits::Sparse(indices) = &self.bits {
+            if indices.len() > bit_vec::word_count(self.bit_vec_size) / 2 {
+                self.densify();
+            }
+        }
+        present
+    }
+
+    /// Returns `true` if `item` has probably been added, and `false` if it
+    /// definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        match &self.bits {
+            Bits::Sparse(indices) => self.probes(item).all(|index| indices.binary_search(&index).is_ok()),
+            Bits::Dense(bit_vec) => self.probes(item).all(|index| bit_vec.get(index)),
+        }
+    }
+
+    /// Switches to the bitmap now, rather than when enough bits are set.
+    pub fn densify(&mut self) {
+        if let Bits::Sparse(indices) = &self.bits {
+            let mut bit_vec = BitVec::new(self.bit_vec_size);
+            for &index in indices {
+                bit_vec.set(index);
+            }
+            self.bits = Bits::Dense(bit_vec);
+        }
+    }
+
+    /// Returns `true` while the set bits are stored as a list of indices.
+    pub fn is_sparse(&self) -> bool {
+        matches!(self.bits, Bits::Sparse(_))
+    }
+
+    /// Removes every item from the filter and frees the bitmap, if any, so
+    /// that the filter starts out sparse again.
+    pub fn clear(&mut self) {
+        self.bits = Bits::Sparse(Vec::new());
+    }
+
+    /// Returns `true` if nothing has been added since the filter was created
+    /// or last cleared.
+    pub fn is_empty(&self) -> bool {
+        match &self.bits {
+            Bits::Sparse(indices) => indices.is_empty(),
+            Bits::Dense(bit_vec) => bit_vec.none(),
+        }
+    }
+
+    /// The number of bits that are set.
+    pub fn count_ones(&self) -> u64 {
+        match &self.bits {
+            Bits::Sparse(indices) => indices.len() as u64,
+            Bits::Dense(bit_vec) => bit_vec.count_ones(),
+        }
+    }
+
+    /// The fraction of bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.bit_vec_size as f64
+    }
+
+    /// Estimates the probability that a lookup of an absent item returns
+    /// `true`, as the filter is now.
+    pub fn current_fpr(&self) -> f64 {
+        math::powf(self.fill_ratio(), self.hash_count as f64)
+    }
+
+    /// Estimates how many distinct items have been added, from the number of
+    /// bits that are set.
+    pub fn estimated_len(&self) -> f64 {
+        let m = self.bit_vec_size as f64;
+        -m / self.hash_count as f64 * math::ln(1.0 - self.count_ones() as f64 / m)
+    }
+
+    /// The number of bits in the filter, whether stored sparsely or not.
+    pub fn bit_vec_size(&self) -> u64 {
+        self.bit_vec_size
+    }
+
+    /// The number of hash functions.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The false positive probability the filter was sized for, if it was
+    /// created from one.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The number of items the filter was sized for, if it was created from
+    /// one.
+    pub fn capacity(&self) -> Option<usize> {
+        self.item_count
+    }
+
+    /// The number of bytes of memory the filter occupies, including its list
+    /// of indices or its bitmap.
+    pub fn memory_bytes(&self) -> usize {
+        let bits = match &self.bits {
+            Bits::Sparse(indices) => indices.capacity() * mem::size_of::<u64>(),
+            Bits::Dense(bit_vec) => bit_vec.heap_bytes(),
+        };
+        mem::size_of_val(self) + bits
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Converts the filter into a [`BloomFilter`] with the same bits set,
+    /// which answers every lookup the same way.
+    pub fn into_bloom_filter(mut self) -> BloomFilter<T, S> {
+        self.densify();
+        let bit_vec = match self.bits {
+            Bits::Dense(bit_vec) => bit_vec,
+            Bits::Sparse(_) => unreachable!(),
+        };
+        BloomFilter::from_parts(
+            bit_vec,
+            self.hash_count,
+            self.item_count,
+            self.false_positive_prob,
+            self.seed,
+            self.hash_builder,
+        )
+    }
+
+    /// The bits `item` maps to, as in a [`BloomFilter`].
+    fn probes<Q: ?Sized + Hash>(&self, item: &Q) -> Probes<S::Hasher> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let size = self.bit_vec_size;
+        Probes::new(hasher, size, self.hash_count, size > params::WIDE_HASH_THRESHOLD)
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for SparseBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(SparseBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        SparseBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        SparseBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        SparseBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        SparseBloomFilter::memory_bytes(self)
+    }
+}
@@ -0,0 +1,357 @@
+use alloc::format;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::packed::PackedVec;
+use crate::probe::Probes;
+use crate::{math, params, ApproximateMembership, BloomError, DefaultBuildHasher, Removable, Result};
+
+/// The counter width [`new`](SpectralBloomFilter::new) uses.
+const DEFAULT_COUNTER_BITS: u32 = 16;
+
+/// A bloom filter that estimates how many times each item was inserted, not
+/// just whether it was (Cohen and Matias, "Spectral Bloom Filters").
+///
+/// Like a [`CountingBloomFilter`](crate::CountingBloomFilter), each slot is
+/// a counter that inserting an item increments. Every counter an item maps
+/// to has been incremented at least once per insertion of that item, and
+/// more by any other items that share it, so the smallest of them is an
+/// estimate that is never too low: the minimum-selection heuristic. It is
+/// exact unless all of the item's counters are shared, which happens about
+/// as often as a false positive does.
+///
+/// Counters are 16 bits by default and saturate at their maximum, beyond
+/// which counts are capped.
+///
+/// ```
+/// use bloom::SpectralBloomFilter;
+///
+/// let mut filter = SpectralBloomFilter::<&str>::new(1000, 0.01);
+/// for word in "the cat saw the dog chase the cat".split(' ') {
+///     filter.insert(&word);
+/// }
+/// assert_eq!(filter.estimate_count(&"the"), 3);
+/// assert_eq!(filter.estimate_count(&"cat"), 2);
+/// ```
+#[derive(Debug)]
+pub struct SpectralBloomFilter<T, S = DefaultBuildHasher> {
+    counters: PackedVec,
+    hash_count: usize,
+    total_count: u64,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for SpectralBloomFilter<T, S> {
+    fn clone(&self) -> SpectralBloomFilter<T, S> {
+        SpectralBloomFilter {
+            counters: self.counters.clone(),
+            hash_count: self.hash_count,
+            total_count: self.total_count,
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> SpectralBloomFilter<T> {
+    /// Creates a filter sized to hold `item_count` distinct items with the
+    /// given false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0 or `false_positive_prob` is not strictly
+    /// between 0 and 1, or if the counters can't be addressed.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> SpectralBloomFilter<T> {
+        SpectralBloomFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](SpectralBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> SpectralBloomFilter<T> {
+        let mut filter = SpectralBloomFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter with exactly `slots` counters of `counter_bits`
+    /// bits and `hashes` hash functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of them is 0, if `counter_bits` is more than 64, or if
+    /// the counters can't be addressed.
+    pub fn from_params(slots: u64, hashes: usize, counter_bits: u32) -> SpectralBloomFilter<T> {
+        SpectralBloomFilter::from_params_with_hasher(slots, hashes, counter_bits, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> SpectralBloomFilter<T, S> {
+    /// Like [`new`](SpectralBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> SpectralBloomFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let slots = params::optimal_bits(item_count, false_positive_prob);
+        let hash_count = params::optimal_hashes(slots, item_count);
+        let mut filter =
+            SpectralBloomFilter::from_params_with_hasher(slots, hash_count, DEFAULT_COUNTER_BITS, hash_builder);
+        filter.item_count = Some(item_count);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](SpectralBloomFilter::from_params), but hashes
+    /// items with `hash_builder`.
+    pub fn from_params_with_hasher(
+        slots: u64,
+        hashes: usize,
+        counter_bits: u32,
+        hash_builder: S,
+    ) -> SpectralBloomFilter<T, S> {
+        let counters = params::validate_layout(slots, hashes).and_then(|()| {
+            if (1..=64).contains(&counter_bits) {
+                PackedVec::new(slots, counter_bits)
+            } else {
+                Err(BloomError::InvalidParams(format!("counters must be 1 to 64 bits (got {})", counter_bits)))
+            }
+        });
+        let counters = match counters {
+            Ok(counters) => counters,
+            Err(e) => panic!("{}", e),
+        };
+        SpectralBloomFilter {
+            counters,
+            hash_count: hashes,
+            total_count: 0,
+            item_count: None,
+            false_positive_prob: None,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records one occurrence of `item`.
+    pub fn add<Q: ?Sized + Hash>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+    {
+        self.insert(item);
+    }
+
+    /// Records one occurrence of `item`, returning `true` if it was probably
+    /// already present.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.insert_count(item, 1)
+    }
+
+    /// Records `count` occurrences of `item` at once, returning `true` if it
+    /// was probably already present. Counters saturate at their maximum
+    /// rather than wrapping.
+    pub fn insert_count<Q: ?Sized + Hash>(&mut self, item: &Q, count: u64) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let mut present = true;
+        let max = self.counters.max();
+        for index in self.probes(item) {
+            let counter = self.counters.get(index);
+            if counter == 0 {
+                present = false;
+            }
+            self.counters.set(index, cmp::min(counter.saturating_add(count), max));
+        }
+        self.total_count = self.total_count.saturating_add(count);
+        present
+    }
+
+    /// Estimates how many times `item` has been inserted, net of removals,
+    /// as the smallest of its counters.
+    ///
+    /// The estimate is never lower than the true count unless a counter has
+    /// saturated or an item that wasn't inserted has been removed. It is 0
+    /// for an item that definitely wasn't inserted.
+    pub fn estimate_count<Q: ?Sized + Hash>(&self, item: &Q) -> u64
+    where
+        T: Borrow<Q>,
+    {
+        self.probes(item).map(|index| self.counters.get(index)).min().unwrap_or(0)
+    }
+
+    /// Returns `true` if `item` has probably been inserted and not removed,
+    /// and `false` if it definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.probes(item).all(|index| self.counters.get(index) != 0)
+    }
+
+    /// Removes one occurrence of `item`, returning `true` if it was probably
+    /// present.
+    ///
+    /// If any of `item`'s counters is zero it was definitely never inserted,
+    /// and nothing is changed. Saturated counters are left as they are.
+    pub fn remove<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        if !self.contains(item) {
+            return false;
+        }
+        let max = self.counters.max();
+        for index in self.probes(item) {
+            // Saturating, since a false positive's counters may reach zero
+            // before all of its probes have been applied.
+            let counter = self.counters.get(index);
+            if counter < max {
+                self.counters.set(index, counter.saturating_sub(1));
+            }
+        }
+        self.total_count = self.total_count.saturating_sub(1);
+        true
+    }
+
+    /// Removes every item from the filter, keeping its allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        self.counters.clear();
+        self.total_count = 0;
+    }
+
+    /// Returns `true` if every counter is zero.
+    pub fn is_empty(&self) -> bool {
+        self.counters.words().iter().all(|&word| word == 0)
+    }
+
+    /// The number of insertions, net of removals, counting repeats.
+    pub fn total_count(&self) -> u64 {
+        self.total_count
+    }
+
+    /// The number of counters that are nonzero.
+    pub fn count_nonzero(&self) -> u64 {
+        (0..self.counters.len())
+            .filter(|&index| self.counters.get(index) != 0)
+            .count() as u64
+    }
+
+    /// Estimates the false positive probability of the filter as it is now,
+    /// from the fraction of nonzero counters.
+    pub fn current_fpr(&self) -> f64 {
+        math::powf(
+            self.count_nonzero() as f64 / self.slots() as f64,
+            self.hash_count as f64,
+        )
+    }
+
+    /// Estimates how many distinct items the filter holds from the number of
+    /// nonzero counters.
+    pub fn estimated_len(&self) -> f64 {
+        let m = self.slots() as f64;
+        -m / self.hash_count as f64 * math::ln(1.0 - self.count_nonzero() as f64 / m)
+    }
+
+    /// The number of counters.
+    pub fn slots(&self) -> u64 {
+        self.counters.len()
+    }
+
+    /// The number of bits in each counter.
+    pub fn counter_bits(&self) -> u32 {
+        self.counters.width()
+    }
+
+    /// The number of hash functions applied to each item.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The false positive probability the filter was sized for, or `None`
+    /// if it was built from explicit parameters.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The number of distinct items the filter was sized for, or `None` if
+    /// it was built from explicit parameters.
+    pub fn capacity(&self) -> Option<usize> {
+        self.item_count
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The counter indices `item` maps to.
+    fn probes<Q: ?Sized + Hash>(&self, item: &Q) -> Probes<S::Hasher> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let slots = self.slots();
+        Probes::new(hasher, slots, self.hash_count, slots > params::WIDE_HASH_THRESHOLD)
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for SpectralBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(SpectralBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        SpectralBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        SpectralBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        SpectralBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(self.counters.words())
+    }
+}
+
+impl<T, Q, S> Removable<Q> for SpectralBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn remove(&mut self, item: &Q) -> bool {
+        SpectralBloomFilter::remove(self, item)
+    }
+}
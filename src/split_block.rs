@@ -0,0 +1,396 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::convert::TryFrom;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::hash::xxh64;
+use crate::simd;
+use crate::{math, params, ApproximateMembership, BloomError, DefaultBuildHasher, Result};
+
+/// The odd constants that turn the low 32 bits of a hash into one bit in
+/// each word of a block, from the Parquet specification.
+pub(crate) const SALT: [u32; 8] = [
+    0x47b6_137b,
+    0x4497_4d91,
+    0x8824_ad5b,
+    0xa2b7_289d,
+    0x7054_95c7,
+    0x2df1_424b,
+    0x9efc_4947,
+    0x5c6b_fb31,
+];
+
+/// The number of bytes in a block.
+const BLOCK_BYTES: usize = 32;
+
+/// The number of items [`contains_many`](SplitBlockBloomFilter::contains_many)
+/// hashes before reading any blocks.
+const BATCH_SIZE: usize = 8;
+
+/// Eight 32-bit words, each of which receives one bit of every item in the
+/// block.
+type Block = [u32; 8];
+
+/// A split-block bloom filter, bit for bit the algorithm Apache Parquet uses
+/// for its column bloom filters.
+///
+/// The filter is an array of 256-bit blocks of eight 32-bit words. The high
+/// 32 bits of an item's 64-bit hash pick a block, and the low 32 bits are
+/// multiplied by eight odd constants to pick one bit in each word. A lookup
+/// reads a single block, and the eight words are checked at once with AVX2
+/// or NEON where the CPU has them.
+///
+/// Items are hashed with the filter's hasher and seed like any other filter
+/// in this crate. To read or write Parquet bloom filters, use
+/// [`insert_bytes`](SplitBlockBloomFilter::insert_bytes) and
+/// [`contains_bytes`](SplitBlockBloomFilter::contains_bytes), which hash the
+/// plain-encoded value with xxHash64 and seed 0 as Parquet does, or pass the
+/// hash directly to [`insert_hash`](SplitBlockBloomFilter::insert_hash), and
+/// exchange the bitset with [`to_bytes`](SplitBlockBloomFilter::to_bytes) and
+/// [`from_bytes`](SplitBlockBloomFilter::from_bytes).
+///
+/// ```
+/// use bloom::SplitBlockBloomFilter;
+///
+/// let mut filter = SplitBlockBloomFilter::<String>::new(1000, 0.01);
+/// filter.insert_bytes("apple");
+/// assert!(filter.contains_bytes("apple"));
+/// ```
+#[derive(Debug)]
+pub struct SplitBlockBloomFilter<T, S = DefaultBuildHasher> {
+    blocks: Box<[Block]>,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for SplitBlockBloomFilter<T, S> {
+    fn clone(&self) -> SplitBlockBloomFilter<T, S> {
+        SplitBlockBloomFilter {
+            blocks: self.blocks.clone(),
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> SplitBlockBloomFilter<T> {
+    /// Creates a filter sized to hold `item_count` items with the given
+    /// false positive probability, as Parquet writers size theirs.
+    ///
+    /// The size is `-8n / ln(1 - p^(1/8))` bits, rounded up to a power of
+    /// two bytes and at least one block.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0 or `false_positive_prob` is not strictly
+    /// between 0 and 1, or if the filter would be too large.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> SplitBlockBloomFilter<T> {
+        SplitBlockBloomFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](SplitBlockBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> SplitBlockBloomFilter<T> {
+        let mut filter = SplitBlockBloomFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter with `blocks` blocks of 32 bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `blocks` is 0, or if the filter would be too large.
+    pub fn from_params(blocks: u64) -> SplitBlockBloomFilter<T> {
+        SplitBlockBloomFilter::from_params_with_hasher(blocks, DefaultBuildHasher::default())
+    }
+
+    /// Reads a bitset written by [`to_bytes`](SplitBlockBloomFilter::to_bytes)
+    /// or by a Parquet writer.
+    ///
+    /// Fails with [`BloomError::CorruptFile`] if `bytes` isn't a whole,
+    /// nonzero number of blocks.
+    pub fn from_bytes(bytes: &[u8]) -> Result<SplitBlockBloomFilter<T>> {
+        if bytes.is_empty() || !bytes.len().is_multiple_of(BLOCK_BYTES) {
+            return Err(BloomError::CorruptFile(format!(
+                "a split-block bitset must be a nonzero multiple of {} bytes (got {})",
+                BLOCK_BYTES,
+                bytes.len()
+            )));
+        }
+        let blocks: Vec<Block> = bytes
+            .chunks(BLOCK_BYTES)
+            .map(|chunk| {
+                let mut block = [0; 8];
+                for (word, bytes) in block.iter_mut().zip(chunk.chunks(4)) {
+                    *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                }
+                block
+            })
+            .collect();
+        Ok(SplitBlockBloomFilter {
+            blocks: blocks.into_boxed_slice(),
+            item_count: None,
+            false_positive_prob: None,
+            seed: 0,
+            hash_builder: DefaultBuildHasher::default(),
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<T: Hash, S: BuildHasher> SplitBlockBloomFilter<T, S> {
+    /// Like [`new`](SplitBlockBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> SplitBlockBloomFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let bits = -8.0 * item_count as f64 / math::ln(1.0 - math::powf(false_positive_prob, 1.0 / 8.0));
+        let bytes = math::ceil(bits / 8.0);
+        let blocks = if bytes >= params::MAX_BITS as f64 / 8.0 {
+            u64::MAX
+        } else {
+            (bytes as u64).div_ceil(BLOCK_BYTES as u64).next_power_of_two()
+        };
+        let mut filter = SplitBlockBloomFilter::from_params_with_hasher(blocks, hash_builder);
+        filter.item_count = Some(item_count);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](SplitBlockBloomFilter::from_params), but hashes
+    /// items with `hash_builder`.
+    pub fn from_params_with_hasher(blocks: u64, hash_builder: S) -> SplitBlockBloomFilter<T, S> {
+        let len = if blocks == 0 {
+            Err(BloomError::InvalidParams("block count must be greater than 0".to_string()))
+        } else {
+            blocks
+                .checked_mul(BLOCK_BYTES as u64 * 8)
+                .filter(|&bits| bits <= params::MAX_BITS)
+                .and_then(|_| usize::try_from(blocks).ok())
+                .ok_or_else(|| BloomError::Capacity(format!("{} blocks can't be addressed", blocks)))
+        };
+        let len = match len {
+            Ok(len) => len,
+            Err(e) => panic!("{}", e),
+        };
+        SplitBlockBloomFilter {
+            blocks: vec![[0; 8]; len].into_boxed_slice(),
+            item_count: None,
+            false_positive_prob: None,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item` in the filter.
+    pub fn add<Q: ?Sized + Hash>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+    {
+        self.insert(item);
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.insert_hash(self.hash(item))
+    }
+
+    /// Returns `true` if `item` has probably been added, and `false` if it
+    /// definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.contains_hash(self.hash(item))
+    }
+
+    /// Records the raw bytes of `key`, hashed with xxHash64 and seed 0
+    /// regardless of the filter's hasher and seed.
+    ///
+    /// This is how Parquet hashes values, so with the plain encoding of a
+    /// value (the bytes of a string, or an integer's little-endian bytes)
+    /// this matches a Parquet filter.
+    pub fn insert_bytes<K: ?Sized + AsRef<[u8]>>(&mut self, key: &K) -> bool {
+        self.insert_hash(xxh64(key.as_ref(), 0))
+    }
+
+    /// Returns `true` if the raw bytes of `key` have probably been added with
+    /// [`insert_bytes`](SplitBlockBloomFilter::insert_bytes).
+    pub fn contains_bytes<K: ?Sized + AsRef<[u8]>>(&self, key: &K) -> bool {
+        self.contains_hash(xxh64(key.as_ref(), 0))
+    }
+
+    /// Records an item by its 64-bit hash, returning `true` if it was
+    /// probably already present.
+    pub fn insert_hash(&mut self, hash: u64) -> bool {
+        let index = self.block_index(hash);
+        simd::split_block_insert(&mut self.blocks[index], hash as u32)
+    }
+
+    /// Returns `true` if an item with the given 64-bit hash has probably been
+    /// added.
+    pub fn contains_hash(&self, hash: u64) -> bool {
+        simd::split_block_contains(&self.blocks[self.block_index(hash)], hash as u32)
+    }
+
+    /// Checks each of `items`, returning whether each is probably present.
+    ///
+    /// Equivalent to calling [`contains`](SplitBlockBloomFilter::contains)
+    /// on each item, but a batch of eight items is hashed and their blocks
+    /// prefetched before any block is read, so hashing and memory accesses
+    /// can overlap. Each item is still hashed on its own; only the test of
+    /// its bits against the block uses SIMD.
+    pub fn contains_many(&self, items: &[T]) -> Vec<bool> {
+        let mut results = Vec::with_capacity(items.len());
+        for batch in items.chunks(BATCH_SIZE) {
+            let mut hashes = [0; BATCH_SIZE];
+            for (hash, item) in hashes.iter_mut().zip(batch) {
+                *hash = self.hash(item);
+                simd::prefetch(&self.blocks[self.block_index(*hash)]);
+            }
+            results.extend(hashes[..batch.len()].iter().map(|&hash| self.contains_hash(hash)));
+        }
+        results
+    }
+
+    /// Removes every item from the filter, keeping its allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        for block in self.blocks.iter_mut() {
+            *block = [0; 8];
+        }
+    }
+
+    /// Returns `true` if nothing has been added since the filter was created
+    /// or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.iter().flatten().all(|&word| word == 0)
+    }
+
+    /// The number of bits that are set.
+    pub fn count_ones(&self) -> u64 {
+        self.blocks.iter().flatten().map(|word| u64::from(word.count_ones())).sum()
+    }
+
+    /// Estimates the false positive probability of the filter as it is now:
+    /// the mean over blocks of the product of each word's fill ratio.
+    pub fn current_fpr(&self) -> f64 {
+        let sum: f64 = self
+            .blocks
+            .iter()
+            .map(|block| block.iter().map(|word| f64::from(word.count_ones()) / 32.0).product::<f64>())
+            .sum();
+        sum / self.blocks.len() as f64
+    }
+
+    /// Estimates how many distinct items have been added, from the number of
+    /// bits set in each word: each item sets one bit in every word of its
+    /// block, so a word with `X` bits set holds about `-32 ln(1 - X/32)`.
+    ///
+    /// Returns infinity once any word is full.
+    pub fn estimated_len(&self) -> f64 {
+        let sum: f64 = self
+            .blocks
+            .iter()
+            .flatten()
+            .map(|word| -32.0 * math::ln(1.0 - f64::from(word.count_ones()) / 32.0))
+            .sum();
+        sum / 8.0
+    }
+
+    /// The bitset in the layout Parquet stores: each block's words in order,
+    /// little-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.blocks.iter().flatten().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    /// The number of 32-byte blocks.
+    pub fn block_count(&self) -> u64 {
+        self.blocks.len() as u64
+    }
+
+    /// The false positive probability the filter was sized for, or `None`
+    /// if it was built from explicit parameters.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The number of items the filter was sized for, or `None` if it was
+    /// built from explicit parameters.
+    pub fn capacity(&self) -> Option<usize> {
+        self.item_count
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    fn hash<Q: ?Sized + Hash>(&self, item: &Q) -> u64 {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The block for `hash`: its high 32 bits scaled onto the block count.
+    fn block_index(&self, hash: u64) -> usize {
+        ((u128::from(hash >> 32) * self.blocks.len() as u128) >> 32) as usize
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for SplitBlockBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(SplitBlockBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        SplitBlockBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        SplitBlockBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        SplitBlockBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(&*self.blocks)
+    }
+}
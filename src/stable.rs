@@ -0,0 +1,327 @@
+use alloc::format;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::packed::PackedVec;
+use crate::probe::Probes;
+use crate::{math, params, ApproximateMembership, BloomError, DefaultBuildHasher, Result};
+
+/// The cell width [`new`](StableBloomFilter::new) uses.
+const DEFAULT_CELL_BITS: u32 = 1;
+
+/// A stable bloom filter (Deng and Rafiei, "Approximately Detecting
+/// Duplicates for Streaming Data using Stable Bloom Filters"), which forgets
+/// old items so that it can take an unbounded stream.
+///
+/// A [`BloomFilter`](crate::BloomFilter) given more items than it was sized
+/// for fills up until every lookup is a false positive. Here each cell is a
+/// small counter instead of a bit: inserting an item first decrements `P`
+/// cells, a run starting at a random cell, then sets the item's `k` cells to
+/// their maximum. Cells set long ago decay back to zero, so the fraction of
+/// nonzero cells, and with it the false positive probability, converges to a
+/// fixed point however long the stream runs. [`new`](StableBloomFilter::new)
+/// chooses `k` and `P` so that this fixed point is the probability asked
+/// for.
+///
+/// The price is false negatives: each insertion may decay some of the cells
+/// of earlier items, so the longer ago an item was last inserted, the likelier
+/// it is to have dropped out of the filter. Wider cells take longer to decay.
+///
+/// ```
+/// use bloom::StableBloomFilter;
+///
+/// let mut filter = StableBloomFilter::<u64>::new(10_000, 0.01);
+/// for event in 0..1_000_000 {
+///     filter.insert(&event);
+/// }
+/// assert!(filter.contains(&999_999));
+/// assert!(filter.current_fpr() < 0.02);
+/// ```
+#[derive(Debug)]
+pub struct StableBloomFilter<T, S = DefaultBuildHasher> {
+    cells: PackedVec,
+    hash_count: usize,
+    decrements: u64,
+    false_positive_prob: Option<f64>,
+    // State for choosing which cells to decrement.
+    rng: u64,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for StableBloomFilter<T, S> {
+    fn clone(&self) -> StableBloomFilter<T, S> {
+        StableBloomFilter {
+            cells: self.cells.clone(),
+            hash_count: self.hash_count,
+            decrements: self.decrements,
+            false_positive_prob: self.false_positive_prob,
+            rng: self.rng,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> StableBloomFilter<T> {
+    /// Creates a filter of `cells` 1-bit cells whose false positive
+    /// probability settles at `false_positive_prob`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cells` is 0 or more than [`MAX_BITS`](params::MAX_BITS),
+    /// or if `false_positive_prob` is not strictly between 0 and 1.
+    pub fn new(cells: u64, false_positive_prob: f64) -> StableBloomFilter<T> {
+        StableBloomFilter::with_hasher(cells, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](StableBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(cells: u64, false_positive_prob: f64, seed: u64) -> StableBloomFilter<T> {
+        let mut filter = StableBloomFilter::new(cells, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter of `cells` cells of `cell_bits` bits, with `hashes`
+    /// hash functions, that decrements `decrements` cells per insertion.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of them is 0, if `cells` is more than
+    /// [`MAX_BITS`](params::MAX_BITS) or if `cell_bits` is more than 8.
+    pub fn from_params(cells: u64, cell_bits: u32, hashes: usize, decrements: u64) -> StableBloomFilter<T> {
+        StableBloomFilter::from_params_with_hasher(cells, cell_bits, hashes, decrements, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> StableBloomFilter<T, S> {
+    /// Like [`new`](StableBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(cells: u64, false_positive_prob: f64, hash_builder: S) -> StableBloomFilter<T, S> {
+        if let Err(e) = params::validate(1, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let hash_count = cmp::max(math::ceil(-math::log2(false_positive_prob)) as usize, 1);
+        let decrements = stable_decrements(cells, DEFAULT_CELL_BITS, hash_count, false_positive_prob);
+        let mut filter =
+            StableBloomFilter::from_params_with_hasher(cells, DEFAULT_CELL_BITS, hash_count, decrements, hash_builder);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](StableBloomFilter::from_params), but hashes
+    /// items with `hash_builder`.
+    pub fn from_params_with_hasher(
+        cells: u64,
+        cell_bits: u32,
+        hashes: usize,
+        decrements: u64,
+        hash_builder: S,
+    ) -> StableBloomFilter<T, S> {
+        let cells = params::validate_layout(cells, hashes).and_then(|()| {
+            if !(1..=8).contains(&cell_bits) || decrements == 0 {
+                Err(BloomError::InvalidParams(format!(
+                    "cells must be 1 to 8 bits and at least one decremented (got {} bits and {})",
+                    cell_bits, decrements
+                )))
+            } else {
+                PackedVec::new(cells, cell_bits)
+            }
+        });
+        let cells = match cells {
+            Ok(cells) => cells,
+            Err(e) => panic!("{}", e),
+        };
+        StableBloomFilter {
+            cells,
+            hash_count: hashes,
+            decrements,
+            false_positive_prob: None,
+            rng: 0x2545_f491_4f6c_dd1d,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item` in the filter.
+    pub fn add<Q: ?Sized + Hash>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+    {
+        self.insert(item);
+    }
+
+    /// Decays the filter and records `item`, returning `true` if it was
+    /// probably present beforehand.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let present = self.contains(item);
+        let len = self.cells.len();
+        let start = self.next_random() % len;
+        for offset in 0..cmp::min(self.decrements, len) {
+            let cell = (start + offset) % len;
+            let value = self.cells.get(cell);
+            if value > 0 {
+                self.cells.set(cell, value - 1);
+            }
+        }
+        let max = self.cells.max();
+        for cell in self.probes(item) {
+            self.cells.set(cell, max);
+        }
+        present
+    }
+
+    /// Returns `true` if `item` has probably been inserted recently, and
+    /// `false` if it has not been, or has decayed out of the filter.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.probes(item).all(|cell| self.cells.get(cell) > 0)
+    }
+
+    /// Removes every item from the filter, keeping its allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// Returns `true` if every cell is zero.
+    pub fn is_empty(&self) -> bool {
+        self.cells.words().iter().all(|&word| word == 0)
+    }
+
+    /// The number of nonzero cells.
+    pub fn count_nonzero(&self) -> u64 {
+        (0..self.cells.len()).filter(|&cell| self.cells.get(cell) > 0).count() as u64
+    }
+
+    /// Estimates the false positive probability of the filter as it is now:
+    /// the fraction of nonzero cells to the power `k`.
+    pub fn current_fpr(&self) -> f64 {
+        let fill = self.count_nonzero() as f64 / self.cells.len() as f64;
+        math::powf(fill, self.hash_count as f64)
+    }
+
+    /// The false positive probability the filter converges to on a long
+    /// enough stream of distinct items.
+    pub fn stable_fpr(&self) -> f64 {
+        let m = self.cells.len() as f64;
+        let k = self.hash_count as f64;
+        let p = self.decrements as f64;
+        let zeros = math::powf(1.0 / (1.0 + 1.0 / (p * (1.0 / k - 1.0 / m))), self.cells.max() as f64);
+        math::powf(1.0 - zeros, k)
+    }
+
+    /// Estimates how many distinct items the filter still remembers, from
+    /// the fraction of nonzero cells as for a bloom filter.
+    pub fn estimated_len(&self) -> f64 {
+        let m = self.cells.len() as f64;
+        -m / self.hash_count as f64 * math::ln(1.0 - self.count_nonzero() as f64 / m)
+    }
+
+    /// The number of cells.
+    pub fn cell_count(&self) -> u64 {
+        self.cells.len()
+    }
+
+    /// The number of bits in each cell.
+    pub fn cell_bits(&self) -> u32 {
+        self.cells.width()
+    }
+
+    /// The number of hash functions applied to each item.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The number of cells each insertion decrements, `P`.
+    pub fn decrements(&self) -> u64 {
+        self.decrements
+    }
+
+    /// The false positive probability the filter was sized to converge to,
+    /// or `None` if it was built from explicit parameters.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    fn probes<Q: ?Sized + Hash>(&self, item: &Q) -> Probes<S::Hasher> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let len = self.cells.len();
+        Probes::new(hasher, len, self.hash_count, len > params::WIDE_HASH_THRESHOLD)
+    }
+
+    /// Steps an xorshift generator; decay only needs to be spread evenly,
+    /// not to be unpredictable.
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+}
+
+/// The number of cells to decrement per insertion for a filter of `m` cells
+/// of `d` bits with `k` hash functions to converge to false positive
+/// probability `p`, from solving the paper's stable point for `P`.
+fn stable_decrements(m: u64, d: u32, k: usize, p: f64) -> u64 {
+    let max = ((1u64 << d) - 1) as f64;
+    let zeros = math::powf(1.0 - math::powf(p, 1.0 / k as f64), 1.0 / max);
+    let denominator = (1.0 / zeros - 1.0) * (1.0 / k as f64 - 1.0 / m as f64);
+    cmp::max(math::ceil(1.0 / denominator) as u64, 1)
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for StableBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(StableBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        StableBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        StableBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        StableBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + mem::size_of_val(self.cells.words())
+    }
+}
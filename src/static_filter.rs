@@ -0,0 +1,141 @@
+use core::hash::{BuildHasher, Hash, Hasher};
+
+use crate::format::{FORMAT_VERSION, HEADER_LEN, MAGIC, WIDE_HASHES};
+use crate::hash::{read_le, xxh64, HashScheme, HASH_SCHEME_VERSION, KEYED_ID};
+use crate::probe::Probes;
+
+/// A read-only filter over bits borrowed from a filter serialized by
+/// [`BloomFilter::write_to`](crate::BloomFilter::write_to), usually embedded
+/// in the program with [`include_filter!`](crate::include_filter).
+///
+/// Nothing is copied or allocated: lookups read the borrowed bytes directly,
+/// so an embedded filter is ready to use with no startup cost.
+#[derive(Debug, Clone, Copy)]
+pub struct StaticBloomFilter {
+    bits: &'static [u8],
+    bit_vec_size: u64,
+    hash_count: usize,
+    seed: u64,
+    wide_hashes: bool,
+    hash_builder: HashScheme,
+}
+
+impl StaticBloomFilter {
+    /// Borrows the filter serialized in `bytes` by
+    /// [`BloomFilter::write_to`](crate::BloomFilter::write_to).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` isn't a valid filter, including if its checksum
+    /// doesn't match, or if it was built with a secret key. When called in a
+    /// constant, as [`include_filter!`](crate::include_filter) does, this is
+    /// a compile error instead.
+    pub const fn parse(bytes: &'static [u8]) -> StaticBloomFilter {
+        if bytes.len() < HEADER_LEN + 8 {
+            panic!("corrupt filter file: too short");
+        }
+        let mut i = 0;
+        while i < MAGIC.len() {
+            if bytes[i] != MAGIC[i] {
+                panic!("corrupt filter file: not a bloom filter file");
+            }
+            i += 1;
+        }
+        if read_le(bytes, 4, 2) != FORMAT_VERSION as u64 {
+            panic!("incompatible filters: unsupported format version");
+        }
+        let hash_builder = match HashScheme::from_id(bytes[6]) {
+            Some(scheme) => scheme,
+            None if bytes[6] == KEYED_ID => panic!("incompatible filters: the filter needs a secret key"),
+            None => panic!("corrupt filter file: unknown hash scheme"),
+        };
+        if read_le(bytes, 8, 4) != HASH_SCHEME_VERSION as u64 {
+            panic!("incompatible filters: unsupported hash scheme version");
+        }
+        let bit_vec_size = read_le(bytes, 12, 8);
+        let hash_count = read_le(bytes, 20, 8);
+        if bit_vec_size == 0 || hash_count == 0 || hash_count > usize::MAX as u64 {
+            panic!("corrupt filter file: invalid layout");
+        }
+        let byte_len = bit_vec_size.div_ceil(8);
+        if byte_len != (bytes.len() - HEADER_LEN - 8) as u64 {
+            panic!("corrupt filter file: wrong length");
+        }
+        let checksum_offset = bytes.len() - 8;
+        let (contents, _) = bytes.split_at(checksum_offset);
+        if xxh64(contents, 0) != read_le(bytes, checksum_offset, 8) {
+            panic!("corrupt filter file: checksum mismatch");
+        }
+        let (_, bits) = contents.split_at(HEADER_LEN);
+        if !bit_vec_size.is_multiple_of(8) && bits[bits.len() - 1] >> (bit_vec_size % 8) != 0 {
+            panic!("corrupt filter file: bits past the end of the filter are set");
+        }
+        StaticBloomFilter {
+            bits,
+            bit_vec_size,
+            hash_count: hash_count as usize,
+            seed: read_le(bytes, 28, 8),
+            wide_hashes: bytes[7] & WIDE_HASHES != 0,
+            hash_builder,
+        }
+    }
+
+    /// Whether `item` may be in the filter. False positives are possible,
+    /// false negatives are not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        Probes::new(hasher, self.bit_vec_size, self.hash_count, self.wide_hashes)
+            .all(|index| self.bits[(index / 8) as usize] & (1 << (index % 8)) != 0)
+    }
+
+    /// The number of bits in the filter.
+    pub const fn bit_vec_size(&self) -> u64 {
+        self.bit_vec_size
+    }
+
+    /// The number of hash functions.
+    pub const fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The seed mixed into every hash.
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hash scheme items are hashed with.
+    pub const fn hasher(&self) -> &HashScheme {
+        &self.hash_builder
+    }
+
+    /// The bits, packed as by [`BloomFilter::to_bytes`](crate::BloomFilter::to_bytes).
+    pub const fn as_bytes(&self) -> &'static [u8] {
+        self.bits
+    }
+}
+
+/// Embeds the filter file at `path` in the program as a
+/// [`StaticBloomFilter`].
+///
+/// `path` is resolved like [`include_bytes!`]'s, relative to the file the
+/// macro is used in. The file is checked while compiling, so a corrupt or
+/// incompatible file is a compile error.
+///
+/// ```ignore
+/// use bloom::{include_filter, StaticBloomFilter};
+///
+/// static BLOCKLIST: StaticBloomFilter = include_filter!("blocklist.bloom");
+///
+/// fn is_blocked(host: &str) -> bool {
+///     BLOCKLIST.contains(host)
+/// }
+/// ```
+#[macro_export]
+macro_rules! include_filter {
+    ($path:expr) => {{
+        const FILTER: $crate::StaticBloomFilter = $crate::StaticBloomFilter::parse(include_bytes!($path));
+        FILTER
+    }};
+}
@@ -0,0 +1,304 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::{ApproximateMembership, Result};
+
+/// Latencies recorded exactly, below which every nanosecond has a bucket.
+const EXACT: u64 = 128;
+
+/// Buckets per power of two above [`EXACT`], which bounds the relative error
+/// of a recorded latency to 1/64.
+const SUB_BUCKETS: u64 = 64;
+
+/// Enough buckets for any latency up to `u64::MAX` nanoseconds.
+const BUCKETS: usize = (EXACT + (64 - 7) * SUB_BUCKETS) as usize;
+
+/// A histogram of latencies in the style of HdrHistogram: nanosecond
+/// resolution below 128ns, and above that 64 buckets per power of two, so
+/// any latency is recorded within 1.6% in a fixed 29 KiB.
+///
+/// Recording takes `&self` and a few relaxed atomic adds, so one histogram
+/// can be shared by every thread querying a filter.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use bloom::LatencyHistogram;
+///
+/// let histogram = LatencyHistogram::new();
+/// for micros in 1..=100 {
+///     histogram.record(Duration::from_micros(micros));
+/// }
+/// assert_eq!(histogram.len(), 100);
+/// let p99 = histogram.quantile(0.99).as_nanos() as f64;
+/// assert!((p99 - 99_000.0).abs() / 99_000.0 < 0.02);
+/// ```
+pub struct LatencyHistogram {
+    counts: Box<[AtomicU64]>,
+    len: AtomicU64,
+    sum: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+}
+
+impl LatencyHistogram {
+    /// Creates an empty histogram.
+    pub fn new() -> LatencyHistogram {
+        LatencyHistogram {
+            counts: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            len: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            min: AtomicU64::new(u64::MAX),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    /// Records one latency.
+    pub fn record(&self, latency: Duration) {
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        self.counts[bucket(nanos)].fetch_add(1, Ordering::Relaxed);
+        self.len.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(nanos, Ordering::Relaxed);
+        self.min.fetch_min(nanos, Ordering::Relaxed);
+        self.max.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    /// The number of latencies recorded.
+    pub fn len(&self) -> u64 {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if no latencies have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The shortest latency recorded, or zero if there are none.
+    pub fn min(&self) -> Duration {
+        match self.min.load(Ordering::Relaxed) {
+            u64::MAX if self.is_empty() => Duration::ZERO,
+            min => Duration::from_nanos(min),
+        }
+    }
+
+    /// The longest latency recorded, or zero if there are none.
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max.load(Ordering::Relaxed))
+    }
+
+    /// The mean latency, or zero if there are none.
+    pub fn mean(&self) -> Duration {
+        match self.len() {
+            0 => Duration::ZERO,
+            len => Duration::from_nanos(self.sum.load(Ordering::Relaxed) / len),
+        }
+    }
+
+    /// The latency that a `quantile` fraction of the recorded latencies are
+    /// at most: 0.5 for the median, 0.99 for the 99th percentile.
+    ///
+    /// The result is the highest latency that falls in the same bucket, so
+    /// it overstates the true quantile by at most 1.6%, and never exceeds
+    /// [`max`](LatencyHistogram::max). It is zero if there are no latencies.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `quantile` is not between 0 and 1.
+    pub fn quantile(&self, quantile: f64) -> Duration {
+        assert!((0.0..=1.0).contains(&quantile), "quantile must be between 0 and 1 (got {})", quantile);
+        let len = self.len();
+        if len == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((quantile * len as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count.load(Ordering::Relaxed);
+            if seen >= rank {
+                return Duration::from_nanos(highest_in_bucket(index).min(self.max.load(Ordering::Relaxed)));
+            }
+        }
+        self.max()
+    }
+
+    /// Adds every latency recorded in `other` to this histogram.
+    pub fn merge(&self, other: &LatencyHistogram) {
+        for (count, other) in self.counts.iter().zip(other.counts.iter()) {
+            count.fetch_add(other.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        self.len.fetch_add(other.len.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.sum.fetch_add(other.sum.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.min.fetch_min(other.min.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.max.fetch_max(other.max.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    /// Forgets every recorded latency.
+    pub fn clear(&mut self) {
+        for count in self.counts.iter_mut() {
+            *count.get_mut() = 0;
+        }
+        *self.len.get_mut() = 0;
+        *self.sum.get_mut() = 0;
+        *self.min.get_mut() = u64::MAX;
+        *self.max.get_mut() = 0;
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> LatencyHistogram {
+        LatencyHistogram::new()
+    }
+}
+
+// Implemented by hand because atomics aren't `Clone`.
+impl Clone for LatencyHistogram {
+    fn clone(&self) -> LatencyHistogram {
+        let histogram = LatencyHistogram::new();
+        histogram.merge(self);
+        histogram
+    }
+}
+
+impl fmt::Debug for LatencyHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LatencyHistogram")
+            .field("len", &self.len())
+            .field("min", &self.min())
+            .field("p50", &self.quantile(0.5))
+            .field("p99", &self.quantile(0.99))
+            .field("max", &self.max())
+            .finish()
+    }
+}
+
+/// The bucket a latency of `nanos` falls in.
+fn bucket(nanos: u64) -> usize {
+    if nanos < EXACT {
+        return nanos as usize;
+    }
+    // At least 7, since `nanos` is at least 128.
+    let magnitude = 63 - u64::from(nanos.leading_zeros());
+    let sub_bucket = (nanos >> (magnitude - 6)) - SUB_BUCKETS;
+    (EXACT + (magnitude - 7) * SUB_BUCKETS + sub_bucket) as usize
+}
+
+/// The highest latency that falls in bucket `index`.
+fn highest_in_bucket(index: usize) -> u64 {
+    let index = index as u64;
+    if index < EXACT {
+        return index;
+    }
+    let magnitude = (index - EXACT) / SUB_BUCKETS + 7;
+    let sub_bucket = (index - EXACT) % SUB_BUCKETS + SUB_BUCKETS;
+    let shift = magnitude - 6;
+    ((sub_bucket + 1) << shift).wrapping_sub(1)
+}
+
+/// The latencies of each kind of operation on an [`InstrumentedFilter`].
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    /// The latencies of inserts.
+    pub insert: LatencyHistogram,
+    /// The latencies of lookups.
+    pub contains: LatencyHistogram,
+}
+
+/// Wraps a filter to record the latency of every insert and lookup made
+/// through it, for services that need to watch the filter's tail latency.
+///
+/// Timing an operation costs two reads of the monotonic clock, tens of
+/// nanoseconds, so only wrap filters whose latency is worth that.
+///
+/// ```
+/// use bloom::{BloomFilter, InstrumentedFilter};
+///
+/// let mut filter = InstrumentedFilter::new(BloomFilter::<u64>::new(1000, 0.01));
+/// filter.insert(&42).unwrap();
+/// assert!(filter.contains(&42));
+/// assert_eq!(filter.stats().insert.len(), 1);
+/// assert_eq!(filter.stats().contains.len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct InstrumentedFilter<F> {
+    filter: F,
+    stats: LatencyStats,
+}
+
+impl<F> InstrumentedFilter<F> {
+    /// Wraps `filter`, with no latencies recorded yet.
+    pub fn new(filter: F) -> InstrumentedFilter<F> {
+        InstrumentedFilter { filter, stats: LatencyStats::default() }
+    }
+
+    /// The latencies recorded so far.
+    pub fn stats(&self) -> &LatencyStats {
+        &self.stats
+    }
+
+    /// Forgets the latencies recorded so far.
+    pub fn reset_stats(&mut self) {
+        self.stats.insert.clear();
+        self.stats.contains.clear();
+    }
+
+    /// The wrapped filter. Operations on it directly aren't recorded.
+    pub fn get_ref(&self) -> &F {
+        &self.filter
+    }
+
+    /// Unwraps the filter, discarding the latencies.
+    pub fn into_inner(self) -> F {
+        self.filter
+    }
+
+    /// Records `item` in the wrapped filter, timing the insert.
+    pub fn insert<T: ?Sized>(&mut self, item: &T) -> Result<bool>
+    where
+        F: ApproximateMembership<T>,
+    {
+        let start = Instant::now();
+        let result = self.filter.insert(item);
+        self.stats.insert.record(start.elapsed());
+        result
+    }
+
+    /// Looks `item` up in the wrapped filter, timing the lookup.
+    pub fn contains<T: ?Sized>(&self, item: &T) -> bool
+    where
+        F: ApproximateMembership<T>,
+    {
+        let start = Instant::now();
+        let found = self.filter.contains(item);
+        self.stats.contains.record(start.elapsed());
+        found
+    }
+}
+
+impl<T: ?Sized, F: ApproximateMembership<T>> ApproximateMembership<T> for InstrumentedFilter<F> {
+    fn insert(&mut self, item: &T) -> Result<bool> {
+        InstrumentedFilter::insert(self, item)
+    }
+
+    fn contains(&self, item: &T) -> bool {
+        InstrumentedFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        self.filter.clear()
+    }
+
+    fn estimated_len(&self) -> f64 {
+        self.filter.estimated_len()
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.filter.fpr_estimate()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.filter.memory_bytes() + 2 * BUCKETS * mem::size_of::<AtomicU64>()
+    }
+}
@@ -0,0 +1,236 @@
+use alloc::format;
+#[cfg(feature = "allocator_api")]
+use alloc::string::ToString;
+#[cfg(feature = "allocator_api")]
+use core::mem;
+
+#[cfg(feature = "allocator_api")]
+use allocator_api2::alloc::{Allocator, Global};
+#[cfg(feature = "allocator_api")]
+use allocator_api2::boxed::Box;
+#[cfg(feature = "allocator_api")]
+use allocator_api2::vec::Vec;
+#[cfg(feature = "roaring")]
+use roaring::RoaringBitmap;
+
+#[cfg(feature = "allocator_api")]
+use crate::bit_vec;
+use crate::bit_vec::BitVec;
+use crate::{params, BloomError, Result};
+
+/// Where a [`BackedBloomFilter`](crate::BackedBloomFilter) keeps its bits.
+///
+/// The crate provides [`DenseBits`], a plain bitmap; with the `roaring`
+/// feature `RoaringBitmap`, which compresses runs of clear bits and combines
+/// bitmaps quickly; and with the `allocator_api` feature `AllocBits`, a
+/// plain bitmap from any allocator. Other representations can be plugged in
+/// by implementing this trait.
+pub trait BitStorage {
+    /// Creates `len` cleared bits.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::Capacity`] if the storage can't hold `len` bits.
+    fn with_len(len: u64) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Returns whether bit `index` is set. Indices are always less than the
+    /// length the bits were created with.
+    fn get(&self, index: u64) -> bool;
+
+    /// Sets bit `index`, returning whether it was already set.
+    fn set(&mut self, index: u64) -> bool;
+
+    /// Clears every bit.
+    fn clear(&mut self);
+
+    /// The number of bits that are set.
+    fn count_ones(&self) -> u64;
+
+    /// Sets every bit that is set in `other`, which has the same length.
+    fn union_with(&mut self, other: &Self);
+
+    /// Clears every bit that is clear in `other`, which has the same length.
+    fn intersect_with(&mut self, other: &Self);
+
+    /// The number of bytes the bits occupy on the heap.
+    fn heap_bytes(&self) -> usize;
+}
+
+/// A plain bitmap, as a [`BloomFilter`](crate::BloomFilter) uses: a bit per
+/// bit, whatever is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DenseBits {
+    bit_vec: BitVec,
+}
+
+impl BitStorage for DenseBits {
+    fn with_len(len: u64) -> Result<DenseBits> {
+        if len > params::MAX_BITS {
+            return Err(BloomError::Capacity(format!("at most {} bits fit in memory (got {})", params::MAX_BITS, len)));
+        }
+        Ok(DenseBits { bit_vec: BitVec::new(len) })
+    }
+
+    fn get(&self, index: u64) -> bool {
+        self.bit_vec.get(index)
+    }
+
+    fn set(&mut self, index: u64) -> bool {
+        self.bit_vec.set(index)
+    }
+
+    fn clear(&mut self) {
+        self.bit_vec.clear();
+    }
+
+    fn count_ones(&self) -> u64 {
+        self.bit_vec.count_ones()
+    }
+
+    fn union_with(&mut self, other: &DenseBits) {
+        self.bit_vec.union(&other.bit_vec);
+    }
+
+    fn intersect_with(&mut self, other: &DenseBits) {
+        self.bit_vec.intersect(&other.bit_vec);
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.bit_vec.heap_bytes()
+    }
+}
+
+/// Roaring bitmaps index bits with `u32`s, so they hold at most 2^32 bits,
+/// enough for about 450 million items at 1%. The length isn't stored: bits
+/// past it are never set.
+#[cfg(feature = "roaring")]
+impl BitStorage for RoaringBitmap {
+    fn with_len(len: u64) -> Result<RoaringBitmap> {
+        if len > 1 << 32 {
+            return Err(BloomError::Capacity(format!("a roaring bitmap holds at most 2^32 bits (got {})", len)));
+        }
+        Ok(RoaringBitmap::new())
+    }
+
+    fn get(&self, index: u64) -> bool {
+        self.contains(index as u32)
+    }
+
+    fn set(&mut self, index: u64) -> bool {
+        !self.insert(index as u32)
+    }
+
+    fn clear(&mut self) {
+        RoaringBitmap::clear(self);
+    }
+
+    fn count_ones(&self) -> u64 {
+        self.len()
+    }
+
+    fn union_with(&mut self, other: &RoaringBitmap) {
+        *self |= other;
+    }
+
+    fn intersect_with(&mut self, other: &RoaringBitmap) {
+        *self &= other;
+    }
+
+    fn heap_bytes(&self) -> usize {
+        let stats = self.statistics();
+        let bytes = stats.n_bytes_array_containers + stats.n_bytes_run_containers + stats.n_bytes_bitset_containers;
+        bytes as usize
+    }
+}
+
+/// A plain bitmap like [`DenseBits`], allocated from `A`: an arena, a
+/// shared-memory segment or a bump allocator rather than the global heap.
+///
+/// The allocator can't be conjured from nothing, so these bits are created
+/// with [`new_in`](AllocBits::new_in), and a filter over them with
+/// [`BackedBloomFilter::new_in`](crate::BackedBloomFilter::new_in) or
+/// [`from_params_in`](crate::BackedBloomFilter::from_params_in);
+/// [`BitStorage::with_len`] always fails.
+#[cfg(feature = "allocator_api")]
+#[derive(Debug, Clone)]
+pub struct AllocBits<A: Allocator = Global> {
+    words: Box<[u64], A>,
+    len: u64,
+}
+
+#[cfg(feature = "allocator_api")]
+impl<A: Allocator> AllocBits<A> {
+    /// Allocates `len` cleared bits from `alloc`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::Capacity`] if `len` is more than
+    /// [`MAX_BITS`](params::MAX_BITS) or the allocator can't provide the
+    /// memory.
+    pub fn new_in(len: u64, alloc: A) -> Result<AllocBits<A>> {
+        if len > params::MAX_BITS {
+            return Err(BloomError::Capacity(format!("at most {} bits fit in memory (got {})", params::MAX_BITS, len)));
+        }
+        let word_count = bit_vec::word_count(len);
+        let mut words = Vec::new_in(alloc);
+        words.try_reserve_exact(word_count).map_err(|_| {
+            BloomError::Capacity(format!("the allocator can't provide {} bytes", word_count * mem::size_of::<u64>()))
+        })?;
+        words.resize(word_count, 0);
+        Ok(AllocBits { words: words.into_boxed_slice(), len })
+    }
+
+    /// The allocator the bits live in.
+    pub fn allocator(&self) -> &A {
+        Box::allocator(&self.words)
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<A: Allocator> BitStorage for AllocBits<A> {
+    fn with_len(_len: u64) -> Result<AllocBits<A>> {
+        Err(BloomError::InvalidParams("allocator-backed bits must be created with `AllocBits::new_in`".to_string()))
+    }
+
+    fn get(&self, index: u64) -> bool {
+        debug_assert!(index < self.len);
+        self.words[(index / 64) as usize] & (1 << (index % 64)) != 0
+    }
+
+    fn set(&mut self, index: u64) -> bool {
+        debug_assert!(index < self.len);
+        let word = &mut self.words[(index / 64) as usize];
+        let mask = 1 << (index % 64);
+        let was_set = *word & mask != 0;
+        *word |= mask;
+        was_set
+    }
+
+    fn clear(&mut self) {
+        for word in self.words.iter_mut() {
+            *word = 0;
+        }
+    }
+
+    fn count_ones(&self) -> u64 {
+        self.words.iter().map(|word| u64::from(word.count_ones())).sum()
+    }
+
+    fn union_with(&mut self, other: &AllocBits<A>) {
+        for (a, &b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a |= b;
+        }
+    }
+
+    fn intersect_with(&mut self, other: &AllocBits<A>) {
+        for (a, &b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a &= b;
+        }
+    }
+
+    fn heap_bytes(&self) -> usize {
+        mem::size_of_val(&*self.words)
+    }
+}
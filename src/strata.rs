@@ -0,0 +1,250 @@
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::mem;
+
+use crate::hash::{fmix64, read_le, xxh64};
+use crate::{BloomError, InvertibleBloomLookupTable, Result};
+
+/// The number of strata [`new`](StrataEstimator::new) uses, enough for
+/// differences of billions of keys.
+const DEFAULT_STRATA: u32 = 32;
+
+/// The cells and hashes of each stratum's table, as Eppstein et al. chose.
+const DEFAULT_CELLS: u64 = 80;
+const DEFAULT_HASH_COUNT: u32 = 4;
+
+const MAX_STRATA: u32 = 64;
+
+const MAGIC: [u8; 4] = *b"BLMS";
+const FORMAT_VERSION: u16 = 1;
+const HEADER_LEN: usize = 8;
+
+/// A strata estimator (Eppstein et al., "What's the Difference? Efficient
+/// Set Reconciliation without Prior Context"), which estimates how many keys
+/// two peers' sets differ in from a few kilobytes of each.
+///
+/// The estimator is a stack of small
+/// [`InvertibleBloomLookupTable`]s. Each key goes into one stratum, stratum
+/// `i` with probability `2^-(i + 1)` by the trailing zeros of its hash, so
+/// each stratum up the stack samples the keys half as often. Subtracting two
+/// estimators leaves the difference in every stratum; the sparse top strata
+/// can be listed, and counting down until one can't be gives the difference
+/// in the strata listed, scaled up by the fraction of keys they sample.
+///
+/// Before reconciling, peers exchange estimators and size their tables for
+/// the estimate, with some margin: the estimate is typically within a factor
+/// of two.
+///
+/// ```
+/// use bloom::{InvertibleBloomLookupTable, StrataEstimator};
+///
+/// let (mut ours, mut theirs) = (StrataEstimator::new(), StrataEstimator::new());
+/// for key in 0..100_000 {
+///     ours.insert(key);
+///     theirs.insert(key + 500);
+/// }
+/// let difference = ours.estimate_difference(&theirs).unwrap();
+/// assert!(difference > 500 && difference < 2000);
+/// let table = InvertibleBloomLookupTable::new(2 * difference);
+/// assert!(table.cell_count() > difference);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StrataEstimator {
+    strata: Vec<InvertibleBloomLookupTable>,
+    seed: u64,
+}
+
+impl Default for StrataEstimator {
+    fn default() -> StrataEstimator {
+        StrataEstimator::new()
+    }
+}
+
+impl StrataEstimator {
+    /// Creates an estimator of 32 strata, each a table of 80 cells with 4
+    /// hashes per key.
+    pub fn new() -> StrataEstimator {
+        StrataEstimator::from_params(DEFAULT_STRATA, DEFAULT_CELLS, DEFAULT_HASH_COUNT)
+    }
+
+    /// Like [`new`](StrataEstimator::new), but mixes `seed` into every hash.
+    pub fn with_seed(seed: u64) -> StrataEstimator {
+        let mut estimator = StrataEstimator::new();
+        estimator.set_seed(seed);
+        estimator
+    }
+
+    /// Creates an estimator of `strata` strata, each a table of `cell_count`
+    /// cells with `hash_count` hashes per key. More cells make estimates of
+    /// small differences exact and larger ones tighter.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `strata` is between 1 and 64, or as for
+    /// [`InvertibleBloomLookupTable::from_params`].
+    pub fn from_params(strata: u32, cell_count: u64, hash_count: u32) -> StrataEstimator {
+        if !(1..=MAX_STRATA).contains(&strata) {
+            panic!(
+                "{}",
+                BloomError::InvalidParams(format!("need 1 to {} strata (got {})", MAX_STRATA, strata))
+            );
+        }
+        StrataEstimator {
+            strata: (0..strata)
+                .map(|_| InvertibleBloomLookupTable::from_params(cell_count, hash_count))
+                .collect(),
+            seed: 0,
+        }
+    }
+
+    /// Adds `key` to its stratum.
+    pub fn insert(&mut self, key: u64) {
+        let stratum = self.stratum(key);
+        self.strata[stratum].insert(key);
+    }
+
+    /// Removes `key` from its stratum.
+    pub fn remove(&mut self, key: u64) {
+        let stratum = self.stratum(key);
+        self.strata[stratum].remove(key);
+    }
+
+    /// Estimates the number of keys held by only one of this estimator and
+    /// `other`.
+    ///
+    /// Both estimators must have the same strata, cells, hashes and seed;
+    /// otherwise [`BloomError::Incompatible`] is returned.
+    pub fn estimate_difference(&self, other: &StrataEstimator) -> Result<u64> {
+        if self.strata.len() != other.strata.len() {
+            return Err(BloomError::Incompatible(format!(
+                "strata counts differ ({} and {})",
+                self.strata.len(),
+                other.strata.len()
+            )));
+        }
+        let mut count = 0u64;
+        for (i, (stratum, other)) in self.strata.iter().zip(other.strata.iter()).enumerate().rev() {
+            let mut difference = stratum.clone();
+            difference.subtract(other)?;
+            match difference.list_entries() {
+                Ok((inserted, removed)) => count += (inserted.len() + removed.len()) as u64,
+                // The strata from `i` down sample every key; those above
+                // `i` sample a fraction `2^-(i + 1)` of them.
+                Err(_) => return Ok(count.saturating_mul(1 << (i + 1).min(63))),
+            }
+        }
+        Ok(count)
+    }
+
+    /// Empties every stratum, keeping the parameters.
+    pub fn clear(&mut self) {
+        for stratum in self.strata.iter_mut() {
+            stratum.clear();
+        }
+    }
+
+    /// Returns `true` if every key inserted has since been removed.
+    pub fn is_empty(&self) -> bool {
+        self.strata.iter().all(|stratum| stratum.is_empty())
+    }
+
+    /// The number of strata.
+    pub fn strata_count(&self) -> u32 {
+        self.strata.len() as u32
+    }
+
+    /// The strata, from the one holding half the keys upward.
+    pub fn strata(&self) -> &[InvertibleBloomLookupTable] {
+        &self.strata
+    }
+
+    /// The number of bytes of memory the estimator occupies, including its
+    /// strata.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + self.strata.iter().map(|stratum| stratum.memory_bytes()).sum::<usize>()
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Encodes the estimator so that
+    /// [`from_bytes`](StrataEstimator::from_bytes) can load it on any
+    /// platform.
+    ///
+    /// The layout is little-endian: the magic `b"BLMS"`, a 2-byte format
+    /// version (1), the number of strata as one byte and a zero byte. Each
+    /// stratum follows as the 8-byte length of its
+    /// [`InvertibleBloomLookupTable::to_bytes`] encoding and the encoding,
+    /// and finally the XXH64 (seed 0) of every preceding byte.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&[self.strata.len() as u8, 0]);
+        for stratum in &self.strata {
+            let encoded = stratum.to_bytes();
+            bytes.extend_from_slice(&(encoded.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(&encoded);
+        }
+        let checksum = xxh64(&bytes, 0);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes an estimator encoded by
+    /// [`to_bytes`](StrataEstimator::to_bytes).
+    ///
+    /// Fails with [`BloomError::CorruptFile`] if `bytes` is malformed or its
+    /// checksum doesn't match, and with [`BloomError::Incompatible`] if it
+    /// is from a newer format version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<StrataEstimator> {
+        if bytes.len() < HEADER_LEN + 8 || bytes[..4] != MAGIC {
+            return Err(BloomError::CorruptFile("not a strata estimator".to_string()));
+        }
+        let (contents, checksum) = bytes.split_at(bytes.len() - 8);
+        if xxh64(contents, 0) != read_le(checksum, 0, 8) {
+            return Err(BloomError::CorruptFile("checksum mismatch".to_string()));
+        }
+        if read_le(bytes, 4, 2) != u64::from(FORMAT_VERSION) {
+            return Err(BloomError::Incompatible("unsupported format version".to_string()));
+        }
+        let strata_count = u32::from(bytes[6]);
+        if !(1..=MAX_STRATA).contains(&strata_count) {
+            return Err(BloomError::CorruptFile("invalid layout".to_string()));
+        }
+        let mut body = &contents[HEADER_LEN..];
+        let mut strata = Vec::with_capacity(strata_count as usize);
+        for _ in 0..strata_count {
+            let len = match body.get(..8).map(|len| usize::try_from(read_le(len, 0, 8))) {
+                Some(Ok(len)) if len <= body.len() - 8 => len,
+                _ => return Err(BloomError::CorruptFile("truncated stratum".to_string())),
+            };
+            strata.push(InvertibleBloomLookupTable::from_bytes(&body[8..8 + len])?);
+            body = &body[8 + len..];
+        }
+        let shape = |stratum: &InvertibleBloomLookupTable| (stratum.cell_count(), stratum.hash_count(), stratum.seed());
+        if !body.is_empty() || strata.iter().any(|stratum| shape(stratum) != shape(&strata[0])) {
+            return Err(BloomError::CorruptFile("invalid layout".to_string()));
+        }
+        let seed = strata[0].seed();
+        Ok(StrataEstimator { strata, seed })
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        for stratum in self.strata.iter_mut() {
+            stratum.set_seed(seed);
+        }
+    }
+
+    /// The stratum `key` belongs in: the number of trailing zeros of its
+    /// hash, capped at the top stratum.
+    fn stratum(&self, key: u64) -> usize {
+        let hash = fmix64(key ^ self.seed ^ 0x5bd1_e995_9e37_79b9);
+        (hash.trailing_zeros() as usize).min(self.strata.len() - 1)
+    }
+}
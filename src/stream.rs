@@ -0,0 +1,90 @@
+use core::borrow::Borrow;
+use core::future;
+use core::hash::{BuildHasher, Hash};
+use core::pin::pin;
+
+use futures_core::Stream;
+use tokio::fs::File;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader};
+
+use crate::{BloomFilter, BloomFilterBuilder, Result};
+
+impl<T: Hash, S: BuildHasher> BloomFilter<T, S> {
+    /// Records every item `stream` yields, returning how many it yielded.
+    ///
+    /// Items are inserted as they arrive, so the task only waits on the
+    /// stream and never blocks the runtime.
+    pub async fn add_stream<St: Stream<Item = T>>(&mut self, stream: St) -> usize {
+        let mut stream = pin!(stream);
+        let mut count = 0;
+        while let Some(item) = future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+            self.add(&item);
+            count += 1;
+        }
+        count
+    }
+
+    /// Records every (trimmed) line `reader` yields, returning how many it
+    /// yielded.
+    ///
+    /// The lines are read into one buffer, so no string is allocated per
+    /// line.
+    ///
+    /// ```
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// use bloom::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::<String>::new(1000, 0.01);
+    /// let input: &[u8] = b"apple\nbanana\n";
+    /// assert_eq!(filter.add_lines(input).await.unwrap(), 2);
+    /// assert!(filter.contains("banana"));
+    /// # });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::Io`](crate::BloomError::Io) if reading fails or
+    /// a line isn't UTF-8. The lines read before it stay in the filter.
+    pub async fn add_lines<R: AsyncBufRead + Unpin>(&mut self, mut reader: R) -> Result<usize>
+    where
+        T: Borrow<str>,
+    {
+        let mut line = String::new();
+        let mut count = 0;
+        while reader.read_line(&mut line).await? > 0 {
+            self.add(line.trim());
+            line.clear();
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+/// Like [`filter_from_file`](crate::filter_from_file), but reads the file
+/// through tokio, so that it can be awaited on a runtime without blocking
+/// its other tasks.
+pub async fn filter_from_file_async(
+    path: &str,
+    capacity: usize,
+    false_positive_prob: f64,
+) -> Result<BloomFilter<String>> {
+    filter_from_async_reader(File::open(path).await?, capacity, false_positive_prob).await
+}
+
+/// Builds a filter holding every (trimmed) line `reader` yields: a socket,
+/// a pipe from a child process or anything else tokio can read.
+///
+/// The filter is sized for `capacity` lines, and its false positive rate
+/// climbs past `false_positive_prob` if the reader yields more.
+pub async fn filter_from_async_reader<R: AsyncRead + Unpin>(
+    reader: R,
+    capacity: usize,
+    false_positive_prob: f64,
+) -> Result<BloomFilter<String>> {
+    let mut filter = BloomFilterBuilder::new()
+        .item_count(capacity)
+        .false_positive_prob(false_positive_prob)
+        .build()?;
+    filter.add_lines(BufReader::new(reader)).await?;
+    Ok(filter)
+}
@@ -0,0 +1,515 @@
+use std::borrow::Borrow;
+use std::cmp;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::bit_vec::BitVec;
+use crate::counting::Counters;
+use crate::probe::Probes;
+use crate::{
+    math, params, ApproximateMembership, BloomFilter, CounterStats, CounterWidth, DefaultBuildHasher, Removable, Result,
+};
+
+/// The number of locks a filter is split into unless told otherwise: enough
+/// that threads rarely contend on machines with dozens of cores.
+const DEFAULT_STRIPES: usize = 64;
+
+/// A run of counters behind one lock.
+#[derive(Debug, Clone)]
+struct Stripe {
+    counters: Counters,
+    saturated: u64,
+}
+
+/// A [`CountingBloomFilter`](crate::CountingBloomFilter) that many threads
+/// can insert into, query and remove from at once, with its counters split
+/// into stripes of consecutive slots that each have their own lock.
+///
+/// Counters can't be updated with a single atomic OR the way the bits of a
+/// `ConcurrentBloomFilter` can, since removing has to check and decrement
+/// them, so each operation locks the stripes its item's counters fall in.
+/// With many stripes, threads rarely want the same one. An insertion or
+/// removal holds all of its stripes at once, taken in increasing order so
+/// that threads can't deadlock, so it is atomic: two threads removing an
+/// item that was inserted once can't both succeed. A lookup takes one
+/// stripe at a time.
+///
+/// Items map to the same slots as in a `CountingBloomFilter` of the same
+/// parameters, and counters behave the same way, saturating at their
+/// maximum.
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// use bloom::StripedCountingBloomFilter;
+///
+/// let filter = Arc::new(StripedCountingBloomFilter::<u64>::new(10_000, 0.01));
+/// let workers: Vec<_> = (0..4u64)
+///     .map(|worker| {
+///         let filter = Arc::clone(&filter);
+///         thread::spawn(move || {
+///             for i in 0..1000 {
+///                 filter.insert(&(worker * 1000 + i));
+///             }
+///             for i in 0..500 {
+///                 filter.remove(&(worker * 1000 + i));
+///             }
+///         })
+///     })
+///     .collect();
+/// for worker in workers {
+///     worker.join().unwrap();
+/// }
+/// assert!(filter.contains(&999));
+/// assert!(!filter.contains(&1));
+/// ```
+#[derive(Debug)]
+pub struct StripedCountingBloomFilter<T, S = DefaultBuildHasher> {
+    stripes: Box<[Mutex<Stripe>]>,
+    /// The number of slots in every stripe but the last.
+    stripe_len: u64,
+    slots: u64,
+    width: CounterWidth,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    hash_count: usize,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because mutexes aren't `Clone`, and deriving would
+// require `T: Clone`.
+impl<T, S: Clone> Clone for StripedCountingBloomFilter<T, S> {
+    fn clone(&self) -> StripedCountingBloomFilter<T, S> {
+        StripedCountingBloomFilter {
+            stripes: self.stripes.iter().map(|stripe| Mutex::new(lock(stripe).clone())).collect(),
+            stripe_len: self.stripe_len,
+            slots: self.slots,
+            width: self.width,
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            hash_count: self.hash_count,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> StripedCountingBloomFilter<T> {
+    /// Creates a filter sized to hold `item_count` items with the given
+    /// false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0 or `false_positive_prob` is not strictly
+    /// between 0 and 1.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> StripedCountingBloomFilter<T> {
+        StripedCountingBloomFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](StripedCountingBloomFilter::new), but mixes `seed` into
+    /// every hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> StripedCountingBloomFilter<T> {
+        let mut filter = StripedCountingBloomFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter with exactly `slots` counters and `hashes` hash
+    /// functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slots` or `hashes` is 0.
+    pub fn from_params(slots: u64, hashes: usize) -> StripedCountingBloomFilter<T> {
+        StripedCountingBloomFilter::from_params_with_hasher(slots, hashes, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> StripedCountingBloomFilter<T, S> {
+    /// Like [`new`](StripedCountingBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(
+        item_count: usize,
+        false_positive_prob: f64,
+        hash_builder: S,
+    ) -> StripedCountingBloomFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let slots = params::optimal_bits(item_count, false_positive_prob);
+        let hash_count = params::optimal_hashes(slots, item_count);
+        let mut filter = StripedCountingBloomFilter::from_params_with_hasher(slots, hash_count, hash_builder);
+        filter.item_count = Some(item_count);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](StripedCountingBloomFilter::from_params), but
+    /// hashes items with `hash_builder`.
+    pub fn from_params_with_hasher(slots: u64, hashes: usize, hash_builder: S) -> StripedCountingBloomFilter<T, S> {
+        if let Err(e) = params::validate_layout(slots, hashes) {
+            panic!("{}", e);
+        }
+        let width = CounterWidth::default();
+        let (stripes, stripe_len) = stripes(slots, DEFAULT_STRIPES, width);
+        StripedCountingBloomFilter {
+            stripes,
+            stripe_len,
+            slots,
+            width,
+            item_count: None,
+            false_positive_prob: None,
+            hash_count: hashes,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Splits the counters into stripes of equal length behind at most
+    /// `stripes` locks, keeping the current counts. Stripes are never
+    /// shorter than one counter.
+    ///
+    /// This is meant to be called on a new filter. More stripes mean less
+    /// contention between threads, at the cost of a lock each.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stripes` is 0.
+    pub fn with_stripes(self, stripes: usize) -> StripedCountingBloomFilter<T, S> {
+        assert!(stripes > 0, "the stripe count must be nonzero");
+        let width = self.width;
+        self.restriped(stripes, width)
+    }
+
+    /// Switches to counters of the given width, keeping the current counts,
+    /// as [`CountingBloomFilter::with_counter_width`](crate::CountingBloomFilter::with_counter_width)
+    /// does.
+    pub fn with_counter_width(self, width: CounterWidth) -> StripedCountingBloomFilter<T, S> {
+        let stripes = self.stripes.len();
+        self.restriped(stripes, width)
+    }
+
+    /// Records `item` in the filter, returning `true` if it was probably
+    /// already present, i.e. every counter it maps to was already nonzero.
+    ///
+    /// The counters are incremented either way, so an item inserted twice
+    /// must be removed twice.
+    pub fn insert<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let indices: Vec<u64> = self.probes(item).collect();
+        let mut locked = self.lock_all(&indices);
+        let max = self.width.max();
+        let mut present = true;
+        for &index in &indices {
+            let (stripe, offset) = self.locate(index);
+            let stripe = stripe_mut(&mut locked, stripe);
+            let count = stripe.counters.get(offset);
+            if count == 0 {
+                present = false;
+            }
+            if count < max {
+                stripe.counters.set(offset, count + 1);
+                if count + 1 == max {
+                    stripe.saturated += 1;
+                }
+            }
+        }
+        present
+    }
+
+    /// Returns `true` if `item` has probably been added and not removed, and
+    /// `false` if it definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.probes(item).all(|index| {
+            let (stripe, offset) = self.locate(index);
+            lock(&self.stripes[stripe]).counters.get(offset) != 0
+        })
+    }
+
+    /// Removes one occurrence of `item`, returning `true` if it was probably
+    /// present.
+    ///
+    /// If any of `item`'s counters is zero it was definitely never added, and
+    /// nothing is changed. Saturated counters are left as they are.
+    pub fn remove<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let indices: Vec<u64> = self.probes(item).collect();
+        let mut locked = self.lock_all(&indices);
+        let present = indices.iter().all(|&index| {
+            let (stripe, offset) = self.locate(index);
+            stripe_mut(&mut locked, stripe).counters.get(offset) != 0
+        });
+        if !present {
+            return false;
+        }
+        let max = self.width.max();
+        for &index in &indices {
+            let (stripe, offset) = self.locate(index);
+            let counters = &mut stripe_mut(&mut locked, stripe).counters;
+            // Saturating, since an item may map to the same counter twice.
+            let count = counters.get(offset);
+            if count < max {
+                counters.set(offset, count.saturating_sub(1));
+            }
+        }
+        true
+    }
+
+    /// A plain [`BloomFilter`] with a bit set for every nonzero counter.
+    ///
+    /// Stripes are copied one at a time, so an item being inserted or
+    /// removed meanwhile may be only partly copied.
+    pub fn to_bloom_filter(&self) -> BloomFilter<T, S>
+    where
+        S: Clone,
+    {
+        let mut bit_vec = BitVec::new(self.slots);
+        for (i, stripe) in self.stripes.iter().enumerate() {
+            let stripe = lock(stripe);
+            let start = i as u64 * self.stripe_len;
+            for offset in (0..stripe.counters.len).filter(|&offset| stripe.counters.get(offset) != 0) {
+                bit_vec.set(start + offset);
+            }
+        }
+        BloomFilter::from_parts(
+            bit_vec,
+            self.hash_count,
+            self.item_count,
+            self.false_positive_prob,
+            self.seed,
+            self.hash_builder.clone(),
+        )
+    }
+
+    /// Removes every item from the filter.
+    ///
+    /// Stripes are cleared one at a time, so items inserted meanwhile may be
+    /// left partly counted.
+    pub fn clear(&self) {
+        for stripe in self.stripes.iter() {
+            let mut stripe = lock(stripe);
+            stripe.counters.clear();
+            stripe.saturated = 0;
+        }
+    }
+
+    /// Returns `true` if every counter is zero.
+    pub fn is_empty(&self) -> bool {
+        self.stripes
+            .iter()
+            .all(|stripe| lock(stripe).counters.bytes.iter().all(|&byte| byte == 0))
+    }
+
+    /// The number of counters that are nonzero.
+    pub fn count_nonzero(&self) -> u64 {
+        self.stripes
+            .iter()
+            .map(|stripe| {
+                let stripe = lock(stripe);
+                (0..stripe.counters.len).filter(|&offset| stripe.counters.get(offset) != 0).count() as u64
+            })
+            .sum()
+    }
+
+    /// The number of nonzero and saturated counters.
+    pub fn stats(&self) -> CounterStats {
+        CounterStats {
+            slots: self.slots,
+            nonzero: self.count_nonzero(),
+            saturated: self.stripes.iter().map(|stripe| lock(stripe).saturated).sum(),
+        }
+    }
+
+    /// Estimates the false positive probability of the filter as it is now,
+    /// from the fraction of nonzero counters.
+    pub fn current_fpr(&self) -> f64 {
+        math::powf(self.count_nonzero() as f64 / self.slots as f64, self.hash_count as f64)
+    }
+
+    /// Estimates how many distinct items the filter holds from the number of
+    /// nonzero counters.
+    pub fn estimated_len(&self) -> f64 {
+        let m = self.slots as f64;
+        -m / self.hash_count as f64 * math::ln(1.0 - self.count_nonzero() as f64 / m)
+    }
+
+    /// The number of counters.
+    pub fn slots(&self) -> u64 {
+        self.slots
+    }
+
+    /// The number of locks the counters are split between.
+    pub fn stripe_count(&self) -> usize {
+        self.stripes.len()
+    }
+
+    /// The width of each counter.
+    pub fn counter_width(&self) -> CounterWidth {
+        self.width
+    }
+
+    /// The number of hash functions applied to each item.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The false positive probability the filter was sized for, or `None`
+    /// if it was built from explicit parameters.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The number of items the filter was sized for, or `None` if it was
+    /// built from explicit parameters.
+    pub fn capacity(&self) -> Option<usize> {
+        self.item_count
+    }
+
+    /// The number of bytes of memory the filter occupies, including its
+    /// locks and counters.
+    pub fn memory_bytes(&self) -> usize {
+        let counters: usize = self.stripes.iter().map(|stripe| lock(stripe).counters.bytes.len()).sum();
+        mem::size_of_val(self) + mem::size_of_val(&*self.stripes) + counters
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Rebuilds the stripes with a new stripe count and counter width,
+    /// capping counts that don't fit.
+    fn restriped(mut self, stripe_count: usize, width: CounterWidth) -> StripedCountingBloomFilter<T, S> {
+        let (stripes, stripe_len) = stripes(self.slots, stripe_count, width);
+        for index in 0..self.slots {
+            let (stripe, offset) = self.locate(index);
+            let count = cmp::min(lock(&self.stripes[stripe]).counters.get(offset), width.max());
+            if count != 0 {
+                let mut stripe = lock(&stripes[(index / stripe_len) as usize]);
+                stripe.counters.set(index % stripe_len, count);
+                if count == width.max() {
+                    stripe.saturated += 1;
+                }
+            }
+        }
+        self.stripes = stripes;
+        self.stripe_len = stripe_len;
+        self.width = width;
+        self
+    }
+
+    /// The stripe holding slot `index`, and the slot's offset within it.
+    fn locate(&self, index: u64) -> (usize, u64) {
+        ((index / self.stripe_len) as usize, index % self.stripe_len)
+    }
+
+    /// Locks the stripes holding the slots `indices`, in increasing order so
+    /// that threads locking overlapping stripes can't deadlock.
+    fn lock_all(&self, indices: &[u64]) -> Vec<(usize, MutexGuard<'_, Stripe>)> {
+        let mut stripes: Vec<usize> = indices.iter().map(|&index| self.locate(index).0).collect();
+        stripes.sort_unstable();
+        stripes.dedup();
+        stripes.into_iter().map(|stripe| (stripe, lock(&self.stripes[stripe]))).collect()
+    }
+
+    /// The counter indices `item` maps to.
+    fn probes<Q: ?Sized + Hash>(&self, item: &Q) -> Probes<S::Hasher> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        Probes::new(hasher, self.slots, self.hash_count, self.slots > params::WIDE_HASH_THRESHOLD)
+    }
+}
+
+/// Splits `slots` counters into at most `count` stripes of equal length, but
+/// for a shorter last one, returning the stripes and their length.
+fn stripes(slots: u64, count: usize, width: CounterWidth) -> (Box<[Mutex<Stripe>]>, u64) {
+    let stripe_len = slots.div_ceil(cmp::min(count as u64, slots));
+    let stripes = (0..slots.div_ceil(stripe_len))
+        .map(|i| {
+            let len = cmp::min(stripe_len, slots - i * stripe_len);
+            let counters = match Counters::new(len, width) {
+                Ok(counters) => counters,
+                Err(e) => panic!("{}", e),
+            };
+            Mutex::new(Stripe { counters, saturated: 0 })
+        })
+        .collect();
+    (stripes, stripe_len)
+}
+
+/// Locks a stripe, ignoring poisoning: counters are never left half-updated
+/// by a panic.
+fn lock(stripe: &Mutex<Stripe>) -> MutexGuard<'_, Stripe> {
+    stripe.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The locked stripe `stripe` among those `lock_all` returned.
+fn stripe_mut<'a>(locked: &'a mut [(usize, MutexGuard<'_, Stripe>)], stripe: usize) -> &'a mut Stripe {
+    let position = match locked.binary_search_by_key(&stripe, |&(locked, _)| locked) {
+        Ok(position) => position,
+        Err(_) => unreachable!("stripe {} wasn't locked", stripe),
+    };
+    &mut locked[position].1
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for StripedCountingBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(StripedCountingBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        StripedCountingBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        StripedCountingBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        StripedCountingBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        StripedCountingBloomFilter::memory_bytes(self)
+    }
+}
+
+impl<T, Q, S> Removable<Q> for StripedCountingBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    fn remove(&mut self, item: &Q) -> bool {
+        StripedCountingBloomFilter::remove(self, item)
+    }
+}
@@ -0,0 +1,202 @@
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use crate::{ApproximateMembership, BloomFilter, BloomFilterBuilder, CountMinSketch, DefaultBuildHasher};
+
+/// How many accesses per cache entry make up a sample, as Einziger et al.
+/// suggest.
+const SAMPLE_FACTOR: usize = 10;
+
+/// The doorkeeper's false positive probability.
+const DOORKEEPER_FALSE_POSITIVE_PROB: f64 = 0.01;
+
+/// The sketch's rows.
+const SKETCH_DEPTH: usize = 4;
+
+/// A TinyLFU cache admission policy (Einziger et al., "TinyLFU: A Highly
+/// Efficient Cache Admission Policy"), which keeps a cache's hit rate up by
+/// only letting in items that are accessed more often than the ones they
+/// would evict.
+///
+/// Accesses are counted in an aging [`CountMinSketch`] behind a doorkeeper
+/// [`BloomFilter`]: an item's first access only goes in the doorkeeper, and
+/// only later ones are counted, so the long tail of items seen once doesn't
+/// crowd the sketch. After every sample of ten accesses per cache entry the
+/// doorkeeper is cleared and every count halved, so frequencies reflect
+/// recent history.
+///
+/// The cache asks the policy on every miss that would evict: if the
+/// candidate isn't estimated to be more frequent than the victim the eviction
+/// policy picked, the candidate isn't cached.
+///
+/// ```
+/// use bloom::TinyLfu;
+///
+/// let mut policy = TinyLfu::<&str>::new(100);
+/// for _ in 0..5 {
+///     policy.record_access(&"popular");
+/// }
+/// policy.record_access(&"one-off");
+/// assert!(policy.should_admit(&"popular", &"one-off"));
+/// assert!(!policy.should_admit(&"one-off", &"popular"));
+/// ```
+#[derive(Debug)]
+pub struct TinyLfu<T, S = DefaultBuildHasher> {
+    doorkeeper: BloomFilter<T, S>,
+    sketch: CountMinSketch<T, S>,
+    capacity: usize,
+    sample_size: usize,
+    /// Accesses since the last reset, halved by each reset.
+    accesses: usize,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for TinyLfu<T, S> {
+    fn clone(&self) -> TinyLfu<T, S> {
+        TinyLfu {
+            doorkeeper: self.doorkeeper.clone(),
+            sketch: self.sketch.clone(),
+            capacity: self.capacity,
+            sample_size: self.sample_size,
+            accesses: self.accesses,
+        }
+    }
+}
+
+impl<T: Hash> TinyLfu<T> {
+    /// Creates a policy for a cache of `capacity` entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new(capacity: usize) -> TinyLfu<T> {
+        TinyLfu::with_hasher(capacity, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](TinyLfu::new), but mixes `seed` into every hash.
+    pub fn with_seed(capacity: usize, seed: u64) -> TinyLfu<T> {
+        TinyLfu::build(capacity, seed, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher + Clone> TinyLfu<T, S> {
+    /// Like [`new`](TinyLfu::new), but hashes items with `hash_builder`.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> TinyLfu<T, S> {
+        TinyLfu::build(capacity, 0, hash_builder)
+    }
+
+    /// Records an access to `item`, whether or not it is cached.
+    pub fn record_access<Q: ?Sized + Hash>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+    {
+        if self.doorkeeper.insert(item) {
+            self.sketch.insert(item);
+        }
+        self.accesses += 1;
+        if self.accesses >= self.sample_size {
+            self.reset();
+        }
+    }
+
+    /// Estimates how often `item` has been accessed recently: its count in
+    /// the sketch, plus one if it has passed the doorkeeper.
+    pub fn frequency<Q: ?Sized + Hash>(&self, item: &Q) -> u64
+    where
+        T: Borrow<Q>,
+    {
+        self.sketch.estimate_count(item) + u64::from(self.doorkeeper.contains(item))
+    }
+
+    /// Returns `true` if `candidate` should replace `victim` in the cache:
+    /// if it is estimated to have been accessed more often recently.
+    pub fn should_admit<Q: ?Sized + Hash, R: ?Sized + Hash>(&self, candidate: &Q, victim: &R) -> bool
+    where
+        T: Borrow<Q> + Borrow<R>,
+    {
+        self.frequency(candidate) > self.frequency(victim)
+    }
+
+    /// Ages the history early: clears the doorkeeper and halves every count,
+    /// as happens after every sample.
+    pub fn reset(&mut self) {
+        self.doorkeeper.clear();
+        self.sketch.halve();
+        self.accesses /= 2;
+    }
+
+    /// Forgets every access, keeping the parameters.
+    pub fn clear(&mut self) {
+        self.doorkeeper.clear();
+        self.sketch.clear();
+        self.accesses = 0;
+    }
+
+    /// The number of cache entries the policy was sized for.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of accesses after which the history is aged.
+    pub fn sample_size(&self) -> usize {
+        self.sample_size
+    }
+
+    /// The filter of items accessed once since the last reset.
+    pub fn doorkeeper(&self) -> &BloomFilter<T, S> {
+        &self.doorkeeper
+    }
+
+    /// The sketch counting accesses past the doorkeeper.
+    pub fn sketch(&self) -> &CountMinSketch<T, S> {
+        &self.sketch
+    }
+
+    /// The number of bytes of memory the policy occupies, including the
+    /// doorkeeper's bits and the sketch's counters.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) - mem::size_of_val(&self.doorkeeper) - mem::size_of_val(&self.sketch)
+            + ApproximateMembership::<T>::memory_bytes(&self.doorkeeper)
+            + self.sketch.memory_bytes()
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.sketch.seed()
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        self.sketch.hasher()
+    }
+
+    fn build(capacity: usize, seed: u64, hash_builder: S) -> TinyLfu<T, S> {
+        assert!(capacity > 0, "the capacity must be nonzero");
+        let sample_size = capacity.saturating_mul(SAMPLE_FACTOR);
+        let doorkeeper = BloomFilterBuilder::new()
+            .hasher(hash_builder.clone())
+            .item_count(sample_size)
+            .false_positive_prob(DOORKEEPER_FALSE_POSITIVE_PROB)
+            .seed(seed)
+            .build();
+        let doorkeeper = match doorkeeper {
+            Ok(doorkeeper) => doorkeeper,
+            Err(e) => panic!("{}", e),
+        };
+        // A counter per entry in each row: only items frequent enough to be
+        // cached need to be told apart.
+        let width = cmp::max(capacity, 64) as u64;
+        let mut sketch =
+            CountMinSketch::from_params_with_hasher(width, SKETCH_DEPTH, hash_builder).with_conservative_update(true);
+        sketch.set_seed(seed);
+        TinyLfu {
+            doorkeeper,
+            sketch,
+            capacity,
+            sample_size,
+            accesses: 0,
+        }
+    }
+}
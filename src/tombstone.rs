@@ -0,0 +1,305 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use crate::{ApproximateMembership, BloomError, BloomFilter, BloomFilterBuilder, DefaultBuildHasher, Removable, Result};
+
+/// A bloom filter that supports occasional removals by recording removed
+/// items in a second, smaller "tombstone" filter, for sets that rarely
+/// shrink and can't afford the counters of a
+/// [`CountingBloomFilter`](crate::CountingBloomFilter).
+///
+/// An item is present if the main filter contains it and the tombstone
+/// filter doesn't. Unlike a counting filter, which undoes an insertion
+/// exactly, a tombstone is a lookup in a filter of its own, so its false
+/// positives hide live items: after removals, a live item is reported
+/// absent with about the tombstone filter's false positive probability.
+/// A removed item also stays removed, even if inserted again, until the
+/// filter is compacted.
+///
+/// A filter made with [`with_journal`](TombstoneBloomFilter::with_journal)
+/// keeps a copy of every live item, so that
+/// [`compact`](TombstoneBloomFilter::compact) can rebuild both filters from
+/// scratch, dropping the tombstones and the false negatives they cause.
+/// Without a journal, the only way back is to [`clear`](TombstoneBloomFilter::clear)
+/// the filter and insert the live items again.
+///
+/// ```
+/// use bloom::TombstoneBloomFilter;
+///
+/// let mut filter = TombstoneBloomFilter::<u64>::new(1000, 100, 0.01).with_journal(true);
+/// filter.insert(&1);
+/// filter.insert(&2);
+/// assert!(filter.remove(&1));
+/// assert!(!filter.contains(&1));
+/// assert!(filter.contains(&2));
+///
+/// filter.insert(&1);
+/// assert!(!filter.contains(&1));
+/// filter.compact().unwrap();
+/// assert!(filter.contains(&1));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TombstoneBloomFilter<T, S = DefaultBuildHasher> {
+    filter: BloomFilter<T, S>,
+    /// The items removed since the filter was last compacted.
+    removed: BloomFilter<T, S>,
+    removal_count: usize,
+    removals: usize,
+    /// Every live item, if kept.
+    journal: Option<Vec<T>>,
+}
+
+impl<T: Hash> TombstoneBloomFilter<T> {
+    /// Creates a filter sized to hold `item_count` items and record
+    /// `removal_count` removals between compactions, both with the given
+    /// false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` or `removal_count` is 0, or if
+    /// `false_positive_prob` is not strictly between 0 and 1.
+    pub fn new(item_count: usize, removal_count: usize, false_positive_prob: f64) -> TombstoneBloomFilter<T> {
+        TombstoneBloomFilter::with_hasher(item_count, removal_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](TombstoneBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(
+        item_count: usize,
+        removal_count: usize,
+        false_positive_prob: f64,
+        seed: u64,
+    ) -> TombstoneBloomFilter<T> {
+        let hash_builder = DefaultBuildHasher::default();
+        TombstoneBloomFilter::build(item_count, removal_count, false_positive_prob, seed, hash_builder)
+    }
+}
+
+impl<T: Hash, S: BuildHasher + Clone> TombstoneBloomFilter<T, S> {
+    /// Like [`new`](TombstoneBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(
+        item_count: usize,
+        removal_count: usize,
+        false_positive_prob: f64,
+        hash_builder: S,
+    ) -> TombstoneBloomFilter<T, S> {
+        TombstoneBloomFilter::build(item_count, removal_count, false_positive_prob, 0, hash_builder)
+    }
+
+    /// Sets whether the filter keeps a journal of its live items for
+    /// [`compact`](TombstoneBloomFilter::compact) to rebuild from.
+    ///
+    /// # Panics
+    ///
+    /// Panics if anything has been inserted, since the journal would miss
+    /// it.
+    pub fn with_journal(mut self, journal: bool) -> TombstoneBloomFilter<T, S> {
+        assert!(self.filter.is_empty(), "the journal must be set before inserting");
+        self.journal = if journal { Some(Vec::new()) } else { None };
+        self
+    }
+
+    /// Returns `true` if `item` has probably been inserted and not removed
+    /// since, and `false` if it definitely hasn't, or has been removed.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.filter.contains(item) && (self.removals == 0 || !self.removed.contains(item))
+    }
+
+    /// Removes every item and tombstone, keeping the filter's allocations
+    /// and parameters.
+    pub fn clear(&mut self) {
+        self.filter.clear();
+        self.removed.clear();
+        self.removals = 0;
+        if let Some(journal) = &mut self.journal {
+            journal.clear();
+        }
+    }
+
+    /// Returns `true` if nothing has been inserted since the filter was
+    /// created or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.filter.is_empty()
+    }
+
+    /// The number of removals since the filter was last compacted.
+    pub fn removals(&self) -> usize {
+        self.removals
+    }
+
+    /// The number of removals the tombstone filter was sized for.
+    pub fn removal_capacity(&self) -> usize {
+        self.removal_count
+    }
+
+    /// Returns `true` once there have been as many removals as the tombstone
+    /// filter was sized for, after which live items are increasingly
+    /// reported absent.
+    pub fn needs_compaction(&self) -> bool {
+        self.removals >= self.removal_count
+    }
+
+    /// Estimates the number of distinct live items: those inserted less
+    /// those removed.
+    pub fn estimated_len(&self) -> f64 {
+        (self.filter.estimated_len() - self.removed.estimated_len()).max(0.0)
+    }
+
+    /// The filter items are inserted into.
+    pub fn filter(&self) -> &BloomFilter<T, S> {
+        &self.filter
+    }
+
+    /// The filter of items removed since the filter was last compacted.
+    pub fn tombstones(&self) -> &BloomFilter<T, S> {
+        &self.removed
+    }
+
+    /// The live items, if the filter keeps a journal.
+    pub fn journal(&self) -> Option<&[T]> {
+        self.journal.as_deref()
+    }
+
+    /// The number of bytes of memory the filter occupies, including both
+    /// filters' bits and the journal.
+    pub fn memory_bytes(&self) -> usize {
+        let journal = self.journal.as_ref().map_or(0, |journal| journal.capacity() * mem::size_of::<T>());
+        mem::size_of_val(self) - mem::size_of_val(&self.filter) - mem::size_of_val(&self.removed)
+            + ApproximateMembership::<T>::memory_bytes(&self.filter)
+            + ApproximateMembership::<T>::memory_bytes(&self.removed)
+            + journal
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.filter.seed()
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        self.filter.hasher()
+    }
+
+    fn build(
+        item_count: usize,
+        removal_count: usize,
+        false_positive_prob: f64,
+        seed: u64,
+        hash_builder: S,
+    ) -> TombstoneBloomFilter<T, S> {
+        let filter = |item_count| {
+            let filter = BloomFilterBuilder::new()
+                .hasher(hash_builder.clone())
+                .item_count(item_count)
+                .false_positive_prob(false_positive_prob)
+                .seed(seed)
+                .build();
+            match filter {
+                Ok(filter) => filter,
+                Err(e) => panic!("{}", e),
+            }
+        };
+        TombstoneBloomFilter {
+            filter: filter(item_count),
+            removed: filter(removal_count),
+            removal_count,
+            removals: 0,
+            journal: None,
+        }
+    }
+}
+
+impl<T: Hash + Clone + Eq, S: BuildHasher + Clone> TombstoneBloomFilter<T, S> {
+    /// Records `item`, returning `true` if it was probably already present.
+    ///
+    /// Inserting a removed item doesn't bring it back until the filter is
+    /// compacted. With a journal, inserting an item that is probably present
+    /// takes time linear in the number of live items.
+    pub fn insert(&mut self, item: &T) -> bool {
+        let present = self.contains(item);
+        self.filter.insert(item);
+        if let Some(journal) = &mut self.journal {
+            // A false positive mustn't keep the item out of the journal.
+            if !present || !journal.contains(item) {
+                journal.push(item.clone());
+            }
+        }
+        present
+    }
+
+    /// Removes `item`, returning `true` if it was probably present. Nothing
+    /// is changed if it definitely wasn't.
+    ///
+    /// With a journal this takes time linear in the number of live items.
+    pub fn remove(&mut self, item: &T) -> bool {
+        if !self.contains(item) {
+            return false;
+        }
+        self.removed.insert(item);
+        self.removals += 1;
+        if let Some(journal) = &mut self.journal {
+            journal.retain(|live| live != item);
+        }
+        true
+    }
+
+    /// Rebuilds the filter from its journal, dropping every tombstone, so
+    /// that removed items are gone without hiding live ones. Items inserted
+    /// again after being removed are present once more.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BloomError::InvalidParams`] if the filter keeps no journal.
+    pub fn compact(&mut self) -> Result<()> {
+        let journal = match &self.journal {
+            Some(journal) => journal,
+            None => return Err(BloomError::InvalidParams("the filter keeps no journal to compact from".to_string())),
+        };
+        self.filter.clear();
+        for item in journal {
+            self.filter.insert(item);
+        }
+        self.removed.clear();
+        self.removals = 0;
+        Ok(())
+    }
+}
+
+impl<T: Hash + Clone + Eq, S: BuildHasher + Clone> ApproximateMembership<T> for TombstoneBloomFilter<T, S> {
+    fn insert(&mut self, item: &T) -> Result<bool> {
+        Ok(TombstoneBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &T) -> bool {
+        TombstoneBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        TombstoneBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        TombstoneBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.filter.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        TombstoneBloomFilter::memory_bytes(self)
+    }
+}
+
+impl<T: Hash + Clone + Eq, S: BuildHasher + Clone> Removable<T> for TombstoneBloomFilter<T, S> {
+    fn remove(&mut self, item: &T) -> bool {
+        TombstoneBloomFilter::remove(self, item)
+    }
+}
@@ -0,0 +1,246 @@
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Reverse;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::mem;
+
+use crate::{CountMinSketch, DefaultBuildHasher};
+
+/// Tracks the approximately `k` most frequent items of a stream, with their
+/// estimated counts.
+///
+/// Every item goes into a [`CountMinSketch`], and a min-heap keeps the `k`
+/// items with the highest estimates seen so far. An item that isn't in the
+/// heap replaces its least frequent entry once its estimate beats it. Only
+/// the `k` tracked items are stored, so memory is bounded however many
+/// distinct items the stream has; items that were frequent early but stop
+/// occurring stay in the heap until something overtakes them.
+///
+/// [`new`](TopK::new) turns on the sketch's
+/// [conservative update](CountMinSketch::with_conservative_update), which
+/// keeps the rare items that make up most of a skewed stream from inflating
+/// each other's estimates past the real heavy hitters.
+///
+/// ```
+/// use bloom::TopK;
+///
+/// let mut top = TopK::<String>::new(2, 0.001, 0.01);
+/// for word in "the cat saw the dog chase the cat".split(' ') {
+///     top.insert(word);
+/// }
+/// assert_eq!(top.top(), [("the".to_string(), 3), ("cat".to_string(), 2)]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TopK<T, S = DefaultBuildHasher> {
+    sketch: CountMinSketch<T, S>,
+    k: usize,
+    /// A binary min-heap of the tracked items by count.
+    heap: Vec<Entry<T>>,
+    /// The heap position of each tracked item, by hash.
+    positions: BTreeMap<u64, usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry<T> {
+    count: u64,
+    hash: u64,
+    item: T,
+}
+
+impl<T: Hash + Eq> TopK<T> {
+    /// Creates a tracker of the `k` most frequent items, counting them in a
+    /// conservative-update [`CountMinSketch`] with the given error bounds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is 0, or as for [`CountMinSketch::new`].
+    pub fn new(k: usize, epsilon: f64, delta: f64) -> TopK<T> {
+        TopK::from_sketch(k, CountMinSketch::new(epsilon, delta).with_conservative_update(true))
+    }
+
+    /// Like [`new`](TopK::new), but mixes `seed` into every hash.
+    pub fn with_seed(k: usize, epsilon: f64, delta: f64, seed: u64) -> TopK<T> {
+        TopK::from_sketch(k, CountMinSketch::with_seed(epsilon, delta, seed).with_conservative_update(true))
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher> TopK<T, S> {
+    /// Like [`new`](TopK::new), but hashes items with `hash_builder`.
+    pub fn with_hasher(k: usize, epsilon: f64, delta: f64, hash_builder: S) -> TopK<T, S> {
+        TopK::from_sketch(
+            k,
+            CountMinSketch::with_hasher(epsilon, delta, hash_builder).with_conservative_update(true),
+        )
+    }
+
+    /// Creates a tracker of the `k` most frequent items that counts them in
+    /// `sketch`, which may already hold counts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is 0.
+    pub fn from_sketch(k: usize, sketch: CountMinSketch<T, S>) -> TopK<T, S> {
+        assert!(k > 0, "k must be nonzero");
+        TopK {
+            sketch,
+            k,
+            heap: Vec::with_capacity(k),
+            positions: BTreeMap::new(),
+        }
+    }
+
+    /// Records one occurrence of `item`.
+    pub fn insert<Q>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash + Eq + ToOwned<Owned = T>,
+    {
+        self.insert_count(item, 1);
+    }
+
+    /// Records `count` occurrences of `item` at once.
+    pub fn insert_count<Q>(&mut self, item: &Q, count: u64)
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash + Eq + ToOwned<Owned = T>,
+    {
+        self.sketch.insert_count(item, count);
+        let estimate = self.sketch.estimate_count(item);
+        let hash = self.hash(item);
+        if let Some(&position) = self.positions.get(&hash) {
+            // Unless a different item with the same 64-bit hash is tracked,
+            // which is left be rather than track two items under one key.
+            if self.heap[position].item.borrow() == item {
+                self.heap[position].count = estimate;
+                self.sift_down(position);
+            }
+            return;
+        }
+        let entry = Entry {
+            count: estimate,
+            hash,
+            item: item.to_owned(),
+        };
+        if self.heap.len() < self.k {
+            self.heap.push(entry);
+            self.positions.insert(hash, self.heap.len() - 1);
+            self.sift_up(self.heap.len() - 1);
+        } else if estimate > self.heap[0].count {
+            self.positions.remove(&self.heap[0].hash);
+            self.positions.insert(hash, 0);
+            self.heap[0] = entry;
+            self.sift_down(0);
+        }
+    }
+
+    /// The tracked items and their estimated counts, most frequent first.
+    pub fn top(&self) -> Vec<(T, u64)>
+    where
+        T: Clone,
+    {
+        let mut top: Vec<(T, u64)> = self.heap.iter().map(|entry| (entry.item.clone(), entry.count)).collect();
+        top.sort_by_key(|&(_, count)| Reverse(count));
+        top
+    }
+
+    /// Estimates how many times `item` has occurred, whether or not it's
+    /// tracked; see [`CountMinSketch::estimate_count`].
+    pub fn estimate_count<Q: ?Sized + Hash>(&self, item: &Q) -> u64
+    where
+        T: Borrow<Q>,
+    {
+        self.sketch.estimate_count(item)
+    }
+
+    /// Returns `true` if `item` is one of the tracked items.
+    pub fn contains<Q>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.positions
+            .get(&self.hash(item))
+            .is_some_and(|&position| self.heap[position].item.borrow() == item)
+    }
+
+    /// Forgets every item and count, keeping the configuration.
+    pub fn clear(&mut self) {
+        self.sketch.clear();
+        self.heap.clear();
+        self.positions.clear();
+    }
+
+    /// Returns `true` if nothing has been inserted since the tracker was
+    /// created or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// The number of tracked items, at most `k`.
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// The number of items tracked once the stream has that many.
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// The sketch items are counted in.
+    pub fn sketch(&self) -> &CountMinSketch<T, S> {
+        &self.sketch
+    }
+
+    /// The number of bytes of memory the tracker occupies, approximating
+    /// the index by its entries. Heap memory owned by the items themselves
+    /// isn't included.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + self.sketch.memory_bytes() - mem::size_of_val(&self.sketch)
+            + self.heap.capacity() * mem::size_of::<Entry<T>>()
+            + self.positions.len() * mem::size_of::<(u64, usize)>()
+    }
+
+    fn hash<Q: ?Sized + Hash>(&self, item: &Q) -> u64 {
+        let mut hasher = self.sketch.hasher().build_hasher();
+        hasher.write_u64(self.sketch.seed());
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn sift_up(&mut self, mut position: usize) {
+        while position > 0 {
+            let parent = (position - 1) / 2;
+            if self.heap[parent].count <= self.heap[position].count {
+                break;
+            }
+            self.swap(parent, position);
+            position = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut position: usize) {
+        loop {
+            let (left, right) = (2 * position + 1, 2 * position + 2);
+            let mut smallest = position;
+            if left < self.heap.len() && self.heap[left].count < self.heap[smallest].count {
+                smallest = left;
+            }
+            if right < self.heap.len() && self.heap[right].count < self.heap[smallest].count {
+                smallest = right;
+            }
+            if smallest == position {
+                break;
+            }
+            self.swap(smallest, position);
+            position = smallest;
+        }
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        self.positions.insert(self.heap[a].hash, a);
+        self.positions.insert(self.heap[b].hash, b);
+    }
+}
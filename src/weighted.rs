@@ -0,0 +1,338 @@
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::bit_vec::BitVec;
+use crate::probe::Probes;
+use crate::{math, params, ApproximateMembership, DefaultBuildHasher, Result};
+
+/// The most hash functions [`hash_count_for`](WeightedBloomFilter::hash_count_for)
+/// gives an item, however heavy.
+const MAX_WEIGHTED_HASH_COUNT: usize = 64;
+
+/// A bloom filter in which each item may use its own number of hash
+/// functions (Bruck et al., "Weighted Bloom Filter"), so that items whose
+/// false positives are costly get fewer of them.
+///
+/// An item with `k` hashes is falsely reported present with probability
+/// about `f^k`, where `f` is the fraction of bits set. Giving important
+/// items more hashes than the optimum and the rest fewer leaves the fill,
+/// and so the memory, about where it was, while the important items' false
+/// positive probability falls by half for every hash they gain. An item
+/// must be looked up with the hash count it was inserted with, so the
+/// caller must be able to tell an item's importance at lookup time too: by
+/// its type, its prefix, or a table of hot keys.
+///
+/// [`hash_count_for`](WeightedBloomFilter::hash_count_for) turns a relative
+/// weight, say the cost of a false positive against the average, into the
+/// hash count that minimises the total cost. `insert` and `contains` use
+/// the filter's own hash count, which suits items of weight 1.
+///
+/// ```
+/// use bloom::WeightedBloomFilter;
+///
+/// let mut filter = WeightedBloomFilter::<&str>::new(1000, 0.01);
+/// let critical = filter.hash_count_for(16.0);
+/// assert_eq!(critical, filter.hash_count() + 4);
+/// filter.insert_weighted(&"admin.example", critical);
+/// filter.insert(&"news.example");
+/// assert!(filter.contains_weighted(&"admin.example", critical));
+/// assert!(filter.contains(&"news.example"));
+/// ```
+#[derive(Debug)]
+pub struct WeightedBloomFilter<T, S = DefaultBuildHasher> {
+    bit_vec: BitVec,
+    item_count: Option<usize>,
+    false_positive_prob: Option<f64>,
+    hash_count: usize,
+    /// Insertions and the hashes they used, for the mean hash count.
+    insertions: u64,
+    hashes_inserted: u64,
+    seed: u64,
+    hash_builder: S,
+    phantom: PhantomData<fn(T)>,
+}
+
+// Implemented by hand because deriving would require `T: Clone`.
+impl<T, S: Clone> Clone for WeightedBloomFilter<T, S> {
+    fn clone(&self) -> WeightedBloomFilter<T, S> {
+        WeightedBloomFilter {
+            bit_vec: self.bit_vec.clone(),
+            item_count: self.item_count,
+            false_positive_prob: self.false_positive_prob,
+            hash_count: self.hash_count,
+            insertions: self.insertions,
+            hashes_inserted: self.hashes_inserted,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash> WeightedBloomFilter<T> {
+    /// Creates a filter sized to hold `item_count` items of weight 1 with
+    /// the given false positive probability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `item_count` is 0 or `false_positive_prob` is not strictly
+    /// between 0 and 1.
+    pub fn new(item_count: usize, false_positive_prob: f64) -> WeightedBloomFilter<T> {
+        WeightedBloomFilter::with_hasher(item_count, false_positive_prob, DefaultBuildHasher::default())
+    }
+
+    /// Like [`new`](WeightedBloomFilter::new), but mixes `seed` into every
+    /// hash.
+    pub fn with_seed(item_count: usize, false_positive_prob: f64, seed: u64) -> WeightedBloomFilter<T> {
+        let mut filter = WeightedBloomFilter::new(item_count, false_positive_prob);
+        filter.seed = seed;
+        filter
+    }
+
+    /// Creates a filter of `bits` bits, whose items of weight 1 use
+    /// `hashes` hash functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either is 0, or if `bits` is more than
+    /// [`MAX_BITS`](params::MAX_BITS).
+    pub fn from_params(bits: u64, hashes: usize) -> WeightedBloomFilter<T> {
+        WeightedBloomFilter::from_params_with_hasher(bits, hashes, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> WeightedBloomFilter<T, S> {
+    /// Like [`new`](WeightedBloomFilter::new), but hashes items with
+    /// `hash_builder`.
+    pub fn with_hasher(item_count: usize, false_positive_prob: f64, hash_builder: S) -> WeightedBloomFilter<T, S> {
+        if let Err(e) = params::validate(item_count, false_positive_prob) {
+            panic!("{}", e);
+        }
+        let bits = params::optimal_bits(item_count, false_positive_prob);
+        let hash_count = params::optimal_hashes(bits, item_count);
+        let mut filter = WeightedBloomFilter::from_params_with_hasher(bits, hash_count, hash_builder);
+        filter.item_count = Some(item_count);
+        filter.false_positive_prob = Some(false_positive_prob);
+        filter
+    }
+
+    /// Like [`from_params`](WeightedBloomFilter::from_params), but hashes
+    /// items with `hash_builder`.
+    pub fn from_params_with_hasher(bits: u64, hashes: usize, hash_builder: S) -> WeightedBloomFilter<T, S> {
+        if let Err(e) = params::validate_layout(bits, hashes) {
+            panic!("{}", e);
+        }
+        WeightedBloomFilter {
+            bit_vec: BitVec::new(bits),
+            item_count: None,
+            false_positive_prob: None,
+            hash_count: hashes,
+            insertions: 0,
+            hashes_inserted: 0,
+            seed: 0,
+            hash_builder,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records `item` with the filter's own hash count, returning `true` if
+    /// it was probably already present.
+    pub fn insert<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.insert_weighted(item, self.hash_count)
+    }
+
+    /// Returns `true` if `item` has probably been added with the filter's
+    /// own hash count, and `false` if it definitely has not.
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.contains_weighted(item, self.hash_count)
+    }
+
+    /// Records `item` using `hash_count` hash functions, returning `true`
+    /// if it was probably already present with that many.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hash_count` is 0.
+    pub fn insert_weighted<Q: ?Sized + Hash>(&mut self, item: &Q, hash_count: usize) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        assert!(hash_count > 0, "the hash count must be nonzero");
+        let mut present = true;
+        for index in self.probes(item, hash_count) {
+            if !self.bit_vec.set(index) {
+                present = false;
+            }
+        }
+        if !present {
+            self.insertions += 1;
+            self.hashes_inserted += hash_count as u64;
+        }
+        present
+    }
+
+    /// Returns `true` if `item` has probably been added with `hash_count`
+    /// hash functions, and `false` if it definitely has not.
+    ///
+    /// An item added with more hashes is also found with fewer, since the
+    /// first probes are the same, but not the other way round.
+    pub fn contains_weighted<Q: ?Sized + Hash>(&self, item: &Q, hash_count: usize) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.probes(item, hash_count).all(|index| self.bit_vec.get(index))
+    }
+
+    /// The hash count that minimises the expected cost of false positives
+    /// for an item of relative importance `weight`, where an item of weight
+    /// 1 gets the filter's own hash count.
+    ///
+    /// Following Bruck et al., that is the filter's hash count plus
+    /// `log2(weight)`, rounded: doubling an item's weight buys it one more
+    /// hash, halving its false positive probability. Weights of 0 or less
+    /// get 1 hash, and no item gets more than 64.
+    pub fn hash_count_for(&self, weight: f64) -> usize {
+        let hash_count = math::round(self.hash_count as f64 + math::log2(weight));
+        // Negative, infinite and NaN counts all saturate.
+        (hash_count as usize).clamp(1, MAX_WEIGHTED_HASH_COUNT)
+    }
+
+    /// The probability, at the current fill, that an absent item looked up
+    /// with `hash_count` hashes is reported present.
+    pub fn item_fpr(&self, hash_count: usize) -> f64 {
+        math::powf(self.fill_ratio(), hash_count as f64)
+    }
+
+    /// Removes every item from the filter, keeping its allocation and
+    /// parameters.
+    pub fn clear(&mut self) {
+        self.bit_vec.clear();
+        self.insertions = 0;
+        self.hashes_inserted = 0;
+    }
+
+    /// Returns `true` if nothing has been added since the filter was created
+    /// or last cleared.
+    pub fn is_empty(&self) -> bool {
+        self.bit_vec.none()
+    }
+
+    /// The number of bits that are set.
+    pub fn count_ones(&self) -> u64 {
+        self.bit_vec.count_ones()
+    }
+
+    /// The fraction of bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.bit_vec.len() as f64
+    }
+
+    /// Estimates the false positive probability for items of weight 1 as
+    /// the filter is now.
+    pub fn current_fpr(&self) -> f64 {
+        self.item_fpr(self.hash_count)
+    }
+
+    /// Estimates how many distinct items have been added, from the number
+    /// of bits set and the mean hash count of the insertions so far.
+    ///
+    /// Returns infinity once every bit is set.
+    pub fn estimated_len(&self) -> f64 {
+        if self.insertions == 0 {
+            return 0.0;
+        }
+        let size = self.bit_vec.len() as f64;
+        -size / self.mean_hash_count() * math::ln(1.0 - self.count_ones() as f64 / size)
+    }
+
+    /// The mean number of hashes the items inserted so far used, or the
+    /// filter's own hash count if none have been.
+    pub fn mean_hash_count(&self) -> f64 {
+        if self.insertions == 0 {
+            self.hash_count as f64
+        } else {
+            self.hashes_inserted as f64 / self.insertions as f64
+        }
+    }
+
+    /// The number of bits in the filter.
+    pub fn bit_vec_size(&self) -> u64 {
+        self.bit_vec.len()
+    }
+
+    /// The number of hash functions items of weight 1 use.
+    pub fn hash_count(&self) -> usize {
+        self.hash_count
+    }
+
+    /// The false positive probability the filter was sized for, or `None`
+    /// if it was built from explicit parameters.
+    pub fn false_positive_prob(&self) -> Option<f64> {
+        self.false_positive_prob
+    }
+
+    /// The number of items the filter was sized for, or `None` if it was
+    /// built from explicit parameters.
+    pub fn capacity(&self) -> Option<usize> {
+        self.item_count
+    }
+
+    /// The seed mixed into every hash.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The hasher items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The first `hash_count` bit indices `item` maps to.
+    fn probes<Q: ?Sized + Hash>(&self, item: &Q, hash_count: usize) -> Probes<S::Hasher> {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        item.hash(&mut hasher);
+        let size = self.bit_vec.len();
+        Probes::new(hasher, size, hash_count, size > params::WIDE_HASH_THRESHOLD)
+    }
+}
+
+impl<T, Q, S> ApproximateMembership<Q> for WeightedBloomFilter<T, S>
+where
+    T: Hash + Borrow<Q>,
+    Q: ?Sized + Hash,
+    S: BuildHasher,
+{
+    /// Records `item` with the filter's own hash count.
+    fn insert(&mut self, item: &Q) -> Result<bool> {
+        Ok(WeightedBloomFilter::insert(self, item))
+    }
+
+    fn contains(&self, item: &Q) -> bool {
+        WeightedBloomFilter::contains(self, item)
+    }
+
+    fn clear(&mut self) {
+        WeightedBloomFilter::clear(self)
+    }
+
+    fn estimated_len(&self) -> f64 {
+        WeightedBloomFilter::estimated_len(self)
+    }
+
+    fn fpr_estimate(&self) -> f64 {
+        self.current_fpr()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        mem::size_of_val(self) + self.bit_vec.heap_bytes()
+    }
+}
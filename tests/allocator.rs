@@ -0,0 +1,74 @@
+extern crate bloom;
+
+use std::alloc::Layout;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use allocator_api2::alloc::{AllocError, Allocator, Global};
+use bloom::{AllocBits, BackedBloomFilter, BitStorage};
+
+/// Allocates from the global heap, counting the bytes it hands out.
+#[derive(Debug, Clone, Copy)]
+struct Counting<'a>(&'a AtomicUsize);
+
+unsafe impl Allocator for Counting<'_> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.0.fetch_add(layout.size(), Ordering::Relaxed);
+        Global.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.0.fetch_sub(layout.size(), Ordering::Relaxed);
+        Global.deallocate(ptr, layout)
+    }
+}
+
+/// Refuses every allocation.
+#[derive(Debug, Clone, Copy)]
+struct Exhausted;
+
+unsafe impl Allocator for Exhausted {
+    fn allocate(&self, _layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        Err(AllocError)
+    }
+
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
+}
+
+#[test]
+fn bits_come_from_the_allocator() {
+    let allocated = AtomicUsize::new(0);
+    let mut filter = BackedBloomFilter::<u64, AllocBits<Counting>>::new_in(1000, 0.01, Counting(&allocated));
+    let bytes = allocated.load(Ordering::Relaxed);
+    assert_eq!(bytes as u64, filter.bit_vec_size().div_ceil(64) * 8);
+    assert_eq!(filter.storage().heap_bytes(), bytes);
+
+    for i in 0..1000 {
+        filter.insert(&i);
+    }
+    assert!((0..1000).all(|i| filter.contains(&i)));
+    let false_positives = (1000..11_000).filter(|i| filter.contains(i)).count();
+    assert!(false_positives < 200, "{} false positives", false_positives);
+
+    drop(filter);
+    assert_eq!(allocated.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn allocator_backed_filters_combine() {
+    let mut evens = BackedBloomFilter::<u64, AllocBits>::from_params_in(9586, 7, Global);
+    let mut odds = BackedBloomFilter::<u64, AllocBits>::from_params_in(9586, 7, Global);
+    for i in 0..500 {
+        evens.insert(&(2 * i));
+        odds.insert(&(2 * i + 1));
+    }
+    evens.try_union(&odds).unwrap();
+    assert!((0..1000).all(|i| evens.contains(&i)));
+}
+
+#[test]
+fn allocation_failures_are_errors() {
+    assert!(AllocBits::new_in(1 << 20, Exhausted).is_err());
+    assert!(AllocBits::new_in(0, Exhausted).is_ok());
+    assert!(<AllocBits as BitStorage>::with_len(64).is_err());
+}
@@ -0,0 +1,83 @@
+extern crate bloom;
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bloom::{filter_from_async_reader, filter_from_file, filter_from_file_async, BloomError, BloomFilter};
+use futures_core::Stream;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+
+/// Yields `0..len`, making the task wait before every other item.
+struct Numbers {
+    next: u64,
+    len: u64,
+    ready: bool,
+}
+
+impl Stream for Numbers {
+    type Item = u64;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<u64>> {
+        if self.next == self.len {
+            return Poll::Ready(None);
+        }
+        if !self.ready {
+            self.ready = true;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        self.ready = false;
+        self.next += 1;
+        Poll::Ready(Some(self.next - 1))
+    }
+}
+
+#[tokio::test]
+async fn adds_every_streamed_item() {
+    let mut filter = BloomFilter::<u64>::new(1000, 0.01);
+    let added = filter.add_stream(Numbers { next: 0, len: 1000, ready: false }).await;
+    assert_eq!(added, 1000);
+    let mut expected = BloomFilter::<u64>::new(1000, 0.01);
+    expected.extend(0..1000);
+    assert_eq!(filter, expected);
+}
+
+#[tokio::test]
+async fn reads_files_like_the_blocking_reader() {
+    let contents: String = (0..3000).map(|i| format!("  line {}\r\n", i)).collect();
+    let path = std::env::temp_dir().join(format!("bloom-async-{}.txt", std::process::id()));
+    std::fs::write(&path, &contents).unwrap();
+    let path_str = path.to_str().unwrap();
+    let filter = filter_from_file_async(path_str, 3000, 0.01).await;
+    let expected = filter_from_file(path_str, 3000, 0.01);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(filter.unwrap(), expected.unwrap());
+
+    let missing = filter_from_file_async("/nonexistent/bloom-async.txt", 10, 0.01).await;
+    assert!(matches!(missing, Err(BloomError::Io(_))));
+}
+
+#[tokio::test]
+async fn reads_lines_from_a_socket() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let sender = tokio::spawn(async move {
+        let mut socket = TcpStream::connect(address).await.unwrap();
+        for i in 0..500 {
+            socket.write_all(format!("key {}\n", i).as_bytes()).await.unwrap();
+        }
+    });
+    let (socket, _) = listener.accept().await.unwrap();
+    let filter = filter_from_async_reader(socket, 500, 0.01).await.unwrap();
+    sender.await.unwrap();
+    assert!((0..500).all(|i| filter.contains(format!("key {}", i).as_str())));
+}
+
+#[tokio::test]
+async fn rejects_lines_that_are_not_utf8() {
+    let mut filter = BloomFilter::<String>::new(10, 0.01);
+    let input: &[u8] = b"fine\n\xff\xfe\n";
+    assert!(matches!(filter.add_lines(input).await, Err(BloomError::Io(_))));
+    assert!(filter.contains("fine"));
+}
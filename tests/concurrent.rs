@@ -0,0 +1,160 @@
+extern crate bloom;
+
+use std::sync::Arc;
+use std::thread;
+
+use bloom::{
+    ApproximateMembership, BloomFilter, BloomFilterBuilder, ConcurrentBloomFilter, CounterWidth, CountingBloomFilter,
+    Removable, StripedCountingBloomFilter,
+};
+
+#[test]
+fn concurrent_inserts_are_all_seen() {
+    let filter = Arc::new(ConcurrentBloomFilter::<u64>::new(80_000, 0.01));
+    let workers: Vec<_> = (0..8u64)
+        .map(|worker| {
+            let filter = Arc::clone(&filter);
+            thread::spawn(move || {
+                for i in 0..10_000 {
+                    filter.insert(&(worker * 10_000 + i));
+                    // Lookups of other workers' items run alongside.
+                    filter.contains(&((worker + 1) % 8 * 10_000 + i));
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    assert!((0..80_000).all(|i| filter.contains(&i)));
+    let false_positives = (80_000..180_000).filter(|i| filter.contains(i)).count();
+    assert!(false_positives < 1500, "{} false positives", false_positives);
+    let estimate = filter.estimated_len();
+    assert!(estimate > 76_000.0 && estimate < 84_000.0, "estimated {} items", estimate);
+}
+
+#[test]
+fn concurrent_filter_matches_and_converts_to_plain_filter() {
+    let concurrent = ConcurrentBloomFilter::<u64>::with_seed(1000, 0.01, 9);
+    let mut plain = BloomFilter::<u64>::with_seed(1000, 0.01, 9);
+    for i in 0..1000 {
+        assert_eq!(concurrent.insert(&i), plain.insert(&i));
+    }
+    assert_eq!(concurrent.to_bloom_filter(), plain);
+
+    let wide = BloomFilterBuilder::<u64>::new()
+        .item_count(1000)
+        .wide_hashes(true)
+        .build()
+        .unwrap();
+    let concurrent = ConcurrentBloomFilter::from(wide.clone());
+    concurrent.insert(&5);
+    let mut expected = wide;
+    expected.insert(&5);
+    assert_eq!(concurrent.into_bloom_filter(), expected);
+}
+
+#[test]
+fn concurrent_filter_is_a_filter() {
+    let mut filter = ConcurrentBloomFilter::<u64>::new(1000, 0.01);
+    for i in 0..1000 {
+        ApproximateMembership::insert(&mut filter, &i).unwrap();
+    }
+    assert!(filter.fpr_estimate() < 0.02);
+    assert!(filter.memory_bytes() > filter.bit_vec_size() as usize / 8);
+    filter.clear();
+    assert!(filter.is_empty());
+}
+
+#[test]
+fn striped_counting_filter_inserts_and_removes_concurrently() {
+    let filter = Arc::new(StripedCountingBloomFilter::<u64>::new(40_000, 0.01).with_stripes(16));
+    assert_eq!(filter.stripe_count(), 16);
+    let workers: Vec<_> = (0..8u64)
+        .map(|worker| {
+            let filter = Arc::clone(&filter);
+            thread::spawn(move || {
+                for i in 0..5000 {
+                    filter.insert(&(worker * 5000 + i));
+                }
+                for i in 0..2500 {
+                    assert!(filter.remove(&(worker * 5000 + i)));
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    for worker in 0..8 {
+        assert!((2500..5000).all(|i| filter.contains(&(worker * 5000 + i))));
+    }
+    let remaining = (0..8).flat_map(|worker| (0..2500).map(move |i| worker * 5000 + i));
+    let false_positives = remaining.filter(|i| filter.contains(i)).count();
+    assert!(false_positives < 400, "{} removed items still present", false_positives);
+    let estimate = filter.estimated_len();
+    assert!(estimate > 19_000.0 && estimate < 21_000.0, "estimated {} items", estimate);
+}
+
+#[test]
+fn striped_counting_filter_only_removes_once() {
+    let filter = Arc::new(StripedCountingBloomFilter::<u64>::new(1000, 0.01));
+    filter.insert(&7);
+    let removers: Vec<_> = (0..8)
+        .map(|_| {
+            let filter = Arc::clone(&filter);
+            thread::spawn(move || filter.remove(&7))
+        })
+        .collect();
+    let removed = removers.into_iter().map(|remover| remover.join().unwrap());
+    assert_eq!(removed.filter(|&removed| removed).count(), 1);
+    assert!(filter.is_empty());
+}
+
+#[test]
+fn striped_counting_filter_matches_counting_filter() {
+    let striped = StripedCountingBloomFilter::<u64>::with_seed(1000, 0.01, 2).with_counter_width(CounterWidth::Four);
+    let mut counting = CountingBloomFilter::<u64>::with_seed(1000, 0.01, 2).with_counter_width(CounterWidth::Four);
+    for i in 0..1000 {
+        assert_eq!(striped.insert(&(i % 700)), counting.insert(&(i % 700)));
+    }
+    for i in 0..300 {
+        assert_eq!(striped.remove(&i), counting.remove(&i));
+    }
+    assert_eq!(striped.stats(), counting.stats());
+    assert_eq!(striped.to_bloom_filter(), counting.to_bloom_filter());
+    // Restriping keeps the counts.
+    let striped = striped.with_stripes(1000);
+    assert!(striped.stripe_count() > 900 && striped.stripe_count() <= 1000);
+    assert_eq!(striped.stats(), counting.stats());
+    assert!((0..2000).all(|i| striped.contains(&i) == counting.contains(&i)));
+
+    let mut filter = StripedCountingBloomFilter::<u64>::from_params(100, 3).with_stripes(1000);
+    assert_eq!(filter.stripe_count(), 100);
+    Removable::remove(&mut filter, &1);
+    ApproximateMembership::insert(&mut filter, &1).unwrap();
+    assert!(Removable::remove(&mut filter, &1));
+    assert!(filter.is_empty());
+}
+
+#[test]
+fn contains_or_insert_reports_each_new_item_to_some_thread() {
+    let filter = Arc::new(ConcurrentBloomFilter::<u64>::new(10_000, 1e-6));
+    // Every thread sees the same stream, each starting at a different point.
+    let workers: Vec<_> = (0..8u64)
+        .map(|worker| {
+            let filter = Arc::clone(&filter);
+            thread::spawn(move || {
+                (0..10_000u64)
+                    .map(|i| (i + worker * 1250) % 10_000)
+                    .filter(|item| !filter.contains_or_insert(item))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let mut first_seen: Vec<u64> = workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect();
+    first_seen.sort_unstable();
+    first_seen.dedup();
+    assert_eq!(first_seen.len(), 10_000);
+    assert!((0..10_000).all(|i| filter.contains_or_insert(&i)));
+}
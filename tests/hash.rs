@@ -0,0 +1,47 @@
+extern crate bloom;
+
+use std::hash::{BuildHasher, Hasher};
+
+use bloom::hash::{HashScheme, WyHash, XxHash64};
+
+fn hash(scheme: HashScheme, bytes: &[u8]) -> u64 {
+    let mut hasher = scheme.build_hasher();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+// Published test vectors, which pin each scheme to its reference algorithm.
+#[test]
+fn matches_reference_vectors() {
+    assert_eq!(hash(HashScheme::XxHash64, b""), 0xef46_db37_51d8_e999);
+    assert_eq!(hash(HashScheme::XxHash64, b"abc"), 0x44bc_2cf5_ad77_0999);
+    assert_eq!(hash(HashScheme::WyHash, b""), 0x0409_638e_e2bd_e459);
+    assert_eq!(hash(HashScheme::Fnv1a, b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(hash(HashScheme::Fnv1a, b"a"), 0xaf63_dc4c_8601_ec8c);
+
+    let mut wy = WyHash::with_seed(1);
+    wy.write(b"a");
+    assert_eq!(wy.finish(), 0xa841_2d09_1b5f_e0a9);
+    let mut wy = WyHash::with_seed(8);
+    wy.write(b"12345678901234567890123456789012345678901234567890123456789012345678901234567890");
+    assert_eq!(wy.finish(), 0x3c35_3b8b_0b93_1bb0);
+}
+
+#[test]
+fn streaming_matches_one_shot() {
+    let data: Vec<u8> = (0..500u32).map(|i| (i * 31 % 251) as u8).collect();
+    for &scheme in HashScheme::ALL.iter() {
+        for &len in [0, 3, 16, 17, 48, 49, 97, 500].iter() {
+            let expected = hash(scheme, &data[..len]);
+            let mut hasher = scheme.build_hasher();
+            for byte in &data[..len] {
+                hasher.write(&[*byte]);
+            }
+            assert_eq!(hasher.finish(), expected, "{:?} over {} bytes", scheme, len);
+        }
+    }
+    let mut xx = XxHash64::with_seed(0);
+    xx.write(&data[..40]);
+    xx.write(&data[40..]);
+    assert_eq!(xx.finish(), hash(HashScheme::XxHash64, &data));
+}
@@ -0,0 +1,26 @@
+extern crate bloom;
+
+use bloom::BloomFilterBuilder;
+
+#[test]
+fn hinted_filters_behave_like_plain_ones() {
+    let hinted = BloomFilterBuilder::<u64>::new()
+        .item_count(10_000_000)
+        .huge_pages(true)
+        .random_access(true)
+        .will_need(true)
+        .build();
+    let plain = BloomFilterBuilder::<u64>::new().item_count(10_000_000).build();
+    let (mut hinted, mut plain) = (hinted.unwrap(), plain.unwrap());
+    for i in 0..100_000 {
+        hinted.insert(&i);
+        plain.insert(&i);
+    }
+    assert!((0..100_000).all(|i| hinted.contains(&i)));
+    assert_eq!(hinted, plain);
+
+    // Filters smaller than a page have nothing to advise.
+    let mut small = BloomFilterBuilder::<u64>::new().item_count(10).huge_pages(true).build().unwrap();
+    small.insert(&1);
+    assert!(small.contains(&1));
+}
@@ -0,0 +1,51 @@
+extern crate bloom;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use bloom::filter_from_file;
+
+/// The system allocator, counting the allocations made on each thread, so
+/// that tests running at the same time don't see each other's.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+// Builds a filter from a file of `lines` lines, returning the number of
+// allocations it took.
+fn allocations_to_read(lines: usize) -> usize {
+    let contents: String = (0..lines).map(|i| format!("  line {}  \n", i)).collect();
+    let path = std::env::temp_dir().join(format!("bloom-ingest-{}-{}.txt", std::process::id(), lines));
+    std::fs::write(&path, contents).unwrap();
+    let path_str = path.to_str().unwrap();
+
+    let before = ALLOCATIONS.with(Cell::get);
+    let filter = filter_from_file(path_str, 10_000, 0.01).unwrap();
+    let allocations = ALLOCATIONS.with(Cell::get) - before;
+    assert!(filter.contains("line 0") && filter.contains(&format!("line {}", lines - 1)));
+    std::fs::remove_file(&path).unwrap();
+    allocations
+}
+
+#[test]
+fn reading_a_file_does_not_allocate_per_line() {
+    let few = allocations_to_read(100);
+    let many = allocations_to_read(10_000);
+    assert!(many < few + 10, "{} allocations for 100 lines, but {} for 10000", few, many);
+}
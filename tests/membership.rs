@@ -0,0 +1,1315 @@
+extern crate bloom;
+
+use bloom::hash::{HashScheme, SecretKey};
+use bloom::{
+    AgePartitionedBloomFilter, ApproximateMembership, AttenuatedBloomFilter, BackedBloomFilter, BlockedBloomFilter,
+    BloomError, BloomFilter, BloomFilterBuilder, BloomSnapshot, BloomierFilter, ConstBloomFilter, CounterWidth,
+    CountingBloomFilter, CountingQuotientFilter, CowBloomFilter, CuckooFilter, DLeftCountingFilter, DecayingBloomFilter,
+    DenseBits, FilterCascade, GolombCodedSet, LearnedBloomFilterBuilder, MortonFilter, MultiSetBloomFilter,
+    PartitionedBloomFilter, PrefixBloomFilter, QuotientFilter, RangeFilter, Removable, RibbonFilter,
+    ScalableBloomFilter, ShardedBloomFilter, SlidingBloomFilter, SparseBloomFilter, SpectralBloomFilter,
+    SplitBlockBloomFilter, StableBloomFilter, TombstoneBloomFilter, WeightedBloomFilter, Window, XorFilter,
+};
+
+// Checks the guarantees every filter variant must give, whatever its
+// false positive behaviour.
+fn check_filter<F: ApproximateMembership<u64>>(filter: &mut F) {
+    for i in 0..1000 {
+        filter.insert(&i).unwrap();
+    }
+    for i in 0..1000 {
+        assert!(filter.contains(&i), "false negative for {}", i);
+    }
+    let estimate = filter.estimated_len();
+    assert!(estimate > 900.0 && estimate < 1100.0, "estimated {} items", estimate);
+    assert!(filter.fpr_estimate() > 0.0 && filter.fpr_estimate() < 1.0);
+    assert!(filter.memory_bytes() > 0);
+
+    filter.clear();
+    assert!(!filter.contains(&0));
+    assert_eq!(filter.estimated_len(), 0.0);
+}
+
+// Checks that removing items undoes inserting them.
+fn check_removal<F: Removable<u64>>(filter: &mut F) {
+    for i in 0..1000 {
+        filter.insert(&i).unwrap();
+    }
+    for i in 0..500 {
+        assert!(filter.remove(&i), "{} wasn't present to remove", i);
+    }
+    for i in 500..1000 {
+        assert!(filter.contains(&i), "false negative for {} after removals", i);
+    }
+    let false_positives = (0..500).filter(|i| filter.contains(i)).count();
+    assert!(false_positives < 50, "{} removed items still present", false_positives);
+    assert!(!filter.remove(&1_000_000));
+}
+
+#[test]
+fn bloom_filter() {
+    check_filter(&mut BloomFilter::<u64>::new(1000, 0.01));
+}
+
+#[test]
+fn bloom_filter_reports_its_set_bits() {
+    let empty = BloomFilter::<u64>::new(1000, 0.01);
+    assert_eq!(empty.fill_ratio(), 0.0);
+    assert_eq!(empty.iter_ones().next(), None);
+
+    // Bits at both ends of the first two words and at the very end.
+    let ones = [0, 2, 63, 64, 127, 199];
+    let mut bytes = vec![0; 25];
+    for &bit in ones.iter() {
+        bytes[bit / 8] |= 1 << (bit % 8);
+    }
+    let filter = BloomFilter::<u64>::from_raw_parts(&bytes, 200, 3, 0).unwrap();
+    assert_eq!(filter.iter_ones().collect::<Vec<_>>(), [0, 2, 63, 64, 127, 199]);
+    assert_eq!(filter.fill_ratio(), 6.0 / 200.0);
+
+    let full = BloomFilter::<u64>::from_raw_parts(&[0xff; 2], 16, 3, 0).unwrap();
+    assert_eq!(full.fill_ratio(), 1.0);
+    assert!(full.iter_ones().eq(0..16));
+
+    let mut filter = BloomFilter::<u64>::new(1000, 0.01);
+    filter.extend(0..1000);
+    let ones: Vec<u64> = filter.iter_ones().collect();
+    assert_eq!(ones.len() as u64, filter.count_ones());
+    assert!(ones.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(ones.iter().all(|&bit| bit < filter.bit_vec_size()));
+    // A filter at capacity has about half its bits set.
+    assert!((filter.fill_ratio() - 0.5).abs() < 0.05, "fill ratio of {}", filter.fill_ratio());
+}
+
+// Two filters holding 0..10_000 and 5_000..15_000, which share half their
+// items.
+fn overlapping_filters() -> (BloomFilter<u64>, BloomFilter<u64>) {
+    let mut a = BloomFilter::<u64>::new(20_000, 0.01);
+    let mut b = BloomFilter::<u64>::new(20_000, 0.01);
+    a.extend(0..10_000);
+    b.extend(5_000..15_000);
+    (a, b)
+}
+
+#[test]
+fn bloom_filter_estimates_set_sizes() {
+    let (a, b) = overlapping_filters();
+    let within = |estimate: f64, actual: f64, tolerance: f64| (estimate - actual).abs() <= actual * tolerance;
+    let union = a.estimate_union_size(&b).unwrap();
+    assert!(within(union, 15_000.0, 0.02), "union of {}", union);
+    let intersection = a.estimate_intersection_size(&b).unwrap();
+    assert!(within(intersection, 5_000.0, 0.03), "intersection of {}", intersection);
+    let difference = a.estimate_difference_size(&b).unwrap();
+    assert!(within(difference, 5_000.0, 0.03), "difference of {}", difference);
+    assert_eq!(union, b.estimate_union_size(&a).unwrap());
+
+    let mut disjoint = BloomFilter::<u64>::new(20_000, 0.01);
+    disjoint.extend(100_000..110_000);
+    assert!(a.estimate_intersection_size(&disjoint).unwrap() < 100.0);
+    assert!(within(a.estimate_difference_size(&disjoint).unwrap(), 10_000.0, 0.02));
+    assert!(a.estimate_difference_size(&a).unwrap() < 1.0);
+
+    let other_seed = BloomFilter::<u64>::with_seed(20_000, 0.01, 1);
+    assert!(matches!(a.estimate_union_size(&other_seed), Err(BloomError::Incompatible(_))));
+}
+
+#[test]
+fn bloom_filter_estimates_jaccard_similarity() {
+    let (a, b) = overlapping_filters();
+    let similarity = a.jaccard_similarity(&b).unwrap();
+    assert!((similarity - 1.0 / 3.0).abs() < 0.02, "similarity of {}", similarity);
+    assert_eq!(a.jaccard_similarity(&a.clone()).unwrap(), 1.0);
+
+    let mut disjoint = BloomFilter::<u64>::new(20_000, 0.01);
+    disjoint.extend(100_000..110_000);
+    let similarity = a.jaccard_similarity(&disjoint).unwrap();
+    assert!(similarity < 0.01, "similarity of {}", similarity);
+
+    let empty = BloomFilter::<u64>::new(20_000, 0.01);
+    assert_eq!(empty.jaccard_similarity(&empty.clone()).unwrap(), 1.0);
+    assert_eq!(empty.jaccard_similarity(&a).unwrap(), 0.0);
+    assert!(a.jaccard_similarity(&BloomFilter::new(10_000, 0.01)).is_err());
+}
+
+#[test]
+fn bloom_filter_collects_and_checks_iterators() {
+    let filter: BloomFilter<u64> = (0..1000).collect();
+    assert_eq!(filter.capacity(), Some(1000));
+    assert!((0..1000).all(|i| filter.contains(&i)));
+    let empty: BloomFilter<u64> = std::iter::empty().collect();
+    assert!(empty.is_empty());
+    assert_eq!(empty.capacity(), Some(1));
+
+    // Longer than a batch, and not a multiple of one.
+    let items: Vec<u64> = (500..1537).collect();
+    let results: Vec<bool> = filter.contains_iter(&items).collect();
+    assert_eq!(results, items.iter().map(|i| filter.contains(i)).collect::<Vec<_>>());
+    assert!(results[..500].iter().all(|&present| present));
+    assert!(results[500..].iter().filter(|&&present| present).count() < 30);
+    assert_eq!(filter.contains_iter(&items).size_hint(), (1037, Some(1037)));
+    assert_eq!(filter.contains_many(&items), results);
+    assert_eq!(filter.contains_iter(&[] as &[u64]).next(), None);
+}
+
+#[test]
+fn bloom_filter_operators_combine_filters() {
+    let (a, b) = overlapping_filters();
+    let mut union = a.clone();
+    union |= &b;
+    assert!((0..15_000).all(|i| union.contains(&i)));
+    let mut expected = a.clone();
+    expected.try_union(&b).unwrap();
+    assert_eq!(union, expected);
+
+    let mut intersection = a.clone();
+    intersection &= &b;
+    assert!((5_000..10_000).all(|i| intersection.contains(&i)));
+    let false_positives = (0..5_000).chain(10_000..15_000).filter(|i| intersection.contains(i)).count();
+    assert!(false_positives < 300, "{} false positives", false_positives);
+    let mut expected = a.clone();
+    expected.try_intersect(&b).unwrap();
+    assert_eq!(intersection, expected);
+}
+
+#[test]
+#[should_panic(expected = "seeds differ")]
+fn bloom_filter_union_operator_panics_on_mismatched_filters() {
+    let mut filter = BloomFilter::<u64>::new(1000, 0.01);
+    filter |= &BloomFilter::with_seed(1000, 0.01, 1);
+}
+
+#[test]
+#[should_panic(expected = "bit vector sizes differ")]
+fn bloom_filter_intersection_operator_panics_on_mismatched_filters() {
+    let mut filter = BloomFilter::<u64>::new(1000, 0.01);
+    filter &= &BloomFilter::new(2000, 0.01);
+}
+
+// Counts the false positives among a million items that were never added,
+// after adding `0..n`.
+fn false_positives<F: ApproximateMembership<u64>>(mut filter: F, n: u64) -> usize {
+    for i in 0..n {
+        filter.insert(&i).unwrap();
+    }
+    (1 << 40..(1 << 40) + 1_000_000).filter(|i| filter.contains(i)).count()
+}
+
+#[test]
+fn bloom_filter_achieves_its_predicted_false_positive_rate() {
+    for &(n, p) in [(10_000, 0.01), (1000, 1e-4), (100, 1e-5)].iter() {
+        let filter = BloomFilter::<u64>::new(n, p);
+        let achieved = filter.achieved_false_positive_rate().unwrap();
+        assert!(achieved <= p * 1.05, "achieved {} for a target of {}", achieved, p);
+        let expected = achieved * 1_000_000.0;
+        let measured = false_positives(filter, n as u64) as f64;
+        // Four standard deviations, plus 10% for the model's error.
+        let tolerance = 4.0 * expected.sqrt() + 0.1 * expected;
+        assert!((measured - expected).abs() < tolerance, "{} false positives, {} expected", measured, expected);
+    }
+    assert_eq!(BloomFilter::<u64>::from_params(1000, 7).achieved_false_positive_rate(), None);
+}
+
+#[test]
+fn small_filters_meet_low_false_positive_targets() {
+    // Plain double hashing had thousands of false positives in each of these.
+    for &(n, p) in [(1, 1e-12), (100, 1e-9)].iter() {
+        let counts = [
+            false_positives(BloomFilter::<u64>::new(n, p), n as u64),
+            false_positives(PartitionedBloomFilter::<u64>::new(n, p), n as u64),
+            false_positives(ShardedBloomFilter::<u64>::new(n, p, 4), n as u64),
+        ];
+        assert!(counts.iter().all(|&count| count <= 2), "{:?} false positives for {} items", counts, n);
+    }
+    // 10 expected.
+    let count = false_positives(BloomFilter::<u64>::new(1000, 1e-5), 1000);
+    assert!(count < 25, "{} false positives", count);
+}
+
+#[test]
+fn bloom_filter_with_wide_hashes() {
+    let mut filter = BloomFilterBuilder::<u64>::new()
+        .item_count(1000)
+        .wide_hashes(true)
+        .build()
+        .unwrap();
+    check_filter(&mut filter);
+    for &bits in [1 << 14, 10_007].iter() {
+        let mut filter = BloomFilterBuilder::<u64>::new()
+            .bit_vec_size(bits)
+            .hash_count(7)
+            .wide_hashes(true)
+            .build()
+            .unwrap();
+        check_filter(&mut filter);
+    }
+}
+
+#[test]
+fn bloom_filters_with_different_hashers_are_incompatible() {
+    let build = |scheme, wide_hashes| {
+        let mut filter = BloomFilterBuilder::<u64>::new()
+            .bit_vec_size(10_000)
+            .hash_count(7)
+            .seed(3)
+            .hasher(scheme)
+            .wide_hashes(wide_hashes)
+            .build()
+            .unwrap();
+        filter.add(&1);
+        filter
+    };
+    let filter = build(HashScheme::SipHash13, false);
+    let key = SecretKey::new([1; 16]);
+    for other in [
+        build(HashScheme::XxHash64, false),
+        build(HashScheme::KeyedSipHash13(key), false),
+        build(HashScheme::SipHash13, true),
+    ]
+    .iter()
+    {
+        let mut union = other.clone();
+        assert!(matches!(union.try_union(&filter), Err(BloomError::Incompatible(_))));
+        assert_ne!(&filter, other);
+        assert_ne!(filter.fingerprint(), other.fingerprint());
+    }
+    let mut keyed = build(HashScheme::KeyedSipHash13(key), false);
+    let other_key = build(HashScheme::KeyedSipHash13(SecretKey::new([2; 16])), false);
+    assert!(keyed.try_union(&other_key).is_err());
+    assert_eq!(filter, build(HashScheme::SipHash13, false));
+}
+
+#[test]
+fn bloom_filter_of_strings_accepts_str() {
+    let mut filter = BloomFilter::<String>::new(100, 0.01);
+    ApproximateMembership::<str>::insert(&mut filter, "hello").unwrap();
+    assert!(ApproximateMembership::<str>::contains(&filter, "hello"));
+}
+
+#[test]
+fn const_bloom_filter() {
+    check_filter(&mut ConstBloomFilter::<150, 7>::new());
+}
+
+#[test]
+fn const_bloom_filter_matches_bloom_filter() {
+    let mut filter = BloomFilter::<u64>::from_params(150 * 64, 7);
+    let mut const_filter = ConstBloomFilter::<150, 7>::new();
+    for i in 0..500 {
+        filter.add(&i);
+        const_filter.add(&i);
+    }
+    assert_eq!(&const_filter.as_words()[..], filter.as_raw_slice());
+}
+
+#[test]
+fn counting_bloom_filter() {
+    check_filter(&mut CountingBloomFilter::<u64>::new(1000, 0.01));
+    check_removal(&mut CountingBloomFilter::<u64>::new(1000, 0.01));
+}
+
+#[test]
+fn counting_bloom_filter_with_four_bit_counters() {
+    let new = || CountingBloomFilter::<u64>::new(1000, 0.01).with_counter_width(CounterWidth::Four);
+    check_filter(&mut new());
+    check_removal(&mut new());
+
+    let mut filter = new();
+    for _ in 0..20 {
+        filter.add(&7);
+    }
+    assert!(filter.contains(&7));
+    assert!(filter.overflow_prob().unwrap() < 1e-9);
+    let eight_bit = CountingBloomFilter::<u64>::new(1000, 0.01);
+    assert!(filter.memory_bytes() < ApproximateMembership::<u64>::memory_bytes(&eight_bit));
+}
+
+#[test]
+fn counting_bloom_filter_saturated_counters_stick() {
+    let mut filter = CountingBloomFilter::<u64>::new(1000, 0.01).with_counter_width(CounterWidth::Four);
+    for _ in 0..20 {
+        filter.add(&7);
+    }
+    let stats = filter.stats();
+    assert!(stats.saturated > 0 && stats.saturated <= filter.hash_count() as u64);
+    assert_eq!(stats.nonzero, stats.saturated);
+    for _ in 0..20 {
+        filter.remove(&7);
+    }
+    assert!(filter.contains(&7));
+    filter.clear();
+    assert_eq!(filter.stats().saturated, 0);
+}
+
+#[test]
+fn counting_bloom_filter_matches_bloom_filter() {
+    let mut filter = BloomFilter::<u64>::new(1000, 0.01);
+    let mut counting = CountingBloomFilter::<u64>::new(1000, 0.01);
+    for i in 0..500 {
+        filter.add(&i);
+        counting.add(&i);
+    }
+    assert_eq!(counting.to_bloom_filter(), filter);
+}
+
+#[test]
+fn d_left_counting_filter() {
+    check_filter(&mut DLeftCountingFilter::<u64>::new(1000, 0.01));
+    check_removal(&mut DLeftCountingFilter::<u64>::new(1000, 0.01));
+}
+
+#[test]
+fn d_left_counting_filter_is_smaller_than_counting_bloom_filter() {
+    let d_left = DLeftCountingFilter::<u64>::new(100_000, 0.01);
+    let counting = CountingBloomFilter::<u64>::new(100_000, 0.01).with_counter_width(CounterWidth::Four);
+    assert!(d_left.memory_bytes() < counting.memory_bytes());
+
+    let mut filter = DLeftCountingFilter::<u64>::new(100_000, 0.01);
+    for i in 0..100_000 {
+        filter.insert(&i).unwrap();
+    }
+    let false_positives = (100_000..200_000).filter(|i| filter.contains(i)).count();
+    assert!(false_positives < 2000, "{} false positives", false_positives);
+}
+
+#[test]
+fn scalable_bloom_filter() {
+    check_filter(&mut ScalableBloomFilter::<u64>::new(1000, 0.01));
+    check_filter(&mut ScalableBloomFilter::<u64>::new(10, 0.01));
+}
+
+#[test]
+fn scalable_bloom_filter_keeps_its_false_positive_bound() {
+    let mut filter = ScalableBloomFilter::<u64>::new(100, 0.01);
+    for i in 0..100_000 {
+        filter.insert(&i).unwrap();
+    }
+    assert!(filter.filter_count() > 5);
+    assert!(filter.current_fpr() < 0.01, "estimated rate {}", filter.current_fpr());
+    let false_positives = (100_000..200_000).filter(|i| filter.contains(i)).count();
+    assert!(false_positives < 1000, "{} false positives", false_positives);
+}
+
+#[test]
+fn partitioned_bloom_filter() {
+    check_filter(&mut PartitionedBloomFilter::<u64>::new(1000, 0.01));
+    check_filter(&mut PartitionedBloomFilter::<u64>::from_params(10_007, 7));
+}
+
+#[test]
+fn partitioned_bloom_filter_fills_partitions_evenly() {
+    let mut filter = PartitionedBloomFilter::<u64>::new(1000, 0.01);
+    for i in 0..1000 {
+        filter.add(&i);
+    }
+    assert_eq!(filter.partition_size() % 64, 0);
+    let ones: Vec<u64> = filter.partition_ones().collect();
+    assert_eq!(ones.len(), filter.hash_count());
+    let mean = ones.iter().sum::<u64>() / ones.len() as u64;
+    for &count in &ones {
+        assert!(count * 10 > mean * 9 && count * 10 < mean * 11, "{} bits set in a partition", count);
+    }
+}
+
+#[test]
+fn blocked_bloom_filter() {
+    check_filter(&mut BlockedBloomFilter::<u64>::new(1000, 0.01));
+    check_filter(&mut BlockedBloomFilter::<u64>::from_params(20, 7));
+}
+
+#[test]
+fn blocked_bloom_filter_meets_its_false_positive_target() {
+    let mut filter = BlockedBloomFilter::<u64>::new(100_000, 0.01);
+    for i in 0..100_000 {
+        filter.add(&i);
+    }
+    let false_positives = (100_000..200_000).filter(|i| filter.contains(i)).count();
+    assert!(false_positives < 1200, "{} false positives", false_positives);
+}
+
+#[test]
+fn blocked_bloom_filter_meets_low_false_positive_targets() {
+    let mut filter = BlockedBloomFilter::<u64>::new(20_000, 1e-5);
+    for i in 0..20_000 {
+        filter.add(&i);
+    }
+    // 10 expected.
+    let false_positives = (1 << 40..(1 << 40) + 1_000_000).filter(|i| filter.contains(i)).count();
+    assert!(false_positives < 25, "{} false positives", false_positives);
+
+    // These once grew towards the largest possible filter without reaching
+    // their targets.
+    assert!(BlockedBloomFilter::<u64>::new(100, 1e-9).bit_vec_size() < 100 * 128);
+    assert_eq!(BlockedBloomFilter::<u64>::new(1, 1e-12).block_count(), 1);
+    let unreachable = BlockedBloomFilter::<u64>::try_new(1, 1e-300);
+    assert!(matches!(unreachable, Err(BloomError::InvalidParams(_))));
+    let too_many = BlockedBloomFilter::<u64>::try_new(usize::MAX, 0.5);
+    assert!(matches!(too_many, Err(BloomError::Capacity(_))));
+}
+
+#[test]
+fn split_block_bloom_filter() {
+    check_filter(&mut SplitBlockBloomFilter::<u64>::new(1000, 0.01));
+    check_filter(&mut SplitBlockBloomFilter::<u64>::from_params(50));
+}
+
+#[test]
+fn split_block_bloom_filter_round_trips_parquet_layout() {
+    let mut filter = SplitBlockBloomFilter::<String>::new(1000, 0.01);
+    assert!(filter.block_count().is_power_of_two());
+    for i in 0..1000 {
+        filter.insert_bytes(&i.to_string());
+    }
+    // Every item sets one bit in each of the eight words of its block.
+    assert!(filter.count_ones() <= 8000);
+    let bytes = filter.to_bytes();
+    assert_eq!(bytes.len() as u64, filter.block_count() * 32);
+    let read = SplitBlockBloomFilter::<String>::from_bytes(&bytes).unwrap();
+    for i in 0..1000 {
+        assert!(read.contains_bytes(&i.to_string()));
+    }
+    assert_eq!(read.to_bytes(), bytes);
+    assert!(SplitBlockBloomFilter::<String>::from_bytes(&bytes[1..]).is_err());
+}
+
+#[test]
+fn cuckoo_filter() {
+    check_filter(&mut CuckooFilter::<u64>::new(1000, 0.01));
+    check_removal(&mut CuckooFilter::<u64>::new(1000, 0.01));
+}
+
+#[test]
+fn cuckoo_filter_reports_when_full() {
+    let mut filter = CuckooFilter::<u64>::new(1000, 0.01);
+    let slots = filter.bucket_count() * 4;
+    let mut inserted = 0;
+    while filter.insert(&inserted).is_ok() {
+        inserted += 1;
+    }
+    assert!(inserted as f64 > slots as f64 * 0.9, "full after {} of {} slots", inserted, slots);
+    for i in 0..inserted {
+        assert!(filter.contains(&i), "false negative for {}", i);
+    }
+    for i in 0..inserted {
+        assert!(filter.remove(&i), "{} wasn't present to remove", i);
+    }
+    assert!(filter.is_empty());
+    assert!(filter.insert(&0).is_ok());
+}
+
+#[test]
+fn learned_bloom_filter() {
+    // Keys are mostly multiples of 3, which the model scores highly but not
+    // perfectly: some keys need the backup filter, and some non-keys fool it.
+    let model = |item: &u64| match item % 3 {
+        0 => 0.8,
+        1 => 0.3,
+        _ => 0.1,
+    };
+    let keys: Vec<u64> = (0..20_000).map(|i| i * 3).chain((0..500).map(|i| i * 3 + 1)).collect();
+    let non_keys: Vec<u64> = (0..20_000)
+        .map(|i| 3_000_000 + i * 3 + 1)
+        .chain((0..50).map(|i| 3_000_000 + i * 3))
+        .collect();
+    let filter = LearnedBloomFilterBuilder::new(model).false_positive_prob(0.01).build(&keys, &non_keys).unwrap();
+    assert!(keys.iter().all(|key| filter.contains(key)));
+    assert_eq!(filter.threshold(), 0.3);
+    assert_eq!(filter.backup().capacity(), Some(500));
+    let plain = BloomFilter::<u64>::new(keys.len(), 0.01);
+    assert!(filter.backup().bit_vec_size() * 10 < plain.bit_vec_size());
+    let others: Vec<u64> = (0..20_000).map(|i| 6_000_000 + i * 3 + 1).collect();
+    let false_positives = others.iter().filter(|item| filter.contains(*item)).count();
+    assert!(false_positives < 400, "{} false positives", false_positives);
+    assert!(filter.model_false_positive_prob() > 0.0);
+    assert!(filter.false_positive_prob() < 0.011);
+
+    let mut filter = filter;
+    assert!(!filter.insert(&2));
+    assert!(filter.contains(&2));
+    assert!(LearnedBloomFilterBuilder::new(model).build(&keys, &[]).is_err());
+}
+
+#[test]
+fn prefix_bloom_filter() {
+    let key = |user: u32, field: &str| format!("user:{:04}:{}", user, field);
+    let mut filter = PrefixBloomFilter::new(9, 2000, 0.01);
+    let mut already_present = 0;
+    for user in 0..1000 {
+        if filter.insert(&key(user, "name")) {
+            already_present += 1;
+        }
+        filter.insert(&key(user, "email"));
+    }
+    assert!(already_present < 5, "{} new items already present", already_present);
+    for user in 0..1000 {
+        assert!(filter.contains(&key(user, "name")));
+        assert!(filter.contains_prefix(&format!("user:{:04}", user)));
+        assert!(filter.contains_prefix(&key(user, "")));
+    }
+    let false_positives = (1000..10_000).filter(|&user| filter.contains_prefix(&format!("user:{:04}", user))).count();
+    assert!(false_positives < 200, "{} false positives", false_positives);
+    let false_positives = (0..1000).filter(|&user| filter.contains(&key(user, "phone"))).count();
+    assert!(false_positives < 50, "{} false positives", false_positives);
+    assert!(filter.contains_prefix("user:"));
+    assert!(filter.contains_prefix("us"));
+
+    // Without whole keys, point lookups only see the prefix.
+    let mut prefixes = PrefixBloomFilter::prefixes_only(9, 1000, 0.01);
+    assert!(prefixes.bit_vec_size() < filter.bit_vec_size());
+    prefixes.insert(&key(7, "name"));
+    assert!(prefixes.contains(&key(7, "phone")));
+    assert!(prefixes.insert("user:0007:email"));
+    // Keys shorter than the prefix are their own prefix.
+    prefixes.insert("user");
+    assert!(prefixes.contains("user"));
+    prefixes.clear();
+    assert!(prefixes.is_empty());
+}
+
+#[test]
+fn quotient_filter() {
+    check_filter(&mut QuotientFilter::<u64>::new(1000, 0.01));
+    check_removal(&mut QuotientFilter::<u64>::new(1000, 0.01));
+}
+
+#[test]
+fn quotient_filter_grows_merges_and_round_trips() {
+    let mut filter = QuotientFilter::<u64, HashScheme>::with_hasher(100, 0.01, HashScheme::WyHash);
+    let (quotient_bits, fingerprint_bits) = (filter.quotient_bits(), filter.fingerprint_bits());
+    for i in 0..1000 {
+        filter.insert(&i).unwrap();
+    }
+    assert!(filter.quotient_bits() > quotient_bits);
+    assert_eq!(filter.fingerprint_bits(), fingerprint_bits);
+    assert_eq!(filter.len(), 1000);
+
+    let mut other = QuotientFilter::<u64, HashScheme>::from_params_with_hasher(
+        quotient_bits,
+        fingerprint_bits - quotient_bits,
+        HashScheme::WyHash,
+    );
+    for i in 1000..1050 {
+        other.insert(&i).unwrap();
+    }
+    filter.try_union(&other).unwrap();
+    assert_eq!(filter.len(), 1050);
+
+    let loaded = QuotientFilter::<u64, HashScheme>::from_bytes(&filter.to_bytes()).unwrap();
+    assert_eq!(loaded.len(), 1050);
+    assert_eq!(loaded.capacity(), Some(100));
+    for i in 0..1050 {
+        assert!(loaded.contains(&i), "false negative for {}", i);
+    }
+    let mut bytes = filter.to_bytes();
+    bytes[50] ^= 1;
+    assert!(QuotientFilter::<u64, HashScheme>::from_bytes(&bytes).is_err());
+}
+
+#[test]
+fn golomb_coded_set() {
+    let items: Vec<u64> = (0..10_000).map(|i| i * 7919).collect();
+    let set = GolombCodedSet::<u64>::from_items(&items, 0.001).unwrap();
+    assert!(set.len() > 9_990 && set.len() <= 10_000, "{} fingerprints", set.len());
+    assert!(items.iter().all(|item| set.contains(item)));
+    let absent: Vec<u64> = (0..100_000).map(|i| i * 7919 + 1).collect();
+    let false_positives = absent.iter().filter(|item| set.contains(*item)).count();
+    assert!(false_positives < 200, "{} false positives", false_positives);
+    assert_eq!(set.contains_many(&absent).iter().filter(|&&found| found).count(), false_positives);
+    assert!(set.contains_many(&items).iter().all(|&found| found));
+    let filter = BloomFilter::<u64>::new(10_000, 0.001);
+    assert!(set.bit_len() < filter.bit_vec_size() * 9 / 10);
+
+    let loaded = GolombCodedSet::<u64>::from_bytes(&set.to_bytes()).unwrap();
+    assert!(items.iter().all(|item| loaded.contains(item)));
+    let mut bytes = set.to_bytes();
+    bytes[100] ^= 1;
+    assert!(GolombCodedSet::<u64>::from_bytes(&bytes).is_err());
+
+    let mut quotient = QuotientFilter::<u64>::new(1000, 0.01);
+    for i in 0..1000 {
+        quotient.insert(&i).unwrap();
+    }
+    let converted = GolombCodedSet::from_quotient_filter(&quotient);
+    assert_eq!(converted.fingerprint_bits(), quotient.fingerprint_bits());
+    assert!((0..1000).all(|i| converted.contains(&i)));
+    let false_positives = (1000..11_000).filter(|i| converted.contains(i)).count();
+    let expected = (1000..11_000).filter(|i| quotient.contains(i)).count();
+    assert_eq!(false_positives, expected);
+}
+
+#[test]
+fn counting_quotient_filter() {
+    check_filter(&mut CountingQuotientFilter::<u64>::new(1000, 0.01));
+    check_removal(&mut CountingQuotientFilter::<u64>::new(1000, 0.01));
+}
+
+#[test]
+fn counting_quotient_filter_counts_through_growth_and_union() {
+    let mut filter = CountingQuotientFilter::<u64>::new(100, 1e-6);
+    let quotient_bits = filter.quotient_bits();
+    for i in 0..500 {
+        filter.insert_count(&i, i + 1).unwrap();
+    }
+    assert!(filter.quotient_bits() > quotient_bits);
+    assert_eq!(filter.len(), 500);
+    assert_eq!(filter.total_count(), 500 * 501 / 2);
+
+    let mut other =
+        CountingQuotientFilter::<u64>::from_params(quotient_bits, filter.fingerprint_bits() - quotient_bits, 4);
+    for i in 0..10 {
+        other.insert_count(&i, 100).unwrap();
+    }
+    filter.try_union(&other).unwrap();
+    for i in 0..500 {
+        let expected = if i < 10 { i + 101 } else { i + 1 };
+        assert!(filter.count(&i) >= expected, "count of {} is {}", i, filter.count(&i));
+    }
+
+    assert_eq!(filter.remove_count(&499, 1000), 500);
+    assert!(!filter.contains(&499));
+    assert_eq!(filter.len(), 499);
+}
+
+#[test]
+fn weighted_bloom_filter() {
+    check_filter(&mut WeightedBloomFilter::<u64>::new(1000, 0.01));
+
+    // A tenth of the items are critical, and the rest give up a hash so
+    // that the filter is no fuller than with uniform weights.
+    let mut filter = WeightedBloomFilter::<u64>::new(10_000, 0.01);
+    let (critical, normal) = (filter.hash_count_for(16.0), filter.hash_count_for(0.5));
+    assert_eq!((critical, normal), (filter.hash_count() + 4, filter.hash_count() - 1));
+    for i in 0..10_000 {
+        filter.insert_weighted(&i, if i < 1000 { critical } else { normal });
+    }
+    for i in 0..10_000 {
+        assert!(filter.contains_weighted(&i, if i < 1000 { critical } else { normal }));
+    }
+    let estimate = filter.estimated_len();
+    assert!(estimate > 9000.0 && estimate < 11_000.0, "estimated {} items", estimate);
+    let critical_false_positives = (10_000..110_000).filter(|i| filter.contains_weighted(i, critical)).count();
+    let normal_false_positives = (10_000..110_000).filter(|i| filter.contains_weighted(i, normal)).count();
+    assert!(critical_false_positives < 250, "{} critical false positives", critical_false_positives);
+    assert!(critical_false_positives * 8 < normal_false_positives);
+    assert!(filter.item_fpr(critical) < filter.current_fpr());
+}
+
+#[test]
+fn xor_filter() {
+    let keys: Vec<u64> = (0..10_000).chain(0..100).map(|i| i * 7919).collect();
+    let filter = XorFilter::from_keys(&keys).unwrap();
+    assert_eq!(filter.len(), 10_000);
+    assert!(keys.iter().all(|&key| filter.contains(key)));
+    let false_positives = (0..100_000).filter(|i| filter.contains(i * 7919 + 1)).count();
+    assert!(false_positives < 600, "{} false positives", false_positives);
+    assert!(filter.slot_count() * 8 < 10_000 * 11);
+
+    let loaded = XorFilter::from_bytes(&filter.to_bytes()).unwrap();
+    assert_eq!(loaded, filter);
+    let mut bytes = filter.to_bytes();
+    bytes[100] ^= 1;
+    assert!(XorFilter::from_bytes(&bytes).is_err());
+
+    let empty = XorFilter::from_keys_with_bits(&[], 16).unwrap();
+    assert!(empty.is_empty());
+    assert!(!empty.contains(0));
+}
+
+#[test]
+fn range_filter() {
+    // Spread-out integer keys, big-endian so that byte order is numeric.
+    let keys: Vec<u64> = (0..10_000u64).map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 16).collect();
+    let encoded: Vec<[u8; 8]> = keys.iter().map(|key| key.to_be_bytes()).collect();
+    let filter = RangeFilter::from_keys(&encoded, 8);
+    assert_eq!(filter.len(), 10_000);
+    assert!(encoded.iter().all(|key| filter.contains(key)));
+    let mut sorted = keys.clone();
+    sorted.sort_unstable();
+    for pair in sorted.windows(2).step_by(7) {
+        let (key, next) = (pair[0], pair[1]);
+        // Ranges around a key, ending at one, and starting at one.
+        assert!(filter.contains_range(&key.saturating_sub(1).to_be_bytes(), &(key + 1).to_be_bytes()));
+        assert!(filter.contains_range(&0u64.to_be_bytes(), &key.to_be_bytes()));
+        assert!(filter.contains_range(&key.to_be_bytes(), &u64::MAX.to_be_bytes()));
+        assert!(filter.contains_range(&key.to_be_bytes(), &key.to_be_bytes()));
+        if next - key > 2 {
+            assert!(!filter.contains_range(&next.to_be_bytes(), &key.to_be_bytes()));
+        }
+    }
+    // The middle halves of the gaps between neighbouring keys hold none.
+    let false_positives = sorted
+        .windows(2)
+        .map(|pair| (pair[0] + (pair[1] - pair[0]) / 4, pair[1] - (pair[1] - pair[0]) / 4))
+        .filter(|(low, high)| filter.contains_range(&low.to_be_bytes(), &high.to_be_bytes()))
+        .count();
+    assert!(false_positives < 500, "{} false positives", false_positives);
+    let absent = (1..10_000u64).map(|i| i.wrapping_mul(0x5851_f42d_4c95_7f2d) >> 16);
+    let false_positives = absent.filter(|key| filter.contains(&key.to_be_bytes())).count();
+    assert!(false_positives < 100, "{} false positives", false_positives);
+    assert!(!filter.contains_range(&(sorted[9999] + (1 << 32)).to_be_bytes(), &u64::MAX.to_be_bytes()));
+    assert!(filter.memory_bytes() < 10_000 * 5);
+
+    // Strings, some prefixes of others.
+    let words = ["apple", "app", "application", "banana", "band", "bandana", "can", "z"];
+    let filter = RangeFilter::from_keys(&words, 0);
+    assert!(words.iter().all(|word| filter.contains(word)));
+    assert!(!filter.contains("ap"));
+    assert!(filter.contains_range("ap", "apz"));
+    assert!(filter.contains_range("apple", "apple"));
+    assert!(filter.contains_range("bandb", "c"));
+    assert!(!filter.contains_range("d", "y"));
+    assert!(!filter.contains_range("", "a"));
+    assert!(!filter.contains_range("b", "a"));
+    let empty = RangeFilter::from_keys(Vec::<&str>::new(), 8);
+    assert!(empty.is_empty() && !empty.contains("") && !empty.contains_range("", "z"));
+}
+
+#[test]
+fn ribbon_filter() {
+    let items: Vec<u64> = (0..10_000).chain(0..100).collect();
+    let mut filter = RibbonFilter::<u64>::from_items(&items, 0.01).unwrap();
+    assert_eq!(filter.len(), 10_000);
+    assert_eq!(filter.result_bits(), 7);
+    assert!(items.iter().all(|item| filter.contains(item)));
+    let false_positives = (10_000..110_000).filter(|item| filter.contains(item)).count();
+    assert!(false_positives < 1000, "{} false positives", false_positives);
+    let bits_per_item = filter.memory_bytes() as f64 * 8.0 / 10_000.0;
+    assert!(bits_per_item < 7.0 * 1.2, "{} bits per item", bits_per_item);
+
+    assert_eq!(ApproximateMembership::insert(&mut filter, &0).ok(), Some(true));
+    let absent = (10_000..).find(|item| !filter.contains(item)).unwrap();
+    assert!(ApproximateMembership::insert(&mut filter, &absent).is_err());
+    filter.clear();
+    assert!(filter.is_empty());
+    assert!(!filter.contains(&1));
+}
+
+#[test]
+fn bloomier_filter() {
+    let entries: Vec<(u64, u64)> = (0..10_000).map(|key| (key, key % 13)).collect();
+    let table = BloomierFilter::<u64>::from_entries(entries.iter().copied().chain(Some((0, 0))), 4, 0.001).unwrap();
+    assert_eq!(table.len(), 10_000);
+    assert_eq!(table.fingerprint_bits(), 10);
+    assert!(entries.iter().all(|&(key, value)| table.get(&key) == Some(value)));
+    let false_positives = (10_000..110_000).filter(|key| table.contains_key(key)).count();
+    assert!(false_positives < 200, "{} false positives", false_positives);
+    assert!(table.slot_count() < 10_000 * 13 / 10);
+
+    assert!(BloomierFilter::<u64>::from_entries(vec![(1, 16)], 4, 0.01).is_err());
+    assert!(BloomierFilter::<u64>::from_entries(vec![(1, 2), (1, 3)], 4, 0.01).is_err());
+    let empty = BloomierFilter::<u64>::from_entries(Vec::<(u64, u64)>::new(), 8, 0.01).unwrap();
+    assert!(empty.is_empty());
+    assert_eq!(empty.get(&0), None);
+}
+
+#[test]
+fn filter_cascade() {
+    let included: Vec<u64> = (0..5000).map(|i| i * 20).collect();
+    let excluded: Vec<u64> = (0..100_000).filter(|i| i % 20 != 0).collect();
+    let cascade = FilterCascade::<u64>::build(&included, &excluded).unwrap();
+    assert!(included.iter().all(|item| cascade.contains(item)));
+    assert!(excluded.iter().all(|item| !cascade.contains(item)));
+    assert!(cascade.level_count() > 1);
+    let first = cascade.levels()[0].bit_vec_size() as f64;
+    let total: u64 = cascade.levels().iter().map(|level| level.bit_vec_size()).sum();
+    assert!((total as f64) < first * 2.0, "{} bits against {} in the first level", total, first);
+
+    let loaded = FilterCascade::<u64>::from_bytes(&cascade.to_bytes()).unwrap();
+    assert_eq!(loaded.level_count(), cascade.level_count());
+    assert!(included.iter().all(|item| loaded.contains(item)));
+    assert!(excluded.iter().all(|item| !loaded.contains(item)));
+    let mut bytes = cascade.to_bytes();
+    bytes[20] ^= 1;
+    assert!(FilterCascade::<u64>::from_bytes(&bytes).is_err());
+
+    assert!(FilterCascade::<u64>::build(&[1, 2], &[2, 3]).is_err());
+    let everything = FilterCascade::<u64>::build(&included, &[]).unwrap();
+    assert_eq!(everything.level_count(), 1);
+}
+
+#[test]
+fn morton_filter() {
+    check_filter(&mut MortonFilter::<u64>::new(1000));
+    check_removal(&mut MortonFilter::<u64>::new(1000));
+}
+
+#[test]
+fn morton_filter_fills_and_batches_lookups() {
+    let mut filter = MortonFilter::<u64>::new(10_000);
+    let mut inserted = 0;
+    while filter.insert(&inserted).is_ok() {
+        inserted += 1;
+    }
+    assert!(filter.load_factor() > 0.9, "full at load {}", filter.load_factor());
+    let items: Vec<u64> = (0..inserted + 10_000).collect();
+    let found = filter.contains_batch(&items);
+    for (item, found) in items.iter().zip(&found) {
+        assert_eq!(*found, filter.contains(item));
+    }
+    assert!(found[..inserted as usize].iter().all(|&found| found));
+    let false_positives = found[inserted as usize..].iter().filter(|&&found| found).count();
+    assert!(false_positives < 300, "{} false positives", false_positives);
+}
+
+#[test]
+fn morton_filter_has_its_documented_false_positive_rate() {
+    let filter = MortonFilter::<u64>::new(10_000);
+    let rate = false_positives(filter, 10_000) as f64 / 1e6;
+    assert!(rate > 0.002 && rate < 0.004, "false positive rate {}", rate);
+}
+
+#[test]
+fn stable_bloom_filter_converges_on_unbounded_stream() {
+    let mut filter = StableBloomFilter::<u64>::new(100_000, 0.01);
+    assert!(filter.stable_fpr() <= 0.01 && filter.stable_fpr() > 0.005, "stable at {}", filter.stable_fpr());
+    for i in 0..1_000_000 {
+        filter.insert(&i);
+    }
+    assert!(filter.current_fpr() < 0.015, "settled at {}", filter.current_fpr());
+    let false_positives = (2_000_000..2_100_000).filter(|i| filter.contains(i)).count();
+    assert!(false_positives < 1500, "{} false positives", false_positives);
+    assert!(filter.contains(&999_999));
+    let recent = (999_900..1_000_000).filter(|i| filter.contains(i)).count();
+    let old = (0..100).filter(|i| filter.contains(i)).count();
+    assert!(recent > 90 && old < 10, "{} recent and {} old items present", recent, old);
+
+    filter.clear();
+    assert!(filter.is_empty());
+}
+
+#[test]
+fn decaying_bloom_filter() {
+    check_filter(&mut DecayingBloomFilter::<u64>::new(1000, 0.01, 60));
+}
+
+#[test]
+fn decaying_bloom_filter_forgets_items_after_the_window() {
+    let mut filter = DecayingBloomFilter::<u64>::new(1000, 0.01, 3600);
+    assert_eq!((filter.tick(), filter.cell_bits()), (450, 4));
+    let start = 1_700_000_000;
+    let already_present = (0..1000).filter(|&i| filter.insert(&i, start + i)).count();
+    assert!(already_present < 5, "{} new items already present", already_present);
+    assert!((0..1000).all(|i| filter.contains(&i, start + 3000)));
+    // Refreshing half the items keeps them for another window.
+    for i in 0..500 {
+        assert!(filter.insert(&i, start + 3000));
+    }
+    let later = start + 3000 + 3000;
+    assert!((0..500).all(|i| filter.contains(&i, later)));
+    let stale = (500..1000).filter(|i| filter.contains(i, later)).count();
+    assert_eq!(stale, 0);
+    // Sweeping on the next insertion agrees with the lookups above.
+    filter.insert(&1_000_000, later);
+    assert!((0..500).all(|i| filter.contains(&i, later)));
+    assert!(filter.estimated_len() > 450.0 && filter.estimated_len() < 560.0, "{}", filter.estimated_len());
+    // Long after, everything has expired, whether or not the clock was advanced.
+    assert!(!filter.contains(&0, later + 3600));
+    filter.advance(later + 100 * 3600);
+    assert!(filter.is_empty());
+}
+
+#[test]
+fn sliding_bloom_filter() {
+    check_filter(&mut SlidingBloomFilter::<u64>::new(1000, 0.01, Window::Items(1000)));
+    check_filter(&mut SlidingBloomFilter::<u64>::new(1000, 0.01, Window::Time(60)));
+}
+
+#[test]
+fn sliding_bloom_filter_expires_old_generations() {
+    let mut by_count = SlidingBloomFilter::<u64>::new(1000, 0.01, Window::Items(1000)).with_generations(5);
+    assert_eq!(by_count.span(), 250);
+    for i in 0..10_000 {
+        by_count.insert(&i);
+    }
+    assert!((9000..10_000).all(|i| by_count.contains(&i)));
+    let stale = (0..8500).filter(|i| by_count.contains(i)).count();
+    assert!(stale < 100, "{} stale items present", stale);
+
+    let mut by_time = SlidingBloomFilter::<u64>::new(1000, 0.01, Window::Time(3600));
+    let start = 1_700_000_000;
+    let repeats = (0..1000).filter(|&i| by_time.insert_at(&i, start + i * 3)).count();
+    assert!(repeats < 30, "{} items mistaken for repeats", repeats);
+    assert!(by_time.insert_at(&0, start + 3000));
+    let later = start + 3000 + 3600;
+    assert!(by_time.contains_at(&0, later));
+    // Nothing outlives the window by more than a span.
+    let stale = (1..1000).filter(|i| by_time.contains_at(i, later + by_time.span())).count();
+    assert_eq!(stale, 0);
+    by_time.advance(later);
+    assert!(by_time.contains(&0));
+    assert!(by_time.estimated_len() < 100.0, "{} items left", by_time.estimated_len());
+}
+
+#[test]
+fn age_partitioned_bloom_filter() {
+    check_filter(&mut AgePartitionedBloomFilter::<u64>::new(1000, 0.01));
+}
+
+#[test]
+fn age_partitioned_bloom_filter_slides_over_the_stream() {
+    let mut filter = AgePartitionedBloomFilter::<u64>::new(10_000, 0.001);
+    assert!(filter.expected_fpr() <= 0.001 && filter.window() >= 10_000);
+    assert!(filter.generation_len() <= 200, "generations of {}", filter.generation_len());
+    for i in 0..100_000 {
+        filter.insert(&i);
+    }
+    assert!((90_000..100_000).all(|i| filter.contains(&i)));
+    let stale = (0..89_500).filter(|i| filter.contains(i)).count();
+    assert!(stale < 180, "{} stale items present", stale);
+    assert!(filter.current_fpr() < 0.002, "at {}", filter.current_fpr());
+
+    let shaped = AgePartitionedBloomFilter::<u64>::from_params(1000, 10, 7);
+    assert_eq!((shaped.slice_count(), shaped.generation_len(), shaped.window()), (17, 143, 1001));
+}
+
+#[test]
+fn attenuated_bloom_filter() {
+    check_filter(&mut AttenuatedBloomFilter::<u64>::new(3, 1000, 0.01));
+}
+
+#[test]
+fn attenuated_bloom_filter_routes_by_distance() {
+    // A line of nodes, each holding 100 items and advertising to the next.
+    let mut nodes: Vec<AttenuatedBloomFilter<u64>> = (0..5).map(|_| AttenuatedBloomFilter::new(3, 500, 0.01)).collect();
+    for (node, filter) in nodes.iter_mut().enumerate() {
+        for item in 0..100 {
+            filter.insert(&(node as u64 * 100 + item));
+        }
+    }
+    for node in 1..5 {
+        let (before, after) = nodes.split_at_mut(node);
+        after[0].try_merge_shifted(&before[node - 1]).unwrap();
+    }
+    let last = &nodes[4];
+    for node in 0..5u64 {
+        let expected = Some(4 - node as usize).filter(|&hops| hops < 3);
+        let matching = (node * 100..node * 100 + 100).filter(|item| last.distance(item) == expected).count();
+        assert!(matching >= 95, "{} of node {}'s items at {:?}", matching, node, expected);
+    }
+    assert!(last.contains_at(&250, 2) && !last.contains_at(&250, 3));
+
+    let mut shifted = last.clone();
+    shifted.shift();
+    assert_eq!(shifted.distance(&450), Some(1));
+    assert!(!shifted.contains_at(&450, 0));
+    let mut merged = AttenuatedBloomFilter::new(3, 500, 0.01);
+    merged.try_merge(&shifted).unwrap();
+    assert_eq!(merged.distance(&350), Some(2));
+    assert!(merged.try_merge(&AttenuatedBloomFilter::new(2, 500, 0.01)).is_err());
+    assert!(merged.try_merge(&AttenuatedBloomFilter::with_seed(3, 500, 0.01, 1)).is_err());
+}
+
+#[test]
+fn spectral_bloom_filter() {
+    check_filter(&mut SpectralBloomFilter::<u64>::new(1000, 0.01));
+    check_removal(&mut SpectralBloomFilter::<u64>::new(1000, 0.01));
+}
+
+#[test]
+fn spectral_bloom_filter_estimates_counts() {
+    let mut filter = SpectralBloomFilter::<u64>::new(1000, 0.01);
+    for i in 0..1000 {
+        filter.insert_count(&i, i % 10 + 1);
+    }
+    let mut overestimates = 0;
+    for i in 0..1000 {
+        let (count, estimate) = (i % 10 + 1, filter.estimate_count(&i));
+        assert!(estimate >= count, "{} estimated at {}", i, estimate);
+        if estimate > count {
+            overestimates += 1;
+        }
+    }
+    assert!(overestimates < 20, "{} overestimates", overestimates);
+    assert_eq!(filter.total_count(), 5500);
+    assert!(filter.remove(&9));
+    assert_eq!(filter.estimate_count(&9), 9);
+
+    let mut narrow = SpectralBloomFilter::<u64>::from_params(1000, 3, 4);
+    narrow.insert_count(&1, 100);
+    assert_eq!(narrow.estimate_count(&1), 15);
+}
+
+#[test]
+fn multi_set_bloom_filter() {
+    let mut filter = MultiSetBloomFilter::<u64>::new(8, 1000, 0.01);
+    for i in 0..8000u64 {
+        filter.insert(&i, (i % 8) as u32);
+    }
+    // Items in two sets report both.
+    assert!(!filter.insert(&3, 5));
+    assert!(filter.insert(&3, 5));
+    for i in 0..8000u64 {
+        let sets = filter.which_sets(&i);
+        assert!(sets & 1 << (i % 8) != 0, "false negative for {}", i);
+        assert!(filter.contains(&i, (i % 8) as u32));
+    }
+    assert!(filter.which_sets(&3) & 0b10_1000 == 0b10_1000);
+    let false_positives: u32 = (8000..18_000u64).map(|i| filter.which_sets(&i).count_ones()).sum();
+    assert!(false_positives < 8 * 10_000 / 50, "{} false positives", false_positives);
+    assert!(!filter.contains(&0, 8));
+    assert!(filter.current_fpr(0) < 0.02);
+
+    filter.clear();
+    assert!(filter.is_empty());
+    assert_eq!(filter.which_sets(&1), 0);
+    assert!(!filter.contains_any(&1));
+}
+
+#[test]
+fn tombstone_bloom_filter() {
+    check_filter(&mut TombstoneBloomFilter::<u64>::new(1000, 100, 0.01));
+
+    let mut filter = TombstoneBloomFilter::<u64>::new(1000, 500, 0.01).with_journal(true);
+    for i in 0..1000 {
+        filter.insert(&i);
+    }
+    // Tombstone false positives hide some live items until compaction, even
+    // ones about to be removed.
+    let hidden = (0..500).filter(|i| !filter.remove(i)).count();
+    assert!(hidden < 25, "{} items hidden before removal", hidden);
+    assert!(!filter.remove(&1_000_000));
+    assert_eq!(filter.removals(), 500 - hidden);
+    assert!((0..500).all(|i| !filter.contains(&i)));
+    let false_negatives = (500..1000).filter(|i| !filter.contains(i)).count();
+    assert!(false_negatives < 25, "{} live items hidden", false_negatives);
+    let estimate = filter.estimated_len();
+    assert!(estimate > 450.0 && estimate < 550.0, "estimated {} items", estimate);
+
+    filter.insert(&7);
+    assert!(!filter.contains(&7));
+    filter.compact().unwrap();
+    assert_eq!(filter.removals(), 0);
+    assert!(!filter.needs_compaction());
+    assert_eq!(filter.journal().unwrap().len(), 501 + hidden);
+    assert!(filter.contains(&7));
+    assert!((500..1000).all(|i| filter.contains(&i)));
+    let false_positives = (0..500).filter(|i| filter.contains(i)).count();
+    assert!(false_positives < 25, "{} removed items still present", false_positives);
+
+    let mut unjournaled = TombstoneBloomFilter::<u64>::new(100, 10, 0.01);
+    unjournaled.insert(&1);
+    assert!(unjournaled.compact().is_err());
+}
+
+#[test]
+fn sparse_bloom_filter() {
+    check_filter(&mut SparseBloomFilter::<u64>::new(1000, 0.01));
+
+    let mut filter = SparseBloomFilter::<u64>::with_seed(100_000, 1e-6, 7);
+    let mut dense = BloomFilter::<u64>::with_seed(100_000, 1e-6, 7);
+    for i in 0..20_000 {
+        assert_eq!(filter.insert(&i), dense.insert(&i));
+        if i == 100 {
+            assert!(filter.is_sparse());
+            assert!(filter.memory_bytes() < 32 * 1024);
+        }
+    }
+    // Past about 1100 items the indices outgrow half the bitmap.
+    assert!(!filter.is_sparse());
+    assert_eq!(filter.count_ones(), dense.count_ones());
+    assert!((0..40_000).all(|i| filter.contains(&i) == dense.contains(&i)));
+    assert_eq!(filter.clone().into_bloom_filter(), dense);
+
+    filter.clear();
+    assert!(filter.is_sparse() && filter.is_empty());
+    filter.insert(&1);
+    filter.densify();
+    assert!(!filter.is_sparse());
+    assert!(filter.contains(&1));
+}
+
+#[test]
+fn backed_bloom_filter() {
+    check_filter(&mut BackedBloomFilter::<u64, DenseBits>::new(1000, 0.01));
+
+    let mut evens = BackedBloomFilter::<u64, DenseBits>::with_seed(1000, 0.01, 3);
+    let mut odds = BackedBloomFilter::<u64, DenseBits>::with_seed(1000, 0.01, 3);
+    let mut plain = BloomFilter::<u64>::with_seed(1000, 0.01, 3);
+    for i in 0..500 {
+        evens.insert(&(2 * i));
+        odds.insert(&(2 * i + 1));
+        plain.insert(&(2 * i));
+    }
+    assert_eq!(evens.count_ones(), plain.count_ones());
+    assert!((0..2000).all(|i| evens.contains(&i) == plain.contains(&i)));
+    evens.try_union(&odds).unwrap();
+    assert!((0..1000).all(|i| evens.contains(&i)));
+    assert!(evens.try_union(&BackedBloomFilter::with_seed(1000, 0.01, 4)).is_err());
+    odds.try_intersect(&evens).unwrap();
+    assert!((0..500).all(|i| odds.contains(&(2 * i + 1))));
+}
+
+#[test]
+fn batch_lookups_match_single_lookups() {
+    let items: Vec<u64> = (0..2000).collect();
+    // Probe 1003 items so that the batches don't divide them evenly.
+    let probes: Vec<u64> = (0..1003).map(|i| i * 3).collect();
+
+    for filter in &mut [BloomFilter::<u64>::new(1000, 0.1), BloomFilter::<u64>::from_params(4096, 5)] {
+        filter.extend(&items[..1000]);
+        let expected: Vec<bool> = probes.iter().map(|item| filter.contains(item)).collect();
+        assert_eq!(filter.contains_many(&probes), expected);
+        assert!(filter.contains_many(&items[..1000]).iter().all(|&found| found));
+    }
+
+    let mut blocked = BlockedBloomFilter::<u64>::new(1000, 0.1);
+    let mut split_block = SplitBlockBloomFilter::<u64>::new(1000, 0.1);
+    for item in &items[..1000] {
+        blocked.insert(item);
+        split_block.insert(item);
+    }
+    let expected: Vec<bool> = probes.iter().map(|item| blocked.contains(item)).collect();
+    assert_eq!(blocked.contains_many(&probes), expected);
+    assert!(expected.contains(&false));
+    let expected: Vec<bool> = probes.iter().map(|item| split_block.contains(item)).collect();
+    assert_eq!(split_block.contains_many(&probes), expected);
+    assert!(expected.contains(&false));
+}
+
+#[test]
+fn split_block_bits_follow_parquet_salts() {
+    const SALT: [u32; 8] =
+        [0x47b6_137b, 0x4497_4d91, 0x8824_ad5b, 0xa2b7_289d, 0x7054_95c7, 0x2df1_424b, 0x9efc_4947, 0x5c6b_fb31];
+    for &hash in &[0u64, 1, 0xdead_beef, 0x0123_4567_89ab_cdef, u64::MAX] {
+        let mut filter = SplitBlockBloomFilter::<u64>::from_params(1);
+        assert!(!filter.insert_hash(hash));
+        assert!(filter.insert_hash(hash));
+        assert!(filter.contains_hash(hash));
+        let expected: Vec<u8> =
+            SALT.iter().flat_map(|salt| (1u32 << ((hash as u32).wrapping_mul(*salt) >> 27)).to_le_bytes()).collect();
+        assert_eq!(filter.to_bytes(), expected);
+    }
+}
+
+#[test]
+fn sharded_bloom_filter() {
+    check_filter(&mut ShardedBloomFilter::<u64>::new(1000, 0.01, 4));
+    check_filter(&mut ShardedBloomFilter::<u64>::new(1000, 0.01, 1));
+
+    // Shards filled from separate threads hold the same bits as inserting
+    // through the filter.
+    let mut direct = ShardedBloomFilter::<u64>::with_seed(10_000, 0.01, 4, 7);
+    let mut threaded = direct.clone();
+    for i in 0..10_000 {
+        direct.insert(&i);
+    }
+    let routes: Vec<usize> = (0..10_000).map(|i| threaded.shard_index(&i)).collect();
+    let counts: Vec<usize> = (0..4).map(|shard| routes.iter().filter(|&&route| route == shard).count()).collect();
+    assert!(counts.iter().all(|&count| count > 2300 && count < 2700), "{:?}", counts);
+    std::thread::scope(|scope| {
+        for (index, shard) in threaded.shards_mut().iter_mut().enumerate() {
+            let routes = &routes;
+            scope.spawn(move || {
+                for i in (0..10_000u64).filter(|&i| routes[i as usize] == index) {
+                    shard.insert(&i);
+                }
+            });
+        }
+    });
+    assert!(direct.shards().iter().zip(threaded.shards()).all(|(a, b)| a == b));
+    let false_positives = (10_000..20_000).filter(|i| threaded.contains(i)).count();
+    assert!(false_positives < 150, "{} false positives", false_positives);
+
+    let mut other = ShardedBloomFilter::<u64>::with_seed(10_000, 0.01, 4, 7);
+    other.insert(&1_000_000);
+    threaded.try_union(&other).unwrap();
+    assert!(threaded.contains(&1_000_000) && threaded.contains(&0));
+    threaded.try_intersect(&other).unwrap();
+    assert!(threaded.contains(&1_000_000));
+    assert!(threaded.estimated_len() < 10.0);
+    assert!(threaded.try_union(&ShardedBloomFilter::with_seed(10_000, 0.01, 3, 7)).is_err());
+    assert!(threaded.try_union(&ShardedBloomFilter::with_seed(10_000, 0.01, 4, 8)).is_err());
+}
+
+#[test]
+fn cow_bloom_filter() {
+    check_filter(&mut CowBloomFilter::<u64>::new(1000, 0.01));
+    check_filter(&mut CowBloomFilter::<u64>::from_params(100_000, 7));
+
+    let mut filter = CowBloomFilter::<u64>::with_seed(200_000, 0.01, 5);
+    let mut plain = BloomFilter::<u64>::with_seed(200_000, 0.01, 5);
+    for i in 0..1000 {
+        filter.insert(&i);
+        plain.insert(&i);
+    }
+    let snapshot = filter.snapshot();
+    assert!(filter.shared_pages() > 0);
+    for i in 1000..200_000 {
+        filter.insert(&i);
+        plain.insert(&i);
+    }
+    assert_eq!(filter.shared_pages(), 0);
+    assert!((0..1000).all(|i| snapshot.contains(&i)));
+    let seen_later = (1000..200_000).filter(|i| snapshot.contains(i)).count();
+    assert!(seen_later < 100, "snapshot sees {} later items", seen_later);
+    assert_eq!(filter.snapshot().to_bloom_filter(), plain);
+
+    let from_plain = CowBloomFilter::from_bloom_filter(plain.clone());
+    assert_eq!(from_plain.snapshot().to_bloom_filter(), plain);
+    filter.clear();
+    assert!(filter.is_empty() && snapshot.count_ones() > 0);
+}
+
+#[test]
+fn cow_snapshots_are_consistent_while_writing() {
+    let mut filter = CowBloomFilter::<u64>::new(100_000, 0.01);
+    let (sender, receiver) = std::sync::mpsc::channel::<(u64, BloomSnapshot<u64>)>();
+    let reader = std::thread::spawn(move || {
+        // Every snapshot holds a whole prefix of the inserts.
+        for (inserted, snapshot) in receiver {
+            assert!((0..inserted).all(|i| snapshot.contains(&i)));
+        }
+    });
+    for i in 0..100_000u64 {
+        filter.insert(&i);
+        if i % 10_000 == 9999 {
+            sender.send((i + 1, filter.snapshot())).unwrap();
+        }
+    }
+    drop(sender);
+    reader.join().unwrap();
+}
+
+#[test]
+fn bits_start_on_cache_lines() {
+    let aligned = |words: &[u64]| (words.as_ptr() as usize).is_multiple_of(64);
+    for &bits in &[1, 64, 100, 513, 95_851] {
+        let mut filter = BloomFilter::<u64>::from_params(bits, 3);
+        filter.insert(&7);
+        assert!(aligned(filter.as_raw_slice()));
+        assert!(aligned(filter.clone().as_raw_slice()));
+        let (bytes, bit_len, hashes, seed) = filter.clone().into_raw_parts();
+        let rebuilt = BloomFilter::<u64>::from_raw_parts(&bytes, bit_len, hashes, seed).unwrap();
+        assert!(aligned(rebuilt.as_raw_slice()));
+        assert_eq!(rebuilt, filter);
+    }
+}
+
+#[test]
+fn memory_use_counts_whole_cache_lines() {
+    let metadata = BloomFilter::<u64>::from_params(1, 1).memory_bytes() - 64;
+    for &(bits, lines) in &[(1, 1), (512, 1), (513, 2), (95_851, 188)] {
+        let filter = BloomFilter::<u64>::from_params(bits, 3);
+        assert_eq!(filter.memory_bytes(), metadata + 64 * lines);
+        assert_eq!(ApproximateMembership::<u64>::memory_bytes(&filter), filter.memory_bytes());
+    }
+    let sharded = ShardedBloomFilter::<u64>::new(10_000, 0.01, 4);
+    let shards: usize = sharded.shards().iter().map(BloomFilter::memory_bytes).sum();
+    assert!(sharded.memory_bytes() > shards);
+}
+
+#[test]
+fn fallible_construction_refuses_oversized_filters() {
+    let mut filter = BloomFilter::<u64>::try_new(1000, 0.01).unwrap();
+    filter.insert(&1);
+    let mut expected = BloomFilter::<u64>::new(1000, 0.01);
+    expected.insert(&1);
+    assert_eq!(filter, expected);
+
+    assert!(matches!(BloomFilter::<u64>::try_new(0, 0.01), Err(BloomError::InvalidParams(_))));
+    assert!(matches!(BloomFilter::<u64>::try_new(usize::MAX, 0.01), Err(BloomError::Capacity(_))));
+
+    let bytes = BloomFilter::<u64>::new(100_000, 0.01).as_raw_slice().len() as u64 * 8;
+    assert!(BloomFilter::<u64>::try_with_capacity(100_000, 0.01, bytes + 64).is_ok());
+    let result = BloomFilter::<u64>::try_with_capacity(100_000, 0.01, bytes / 2);
+    assert!(matches!(result, Err(BloomError::Capacity(_))));
+}
@@ -0,0 +1,63 @@
+extern crate bloom;
+extern crate rayon;
+
+use std::hash::{Hash, Hasher};
+use std::panic::{self, AssertUnwindSafe};
+
+use bloom::BloomFilter;
+use rayon::iter::{IntoParallelIterator, ParallelExtend};
+
+#[test]
+fn parallel_insertion_matches_sequential() {
+    let lines: Vec<String> = (0..50_000).map(|i| format!("line {}", i)).collect();
+    let mut sequential = BloomFilter::<String>::with_seed(lines.len(), 0.01, 3);
+    sequential.extend(&lines);
+
+    let mut parallel = BloomFilter::<String>::with_seed(lines.len(), 0.01, 3);
+    parallel.par_insert_all(&lines);
+    assert_eq!(parallel, sequential);
+
+    let mut by_ref = BloomFilter::<String>::with_seed(lines.len(), 0.01, 3);
+    by_ref.par_extend(&lines);
+    assert_eq!(by_ref, sequential);
+
+    let mut owned = BloomFilter::<String>::with_seed(lines.len(), 0.01, 3);
+    owned.par_extend(lines.clone());
+    assert_eq!(owned, sequential);
+    assert!(lines.iter().all(|line| owned.contains(line)));
+}
+
+#[test]
+fn parallel_insertion_keeps_existing_items() {
+    let mut filter = BloomFilter::<u64>::new(200_000, 0.01);
+    for i in 0..1000 {
+        filter.insert(&i);
+    }
+    filter.par_extend((1000..200_000u64).into_par_iter());
+    assert!((0..200_000).all(|i| filter.contains(&i)));
+    let false_positives = (200_000..300_000).filter(|i| filter.contains(i)).count();
+    assert!(false_positives < 1500, "{} false positives", false_positives);
+}
+
+struct Bomb(u64);
+
+impl Hash for Bomb {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if self.0 == 500 {
+            panic!("boom");
+        }
+        self.0.hash(state);
+    }
+}
+
+#[test]
+fn parallel_insertion_leaves_filter_whole_after_panic() {
+    let mut filter = BloomFilter::<Bomb>::new(1000, 0.01);
+    filter.insert(&Bomb(2000));
+    let items: Vec<Bomb> = (0..1000).map(Bomb).collect();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| filter.par_insert_all(&items)));
+    assert!(result.is_err());
+    assert!(filter.contains(&Bomb(2000)));
+    filter.insert(&Bomb(3000));
+    assert!(filter.contains(&Bomb(3000)));
+}
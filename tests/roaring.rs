@@ -0,0 +1,51 @@
+extern crate bloom;
+
+use bloom::{ApproximateMembership, BackedBloomFilter, BloomFilter, RoaringBitmap};
+
+#[test]
+fn roaring_backed_filter_matches_plain_filter() {
+    let mut filter = BackedBloomFilter::<u64, RoaringBitmap>::with_seed(1_000_000, 0.01, 5);
+    let mut plain = BloomFilter::<u64>::with_seed(1_000_000, 0.01, 5);
+    for i in 0..1000 {
+        assert_eq!(filter.insert(&i), plain.insert(&i));
+    }
+    assert_eq!(filter.count_ones(), plain.count_ones());
+    assert!((0..10_000).all(|i| filter.contains(&i) == plain.contains(&i)));
+    // A thousand items in a filter sized for a million take a fraction of
+    // the bitmap.
+    assert!(filter.memory_bytes() < plain.bit_vec_size() as usize / 8 / 20);
+
+    filter.clear();
+    assert!(filter.is_empty());
+    assert!(!filter.contains(&1));
+}
+
+#[test]
+fn roaring_backed_filters_combine() {
+    let mut filters: Vec<_> = (0..8u64)
+        .map(|shard| {
+            let mut filter = BackedBloomFilter::<u64, RoaringBitmap>::new(100_000, 0.01);
+            for i in 0..100 {
+                filter.insert(&(shard * 1000 + i));
+            }
+            filter
+        })
+        .collect();
+    let mut union = filters.pop().unwrap();
+    for filter in &filters {
+        union.try_union(filter).unwrap();
+    }
+    assert!((0..8).all(|shard| (0..100).all(|i| union.contains(&(shard * 1000 + i)))));
+    let estimate = ApproximateMembership::<u64>::estimated_len(&union);
+    assert!(estimate > 750.0 && estimate < 850.0, "estimated {} items", estimate);
+
+    let mut intersection = filters[0].clone();
+    intersection.try_intersect(&filters[1]).unwrap();
+    assert!(intersection.count_ones() < 10);
+}
+
+#[test]
+#[should_panic(expected = "2^32 bits")]
+fn roaring_backed_filter_rejects_too_many_bits() {
+    BackedBloomFilter::<u64, RoaringBitmap>::from_params((1 << 32) + 1, 3);
+}
@@ -0,0 +1,40 @@
+extern crate bloom;
+extern crate serde_json;
+
+use bloom::hash::{HashScheme, SecretKey};
+use bloom::{BloomFilter, BloomFilterBuilder};
+
+#[test]
+fn round_trips_through_json() {
+    let mut filter = BloomFilterBuilder::<u64>::new()
+        .item_count(200)
+        .seed(3)
+        .hasher(HashScheme::XxHash64)
+        .build()
+        .unwrap();
+    for i in 0..200 {
+        filter.add(&i);
+    }
+    let json = serde_json::to_string(&filter).unwrap();
+    let loaded: BloomFilter<u64> = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, filter);
+    assert_eq!(*loaded.hasher(), HashScheme::XxHash64);
+    assert_eq!(loaded.capacity(), Some(200));
+    assert_eq!(loaded.false_positive_prob(), Some(0.01));
+}
+
+#[test]
+fn rejects_invalid_filters() {
+    let json = r#"{"scheme":1,"wide_hashes":false,"bit_vec_size":10,"hash_count":2,"seed":0,"bits":[0,2]}"#;
+    assert!(serde_json::from_str::<BloomFilter<u64>>(json).is_ok());
+    let trailing_bit = json.replace("[0,2]", "[0,4]");
+    assert!(serde_json::from_str::<BloomFilter<u64>>(&trailing_bit).is_err());
+    let unknown_scheme = json.replace(r#""scheme":1"#, r#""scheme":9"#);
+    assert!(serde_json::from_str::<BloomFilter<u64>>(&unknown_scheme).is_err());
+}
+
+#[test]
+fn refuses_to_serialize_keys() {
+    let filter = BloomFilter::<u64>::with_hasher(100, 0.01, HashScheme::KeyedSipHash13(SecretKey::new([1; 16])));
+    assert!(serde_json::to_string(&filter).is_err());
+}
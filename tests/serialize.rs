@@ -0,0 +1,207 @@
+extern crate bloom;
+
+use std::hash::Hasher;
+
+use bloom::hash::{HashScheme, SecretKey, XxHash64};
+use bloom::{
+    filter_from_file, filter_from_file_with_jobs, BloomError, BloomFilter, BloomFilterBuilder, PagedBloomFilter,
+};
+
+fn filled(scheme: HashScheme) -> BloomFilter<u64> {
+    // An odd size, so the last byte is only partly used.
+    let mut filter = BloomFilterBuilder::new()
+        .item_count(500)
+        .bit_vec_size(70_001)
+        .seed(7)
+        .hasher(scheme)
+        .build()
+        .unwrap();
+    for i in 0..500 {
+        filter.add(&i);
+    }
+    filter
+}
+
+fn serialized(filter: &BloomFilter<u64>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    filter.write_to(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn round_trips_every_scheme() {
+    for &scheme in HashScheme::ALL.iter() {
+        let filter = filled(scheme);
+        let loaded = BloomFilter::<u64>::read_from(&serialized(&filter)[..]).unwrap();
+        assert_eq!(loaded, filter);
+        assert_eq!(*loaded.hasher(), scheme);
+        assert_eq!(loaded.capacity(), Some(500));
+        assert_eq!(loaded.false_positive_prob(), None);
+        assert_eq!(loaded.seed(), 7);
+        assert!((0..500).all(|i| loaded.contains(&i)));
+    }
+}
+
+#[test]
+fn round_trips_wide_hashes() {
+    let mut filter = BloomFilterBuilder::new().item_count(100).wide_hashes(true).build().unwrap();
+    filter.add(&1);
+    let loaded = BloomFilter::<u64>::read_from(&serialized(&filter)[..]).unwrap();
+    assert!(loaded.wide_hashes());
+    assert_eq!(loaded, filter);
+    assert!(!filled(HashScheme::default()).wide_hashes());
+
+    // Wide hashes are a flag, and don't change the hash scheme version.
+    let bytes = serialized(&filter);
+    assert_eq!(bytes[7] & 4, 4);
+    assert_eq!(bytes[8..12], serialized(&filled(HashScheme::default()))[8..12]);
+    assert_eq!(serialized(&filled(HashScheme::default()))[7] & 4, 0);
+}
+
+#[test]
+fn rejects_newer_versions() {
+    let bytes = serialized(&filled(HashScheme::default()));
+    // The format version, then the hash scheme version.
+    for &offset in [4, 8].iter() {
+        let mut newer = bytes.clone();
+        newer[offset] += 1;
+        let checksum_offset = newer.len() - 8;
+        let mut digest = XxHash64::with_seed(0);
+        digest.write(&newer[..checksum_offset]);
+        newer[checksum_offset..].copy_from_slice(&digest.finish().to_le_bytes());
+        let result = BloomFilter::<u64>::read_from(&newer[..]);
+        assert!(matches!(result, Err(BloomError::Incompatible(_))), "version at byte {}", offset);
+    }
+}
+
+#[test]
+fn refuses_headers_claiming_huge_filters() {
+    let mut bytes = serialized(&filled(HashScheme::default()));
+    bytes[12..20].copy_from_slice(&(1u64 << 60).to_le_bytes());
+    assert!(matches!(BloomFilter::<u64>::read_from(&bytes[..]), Err(BloomError::Capacity(_))));
+}
+
+#[test]
+fn schemes_set_different_bits() {
+    assert_ne!(filled(HashScheme::SipHash13), filled(HashScheme::XxHash64));
+    assert_ne!(filled(HashScheme::WyHash), filled(HashScheme::Fnv1a));
+}
+
+#[test]
+fn rejects_corruption() {
+    let bytes = serialized(&filled(HashScheme::default()));
+    for &i in [0, 6, 40, bytes.len() / 2, bytes.len() - 1].iter() {
+        let mut corrupted = bytes.clone();
+        corrupted[i] ^= 0x10;
+        let result = BloomFilter::<u64>::read_from(&corrupted[..]);
+        assert!(matches!(result, Err(BloomError::CorruptFile(_))), "flipped byte {}", i);
+    }
+    let result = BloomFilter::<u64>::read_from(&bytes[..bytes.len() - 3]);
+    assert!(matches!(result, Err(BloomError::Io(_))));
+}
+
+#[test]
+fn saves_and_loads_files() {
+    let filter = filled(HashScheme::XxHash64);
+    let path = std::env::temp_dir().join(format!("bloom-serialize-{}.blm", std::process::id()));
+    filter.save(&path).unwrap();
+    let loaded = BloomFilter::<u64>::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.unwrap(), filter);
+}
+
+#[test]
+fn pages_in_saved_filters() {
+    let key = SecretKey::new([7; 16]);
+    for (n, &scheme) in [HashScheme::XxHash64, HashScheme::KeyedSipHash13(key)].iter().enumerate() {
+        let filter = filled(scheme);
+        let path = std::env::temp_dir().join(format!("bloom-paged-{}-{}.blm", std::process::id(), n));
+        filter.save(&path).unwrap();
+
+        let open = || match scheme {
+            HashScheme::KeyedSipHash13(key) => PagedBloomFilter::<u64>::open_keyed(&path, key),
+            _ => PagedBloomFilter::<u64>::open(&path),
+        };
+        // 70,001 bits span three pages, so one cached page means rereads.
+        let paged = open().unwrap().with_cache_pages(1);
+        assert_eq!(paged.bit_vec_size(), filter.bit_vec_size());
+        assert_eq!(paged.hash_count(), filter.hash_count());
+        assert_eq!(paged.seed(), 7);
+        for i in 0..2000 {
+            assert_eq!(paged.contains(&i).unwrap(), filter.contains(&i), "item {}", i);
+        }
+        assert!(paged.pages_read() > 3);
+        paged.verify().unwrap();
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 0x10;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(matches!(open().unwrap().verify(), Err(BloomError::CorruptFile(_))));
+
+        std::fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+        let truncated = open();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(truncated, Err(BloomError::CorruptFile(_))));
+    }
+}
+
+#[test]
+fn keyed_filters_need_their_key() {
+    let key = SecretKey::new([7; 16]);
+    let filter = filled(HashScheme::KeyedSipHash13(key));
+    assert_ne!(filter, filled(HashScheme::SipHash13));
+    let bytes = serialized(&filter);
+    assert!(!bytes.windows(16).any(|window| window == key.as_bytes()));
+
+    let loaded = BloomFilter::<u64>::read_from_keyed(&bytes[..], key).unwrap();
+    assert_eq!(loaded, filter);
+    assert!((0..500).all(|i| loaded.contains(&i)));
+
+    let wrong_key = BloomFilter::<u64>::read_from_keyed(&bytes[..], SecretKey::new([8; 16]));
+    assert!(matches!(wrong_key, Err(BloomError::Incompatible(_))));
+    let no_key = BloomFilter::<u64>::read_from(&bytes[..]);
+    assert!(matches!(no_key, Err(BloomError::Incompatible(_))));
+    let unkeyed = serialized(&filled(HashScheme::default()));
+    let unexpected_key = BloomFilter::<u64>::read_from_keyed(&unkeyed[..], key);
+    assert!(matches!(unexpected_key, Err(BloomError::Incompatible(_))));
+}
+
+#[test]
+fn keys_are_not_debug_printed() {
+    let key = SecretKey::new([0xab; 16]);
+    assert_eq!(format!("{:?}", HashScheme::KeyedSipHash13(key)), "KeyedSipHash13(SecretKey(..))");
+    assert_ne!(SecretKey::random(), SecretKey::random());
+}
+
+#[test]
+fn reads_lines_on_any_number_of_jobs() {
+    let mut contents = String::new();
+    for i in 0..5000 {
+        let ending = if i % 3 == 0 { "\r\n" } else { "\n" };
+        contents.push_str(&format!("  line {}{}", i, ending));
+    }
+    contents.push_str("\nlast line");
+    let path = std::env::temp_dir().join(format!("bloom-lines-{}.txt", std::process::id()));
+    std::fs::write(&path, &contents).unwrap();
+    let path_str = path.to_str().unwrap();
+
+    let mut expected = BloomFilterBuilder::<String>::new().item_count(5002).build().unwrap();
+    for line in contents.lines() {
+        expected.add(line.trim());
+    }
+    for &jobs in &[1, 2, 3, 7, 16] {
+        let filter = filter_from_file_with_jobs(path_str, 5002, 0.01, jobs).unwrap();
+        assert_eq!(filter, expected, "{} jobs", jobs);
+    }
+    assert_eq!(filter_from_file(path_str, 5002, 0.01).unwrap(), expected);
+    assert!(matches!(filter_from_file_with_jobs(path_str, 5002, 0.01, 0), Err(BloomError::InvalidParams(_))));
+
+    std::fs::write(&path, "a\nb\n").unwrap();
+    let filter = filter_from_file_with_jobs(path_str, 10, 0.01, 1000).unwrap();
+    assert!(filter.contains("a") && filter.contains("b"));
+
+    std::fs::write(&path, b"ok\n\xff\n").unwrap();
+    assert!(matches!(filter_from_file_with_jobs(path_str, 10, 0.01, 2), Err(BloomError::Io(_))));
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,262 @@
+extern crate bloom;
+
+use bloom::{
+    BloomError, CountMinSketch, CountSketch, HyperLogLog, InvertibleBloomLookupTable, MinHash, StrataEstimator, TinyLfu,
+    TopK,
+};
+
+// A skewed stream: item `i` occurs `1000 / (i + 1)` times.
+fn zipf_stream() -> impl Iterator<Item = (u64, u64)> {
+    (0..1000).map(|i| (i, 1000 / (i + 1)))
+}
+
+#[test]
+fn count_min_sketch_bounds_its_error() {
+    let mut sketch = CountMinSketch::<u64>::new(0.001, 0.001);
+    assert_eq!((sketch.width(), sketch.depth()), (2719, 7));
+    for (item, count) in zipf_stream() {
+        sketch.insert_count(&item, count);
+    }
+    let bound = (sketch.epsilon() * sketch.total_count() as f64) as u64;
+    for (item, count) in zipf_stream() {
+        let estimate = sketch.estimate_count(&item);
+        assert!(estimate >= count && estimate <= count + bound, "{} estimated at {}", count, estimate);
+    }
+    assert_eq!(sketch.estimate_count(&1_000_000), 0);
+}
+
+#[test]
+fn count_min_sketch_merges() {
+    let mut left = CountMinSketch::<u64>::from_params(1000, 4);
+    let mut right = CountMinSketch::<u64>::from_params(1000, 4);
+    for (item, count) in zipf_stream() {
+        left.insert_count(&item, count);
+        right.insert_count(&item, 2 * count);
+    }
+    left.try_merge(&right).unwrap();
+    let bound = (left.epsilon() * left.total_count() as f64) as u64;
+    assert!((3000..=3000 + bound).contains(&left.estimate_count(&0)));
+    assert_eq!(left.total_count(), 3 * right.total_count() / 2);
+
+    let narrow = CountMinSketch::<u64>::from_params(500, 4);
+    assert!(matches!(left.try_merge(&narrow), Err(BloomError::Incompatible(_))));
+    let seeded = CountMinSketch::<u64>::with_seed(0.01, 0.01, 7);
+    assert!(matches!(CountMinSketch::new(0.01, 0.01).try_merge(&seeded), Err(BloomError::Incompatible(_))));
+    left.clear();
+    assert!(left.is_empty() && left.estimate_count(&0) == 0);
+}
+
+#[test]
+fn conservative_update_overestimates_less() {
+    let mut plain = CountMinSketch::<u64>::from_params(200, 4);
+    let mut conservative = CountMinSketch::<u64>::from_params(200, 4).with_conservative_update(true);
+    for (item, count) in zipf_stream() {
+        plain.insert_count(&item, count);
+        conservative.insert_count(&item, count);
+    }
+    let error = |sketch: &CountMinSketch<u64>| -> u64 {
+        zipf_stream().map(|(item, count)| sketch.estimate_count(&item) - count).sum()
+    };
+    assert!(error(&conservative) < error(&plain) * 3 / 4, "{} against {}", error(&conservative), error(&plain));
+
+    let mut merged = conservative.clone();
+    merged.try_merge(&conservative).unwrap();
+    assert!(merged.conservative_update());
+    for (item, count) in zipf_stream() {
+        assert!(merged.estimate_count(&item) >= 2 * count);
+    }
+    assert!(matches!(merged.try_merge(&plain), Err(BloomError::Incompatible(_))));
+}
+
+#[test]
+fn count_sketch_is_unbiased() {
+    let mut sketch = CountSketch::<u64>::from_params(256, 5);
+    for (item, count) in zipf_stream() {
+        sketch.insert_count(&item, count as i64);
+    }
+    let norm = zipf_stream().map(|(_, count)| (count * count) as f64).sum::<f64>().sqrt();
+    let errors: Vec<i64> = zipf_stream().map(|(item, count)| sketch.estimate_count(&item) - count as i64).collect();
+    let bias = errors.iter().sum::<i64>() as f64 / errors.len() as f64;
+    assert!(bias.abs() < 0.05 * norm, "bias {} against a norm of {}", bias, norm);
+    assert!(errors.iter().any(|&error| error < 0) && errors.iter().any(|&error| error > 0));
+    assert!(errors[..10].iter().all(|&error| (error.abs() as f64) < sketch.epsilon() * norm));
+
+    let second_moment = sketch.inner_product(&sketch).unwrap() as f64;
+    assert!((second_moment - norm * norm).abs() < 0.1 * norm * norm, "{} against {}", second_moment, norm * norm);
+    for (item, count) in zipf_stream() {
+        sketch.insert_count(&item, -(count as i64));
+    }
+    assert!(sketch.is_empty() && sketch.total_count() == 0);
+    assert!(matches!(sketch.try_merge(&CountSketch::from_params(256, 3)), Err(BloomError::Incompatible(_))));
+}
+
+#[test]
+fn top_k_finds_heavy_hitters() {
+    let mut top = TopK::<u64>::new(10, 0.001, 0.01);
+    // Interleave the stream so that heavy hitters don't simply come first.
+    for round in 0..1000 {
+        for (item, count) in zipf_stream() {
+            if round < count {
+                top.insert(&item);
+            }
+        }
+    }
+    let found = top.top();
+    assert_eq!(found.len(), 10);
+    let items: Vec<u64> = found.iter().map(|&(item, _)| item).collect();
+    assert_eq!(items, (0..10).collect::<Vec<u64>>());
+    for (item, count) in found {
+        assert!(count >= 1000 / (item + 1) && count <= 1000 / (item + 1) + 10, "{} counted {}", item, count);
+    }
+    assert!(top.contains(&3) && !top.contains(&500));
+    top.clear();
+    assert!(top.is_empty() && top.top().is_empty());
+}
+
+#[test]
+fn tiny_lfu_admits_frequent_items() {
+    let mut policy = TinyLfu::<u64>::new(100);
+    assert_eq!(policy.sample_size(), 1000);
+    for (item, count) in zipf_stream().take(20) {
+        for _ in 0..count / 10 {
+            policy.record_access(&item);
+        }
+    }
+    // 354 accesses, too few to age the history.
+    assert!((100..=102).contains(&policy.frequency(&0)), "{}", policy.frequency(&0));
+    assert!(policy.should_admit(&0, &10));
+    assert!(!policy.should_admit(&10, &0));
+    // Items seen once only pass the doorkeeper.
+    policy.record_access(&1_000_000);
+    assert_eq!(policy.frequency(&1_000_000), 1);
+    assert!(policy.should_admit(&1_000_000, &2_000_000));
+    assert!(!policy.should_admit(&1_000_000, &1));
+
+    policy.reset();
+    assert!((49..=51).contains(&policy.frequency(&0)), "{}", policy.frequency(&0));
+    assert!(policy.doorkeeper().is_empty());
+    for _ in 0..1000 {
+        policy.record_access(&5);
+    }
+    // Aged again along the way.
+    assert!(policy.frequency(&0) <= 26 && policy.should_admit(&5, &0));
+    policy.clear();
+    assert_eq!(policy.frequency(&0), 0);
+
+    let mut sketch = CountMinSketch::<u64>::from_params(1000, 4);
+    sketch.insert_count(&1, 7);
+    sketch.halve();
+    assert_eq!((sketch.estimate_count(&1), sketch.total_count()), (3, 3));
+}
+
+#[test]
+fn hyperloglog_estimates_and_merges() {
+    let mut small = HyperLogLog::<u64>::from_params(12);
+    for i in 0..100 {
+        small.insert(&i);
+    }
+    assert!((small.estimate() - 100.0).abs() < 5.0, "estimated {}", small.estimate());
+
+    let (mut left, mut right) = (HyperLogLog::<u64>::new(0.01), HyperLogLog::<u64>::new(0.01));
+    assert_eq!(left.precision(), 14);
+    for i in 0..600_000 {
+        left.insert(&i);
+        right.insert(&(i + 400_000));
+    }
+    assert!(!left.insert(&0));
+    let error = |sketch: &HyperLogLog<u64>, count: f64| (sketch.estimate() - count).abs() / count;
+    assert!(error(&left, 600_000.0) < 3.0 * left.relative_error());
+    left.try_merge(&right).unwrap();
+    assert!(error(&left, 1_000_000.0) < 3.0 * left.relative_error(), "estimated {}", left.estimate());
+
+    assert!(matches!(left.try_merge(&HyperLogLog::from_params(10)), Err(BloomError::Incompatible(_))));
+    left.clear();
+    assert!(left.is_empty() && left.estimate() == 0.0);
+}
+
+#[test]
+fn minhash_estimates_jaccard_similarity() {
+    let (mut a, mut b) = (MinHash::<u64>::new(512), MinHash::<u64>::new(512));
+    assert_eq!(a.jaccard(&b).unwrap(), 1.0);
+    for i in 0..10_000 {
+        a.insert(&i);
+        b.insert(&(i + 2_500));
+    }
+    // 7,500 shared of 12,500.
+    let similarity = a.jaccard(&b).unwrap();
+    assert!((similarity - 0.6).abs() < 3.0 * a.max_error(), "estimated {}", similarity);
+    assert!(!a.insert(&0));
+
+    let mut union = a.clone();
+    union.try_merge(&b).unwrap();
+    let mut whole = MinHash::<u64>::new(512);
+    for i in 0..12_500 {
+        whole.insert(&i);
+    }
+    assert_eq!(union.signature(), whole.signature());
+
+    assert!(matches!(a.jaccard(&MinHash::new(256)), Err(BloomError::Incompatible(_))));
+    assert!(matches!(a.try_merge(&MinHash::with_seed(512, 1)), Err(BloomError::Incompatible(_))));
+    a.clear();
+    assert!(a.is_empty());
+}
+
+#[test]
+fn iblt_reconciles_sets() {
+    let (mut ours, mut theirs) = (InvertibleBloomLookupTable::new(100), InvertibleBloomLookupTable::new(100));
+    for key in 0..50_000 {
+        ours.insert(key * 3);
+        theirs.insert(key * 3 + (key % 1000 == 0) as u64);
+    }
+    ours.subtract(&theirs).unwrap();
+    let (mut only_ours, mut only_theirs) = ours.list_entries().unwrap();
+    only_ours.sort_unstable();
+    only_theirs.sort_unstable();
+    assert_eq!(only_ours, (0..50).map(|i| i * 3000).collect::<Vec<u64>>());
+    assert_eq!(only_theirs, (0..50).map(|i| i * 3000 + 1).collect::<Vec<u64>>());
+
+    let loaded = InvertibleBloomLookupTable::from_bytes(&ours.to_bytes()).unwrap();
+    assert_eq!(loaded, ours);
+    let mut bytes = ours.to_bytes();
+    bytes[30] ^= 1;
+    assert!(InvertibleBloomLookupTable::from_bytes(&bytes).is_err());
+
+    // Far more differences than cells can't be listed.
+    let mut small = InvertibleBloomLookupTable::new(10);
+    for key in 0..1000 {
+        small.insert(key);
+    }
+    assert!(matches!(small.list_entries(), Err(BloomError::Capacity(_))));
+    for key in 0..1000 {
+        small.remove(key);
+    }
+    assert!(small.is_empty());
+    assert!(matches!(small.subtract(&ours), Err(BloomError::Incompatible(_))));
+}
+
+#[test]
+fn strata_estimator_estimates_set_differences() {
+    let empty = StrataEstimator::new();
+    assert_eq!(empty.estimate_difference(&StrataEstimator::new()).unwrap(), 0);
+    for &difference in &[10u64, 1000, 20_000] {
+        let (mut ours, mut theirs) = (StrataEstimator::with_seed(7), StrataEstimator::with_seed(7));
+        for key in 0..100_000 {
+            ours.insert(key);
+            theirs.insert(key + difference / 2);
+        }
+        let estimate = ours.estimate_difference(&theirs).unwrap();
+        if difference == 10 {
+            assert_eq!(estimate, 10);
+        }
+        assert!(estimate >= difference / 2 && estimate <= difference * 2, "estimated {} for {}", estimate, difference);
+    }
+
+    let mut estimator = StrataEstimator::with_seed(7);
+    estimator.insert(1);
+    let loaded = StrataEstimator::from_bytes(&estimator.to_bytes()).unwrap();
+    assert_eq!(loaded, estimator);
+    let mut bytes = estimator.to_bytes();
+    bytes[40] ^= 1;
+    assert!(StrataEstimator::from_bytes(&bytes).is_err());
+    assert!(matches!(estimator.estimate_difference(&empty), Err(BloomError::Incompatible(_))));
+}
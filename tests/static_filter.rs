@@ -0,0 +1,34 @@
+extern crate bloom;
+
+use bloom::hash::HashScheme;
+use bloom::{include_filter, BloomFilter, StaticBloomFilter};
+
+const FRUIT_PATH: &str = "tests/fixtures/fruit.bloom";
+static FRUIT: StaticBloomFilter = include_filter!("fixtures/fruit.bloom");
+
+#[test]
+fn embedded_filter_matches_loaded_filter() {
+    let loaded = BloomFilter::<String>::load(FRUIT_PATH).unwrap();
+    assert_eq!(FRUIT.bit_vec_size(), 2001);
+    assert_eq!(FRUIT.bit_vec_size(), loaded.bit_vec_size());
+    assert_eq!(FRUIT.hash_count(), loaded.hash_count());
+    assert_eq!(FRUIT.seed(), 9);
+    assert_eq!(*FRUIT.hasher(), HashScheme::XxHash64);
+    assert_eq!(FRUIT.as_bytes(), &loaded.to_bytes()[..]);
+
+    for fruit in ["apple", "banana", "cherry", "damson", "elderberry"].iter() {
+        assert!(FRUIT.contains(*fruit));
+    }
+    for i in 0..1000 {
+        let item = format!("not a fruit {}", i);
+        assert_eq!(FRUIT.contains(&item), loaded.contains(&item));
+    }
+}
+
+#[test]
+#[should_panic(expected = "checksum mismatch")]
+fn parse_rejects_corruption() {
+    let mut bytes = std::fs::read(FRUIT_PATH).unwrap();
+    bytes[100] ^= 1;
+    StaticBloomFilter::parse(Box::leak(bytes.into_boxed_slice()));
+}
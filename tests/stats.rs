@@ -0,0 +1,76 @@
+extern crate bloom;
+
+use std::thread;
+use std::time::Duration;
+
+use bloom::{ApproximateMembership, BloomFilter, CuckooFilter, InstrumentedFilter, LatencyHistogram};
+
+#[test]
+fn quantiles_are_within_the_bucket_error() {
+    let histogram = LatencyHistogram::new();
+    assert_eq!(histogram.quantile(0.5), Duration::ZERO);
+    assert_eq!(histogram.min(), Duration::ZERO);
+    for nanos in 1..=100_000 {
+        histogram.record(Duration::from_nanos(nanos));
+    }
+    assert_eq!(histogram.len(), 100_000);
+    assert_eq!(histogram.min(), Duration::from_nanos(1));
+    assert_eq!(histogram.max(), Duration::from_nanos(100_000));
+    assert_eq!(histogram.mean(), Duration::from_nanos(50_000));
+    assert_eq!(histogram.quantile(0.0), Duration::from_nanos(1));
+    assert_eq!(histogram.quantile(0.001), Duration::from_nanos(100));
+    assert_eq!(histogram.quantile(1.0), histogram.max());
+    for &quantile in &[0.25, 0.5, 0.9, 0.99, 0.999] {
+        let exact = quantile * 100_000.0;
+        let estimate = histogram.quantile(quantile).as_nanos() as f64;
+        assert!(estimate >= exact && estimate <= exact * (1.0 + 1.0 / 64.0), "{} at {}", estimate, quantile);
+    }
+
+    // Latencies too long to bucket finely still land somewhere.
+    histogram.record(Duration::from_secs(u64::MAX));
+    assert_eq!(histogram.max(), Duration::from_nanos(u64::MAX));
+    assert_eq!(histogram.quantile(1.0), Duration::from_nanos(u64::MAX));
+}
+
+#[test]
+fn histograms_merge_and_clear() {
+    let (mut a, b) = (LatencyHistogram::new(), LatencyHistogram::new());
+    a.record(Duration::from_micros(5));
+    b.record(Duration::from_micros(1));
+    b.record(Duration::from_millis(3));
+    a.merge(&b);
+    assert_eq!(a.len(), 3);
+    assert_eq!(a.min(), Duration::from_micros(1));
+    assert_eq!(a.max(), Duration::from_millis(3));
+    assert_eq!(a.clone().quantile(0.5), a.quantile(0.5));
+    a.clear();
+    assert!(a.is_empty());
+    assert_eq!(a.max(), Duration::ZERO);
+}
+
+#[test]
+fn records_every_operation() {
+    let mut filter = InstrumentedFilter::new(BloomFilter::<u64>::new(1000, 0.01));
+    for i in 0..1000 {
+        filter.insert(&i).unwrap();
+    }
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| assert!((0..1000).all(|i| filter.contains(&i))));
+        }
+    });
+    assert_eq!(filter.stats().insert.len(), 1000);
+    assert_eq!(filter.stats().contains.len(), 4000);
+    assert!(filter.stats().contains.quantile(0.5) <= filter.stats().contains.max());
+    assert!(filter.memory_bytes() > filter.get_ref().memory_bytes());
+
+    filter.reset_stats();
+    assert!(filter.stats().insert.is_empty());
+    assert!(filter.into_inner().contains(&7));
+
+    // Failed inserts are timed too.
+    let mut cuckoo = InstrumentedFilter::new(CuckooFilter::<u64>::new(10, 0.01));
+    let failed = (0..1000).filter(|i| cuckoo.insert(i).is_err()).count();
+    assert!(failed > 0);
+    assert_eq!(cuckoo.stats().insert.len(), 1000);
+}
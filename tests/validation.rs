@@ -0,0 +1,88 @@
+extern crate bloom;
+
+use bloom::{params, BlockedBloomFilter, BloomError, BloomFilter, BloomFilterBuilder};
+
+fn build(item_count: usize, false_positive_prob: f64) -> Result<BloomFilter<String>, BloomError> {
+    BloomFilterBuilder::new()
+        .item_count(item_count)
+        .false_positive_prob(false_positive_prob)
+        .build()
+}
+
+fn is_invalid_params(result: Result<BloomFilter<String>, BloomError>) -> bool {
+    matches!(result, Err(BloomError::InvalidParams(_)))
+}
+
+#[test]
+fn rejects_zero_item_count() {
+    assert!(is_invalid_params(build(0, 0.1)));
+}
+
+#[test]
+fn rejects_out_of_range_probability() {
+    assert!(is_invalid_params(build(1000, 0.0)));
+    assert!(is_invalid_params(build(1000, -0.1)));
+    assert!(is_invalid_params(build(1000, 1.0)));
+    assert!(is_invalid_params(build(1000, 1.5)));
+    assert!(is_invalid_params(build(1000, f64::NAN)));
+}
+
+#[test]
+fn accepts_probabilities_near_the_bounds() {
+    assert!(build(1000, 1e-9).is_ok());
+    assert!(build(1000, 0.5).is_ok());
+}
+
+#[test]
+fn sizing_clamps_huge_item_counts_and_tiny_probabilities() {
+    // Probes add bit indices, which must not overflow.
+    assert!(params::MAX_BITS <= 1 << 63 && params::MAX_BITS.is_multiple_of(64));
+    assert_eq!(params::optimal_bits(usize::MAX, 0.01), params::MAX_BITS);
+    assert_eq!(params::optimal_bits(usize::MAX, f64::MIN_POSITIVE), params::MAX_BITS);
+    assert_eq!(params::optimal_bits(1, 5e-324), 1550);
+    assert!(params::optimal_hashes(params::MAX_BITS, 1) > 1 << 60);
+    let fpr = params::expected_fpr(params::MAX_BITS, usize::MAX, 1);
+    assert!(fpr > 0.0 && fpr < 1.0);
+    let blocked = params::expected_blocked_fpr(params::MAX_BITS, usize::MAX, 16, 512);
+    assert!((0.0..=1.0).contains(&blocked));
+}
+
+#[test]
+fn huge_filters_are_refused_rather_than_allocated() {
+    let is_capacity = |result: Result<BloomFilter<String>, BloomError>| matches!(result, Err(BloomError::Capacity(_)));
+    assert!(is_capacity(build(usize::MAX, 0.01)));
+    assert!(is_capacity(build(usize::MAX, f64::MIN_POSITIVE)));
+    assert!(is_capacity(build(1 << 60, 1e-300)));
+    assert!(matches!(BloomFilter::<u64>::try_new(usize::MAX, 1e-300), Err(BloomError::Capacity(_))));
+    assert!(build(1, f64::MIN_POSITIVE).is_ok());
+
+    assert!(matches!(BlockedBloomFilter::<u64>::try_new(usize::MAX, 0.01), Err(BloomError::Capacity(_))));
+    assert!(matches!(BlockedBloomFilter::<u64>::try_new(1 << 50, 1e-9), Err(BloomError::Capacity(_))));
+    assert!(matches!(BlockedBloomFilter::<u64>::try_new(1, 1e-300), Err(BloomError::InvalidParams(_))));
+    assert!(BlockedBloomFilter::<u64>::try_new(1, 1e-20).is_ok());
+}
+
+#[test]
+fn rejects_zero_bits_or_hashes() {
+    let zero_bits = BloomFilterBuilder::<String>::new().bit_vec_size(0).hash_count(3).build();
+    assert!(is_invalid_params(zero_bits));
+    let zero_hashes = BloomFilterBuilder::<String>::new().bit_vec_size(64).hash_count(0).build();
+    assert!(is_invalid_params(zero_hashes));
+}
+
+#[test]
+fn requires_item_count_or_size() {
+    assert!(is_invalid_params(BloomFilterBuilder::<String>::new().build()));
+}
+
+#[test]
+#[should_panic(expected = "item count must be greater than 0")]
+fn new_panics_on_zero_item_count() {
+    BloomFilter::<String>::new(0, 0.1);
+}
+
+#[test]
+#[should_panic(expected = "false positive probability")]
+fn new_panics_on_invalid_probability() {
+    BloomFilter::<String>::new(100, 1.5);
+}